@@ -9,14 +9,133 @@ struct task_info {
     __s32 nice;
     __u32 tickets;
     __u64 last_switch_in_ts;
+    /* Switches out while still runnable (preempted or yielded); the rest
+     * of `switches` blocked or slept. */
+    __u64 preemptions;
 };
 
+/* sched_switch reports a preempted task's prev_state as TASK_REPORT_MAX
+ * rather than TASK_RUNNING (0). */
+#define TASK_REPORT_MAX 0x100
+
+/* Log2 buckets of wakeup-to-run latency in microseconds: slot 0 counts
+ * waits under 1 us, slot i waits of [2^(i-1), 2^i) us, and the last slot
+ * everything longer. */
+#define LATENCY_SLOTS 24
+
+struct task_latency {
+    __u64 wakeup_ts;    /* pending wakeup, 0 once the task ran */
+    __u64 total_ns;
+    __u64 count;
+    __u64 max_ns;
+    __u32 slots[LATENCY_SLOTS];
+};
+
+#ifdef TASK_MAP_ARRAY
+/* One slot per pid below TASK_MAP_ARRAY, mmapable so userspace can sample
+ * it without a syscall per entry. Higher pids are not recorded. */
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(map_flags, BPF_F_MMAPABLE);
+    __type(key, __u32);
+    __type(value, struct task_info);
+    __uint(max_entries, TASK_MAP_ARRAY);
+} task_map SEC(".maps");
+#else
 struct {
     __uint(type, BPF_MAP_TYPE_HASH);
     __type(key, __u32);
     __type(value, struct task_info);
     __uint(max_entries, 10240);
 } task_map SEC(".maps");
+#endif
+
+/* Userspace ticket assignments (pid -> tickets) that take precedence over
+ * the nice-derived count; written by `rust-runner dump --enforce-policy`. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __u32);
+    __type(value, __u32);
+    __uint(max_entries, 10240);
+} ticket_override SEC(".maps");
+
+/* Wakeup-to-run latency per pid, filled by the sched_wakeup programs and
+ * sched_switch; pinned by the loader's --latency-pin. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __u32);
+    __type(value, struct task_latency);
+    __uint(max_entries, 10240);
+} task_latency SEC(".maps");
+
+/* Scheduler events besides sched_switch, counted per pid. Each tracepoint
+ * family has its own map and program so the loader's --events attaches and
+ * pins only the ones asked for. */
+struct sched_event_count {
+    __u64 count;
+    __u64 last_ts;
+    /* CPU of the latest event: the wakeup target or migration destination. */
+    __u32 cpu;
+    __u32 _pad;
+};
+
+/* sched_wakeup and sched_wakeup_new: the task became runnable. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __u32);
+    __type(value, struct sched_event_count);
+    __uint(max_entries, 10240);
+} wakeup_events SEC(".maps");
+
+/* sched_waking: a wakeup was issued, including ones of already runnable
+ * tasks that sched_wakeup never reports. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __u32);
+    __type(value, struct sched_event_count);
+    __uint(max_entries, 10240);
+} waking_events SEC(".maps");
+
+/* sched_migrate_task: the task moved to another CPU's run queue. */
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __u32);
+    __type(value, struct sched_event_count);
+    __uint(max_entries, 10240);
+} migrate_events SEC(".maps");
+
+/* Runtime per cgroup (v2 id), over every task in it, tracked or not. */
+struct cgroup_info {
+    __u64 runtime_ns;
+    __u64 switches;
+    __u64 preemptions;
+    /* Tickets times microseconds run: over runtime_ns, the mean tickets
+     * the cgroup's tasks ran with. */
+    __u64 ticket_us;
+};
+
+/* LRU so the entries of removed cgroups make room for new ones. */
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __type(key, __u64);
+    __type(value, struct cgroup_info);
+    __uint(max_entries, 4096);
+} cgroup_map SEC(".maps");
+
+/* The task each CPU switched in last, kept apart from task_map so tasks it
+ * has no entry for are charged to their cgroup too. */
+struct cpu_run {
+    __u64 switch_in_ts;
+    __u32 tickets;
+    __u32 _pad;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __type(key, __u32);
+    __type(value, struct cpu_run);
+    __uint(max_entries, 1);
+} cpu_run SEC(".maps");
 
 static __always_inline __u32 nice_to_tickets(__s32 nice)
 {
@@ -35,6 +154,10 @@ static __always_inline __u32 nice_to_tickets(__s32 nice)
 
 static __always_inline struct task_info *get_task_info(__u32 pid)
 {
+#ifdef TASK_MAP_ARRAY
+    /* Array slots always exist; out-of-range pids come back NULL. */
+    return bpf_map_lookup_elem(&task_map, &pid);
+#else
     struct task_info *info = bpf_map_lookup_elem(&task_map, &pid);
     if (!info) {
         struct task_info zero = {};
@@ -43,33 +166,175 @@ static __always_inline struct task_info *get_task_info(__u32 pid)
     }
 
     return info;
+#endif
+}
+
+/* Start the clock on a task becoming runnable; a wakeup already pending
+ * keeps its earlier start. */
+static __always_inline void mark_runnable(__u32 pid, __u64 now)
+{
+    struct task_latency *lat = bpf_map_lookup_elem(&task_latency, &pid);
+    if (!lat) {
+        struct task_latency zero = {};
+        bpf_map_update_elem(&task_latency, &pid, &zero, BPF_NOEXIST);
+        lat = bpf_map_lookup_elem(&task_latency, &pid);
+        if (!lat)
+            return;
+    }
+    if (!lat->wakeup_ts)
+        lat->wakeup_ts = now;
+}
+
+/* Record how long `pid` waited since it became runnable. */
+static __always_inline void record_latency(__u32 pid, __u64 now)
+{
+    struct task_latency *lat = bpf_map_lookup_elem(&task_latency, &pid);
+    if (!lat || !lat->wakeup_ts)
+        return;
+    __u64 delta = now > lat->wakeup_ts ? now - lat->wakeup_ts : 0;
+    lat->wakeup_ts = 0;
+    lat->total_ns += delta;
+    lat->count += 1;
+    if (delta > lat->max_ns)
+        lat->max_ns = delta;
+
+    __u64 us = delta / 1000;
+    __u32 slot = 0;
+#pragma unroll
+    for (int i = 0; i < LATENCY_SLOTS - 1; i++) {
+        if (!us)
+            break;
+        us >>= 1;
+        slot++;
+    }
+    if (slot < LATENCY_SLOTS)
+        __sync_fetch_and_add(&lat->slots[slot], 1);
+}
+
+/* Charge a switch out to the cgroup of the task still current in
+ * sched_switch, the one switched out. */
+static __always_inline void account_cgroup(__u64 runtime, bool preempted, __u32 tickets)
+{
+    __u64 id = bpf_get_current_cgroup_id();
+    struct cgroup_info *cg = bpf_map_lookup_elem(&cgroup_map, &id);
+    if (!cg) {
+        struct cgroup_info zero = {};
+        bpf_map_update_elem(&cgroup_map, &id, &zero, BPF_NOEXIST);
+        cg = bpf_map_lookup_elem(&cgroup_map, &id);
+        if (!cg)
+            return;
+    }
+    __sync_fetch_and_add(&cg->runtime_ns, runtime);
+    __sync_fetch_and_add(&cg->switches, 1);
+    if (preempted)
+        __sync_fetch_and_add(&cg->preemptions, 1);
+    __sync_fetch_and_add(&cg->ticket_us, (__u64)tickets * runtime / 1000);
+}
+
+SEC("tracepoint/sched/sched_wakeup")
+int handle_sched_wakeup(struct trace_event_raw_sched_wakeup_template *ctx)
+{
+    if (ctx->pid)
+        mark_runnable(ctx->pid, bpf_ktime_get_ns());
+    return 0;
+}
+
+SEC("tracepoint/sched/sched_wakeup_new")
+int handle_sched_wakeup_new(struct trace_event_raw_sched_wakeup_template *ctx)
+{
+    if (ctx->pid)
+        mark_runnable(ctx->pid, bpf_ktime_get_ns());
+    return 0;
+}
+
+static __always_inline void count_event(void *map, __u32 pid, __u32 cpu)
+{
+    if (!pid)
+        return;
+    struct sched_event_count *ev = bpf_map_lookup_elem(map, &pid);
+    if (!ev) {
+        struct sched_event_count zero = {};
+        bpf_map_update_elem(map, &pid, &zero, BPF_NOEXIST);
+        ev = bpf_map_lookup_elem(map, &pid);
+        if (!ev)
+            return;
+    }
+    __sync_fetch_and_add(&ev->count, 1);
+    ev->last_ts = bpf_ktime_get_ns();
+    ev->cpu = cpu;
+}
+
+/* Attached to both sched_wakeup and sched_wakeup_new. */
+SEC("tracepoint/sched/sched_wakeup")
+int count_sched_wakeup(struct trace_event_raw_sched_wakeup_template *ctx)
+{
+    count_event(&wakeup_events, ctx->pid, ctx->target_cpu);
+    return 0;
+}
+
+SEC("tracepoint/sched/sched_waking")
+int count_sched_waking(struct trace_event_raw_sched_wakeup_template *ctx)
+{
+    count_event(&waking_events, ctx->pid, ctx->target_cpu);
+    return 0;
+}
+
+SEC("tracepoint/sched/sched_migrate_task")
+int count_sched_migrate_task(struct trace_event_raw_sched_migrate_task *ctx)
+{
+    count_event(&migrate_events, ctx->pid, ctx->dest_cpu);
+    return 0;
 }
 
 SEC("tracepoint/sched/sched_switch")
 int handle_sched_switch(struct trace_event_raw_sched_switch *ctx)
 {
     __u64 now = bpf_ktime_get_ns();
+    __u32 zero = 0;
+    struct cpu_run *run = bpf_map_lookup_elem(&cpu_run, &zero);
 
     __u32 prev_pid = ctx->prev_pid;
     if (prev_pid) {
         struct task_info *prev_info = get_task_info(prev_pid);
+        /* Preempted while runnable: it waits for the CPU from here. */
+        bool preempted = !(ctx->prev_state & ~TASK_REPORT_MAX);
+        __u64 runtime = 0;
         if (prev_info) {
             if (prev_info->last_switch_in_ts && now > prev_info->last_switch_in_ts) {
-                prev_info->runtime_ns += now - prev_info->last_switch_in_ts;
+                runtime = now - prev_info->last_switch_in_ts;
+                prev_info->runtime_ns += runtime;
             }
             prev_info->switches += 1;
         }
+        if (run) {
+            __u64 ran = run->switch_in_ts && now > run->switch_in_ts ? now - run->switch_in_ts : 0;
+            account_cgroup(ran, preempted, run->tickets);
+        }
+        if (preempted) {
+            if (prev_info)
+                prev_info->preemptions += 1;
+            mark_runnable(prev_pid, now);
+        }
     }
 
     __u32 next_pid = ctx->next_pid;
+    __u32 tickets = 0;
     if (next_pid) {
+        __s32 nice = ctx->next_prio - 120;
+        __u32 *override = bpf_map_lookup_elem(&ticket_override, &next_pid);
+        tickets = override ? *override : nice_to_tickets(nice);
         struct task_info *next_info = get_task_info(next_pid);
         if (next_info) {
             next_info->last_switch_in_ts = now;
-            __s32 nice = ctx->next_prio - 120;
             next_info->nice = nice;
-            next_info->tickets = nice_to_tickets(nice);
+            next_info->tickets = tickets;
         }
+        record_latency(next_pid, now);
+    }
+    if (run) {
+        /* The idle task is not charged. */
+        run->switch_in_ts = next_pid ? now : 0;
+        run->tickets = tickets;
     }
 
     return 0;
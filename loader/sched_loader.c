@@ -1,5 +1,6 @@
 #include <errno.h>
 #include <getopt.h>
+#include <libgen.h>
 #include <signal.h>
 #include <stdbool.h>
 #include <stdio.h>
@@ -19,6 +20,10 @@ struct config {
     const char *prog_pin;
     const char *map_pin;
     const char *link_pin;
+    const char *override_pin;
+    const char *latency_pin;
+    const char *cgroup_pin;
+    const char *events;
     const char *trace_point;
     const char *btf_path;
 };
@@ -27,6 +32,8 @@ static void usage(const char *prog)
 {
     fprintf(stderr,
             "Usage: %s --obj PATH --prog-pin PATH --map-pin PATH --link-pin PATH "
+            "[--override-pin PATH] [--latency-pin PATH] [--cgroup-pin PATH] "
+            "[--events wakeup,waking,migrate] "
             "[--trace category:name] [--btf PATH]\n",
             prog);
 }
@@ -103,6 +110,140 @@ out:
     return err;
 }
 
+/* Pin the task_latency map and attach the wakeup programs that fill it;
+ * their links are pinned next to the sched_switch one. */
+static int attach_latency(struct bpf_object *obj, const struct config *cfg)
+{
+    static const struct {
+        const char *prog;
+        const char *trace;
+        const char *suffix;
+    } wakeups[] = {
+        {"handle_sched_wakeup", "sched:sched_wakeup", "_wakeup"},
+        {"handle_sched_wakeup_new", "sched:sched_wakeup_new", "_wakeup_new"},
+    };
+    struct bpf_map *latency = bpf_object__find_map_by_name(obj, "task_latency");
+    char link_pin[4096];
+    size_t i;
+    int err;
+
+    if (!latency) {
+        fprintf(stderr, "Map task_latency not found in %s\n", cfg->obj_path);
+        return -ENOENT;
+    }
+    err = repin_map(latency, cfg->latency_pin);
+    if (err) {
+        fprintf(stderr, "Failed to pin map at %s: %s\n", cfg->latency_pin, strerror(-err));
+        return err;
+    }
+
+    for (i = 0; i < sizeof(wakeups) / sizeof(wakeups[0]); i++) {
+        struct bpf_program *prog = bpf_object__find_program_by_name(obj, wakeups[i].prog);
+        if (!prog) {
+            fprintf(stderr, "Program %s not found in %s\n", wakeups[i].prog, cfg->obj_path);
+            return -ENOENT;
+        }
+        if (snprintf(link_pin, sizeof(link_pin), "%s%s", cfg->link_pin, wakeups[i].suffix) >=
+            (int)sizeof(link_pin))
+            return -ENAMETOOLONG;
+        err = attach_tracepoint(prog, wakeups[i].trace, link_pin);
+        if (err) {
+            fprintf(stderr, "Failed to attach %s: %s\n", wakeups[i].trace, strerror(-err));
+            return err;
+        }
+    }
+    return 0;
+}
+
+/* Pin the counter map of each event in cfg->events next to the task map
+ * and attach its program; links are pinned next to the sched_switch one. */
+static int attach_events(struct bpf_object *obj, const struct config *cfg)
+{
+    static const struct {
+        const char *event;
+        const char *map;
+        const char *prog;
+        const char *trace;
+        const char *suffix;
+    } events[] = {
+        {"wakeup", "wakeup_events", "count_sched_wakeup", "sched:sched_wakeup", "_wakeup_events"},
+        {"wakeup", "wakeup_events", "count_sched_wakeup", "sched:sched_wakeup_new",
+         "_wakeup_new_events"},
+        {"waking", "waking_events", "count_sched_waking", "sched:sched_waking", "_waking_events"},
+        {"migrate", "migrate_events", "count_sched_migrate_task", "sched:sched_migrate_task",
+         "_migrate_events"},
+    };
+    char *list = strdup(cfg->events);
+    char *map_dir = strdup(cfg->map_pin);
+    const char *dir;
+    char *event, *save = NULL;
+    char pin[4096];
+    size_t i;
+    int err = 0;
+
+    if (!list || !map_dir) {
+        err = -ENOMEM;
+        goto out;
+    }
+    dir = dirname(map_dir);
+    for (event = strtok_r(list, ",", &save); event; event = strtok_r(NULL, ",", &save)) {
+        bool known = false;
+
+        for (i = 0; i < sizeof(events) / sizeof(events[0]); i++) {
+            struct bpf_program *prog;
+            struct bpf_map *map;
+
+            if (strcmp(event, events[i].event))
+                continue;
+            if (!known) {
+                known = true;
+                map = bpf_object__find_map_by_name(obj, events[i].map);
+                if (!map) {
+                    fprintf(stderr, "Map %s not found in %s\n", events[i].map, cfg->obj_path);
+                    err = -ENOENT;
+                    goto out;
+                }
+                if (snprintf(pin, sizeof(pin), "%s/%s", dir, events[i].map) >=
+                    (int)sizeof(pin)) {
+                    err = -ENAMETOOLONG;
+                    goto out;
+                }
+                err = repin_map(map, pin);
+                if (err) {
+                    fprintf(stderr, "Failed to pin map at %s: %s\n", pin, strerror(-err));
+                    goto out;
+                }
+            }
+            prog = bpf_object__find_program_by_name(obj, events[i].prog);
+            if (!prog) {
+                fprintf(stderr, "Program %s not found in %s\n", events[i].prog, cfg->obj_path);
+                err = -ENOENT;
+                goto out;
+            }
+            if (snprintf(pin, sizeof(pin), "%s%s", cfg->link_pin, events[i].suffix) >=
+                (int)sizeof(pin)) {
+                err = -ENAMETOOLONG;
+                goto out;
+            }
+            err = attach_tracepoint(prog, events[i].trace, pin);
+            if (err) {
+                fprintf(stderr, "Failed to attach %s: %s\n", events[i].trace, strerror(-err));
+                goto out;
+            }
+        }
+        if (!known) {
+            fprintf(stderr, "Unknown event %s (expected wakeup, waking, migrate)\n", event);
+            err = -EINVAL;
+            goto out;
+        }
+    }
+
+out:
+    free(map_dir);
+    free(list);
+    return err;
+}
+
 int main(int argc, char **argv)
 {
     static const struct option opts[] = {
@@ -110,6 +251,10 @@ int main(int argc, char **argv)
         {"prog-pin", required_argument, NULL, 'p'},
         {"map-pin", required_argument, NULL, 'm'},
         {"link-pin", required_argument, NULL, 'l'},
+        {"override-pin", required_argument, NULL, 'r'},
+        {"latency-pin", required_argument, NULL, 'L'},
+        {"cgroup-pin", required_argument, NULL, 'c'},
+        {"events", required_argument, NULL, 'e'},
         {"trace", required_argument, NULL, 't'},
         {"btf", required_argument, NULL, 'b'},
         {"help", no_argument, NULL, 'h'},
@@ -139,6 +284,18 @@ int main(int argc, char **argv)
         case 'l':
             cfg.link_pin = optarg;
             break;
+        case 'r':
+            cfg.override_pin = optarg;
+            break;
+        case 'L':
+            cfg.latency_pin = optarg;
+            break;
+        case 'c':
+            cfg.cgroup_pin = optarg;
+            break;
+        case 'e':
+            cfg.events = optarg;
+            break;
         case 't':
             cfg.trace_point = optarg;
             break;
@@ -207,6 +364,34 @@ int main(int argc, char **argv)
         goto cleanup;
     }
 
+    if (cfg.override_pin) {
+        struct bpf_map *override = bpf_object__find_map_by_name(obj, "ticket_override");
+        if (!override) {
+            fprintf(stderr, "Map ticket_override not found in %s\n", cfg.obj_path);
+            err = -ENOENT;
+            goto cleanup;
+        }
+        err = repin_map(override, cfg.override_pin);
+        if (err) {
+            fprintf(stderr, "Failed to pin map at %s: %s\n", cfg.override_pin, strerror(-err));
+            goto cleanup;
+        }
+    }
+
+    if (cfg.cgroup_pin) {
+        struct bpf_map *cgroups = bpf_object__find_map_by_name(obj, "cgroup_map");
+        if (!cgroups) {
+            fprintf(stderr, "Map cgroup_map not found in %s\n", cfg.obj_path);
+            err = -ENOENT;
+            goto cleanup;
+        }
+        err = repin_map(cgroups, cfg.cgroup_pin);
+        if (err) {
+            fprintf(stderr, "Failed to pin map at %s: %s\n", cfg.cgroup_pin, strerror(-err));
+            goto cleanup;
+        }
+    }
+
     err = repin_program(prog, cfg.prog_pin);
     if (err) {
         fprintf(stderr, "Failed to pin program at %s: %s\n", cfg.prog_pin, strerror(-err));
@@ -219,6 +404,18 @@ int main(int argc, char **argv)
         goto cleanup;
     }
 
+    if (cfg.latency_pin) {
+        err = attach_latency(obj, &cfg);
+        if (err)
+            goto cleanup;
+    }
+
+    if (cfg.events && *cfg.events) {
+        err = attach_events(obj, &cfg);
+        if (err)
+            goto cleanup;
+    }
+
     printf("Loaded %s, pinned prog=%s map=%s link=%s\n",
            cfg.obj_path, cfg.prog_pin, cfg.map_pin, cfg.link_pin);
 
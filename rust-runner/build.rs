@@ -1,3 +1,128 @@
+//! With the `embed-bpf` feature, compile `bpf/sched_lottery.bpf.c` into a
+//! CO-RE object and embed it in the binary for `rust-runner attach`.
+//!
+//! - `NEO_EBPF_BPF_OBJECT=PATH` embeds a prebuilt object instead, e.g. for
+//!   cross builds or hosts without clang.
+//! - `BPF_CLANG` picks the compiler (default `clang`).
+//! - `NEO_EBPF_VMLINUX_H=PATH` supplies the kernel type header; otherwise
+//!   `bpf/vmlinux.h` is used if present, else it is dumped with bpftool from
+//!   the build host's `/sys/kernel/btf/vmlinux`. CO-RE relocates the types
+//!   on load, so the object runs on other kernels with BTF.
+//! - `NEO_EBPF_TASK_MAP_PIDS=N` builds `task_map` as an mmapable array of N
+//!   slots, like `make TASK_MAP=array`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OBJECT: &str = "sched_lottery.bpf.o";
+
 fn main() {
-    println!("cargo:rustc-link-lib=bpf");
+    println!("cargo:rerun-if-changed=build.rs");
+    if env::var_os("CARGO_FEATURE_EMBED_BPF").is_none() {
+        return;
+    }
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let object = out_dir.join(OBJECT);
+    for var in [
+        "NEO_EBPF_BPF_OBJECT",
+        "BPF_CLANG",
+        "NEO_EBPF_VMLINUX_H",
+        "NEO_EBPF_TASK_MAP_PIDS",
+    ] {
+        println!("cargo:rerun-if-env-changed={var}");
+    }
+
+    if let Some(prebuilt) = env::var_os("NEO_EBPF_BPF_OBJECT") {
+        let prebuilt = PathBuf::from(prebuilt);
+        println!("cargo:rerun-if-changed={}", prebuilt.display());
+        if let Err(err) = fs::copy(&prebuilt, &object) {
+            panic!(
+                "cannot copy NEO_EBPF_BPF_OBJECT {}: {err}",
+                prebuilt.display()
+            );
+        }
+        return;
+    }
+
+    let bpf_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../bpf");
+    let source = bpf_dir.join("sched_lottery.bpf.c");
+    println!("cargo:rerun-if-changed={}", source.display());
+    let include_dir = vmlinux_header(&bpf_dir, &out_dir);
+
+    let clang = env::var("BPF_CLANG").unwrap_or_else(|_| "clang".to_string());
+    let mut command = Command::new(&clang);
+    // Same flags as bpf/Makefile.
+    command
+        .args(["-O2", "-g", "-target", "bpf"])
+        .arg(format!("-D__TARGET_ARCH_{}", target_arch()))
+        .args(["-Wall", "-Werror", "-Wno-unused-value", "-Wno-pointer-sign"])
+        .arg("-I")
+        .arg(&include_dir)
+        .arg("-c")
+        .arg(&source)
+        .arg("-o")
+        .arg(&object);
+    if let Ok(pids) = env::var("NEO_EBPF_TASK_MAP_PIDS") {
+        command.arg(format!("-DTASK_MAP_ARRAY={pids}"));
+    }
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => panic!("{clang} failed to compile {}: {status}", source.display()),
+        Err(err) => panic!(
+            "cannot run {clang} ({err}); install clang, or point NEO_EBPF_BPF_OBJECT at a prebuilt {OBJECT}"
+        ),
+    }
+}
+
+/// Directory holding the `vmlinux.h` the source includes.
+fn vmlinux_header(bpf_dir: &Path, out_dir: &Path) -> PathBuf {
+    if let Some(header) = env::var_os("NEO_EBPF_VMLINUX_H") {
+        let header = PathBuf::from(header);
+        println!("cargo:rerun-if-changed={}", header.display());
+        if let Err(err) = fs::copy(&header, out_dir.join("vmlinux.h")) {
+            panic!("cannot copy NEO_EBPF_VMLINUX_H {}: {err}", header.display());
+        }
+        return out_dir.to_path_buf();
+    }
+    if bpf_dir.join("vmlinux.h").exists() {
+        return bpf_dir.to_path_buf();
+    }
+    let dump = Command::new("bpftool")
+        .args([
+            "btf",
+            "dump",
+            "file",
+            "/sys/kernel/btf/vmlinux",
+            "format",
+            "c",
+        ])
+        .output();
+    match dump {
+        Ok(output) if output.status.success() => {
+            if let Err(err) = fs::write(out_dir.join("vmlinux.h"), output.stdout) {
+                panic!("cannot write vmlinux.h: {err}");
+            }
+            out_dir.to_path_buf()
+        }
+        Ok(output) => panic!(
+            "bpftool could not dump /sys/kernel/btf/vmlinux ({}); set NEO_EBPF_VMLINUX_H",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(err) => panic!("cannot run bpftool ({err}); set NEO_EBPF_VMLINUX_H or install bpftool"),
+    }
+}
+
+/// `__TARGET_ARCH_*` name of the target, for `bpf_tracing.h`.
+fn target_arch() -> &'static str {
+    match env::var("CARGO_CFG_TARGET_ARCH").as_deref() {
+        Ok("aarch64") => "arm64",
+        Ok("arm") => "arm",
+        Ok("riscv64") => "riscv",
+        Ok("powerpc64") => "powerpc",
+        Ok("s390x") => "s390",
+        Ok("loongarch64") => "loongarch",
+        _ => "x86",
+    }
 }
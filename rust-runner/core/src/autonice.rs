@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+
+use tracing::{debug, warn};
+
+use crate::error::{Result, RunnerError};
+use crate::export::ChangeEvent;
+use crate::stats::TaskSnapshot;
+use crate::stream::SnapshotStream;
+
+/// Bounds and patience of an [`AutoNice`] controller.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoNiceConfig {
+    /// Consecutive windows a task must miss (or meet) its heuristic deadline
+    /// before its nice value moves by one.
+    pub windows: u32,
+    /// How far a task may move from the nice value it had when first seen.
+    pub range: i32,
+    /// Lowest nice value ever assigned; lowering nice needs `CAP_SYS_NICE`.
+    pub floor: i32,
+}
+
+/// Per-task feedback state.
+struct Tracked {
+    original: i32,
+    /// Whether this controller has reniced the task.
+    changed: bool,
+    /// Positive: consecutive misses; negative: consecutive meets.
+    streak: i32,
+}
+
+/// Feedback controller on nice values: a task that keeps missing its
+/// heuristic EDF deadline gets one nice step more priority, one that keeps
+/// meeting it gives one step back, within `original ± range` (and not below
+/// `floor`). Only tasks that ran in a window count, so idle tasks drift
+/// nowhere. Every change becomes a [`ChangeEvent`].
+pub struct AutoNice {
+    config: AutoNiceConfig,
+    tracked: HashMap<u32, Tracked>,
+    /// Tasks that could not be reniced (e.g. `EPERM`); left alone from then on.
+    skipped: HashSet<u32>,
+}
+
+impl AutoNice {
+    pub fn new(config: AutoNiceConfig) -> Self {
+        Self {
+            config: AutoNiceConfig {
+                windows: config.windows.max(1),
+                range: config.range.clamp(0, 39),
+                floor: config.floor.clamp(-20, 19),
+            },
+            tracked: HashMap::new(),
+            skipped: HashSet::new(),
+        }
+    }
+
+    /// Feed one window of `stream` and renice tasks whose streak is long
+    /// enough. Tasks that vanish are forgotten.
+    pub fn step(
+        &mut self,
+        stream: &mut SnapshotStream,
+        snapshots: &[TaskSnapshot],
+    ) -> Result<Vec<ChangeEvent>> {
+        let live: HashSet<u32> = snapshots.iter().map(|snap| snap.pid).collect();
+        self.tracked.retain(|pid, _| live.contains(pid));
+        self.skipped.retain(|pid| live.contains(pid));
+
+        let windows = self.config.windows as i32;
+        let mut events = Vec::new();
+        for snap in snapshots {
+            if snap.runtime_delta_ns == 0 || self.skipped.contains(&snap.pid) {
+                continue;
+            }
+            let tracked = self.tracked.entry(snap.pid).or_insert(Tracked {
+                original: snap.info.nice,
+                changed: false,
+                streak: 0,
+            });
+            tracked.streak = match (snap.deadline_missed(), tracked.streak) {
+                (true, streak) if streak > 0 => streak + 1,
+                (true, _) => 1,
+                (false, streak) if streak < 0 => streak - 1,
+                (false, _) => -1,
+            };
+            let low = (tracked.original - self.config.range).max(self.config.floor);
+            let high = (tracked.original + self.config.range).min(19);
+            let current = snap.info.nice;
+            let (wanted, reason) = if tracked.streak >= windows {
+                (
+                    current - 1,
+                    format!("missed deadline {} windows in a row", tracked.streak),
+                )
+            } else if tracked.streak <= -windows {
+                (
+                    current + 1,
+                    format!("met deadline {} windows in a row", -tracked.streak),
+                )
+            } else {
+                continue;
+            };
+            if !(low..=high).contains(&wanted) {
+                continue;
+            }
+            tracked.streak = 0;
+            match stream.renice(snap.pid, wanted) {
+                Ok(change) => {
+                    tracked.changed = true;
+                    debug!(target: "map", pid = snap.pid, from = change.old_nice, to = change.new_nice, "auto-nice");
+                    events.push(ChangeEvent {
+                        pid: snap.pid,
+                        field: "nice",
+                        from: change.old_nice.into(),
+                        to: change.new_nice.into(),
+                        source: "auto-nice",
+                        reason,
+                    });
+                }
+                // Gone, or not ours to change: stop steering this task.
+                Err(RunnerError::Control { target, source }) => {
+                    warn!(target: "map", "auto-nice leaves {target} alone: {source}");
+                    self.skipped.insert(snap.pid);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(events)
+    }
+
+    /// Put every task still tracked back on the nice value it started with.
+    pub fn release(&mut self, stream: &mut SnapshotStream) -> Result<()> {
+        for (pid, tracked) in self.tracked.drain() {
+            if !tracked.changed {
+                continue;
+            }
+            match stream.renice(pid, tracked.original) {
+                Ok(_) => {}
+                // Exited since the last sample.
+                Err(RunnerError::Control { source, .. })
+                    if source.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::OnceLock;
+
+use crate::export::SnapshotBatch;
+use crate::stats::TaskSnapshot;
+
+/// Avro schema of the records written by [`encode_task`], in Parsing
+/// Canonical Form so its [`fingerprint`] matches what registries compute.
+pub const TASK_SCHEMA: &str = concat!(
+    r#"{"name":"neo_ebpf.TaskSnapshot","type":"record","fields":["#,
+    r#"{"name":"host","type":"string"},"#,
+    r#"{"name":"map","type":"string"},"#,
+    r#"{"name":"iteration","type":"long"},"#,
+    r#"{"name":"timestamp_s","type":"double"},"#,
+    r#"{"name":"clock","type":"string"},"#,
+    r#"{"name":"total_tickets","type":"long"},"#,
+    r#"{"name":"pid","type":"long"},"#,
+    r#"{"name":"comm","type":["null","string"]},"#,
+    r#"{"name":"runtime_ns","type":"long"},"#,
+    r#"{"name":"delta_ns","type":"long"},"#,
+    r#"{"name":"rolling_runtime_ms","type":"double"},"#,
+    r#"{"name":"switch_delta","type":"long"},"#,
+    r#"{"name":"estimated_period_ms","type":"double"},"#,
+    r#"{"name":"deadline_ms","type":"double"},"#,
+    r#"{"name":"lateness_ms","type":"double"},"#,
+    r#"{"name":"utilization","type":"double"},"#,
+    r#"{"name":"nice","type":"int"},"#,
+    r#"{"name":"tickets","type":"long"},"#,
+    r#"{"name":"ticket_share","type":"double"},"#,
+    r#"{"name":"cgroup","type":["null","string"]},"#,
+    r#"{"name":"container_id","type":["null","string"]},"#,
+    r#"{"name":"user","type":["null","string"]},"#,
+    r#"{"name":"cmdline","type":["null","string"]},"#,
+    r#"{"name":"last_cpu","type":["null","int"]},"#,
+    r#"{"name":"policy","type":["null","string"]},"#,
+    r#"{"name":"kthread","type":"boolean"}"#,
+    r#"]}"#,
+);
+
+/// Marker of Avro single-object encoding.
+const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xc3, 0x01];
+
+/// CRC-64-AVRO (Rabin) fingerprint of `TASK_SCHEMA`.
+pub fn fingerprint() -> u64 {
+    const EMPTY: u64 = 0xc15d_213a_a4d7_a795;
+    static FINGERPRINT: OnceLock<u64> = OnceLock::new();
+    *FINGERPRINT.get_or_init(|| {
+        let mut table = [0_u64; 256];
+        for (i, slot) in table.iter_mut().enumerate() {
+            let mut fp = i as u64;
+            for _ in 0..8 {
+                fp = (fp >> 1) ^ (EMPTY & (fp & 1).wrapping_neg());
+            }
+            *slot = fp;
+        }
+        TASK_SCHEMA.bytes().fold(EMPTY, |fp, byte| {
+            (fp >> 8) ^ table[((fp ^ byte as u64) & 0xff) as usize]
+        })
+    })
+}
+
+/// One task record in Avro single-object encoding: the magic bytes, the
+/// schema [`fingerprint`], then the binary datum.
+pub fn encode_task(host: &str, batch: &SnapshotBatch, entry: &TaskSnapshot, out: &mut Vec<u8>) {
+    out.extend_from_slice(&SINGLE_OBJECT_MAGIC);
+    out.extend_from_slice(&fingerprint().to_le_bytes());
+    let meta = &entry.meta;
+    string(out, host);
+    string(out, &batch.map);
+    long(out, i64::from(batch.iteration) + 1);
+    double(out, batch.timestamp);
+    string(out, batch.clock.as_str());
+    long(out, batch.total_tickets as i64);
+    long(out, entry.pid.into());
+    optional(out, meta.comm.as_deref(), string);
+    long(out, entry.info.runtime_ns as i64);
+    long(out, entry.runtime_delta_ns as i64);
+    double(out, entry.rolling_runtime_ms);
+    long(out, entry.switch_delta as i64);
+    double(out, entry.estimated_period_ms);
+    double(out, entry.deadline_ms);
+    double(out, entry.lateness_ms);
+    double(out, entry.utilization);
+    long(out, entry.info.nice.into());
+    long(out, entry.info.tickets.into());
+    double(out, entry.ticket_share);
+    optional(out, meta.cgroup.as_deref(), string);
+    optional(out, meta.container_id.as_deref(), string);
+    optional(out, meta.user.as_deref(), string);
+    optional(out, meta.cmdline.as_deref(), string);
+    optional(out, meta.last_cpu.map(i64::from), long);
+    let policy = meta.sched.map(|attr| attr.policy.to_string());
+    optional(out, policy.as_deref(), string);
+    out.push(u8::from(meta.kthread));
+}
+
+/// Zig-zag varint, used for both `int` and `long`.
+fn long(out: &mut Vec<u8>, value: i64) {
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn double(out: &mut Vec<u8>, value: f64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn string(out: &mut Vec<u8>, value: &str) {
+    long(out, value.len() as i64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// `["null", T]` union: branch index, then the value if present.
+fn optional<T>(out: &mut Vec<u8>, value: Option<T>, write: fn(&mut Vec<u8>, T)) {
+    match value {
+        Some(value) => {
+            long(out, 1);
+            write(out, value);
+        }
+        None => long(out, 0),
+    }
+}
@@ -0,0 +1,696 @@
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::hash::Hash;
+use std::io;
+use std::os::fd::RawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::{debug, info};
+
+use crate::cgroup::CgroupInfo;
+use crate::error::{Result, RunnerError};
+use crate::events::EventCount;
+use crate::latency::TaskLatency;
+use crate::stats::TaskInfo;
+
+const BPF_MAP_TYPE_HASH: u32 = 1;
+pub const BPF_MAP_TYPE_ARRAY: u32 = 2;
+/// `map_flags` bit of array maps whose values can be mmap()ed.
+pub const BPF_F_MMAPABLE: u32 = 1 << 10;
+/// `file_flags` of `BPF_OBJ_GET` for an fd that can only read the map.
+const BPF_F_RDONLY: u32 = 1 << 3;
+
+static BPF_SYSCALLS: AtomicU64 = AtomicU64::new(0);
+
+unsafe extern "C" {
+    fn bpf_map_create(
+        map_type: libc::c_uint,
+        map_name: *const libc::c_char,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        opts: *const libc::c_void,
+    ) -> libc::c_int;
+    fn bpf_map_update_elem(
+        fd: libc::c_int,
+        key: *const libc::c_void,
+        value: *const libc::c_void,
+        flags: u64,
+    ) -> libc::c_int;
+    fn bpf_map_delete_elem(fd: libc::c_int, key: *const libc::c_void) -> libc::c_int;
+    fn bpf_obj_get(pathname: *const libc::c_char) -> libc::c_int;
+    fn bpf_obj_get_opts(pathname: *const libc::c_char, opts: *const ObjGetOpts) -> libc::c_int;
+    fn bpf_map_get_next_key(
+        fd: libc::c_int,
+        key: *const libc::c_void,
+        next_key: *mut libc::c_void,
+    ) -> libc::c_int;
+    fn bpf_map_lookup_elem(
+        fd: libc::c_int,
+        key: *const libc::c_void,
+        value: *mut libc::c_void,
+    ) -> libc::c_int;
+    fn bpf_obj_get_info_by_fd(
+        fd: libc::c_int,
+        info: *mut libc::c_void,
+        len: *mut u32,
+    ) -> libc::c_int;
+}
+
+/// libbpf's `struct bpf_obj_get_opts`. `path_fd` is only read with
+/// `BPF_F_PATH_FD`, but libbpf rejects non-zero bytes past the fields it
+/// knows, so the padding it fills must be zeroed.
+#[repr(C)]
+struct ObjGetOpts {
+    sz: usize,
+    file_flags: u32,
+    path_fd: i32,
+}
+
+/// What an fd from [`open_pinned_map`] may do with the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapAccess {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl MapAccess {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MapAccess::ReadOnly => "read-only",
+            MapAccess::ReadWrite => "read-write",
+        }
+    }
+}
+
+/// Leading fields of the kernel's `struct bpf_map_info`; the kernel fills
+/// as much as we ask for.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MapInfo {
+    pub map_type: u32,
+    pub id: u32,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    pub map_flags: u32,
+}
+
+impl MapInfo {
+    /// An array map the kernel lets us mmap, see [`MappedArray`].
+    pub fn is_mmapable_array(&self) -> bool {
+        self.map_type == BPF_MAP_TYPE_ARRAY && self.map_flags & BPF_F_MMAPABLE != 0
+    }
+}
+
+/// `struct task_info` layouts the runner can decode, told apart by value size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueLayout {
+    /// The current layout, read straight into [`TaskInfo`].
+    Current,
+    /// Objects built before `preemptions`; decoded with it 0, and the
+    /// stream reports preemptions as unknown.
+    NoPreemptions,
+    /// Older BPF objects without `last_switch_in_ts` either; decoded with
+    /// it 0, which outputs treat as unknown.
+    NoSwitchIn,
+}
+
+/// `struct task_info` before `preemptions` was added.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TaskInfoNoPreemptions {
+    runtime_ns: u64,
+    switches: u64,
+    nice: i32,
+    tickets: u32,
+    last_switch_in_ts: u64,
+}
+
+/// `struct task_info` before `last_switch_in_ts` was added.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TaskInfoNoSwitchIn {
+    runtime_ns: u64,
+    switches: u64,
+    nice: i32,
+    tickets: u32,
+}
+
+/// Room for the largest layout, 8-byte aligned like the kernel's values.
+type ValueBuf = [u64; std::mem::size_of::<TaskInfo>() / 8];
+
+impl ValueLayout {
+    pub fn size(self) -> usize {
+        match self {
+            ValueLayout::Current => std::mem::size_of::<TaskInfo>(),
+            ValueLayout::NoPreemptions => std::mem::size_of::<TaskInfoNoPreemptions>(),
+            ValueLayout::NoSwitchIn => std::mem::size_of::<TaskInfoNoSwitchIn>(),
+        }
+    }
+
+    /// The layout of the task map at `path`, or
+    /// [`RunnerError::ValueSizeMismatch`] when its keys are not pids or its
+    /// values match no known layout: reading those would overrun our buffers.
+    pub fn of(path: &str, info: &MapInfo) -> Result<Self> {
+        let layout = [
+            ValueLayout::Current,
+            ValueLayout::NoPreemptions,
+            ValueLayout::NoSwitchIn,
+        ]
+        .into_iter()
+        .find(|layout| layout.size() == info.value_size as usize);
+        match layout {
+            Some(layout) if info.key_size as usize == std::mem::size_of::<u32>() => Ok(layout),
+            _ => Err(RunnerError::ValueSizeMismatch {
+                path: path.to_string(),
+                key_size: info.key_size,
+                value_size: info.value_size,
+            }),
+        }
+    }
+
+    /// # Safety
+    /// `value` must point to at least [`size`](Self::size) readable bytes.
+    unsafe fn decode(self, value: *const u8) -> TaskInfo {
+        match self {
+            ValueLayout::Current => unsafe { ptr::read_volatile(value as *const TaskInfo) },
+            ValueLayout::NoPreemptions => {
+                let old = unsafe { ptr::read_volatile(value as *const TaskInfoNoPreemptions) };
+                TaskInfo {
+                    runtime_ns: old.runtime_ns,
+                    switches: old.switches,
+                    nice: old.nice,
+                    tickets: old.tickets,
+                    last_switch_in_ts: old.last_switch_in_ts,
+                    preemptions: 0,
+                }
+            }
+            ValueLayout::NoSwitchIn => {
+                let old = unsafe { ptr::read_volatile(value as *const TaskInfoNoSwitchIn) };
+                TaskInfo {
+                    runtime_ns: old.runtime_ns,
+                    switches: old.switches,
+                    nice: old.nice,
+                    tickets: old.tickets,
+                    last_switch_in_ts: 0,
+                    preemptions: 0,
+                }
+            }
+        }
+    }
+}
+
+pub fn map_info(map_fd: RawFd) -> io::Result<MapInfo> {
+    let mut info = MapInfo::default();
+    let mut len = std::mem::size_of::<MapInfo>() as u32;
+    count_syscall();
+    let ret = unsafe {
+        bpf_obj_get_info_by_fd(
+            map_fd,
+            &mut info as *mut MapInfo as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(info)
+    }
+}
+
+/// Number of bpf(2) calls issued through this module so far.
+pub fn syscall_count() -> u64 {
+    BPF_SYSCALLS.load(Ordering::Relaxed)
+}
+
+fn count_syscall() {
+    BPF_SYSCALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Leading fields of libbpf's `struct bpf_map_create_opts`.
+#[repr(C)]
+#[derive(Default)]
+struct MapCreateOpts {
+    sz: usize,
+    btf_fd: u32,
+    btf_key_type_id: u32,
+    btf_value_type_id: u32,
+    btf_vmlinux_value_type_id: u32,
+    inner_map_fd: u32,
+    map_flags: u32,
+    map_extra: u64,
+    numa_node: u32,
+    map_ifindex: u32,
+}
+
+pub fn create_task_map(max_entries: u32) -> io::Result<RawFd> {
+    create_map(BPF_MAP_TYPE_HASH, max_entries.max(1), None)
+}
+
+/// An mmapable array map with a slot for every pid up to `max_pid`, laid
+/// out like a task map built with `TASK_MAP=array` (see [`MappedArray`]).
+pub fn create_task_array(max_pid: u32) -> io::Result<RawFd> {
+    let opts = MapCreateOpts {
+        sz: std::mem::size_of::<MapCreateOpts>(),
+        map_flags: BPF_F_MMAPABLE,
+        ..MapCreateOpts::default()
+    };
+    create_map(BPF_MAP_TYPE_ARRAY, max_pid.saturating_add(1), Some(&opts))
+}
+
+fn create_map(map_type: u32, max_entries: u32, opts: Option<&MapCreateOpts>) -> io::Result<RawFd> {
+    let name = CString::new("bench_map").expect("static name");
+    count_syscall();
+    let fd = unsafe {
+        bpf_map_create(
+            map_type,
+            name.as_ptr(),
+            std::mem::size_of::<u32>() as u32,
+            std::mem::size_of::<TaskInfo>() as u32,
+            max_entries,
+            opts.map_or(ptr::null(), |opts| {
+                opts as *const MapCreateOpts as *const libc::c_void
+            }),
+        )
+    };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(fd as RawFd)
+    }
+}
+
+pub fn update_task_info(map_fd: RawFd, pid: u32, info: &TaskInfo) -> io::Result<()> {
+    count_syscall();
+    let ret = unsafe {
+        bpf_map_update_elem(
+            map_fd,
+            &pid as *const u32 as *const libc::c_void,
+            info as *const TaskInfo as *const libc::c_void,
+            0,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Set the `ticket_override` entry for `pid`; the BPF program uses it instead
+/// of the nice-derived count from the task's next switch-in.
+pub fn set_ticket_override(map_fd: RawFd, pid: u32, tickets: u32) -> io::Result<()> {
+    count_syscall();
+    let ret = unsafe {
+        bpf_map_update_elem(
+            map_fd,
+            &pid as *const u32 as *const libc::c_void,
+            &tickets as *const u32 as *const libc::c_void,
+            0,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Drop the `ticket_override` entry for `pid`; a missing entry is not an error.
+pub fn clear_ticket_override(map_fd: RawFd, pid: u32) -> io::Result<()> {
+    count_syscall();
+    let ret = unsafe { bpf_map_delete_elem(map_fd, &pid as *const u32 as *const libc::c_void) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// `pid`'s entry of a `task_latency` map, or `None` when it has none.
+pub fn lookup_latency(map_fd: RawFd, pid: u32) -> io::Result<Option<TaskLatency>> {
+    lookup_pid(map_fd, pid)
+}
+
+/// The value under `pid` of a map whose values are `T`.
+fn lookup_pid<T: Default>(map_fd: RawFd, pid: u32) -> io::Result<Option<T>> {
+    let mut value = T::default();
+    count_syscall();
+    let ret = unsafe {
+        bpf_map_lookup_elem(
+            map_fd,
+            &pid as *const u32 as *const libc::c_void,
+            &mut value as *mut T as *mut libc::c_void,
+        )
+    };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENOENT) {
+            return Ok(None);
+        }
+        return Err(err);
+    }
+    Ok(Some(value))
+}
+
+/// Open a pinned map. With `access` read-only, the fd is requested with
+/// `BPF_F_RDONLY` first, so LSM/bpf policies that forbid writing the
+/// scheduler's map still let us read it; only when that is refused (e.g.
+/// by kernels older than 4.15) is a read-write fd tried. Returns the access
+/// obtained.
+pub fn open_pinned_map(path: &str, access: MapAccess) -> Result<(RawFd, MapAccess)> {
+    let map_open = |source| RunnerError::MapOpen {
+        path: path.to_string(),
+        source,
+    };
+    let c_path = CString::new(path).map_err(|_| {
+        map_open(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path contains embedded NUL byte",
+        ))
+    })?;
+
+    if access == MapAccess::ReadOnly {
+        let opts = ObjGetOpts {
+            sz: std::mem::size_of::<ObjGetOpts>(),
+            file_flags: BPF_F_RDONLY,
+            path_fd: 0,
+        };
+        count_syscall();
+        let fd = unsafe { bpf_obj_get_opts(c_path.as_ptr(), &opts) };
+        if fd >= 0 {
+            info!(target: "map", path, fd, "opened pinned map read-only");
+            return Ok((fd as RawFd, MapAccess::ReadOnly));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::NotFound {
+            return Err(map_open(err));
+        }
+        debug!(target: "map", path, error = %err, "read-only bpf_obj_get failed; trying read-write");
+    }
+
+    count_syscall();
+    let fd = unsafe { bpf_obj_get(c_path.as_ptr()) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        debug!(target: "map", path, error = %err, "bpf_obj_get failed");
+        Err(map_open(err))
+    } else {
+        if access == MapAccess::ReadOnly {
+            info!(target: "map", path, fd, "opened pinned map read-write (read-only access was refused)");
+        } else {
+            debug!(target: "map", path, fd, "opened pinned map");
+        }
+        Ok((fd as RawFd, MapAccess::ReadWrite))
+    }
+}
+
+/// Restarts after which [`walk_task_map`] gives up on a map that keeps
+/// losing the key it stands on.
+const MAX_WALK_RESTARTS: u32 = 16;
+
+/// One walk over a task map. Entries deleted while it runs make the kernel
+/// start the walk over, or vanish between being listed and looked up;
+/// either way the entries may not all be from the same moment.
+#[derive(Debug, Default)]
+pub struct MapWalk {
+    /// In pid order, each pid once.
+    pub entries: Vec<(u32, TaskInfo)>,
+    /// Times the walk started over because its current key was deleted.
+    pub restarts: u32,
+    /// Keys deleted between being listed and looked up.
+    pub vanished: u32,
+    /// The walk stopped after [`MAX_WALK_RESTARTS`]; keys may be missing.
+    pub truncated: bool,
+}
+
+impl MapWalk {
+    /// The map did not change under the walk.
+    pub fn consistent(&self) -> bool {
+        self.restarts == 0 && self.vanished == 0
+    }
+
+    /// Merge a second walk, taken because `first` was not consistent, into
+    /// one result. This walk's values win; only when it was cut short are
+    /// the pids that just `first` saw kept, since a pid missing from a full
+    /// walk was deleted.
+    pub fn reconcile(mut self, first: MapWalk) -> MapWalk {
+        if self.truncated {
+            let mut merged = Vec::with_capacity(self.entries.len().max(first.entries.len()));
+            let mut second = self.entries.into_iter().peekable();
+            for (pid, info) in first.entries {
+                while let Some(entry) = second.next_if(|(next, _)| *next < pid) {
+                    merged.push(entry);
+                }
+                match second.next_if(|(next, _)| *next == pid) {
+                    Some(entry) => merged.push(entry),
+                    None => merged.push((pid, info)),
+                }
+            }
+            merged.extend(second);
+            self.entries = merged;
+        }
+        self
+    }
+}
+
+/// Read every entry of a map with the current layout. Errors name the map
+/// by fd; [`SnapshotStream`] relabels them with the map's label.
+///
+/// [`SnapshotStream`]: crate::stream::SnapshotStream
+pub fn iterate_task_info(map_fd: RawFd) -> Result<Vec<(u32, TaskInfo)>> {
+    Ok(walk_task_map(map_fd, ValueLayout::Current, &mut HashSet::new())?.entries)
+}
+
+/// Read every entry, noticing when the map changes under the walk: a key
+/// listed twice means the walk started over (its key was deleted), so the
+/// repeat is skipped rather than duplicated, and a key gone before its
+/// lookup is left out rather than failing the read. Values are decoded as
+/// `layout`, which must be the map's (see [`ValueLayout::of`]). `seen` is
+/// scratch space, kept by the caller across walks.
+pub fn walk_task_map(
+    map_fd: RawFd,
+    layout: ValueLayout,
+    seen: &mut HashSet<u32>,
+) -> Result<MapWalk> {
+    let map_iterate = |pid, source| RunnerError::MapIterate {
+        map: format!("fd {map_fd}"),
+        pid,
+        source,
+    };
+    let mut walk = MapWalk::default();
+    seen.clear();
+    let mut key: u32 = 0;
+    let mut next_key: u32 = 0;
+    let mut first = true;
+    // Passing keys already read after a start-over.
+    let mut replaying = false;
+
+    loop {
+        let key_ptr = if first {
+            ptr::null()
+        } else {
+            &key as *const u32
+        };
+
+        count_syscall();
+        let ret = unsafe {
+            bpf_map_get_next_key(
+                map_fd,
+                key_ptr as *const libc::c_void,
+                &mut next_key as *mut u32 as *mut libc::c_void,
+            )
+        };
+
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                break;
+            } else {
+                return Err(map_iterate(None, err));
+            }
+        }
+
+        first = false;
+        key = next_key;
+        if !seen.insert(key) {
+            if !replaying {
+                replaying = true;
+                walk.restarts += 1;
+                if walk.restarts > MAX_WALK_RESTARTS {
+                    walk.truncated = true;
+                    break;
+                }
+            }
+            continue;
+        }
+        replaying = false;
+
+        let mut value: ValueBuf = Default::default();
+        count_syscall();
+        let lookup_ret = unsafe {
+            bpf_map_lookup_elem(
+                map_fd,
+                &key as *const u32 as *const libc::c_void,
+                value.as_mut_ptr() as *mut libc::c_void,
+            )
+        };
+        if lookup_ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                walk.vanished += 1;
+                continue;
+            }
+            return Err(map_iterate(Some(key), err));
+        }
+        let value = unsafe { layout.decode(value.as_ptr() as *const u8) };
+        walk.entries.push((key, value));
+    }
+
+    walk.entries.sort_by_key(|(pid, _)| *pid);
+    Ok(walk)
+}
+
+/// Read every entry of a `cgroup_map`. Its entries are only ever evicted,
+/// so a walk that starts over just skips the cgroups it already read, and
+/// one evicted before its lookup is left out.
+pub fn walk_cgroup_map(map_fd: RawFd) -> io::Result<Vec<(u64, CgroupInfo)>> {
+    walk_map(map_fd)
+}
+
+/// Read every entry of a scheduler event map (`wakeup_events`, ...) in one
+/// walk; pids without an entry have not had the event yet. Like
+/// `cgroup_map`, entries are only added or evicted.
+pub fn walk_event_map(map_fd: RawFd) -> io::Result<Vec<(u32, EventCount)>> {
+    walk_map(map_fd)
+}
+
+/// Read every entry of a map keyed by `K` whose values are `V`, skipping
+/// keys seen before a restart and entries gone before their lookup.
+fn walk_map<K, V>(map_fd: RawFd) -> io::Result<Vec<(K, V)>>
+where
+    K: Copy + Default + Eq + Hash,
+    V: Default,
+{
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    let mut key: Option<K> = None;
+    loop {
+        let mut next_key = K::default();
+        count_syscall();
+        let ret = unsafe {
+            bpf_map_get_next_key(
+                map_fd,
+                key.as_ref()
+                    .map_or(ptr::null(), |key| key as *const K as *const libc::c_void),
+                &mut next_key as *mut K as *mut libc::c_void,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                break;
+            }
+            return Err(err);
+        }
+        key = Some(next_key);
+        if !seen.insert(next_key) {
+            continue;
+        }
+        let mut value = V::default();
+        count_syscall();
+        let ret = unsafe {
+            bpf_map_lookup_elem(
+                map_fd,
+                &next_key as *const K as *const libc::c_void,
+                &mut value as *mut V as *mut libc::c_void,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                continue;
+            }
+            return Err(err);
+        }
+        entries.push((next_key, value));
+    }
+    Ok(entries)
+}
+
+/// A `BPF_MAP_TYPE_ARRAY` task map created with `BPF_F_MMAPABLE`, mapped
+/// read-only: slot `pid` holds that pid's `task_info`, and a sample is a
+/// plain memory copy with no bpf(2) calls.
+pub struct MappedArray {
+    base: *const u8,
+    len: usize,
+    slots: u32,
+    /// Values are laid out 8-byte aligned.
+    stride: usize,
+    layout: ValueLayout,
+}
+
+// The mapping is only read, through volatile copies.
+unsafe impl Send for MappedArray {}
+
+impl MappedArray {
+    /// Map an array whose values are laid out as `layout`.
+    pub fn map(map_fd: RawFd, info: &MapInfo, layout: ValueLayout) -> io::Result<Self> {
+        if !info.is_mmapable_array()
+            || info.key_size != 4
+            || info.value_size as usize != layout.size()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not an mmapable array of struct task_info",
+            ));
+        }
+        let stride = (info.value_size as usize).next_multiple_of(8);
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let len = (stride * info.max_entries as usize).next_multiple_of(page);
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                map_fd,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        debug!(target: "map", slots = info.max_entries, bytes = len, "mmapped array task map");
+        Ok(Self {
+            base: base as *const u8,
+            len,
+            slots: info.max_entries,
+            stride,
+            layout,
+        })
+    }
+
+    /// Every pid that has been switched at least once, in pid order.
+    pub fn read(&self) -> Vec<(u32, TaskInfo)> {
+        (0..self.slots)
+            .filter_map(|pid| {
+                let slot = unsafe { self.base.add(pid as usize * self.stride) };
+                let info = unsafe { self.layout.decode(slot) };
+                (pid != 0 && !info.is_empty()).then_some((pid, info))
+            })
+            .collect()
+    }
+}
+
+impl Drop for MappedArray {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.len);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::control::CGROUP_ROOT;
+use crate::stats::{TaskSnapshot, preemption_rate};
+
+/// File name the per-cgroup map is pinned under, next to the task map.
+pub const CGROUP_PIN_NAME: &str = "cgroup_map";
+
+/// Least time between two walks of cgroupfs for new cgroup ids; systemd
+/// creates transient scopes all the time.
+const WALK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Mirror of the BPF program's `struct cgroup_info`: the runtime of every
+/// task in a cgroup (v2), keyed by cgroup id, whether the task map tracks
+/// the task or not (switch-ins are stamped per CPU for this).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CgroupInfo {
+    pub runtime_ns: u64,
+    pub switches: u64,
+    pub preemptions: u64,
+    /// Tickets times microseconds run, summed over the switches out.
+    pub ticket_us: u64,
+}
+
+/// One cgroup over one window, the cgroup counterpart of a
+/// [`TaskSnapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupSnapshot {
+    /// Cgroup id, the inode number of its directory in cgroupfs.
+    pub id: u64,
+    /// Path relative to the cgroup root, like [`TaskMeta::cgroup`]; `None`
+    /// once the cgroup is gone, and for a new one until cgroupfs is walked
+    /// again.
+    ///
+    /// [`TaskMeta::cgroup`]: crate::procfs::TaskMeta::cgroup
+    pub path: Option<String>,
+    /// Cumulative counters as read from the map.
+    pub info: CgroupInfo,
+    pub runtime_delta_ns: u64,
+    pub switch_delta: u64,
+    pub preempt_delta: u64,
+    /// Mean tickets of the cgroup's tasks over the window, weighted by how
+    /// long each ran; `None` when none ran.
+    pub tickets: Option<f64>,
+    /// Sampled tasks in the cgroup.
+    pub tasks: usize,
+    /// CPUs the cgroup kept busy over the window (1.0: one CPU).
+    pub utilization: f64,
+    /// The counters went backwards (the BPF program was reloaded), so the
+    /// deltas start over next window.
+    pub counter_reset: bool,
+}
+
+impl CgroupSnapshot {
+    pub fn runtime_delta_ms(&self) -> f64 {
+        self.runtime_delta_ns as f64 / 1_000_000.0
+    }
+
+    /// Switches out the cgroup's tasks made themselves.
+    pub fn voluntary_delta(&self) -> u64 {
+        self.switch_delta.saturating_sub(self.preempt_delta)
+    }
+
+    /// See [`TaskSnapshot::preemption_rate`].
+    pub fn preemption_rate(&self) -> Option<f64> {
+        preemption_rate(Some(self.preempt_delta), self.switch_delta)
+    }
+
+    /// The path, or the id for a cgroup that no longer exists.
+    pub fn label(&self) -> String {
+        self.path
+            .clone()
+            .unwrap_or_else(|| format!("cgroup {}", self.id))
+    }
+}
+
+/// Turns the cumulative `cgroup_map` counters into per-window
+/// [`CgroupSnapshot`]s.
+#[derive(Debug, Default)]
+pub struct CgroupTracker {
+    last: HashMap<u64, CgroupInfo>,
+}
+
+impl CgroupTracker {
+    /// Snapshots of `entries` over a window of `window_ms`, busiest first.
+    /// A cgroup's first reading has no deltas. `path` resolves an id, and
+    /// `snapshots` are counted into the cgroups of their tasks.
+    pub fn update(
+        &mut self,
+        entries: Vec<(u64, CgroupInfo)>,
+        mut path: impl FnMut(u64) -> Option<String>,
+        window_ms: f64,
+        snapshots: &[TaskSnapshot],
+    ) -> Vec<CgroupSnapshot> {
+        let mut tasks: HashMap<&str, usize> = HashMap::new();
+        for snap in snapshots {
+            if let Some(cgroup) = &snap.meta.cgroup {
+                *tasks.entry(cgroup.as_str()).or_default() += 1;
+            }
+        }
+        let live: HashSet<u64> = entries.iter().map(|(id, _)| *id).collect();
+        let mut cgroups: Vec<CgroupSnapshot> = entries
+            .into_iter()
+            .map(|(id, info)| {
+                let before = self.last.insert(id, info);
+                let counter_reset = before.is_some_and(|before| {
+                    info.runtime_ns < before.runtime_ns || info.switches < before.switches
+                });
+                let before = before.filter(|_| !counter_reset).unwrap_or(info);
+                let runtime_delta_ns = info.runtime_ns - before.runtime_ns;
+                let path = path(id);
+                CgroupSnapshot {
+                    id,
+                    tasks: path
+                        .as_deref()
+                        .and_then(|path| tasks.get(path))
+                        .copied()
+                        .unwrap_or(0),
+                    path,
+                    info,
+                    runtime_delta_ns,
+                    switch_delta: info.switches - before.switches,
+                    preempt_delta: info.preemptions.saturating_sub(before.preemptions),
+                    tickets: (runtime_delta_ns > 0).then(|| {
+                        info.ticket_us.wrapping_sub(before.ticket_us) as f64
+                            / (runtime_delta_ns as f64 / 1_000.0)
+                    }),
+                    utilization: runtime_delta_ns as f64 / 1_000_000.0 / window_ms.max(1.0),
+                    counter_reset,
+                }
+            })
+            .collect();
+        self.last.retain(|id, _| live.contains(id));
+        cgroups.sort_by(|a, b| {
+            b.runtime_delta_ns
+                .cmp(&a.runtime_delta_ns)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        cgroups
+    }
+}
+
+/// Cgroup ids resolved to paths by walking cgroupfs, where a cgroup's id is
+/// the inode number of its directory.
+#[derive(Debug, Default)]
+pub struct CgroupPaths {
+    paths: HashMap<u64, String>,
+    /// Ids not found by a walk; cgroup ids are never reused, so they stay
+    /// unresolved instead of walking the tree again for them.
+    missing: HashSet<u64>,
+    last_walk: Option<Instant>,
+}
+
+impl CgroupPaths {
+    /// Resolve the ids of `ids` that can be, the map's live ids, walking
+    /// cgroupfs again when one was not seen before and the last walk is at
+    /// least [`WALK_INTERVAL`] old. Ids no longer live are forgotten.
+    pub fn refresh(&mut self, ids: impl IntoIterator<Item = u64>) {
+        let live: HashSet<u64> = ids.into_iter().collect();
+        self.paths.retain(|id, _| live.contains(id));
+        self.missing.retain(|id| live.contains(id));
+        let unknown: Vec<u64> = live
+            .iter()
+            .copied()
+            .filter(|id| !self.paths.contains_key(id) && !self.missing.contains(id))
+            .collect();
+        if unknown.is_empty()
+            || self
+                .last_walk
+                .is_some_and(|last| last.elapsed() < WALK_INTERVAL)
+        {
+            return;
+        }
+        self.last_walk = Some(Instant::now());
+        let mut paths = HashMap::new();
+        walk_cgroups(Path::new(CGROUP_ROOT), "", &mut paths);
+        paths.retain(|id, _| live.contains(id));
+        self.paths = paths;
+        self.missing.extend(
+            unknown
+                .into_iter()
+                .filter(|id| !self.paths.contains_key(id)),
+        );
+    }
+
+    pub fn get(&self, id: u64) -> Option<String> {
+        self.paths.get(&id).cloned()
+    }
+}
+
+fn walk_cgroups(dir: &Path, relative: &str, paths: &mut HashMap<u64, String>) {
+    let Ok(meta) = fs::metadata(dir) else {
+        return;
+    };
+    let path = if relative.is_empty() { "/" } else { relative };
+    paths.insert(meta.ino(), path.to_string());
+    let Ok(children) = fs::read_dir(dir) else {
+        return;
+    };
+    for child in children.flatten() {
+        if child.file_type().is_ok_and(|kind| kind.is_dir()) {
+            let name = child.file_name();
+            let relative = format!("{relative}/{}", name.to_string_lossy());
+            walk_cgroups(&child.path(), &relative, paths);
+        }
+    }
+}
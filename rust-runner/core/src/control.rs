@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::export::ChangeEvent;
+use crate::sched_attr::{SchedAttr, sched_getattr, sched_setattr_deadline};
+use crate::stats::{TaskSnapshot, nice_to_tickets};
+
+/// Outcome of a [`renice`]: the nice value before and after the change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Renice {
+    pub pid: u32,
+    pub old_nice: i32,
+    pub new_nice: i32,
+}
+
+impl Renice {
+    /// Tickets the BPF program assigned at the old nice value.
+    pub fn old_tickets(&self) -> u32 {
+        nice_to_tickets(self.old_nice)
+    }
+
+    /// Tickets the BPF program will assign from the task's next switch-in.
+    pub fn new_tickets(&self) -> u32 {
+        nice_to_tickets(self.new_nice)
+    }
+}
+
+/// Current nice value of `pid` (a thread id) via getpriority(2).
+pub fn nice_of(pid: u32) -> io::Result<i32> {
+    // -1 is a valid nice value, so errors are told apart through errno.
+    unsafe { *libc::__errno_location() = 0 };
+    let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t) };
+    if nice == -1 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(0) {
+            return Err(err);
+        }
+    }
+    Ok(nice)
+}
+
+/// Set the nice value of `pid` (a thread id, as keyed in `task_map`) via
+/// setpriority(2). Values are clamped to -20..=19; lowering nice needs
+/// `CAP_SYS_NICE` (or a matching RLIMIT_NICE).
+pub fn renice(pid: u32, nice: i32) -> io::Result<Renice> {
+    let old_nice = nice_of(pid)?;
+    let new_nice = nice.clamp(-20, 19);
+    let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, new_nice) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Renice {
+        pid,
+        old_nice,
+        new_nice,
+    })
+}
+
+/// Mount point of the unified (v2) cgroup hierarchy.
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Outcome of [`set_cgroup_weight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightChange {
+    /// Cgroup path relative to [`CGROUP_ROOT`], as shown in the `cgroup` column.
+    pub cgroup: String,
+    pub old_weight: u32,
+    pub new_weight: u32,
+}
+
+/// `cgroup` as shown in `/proc/<pid>/cgroup` (e.g. `/system.slice/x.service`);
+/// a full path under [`CGROUP_ROOT`] is accepted too. `.` and `..`
+/// components are rejected so the result cannot leave the hierarchy.
+pub fn normalize_cgroup(cgroup: &str) -> io::Result<String> {
+    let relative = cgroup.strip_prefix(CGROUP_ROOT).unwrap_or(cgroup);
+    let relative = relative.trim_matches('/');
+    if relative.split('/').any(|part| part == "." || part == "..") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cgroup path {cgroup} must not contain . or .."),
+        ));
+    }
+    Ok(format!("/{relative}"))
+}
+
+fn cgroup_dir(cgroup: &str) -> io::Result<PathBuf> {
+    Ok(PathBuf::from(CGROUP_ROOT).join(normalize_cgroup(cgroup)?.trim_start_matches('/')))
+}
+
+/// Current `cpu.weight` of `cgroup`.
+pub fn cgroup_weight(cgroup: &str) -> io::Result<u32> {
+    let raw = fs::read_to_string(cgroup_dir(cgroup)?.join("cpu.weight"))?;
+    raw.trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("cpu.weight: {err}")))
+}
+
+/// Write `cpu.weight` (1-10000, default 100) of `cgroup`. The cpu controller
+/// must be enabled in the parent's `cgroup.subtree_control`.
+pub fn set_cgroup_weight(cgroup: &str, weight: u32) -> io::Result<WeightChange> {
+    if !(1..=10_000).contains(&weight) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cpu.weight must be 1-10000, got {weight}"),
+        ));
+    }
+    let old_weight = cgroup_weight(cgroup)?;
+    fs::write(cgroup_dir(cgroup)?.join("cpu.weight"), weight.to_string())?;
+    Ok(WeightChange {
+        cgroup: normalize_cgroup(cgroup)?,
+        old_weight,
+        new_weight: weight,
+    })
+}
+
+/// Notices `cpu.weight` changes of the cgroups sampled tasks run in, made by
+/// `set-weight` or anything else, and reports them against every task of the
+/// cgroup's subtree so the samples that follow carry the change.
+#[derive(Debug, Default)]
+pub struct WeightWatcher {
+    /// Last weight read per cgroup; `None` where it cannot be read (no cpu
+    /// controller, or the root cgroup).
+    weights: HashMap<String, Option<u32>>,
+}
+
+impl WeightWatcher {
+    /// Read the weight of every cgroup in `snapshots` and return one
+    /// `cpu.weight` event per task under a cgroup whose weight changed
+    /// since the previous call. Cgroups seen for the first time only
+    /// establish their weight.
+    pub fn step(&mut self, snapshots: &[TaskSnapshot]) -> Vec<ChangeEvent> {
+        let cgroups: HashSet<&str> = snapshots
+            .iter()
+            .filter_map(|entry| entry.meta.cgroup.as_deref())
+            .collect();
+        let mut weights = HashMap::with_capacity(cgroups.len());
+        let mut events = Vec::new();
+        for cgroup in cgroups {
+            let weight = cgroup_weight(cgroup).ok();
+            if let (Some(Some(from)), Some(to)) = (self.weights.get(cgroup), weight)
+                && *from != to
+            {
+                let nested = format!("{}/", cgroup.trim_end_matches('/'));
+                events.extend(
+                    snapshots
+                        .iter()
+                        .filter(|entry| {
+                            entry
+                                .meta
+                                .cgroup
+                                .as_deref()
+                                .is_some_and(|path| path == cgroup || path.starts_with(&nested))
+                        })
+                        .map(|entry| ChangeEvent {
+                            pid: entry.pid,
+                            field: "cpu.weight",
+                            from: (*from).into(),
+                            to: to.into(),
+                            source: "cgroup",
+                            reason: format!("cgroup {cgroup} cpu.weight {from} -> {to}"),
+                        }),
+                );
+            }
+            weights.insert(cgroup.to_string(), weight);
+        }
+        self.weights = weights;
+        events
+    }
+}
+
+/// Current `cpu.max` of `cgroup` (`"<quota|max> <period>"`, in µs).
+pub fn cgroup_cpu_max(cgroup: &str) -> io::Result<String> {
+    Ok(fs::read_to_string(cgroup_dir(cgroup)?.join("cpu.max"))?
+        .trim()
+        .to_string())
+}
+
+/// Write `cpu.max` of `cgroup`, e.g. `"25000 100000"` for a quarter of one
+/// CPU or `"max 100000"` for no limit.
+pub fn set_cgroup_cpu_max(cgroup: &str, value: &str) -> io::Result<()> {
+    fs::write(cgroup_dir(cgroup)?.join("cpu.max"), value)
+}
+
+/// Send `signal` to `pid` via kill(2).
+pub fn signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Outcome of [`set_deadline`].
+#[derive(Debug, Clone, Copy)]
+pub struct DeadlineChange {
+    pub pid: u32,
+    /// Policy and parameters before the change, when they could be read.
+    pub previous: Option<SchedAttr>,
+    pub runtime: Duration,
+    pub deadline: Duration,
+    pub period: Duration,
+}
+
+/// Put `pid` under SCHED_DEADLINE with the given reservation. The kernel
+/// needs runtime <= deadline <= period and a runtime of at least 1 µs
+/// (1024 ns); those are checked up front for a clearer error than `EINVAL`.
+pub fn set_deadline(
+    pid: u32,
+    runtime: Duration,
+    deadline: Duration,
+    period: Duration,
+) -> io::Result<DeadlineChange> {
+    if runtime.as_nanos() < 1024 || runtime > deadline || deadline > period {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "need 1us <= runtime ({runtime:?}) <= deadline ({deadline:?}) <= period ({period:?})"
+            ),
+        ));
+    }
+    let previous = sched_getattr(pid);
+    sched_setattr_deadline(
+        pid,
+        runtime.as_nanos() as u64,
+        deadline.as_nanos() as u64,
+        period.as_nanos() as u64,
+    )?;
+    Ok(DeadlineChange {
+        pid,
+        previous,
+        runtime,
+        deadline,
+        period,
+    })
+}
@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cgroup::CgroupInfo;
+use crate::events::{SchedEvent, SchedEvents};
+use crate::irq::IrqTime;
+use crate::latency::TaskLatency;
+use crate::procfs::{TaskMeta, parse_container_id};
+use crate::runqueue::RunQueueDepth;
+use crate::sched_attr::{SchedAttr, SchedPolicy};
+use crate::stats::{SchedClass, TaskInfo, nice_to_tickets};
+use crate::stream::Clock;
+
+/// Pids handed to synthetic tasks start here, well clear of early boot pids.
+const FIRST_PID: u32 = 40_000;
+
+/// Ids handed to synthetic cgroups other than the root (id 1) start here.
+const FIRST_CGROUP_ID: u64 = 1_000;
+
+/// Mixed into the seed of [`DemoPopulation::detail`].
+const DETAIL_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// How nice values (and therefore tickets) are spread over the population.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum TicketDistribution {
+    /// Every fair task runs at nice 0 (100 tickets each).
+    Uniform,
+    /// Mostly nice 0, with some niced-down batch work and a few boosted tasks.
+    #[default]
+    Mixed,
+    /// A handful of heavily boosted tasks against a long tail at nice 19.
+    Skewed,
+}
+
+/// Shape of the generated population; see [`DemoPopulation`].
+#[derive(Debug, Clone)]
+pub struct DemoConfig {
+    /// Number of tasks to generate.
+    pub tasks: usize,
+    pub tickets: TicketDistribution,
+    /// 0 keeps every task steadily runnable; towards 1 tasks alternate
+    /// between bursts and idle stretches and their demand jitters more.
+    pub burstiness: f64,
+    /// CPUs the synthetic tasks compete for.
+    pub cpus: u32,
+    /// Seed for reproducible populations and traces.
+    pub seed: Option<u64>,
+    /// Give every fair task nice 0 once its nice is drawn: the tasks of the
+    /// same seed under `tickets`, with uniform tickets.
+    pub level_nice: bool,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            tasks: 24,
+            tickets: TicketDistribution::Mixed,
+            burstiness: 0.3,
+            cpus: std::thread::available_parallelism().map_or(4, |n| n.get() as u32),
+            seed: None,
+            level_nice: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Workload {
+    /// Wants a whole CPU while runnable.
+    CpuBound,
+    /// Short bursts with frequent sleeps.
+    Interactive,
+    /// Periodic real-time work that preempts the lottery.
+    RealTime,
+    /// Kernel housekeeping threads.
+    Kernel,
+}
+
+struct Template {
+    comm: &'static str,
+    user: &'static str,
+    uid: u32,
+    cgroup: &'static str,
+    workload: Workload,
+    threads: usize,
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        comm: "postgres",
+        user: "postgres",
+        uid: 113,
+        cgroup: "/system.slice/postgresql.service",
+        workload: Workload::Interactive,
+        threads: 4,
+    },
+    Template {
+        comm: "ffmpeg",
+        user: "alice",
+        uid: 1000,
+        cgroup: "/user.slice/user-1000.slice/session-2.scope",
+        workload: Workload::CpuBound,
+        threads: 3,
+    },
+    Template {
+        comm: "nginx",
+        user: "www-data",
+        uid: 33,
+        cgroup: "/system.slice/docker-4f3c2a9e8b7d6c5f4e3d2c1b0a9f8e7d6c5b4a3f2e1d0c9b8a7f6e5d4c3b2a19.scope",
+        workload: Workload::Interactive,
+        threads: 2,
+    },
+    Template {
+        comm: "rustc",
+        user: "alice",
+        uid: 1000,
+        cgroup: "/user.slice/user-1000.slice/session-2.scope",
+        workload: Workload::CpuBound,
+        threads: 2,
+    },
+    Template {
+        comm: "pipewire",
+        user: "alice",
+        uid: 1000,
+        cgroup: "/user.slice/user-1000.slice/user@1000.service/app.slice/pipewire.service",
+        workload: Workload::RealTime,
+        threads: 1,
+    },
+    Template {
+        comm: "kworker/u8:2",
+        user: "root",
+        uid: 0,
+        cgroup: "/",
+        workload: Workload::Kernel,
+        threads: 1,
+    },
+    Template {
+        comm: "sshd",
+        user: "root",
+        uid: 0,
+        cgroup: "/system.slice/ssh.service",
+        workload: Workload::Interactive,
+        threads: 1,
+    },
+    Template {
+        comm: "backup",
+        user: "root",
+        uid: 0,
+        cgroup: "/system.slice/backup.service",
+        workload: Workload::CpuBound,
+        threads: 1,
+    },
+];
+
+struct DemoTask {
+    pid: u32,
+    info: TaskInfo,
+    meta: TaskMeta,
+    workload: Workload,
+    /// Fraction of one CPU the task asks for while runnable.
+    demand: f64,
+    /// Typical time slice before the task yields or is preempted.
+    slice_ns: f64,
+    /// Tickets set through the override map, replacing the nice-derived count.
+    tickets_override: Option<u32>,
+    runnable: bool,
+    /// What the `task_latency` map would hold for the task.
+    latency: TaskLatency,
+    /// What the event maps would count for the task.
+    events: SchedEvents,
+    /// Id of the task's cgroup in [`DemoPopulation::cgroups`].
+    cgroup_id: u64,
+}
+
+/// A synthetic task population that evolves like a busy machine, so the
+/// whole sampling pipeline can run without root or a loaded BPF program.
+///
+/// Each read advances the model by the wall time since the previous read:
+/// runnable tasks compete for `cpus` CPUs, real-time tasks are served first,
+/// and the remaining capacity is handed out in proportion to tickets.
+pub struct DemoPopulation {
+    tasks: Vec<DemoTask>,
+    /// Index into `tasks` by pid; the population never changes size.
+    by_pid: HashMap<u32, usize>,
+    cpus: u32,
+    burstiness: f64,
+    rng: StdRng,
+    /// Draws added to the model since seeds were first published (switch
+    /// kinds, wakeup latency, events, interrupts) come from here, so a seed
+    /// keeps producing the tasks and runs it always did.
+    detail: StdRng,
+    last_read: Option<Instant>,
+    /// Run-queue depth of the last window; see [`runqueue`](Self::runqueue).
+    depth: Option<RunQueueDepth>,
+    /// Interrupt time of the last window; see [`irq`](Self::irq).
+    irq: Option<IrqTime>,
+    /// What the `cgroup_map` would hold: id, path, and counters of every
+    /// template's cgroup.
+    cgroups: Vec<(u64, &'static str, CgroupInfo)>,
+}
+
+impl DemoPopulation {
+    pub fn new(config: &DemoConfig) -> Self {
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let detail = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ DETAIL_SEED),
+            None => StdRng::from_entropy(),
+        };
+        let cpus = config.cpus.max(1);
+        let mut tasks = Vec::with_capacity(config.tasks);
+        let mut cgroups: Vec<(u64, &'static str, CgroupInfo)> = Vec::new();
+        let mut next_pid = FIRST_PID;
+        'spawn: for template in TEMPLATES.iter().cycle() {
+            if config.tasks == 0 {
+                break;
+            }
+            let tgid = next_pid;
+            for thread in 0..template.threads {
+                if tasks.len() == config.tasks {
+                    break 'spawn;
+                }
+                let pid = next_pid;
+                next_pid += rng.gen_range(1..4);
+                let nice = match template.workload {
+                    // The BPF program records `prio - 120`; RT priorities sit below -20.
+                    Workload::RealTime => -21 - rng.gen_range(1..50),
+                    Workload::Kernel => 0,
+                    _ => {
+                        // Drawn either way, so a levelled population keeps
+                        // the pids and workloads of the seed.
+                        let nice = pick_nice(config.tickets, &mut rng);
+                        if config.level_nice { 0 } else { nice }
+                    }
+                };
+                let runtime_ns = rng.gen_range(0..5_000_000_000);
+                let switches = rng.gen_range(0..50_000);
+                let cgroup_id = match cgroups.iter().find(|(_, path, _)| *path == template.cgroup) {
+                    Some((id, ..)) => *id,
+                    None => {
+                        let id = if template.cgroup == "/" {
+                            1
+                        } else {
+                            FIRST_CGROUP_ID + cgroups.len() as u64
+                        };
+                        cgroups.push((id, template.cgroup, CgroupInfo::default()));
+                        id
+                    }
+                };
+                tasks.push(DemoTask {
+                    pid,
+                    info: TaskInfo {
+                        runtime_ns,
+                        switches,
+                        nice,
+                        tickets: nice_to_tickets(nice),
+                        last_switch_in_ts: 0,
+                        preemptions: switches / 3,
+                    },
+                    meta: synth_meta(template, tgid, thread, nice, cpus, &mut rng),
+                    workload: template.workload,
+                    demand: match template.workload {
+                        Workload::CpuBound => 1.0,
+                        Workload::Interactive => rng.gen_range(0.05..0.4),
+                        Workload::RealTime => rng.gen_range(0.02..0.1),
+                        Workload::Kernel => rng.gen_range(0.005..0.03),
+                    },
+                    slice_ns: match template.workload {
+                        Workload::CpuBound => rng.gen_range(3.0e6..12.0e6),
+                        Workload::Interactive => rng.gen_range(0.2e6..2.0e6),
+                        Workload::RealTime => rng.gen_range(0.5e6..1.0e6),
+                        Workload::Kernel => rng.gen_range(0.05e6..0.3e6),
+                    },
+                    runnable: true,
+                    tickets_override: None,
+                    latency: TaskLatency::default(),
+                    events: SchedEvents {
+                        wakeups: Some(0),
+                        wakings: Some(0),
+                        migrations: Some(0),
+                    },
+                    cgroup_id,
+                });
+            }
+        }
+        // The cgroups start out with their tasks' history.
+        for task in &tasks {
+            if let Some((.., info)) = cgroups.iter_mut().find(|(id, ..)| *id == task.cgroup_id) {
+                info.runtime_ns += task.info.runtime_ns;
+                info.switches += task.info.switches;
+                info.preemptions += task.info.preemptions;
+                info.ticket_us += task.info.tickets as u64 * task.info.runtime_ns / 1_000;
+            }
+        }
+        let by_pid = tasks
+            .iter()
+            .enumerate()
+            .map(|(index, task)| (task.pid, index))
+            .collect();
+        Self {
+            tasks,
+            by_pid,
+            cpus,
+            burstiness: config.burstiness.clamp(0.0, 1.0),
+            rng,
+            detail,
+            last_read: None,
+            depth: None,
+            irq: None,
+            cgroups,
+        }
+    }
+
+    fn task(&self, pid: u32) -> Option<&DemoTask> {
+        self.by_pid.get(&pid).map(|index| &self.tasks[*index])
+    }
+
+    fn task_mut(&mut self, pid: u32) -> Option<&mut DemoTask> {
+        self.by_pid.get(&pid).map(|index| &mut self.tasks[*index])
+    }
+
+    /// Advance the model to now and return the map contents, keyed like the
+    /// real `task_map`.
+    pub fn read(&mut self) -> Vec<(u32, TaskInfo)> {
+        let now = Instant::now();
+        let elapsed = match self.last_read.replace(now) {
+            Some(last) => now - last,
+            None => Duration::ZERO,
+        };
+        // Stamped like bpf_ktime_get_ns so --clock aligns demo runs too.
+        let clock_ns = Clock::Monotonic.now_ns() as u64;
+        self.advance(elapsed.as_nanos() as f64, clock_ns);
+        self.tasks
+            .iter_mut()
+            .map(|task| {
+                if task.runnable {
+                    task.info.last_switch_in_ts = clock_ns;
+                }
+                (task.pid, task.info)
+            })
+            .collect()
+    }
+
+    /// CPUs the population competes for.
+    pub fn cpus(&self) -> u32 {
+        self.cpus
+    }
+
+    /// Mean runnable tasks per simulated CPU over the last window; `None`
+    /// before the first.
+    pub fn runqueue(&self) -> Option<RunQueueDepth> {
+        self.depth.clone()
+    }
+
+    /// Interrupt time of each simulated CPU over the last window; `None`
+    /// before the first.
+    pub fn irq(&self) -> Option<IrqTime> {
+        self.irq.clone()
+    }
+
+    /// The generated task's latency counters, keyed like `task_latency`.
+    pub fn latency(&self, pid: u32) -> Option<TaskLatency> {
+        self.task(pid).map(|task| task.latency)
+    }
+
+    /// The generated task's `event` count, keyed like its event map.
+    pub fn event_count(&self, pid: u32, event: SchedEvent) -> Option<u64> {
+        self.task(pid).and_then(|task| task.events.get(event))
+    }
+
+    /// The generated cgroups' counters, keyed like `cgroup_map`.
+    pub fn cgroups(&self) -> Vec<(u64, CgroupInfo)> {
+        self.cgroups
+            .iter()
+            .map(|(id, _, info)| (*id, *info))
+            .collect()
+    }
+
+    /// Path of a generated cgroup id.
+    pub fn cgroup_path(&self, id: u64) -> Option<&'static str> {
+        self.cgroups
+            .iter()
+            .find(|(known, ..)| *known == id)
+            .map(|(_, path, _)| *path)
+    }
+
+    /// Synthetic `/proc` attributes for a generated pid.
+    pub fn meta(&self, pid: u32) -> Option<&TaskMeta> {
+        self.task(pid).map(|task| &task.meta)
+    }
+
+    /// Change a generated task's nice value (and, for fair tasks, its
+    /// tickets); returns the previous nice, or `None` for an unknown pid.
+    pub fn renice(&mut self, pid: u32, nice: i32) -> Option<i32> {
+        let task = self.task_mut(pid)?;
+        let sched = task.meta.sched.as_mut()?;
+        let old = sched.nice;
+        sched.nice = nice;
+        // Real-time tasks keep their priority; nice only matters once they
+        // drop back to the fair class.
+        if !sched.policy.is_realtime() {
+            task.info.nice = nice;
+            task.info.tickets = task.tickets_override.unwrap_or(nice_to_tickets(nice));
+        }
+        Some(old)
+    }
+
+    /// Mirror of a `ticket_override` entry: `Some` pins the task's tickets,
+    /// `None` restores the nice-derived count. Returns `false` for an
+    /// unknown pid.
+    pub fn override_tickets(&mut self, pid: u32, tickets: Option<u32>) -> bool {
+        let Some(task) = self.task_mut(pid) else {
+            return false;
+        };
+        task.tickets_override = tickets;
+        if task.info.sched_class() == SchedClass::Fair {
+            task.info.tickets = tickets.unwrap_or(nice_to_tickets(task.info.nice));
+        }
+        true
+    }
+
+    fn advance(&mut self, window_ns: f64, clock_ns: u64) {
+        for task in self.tasks.iter_mut() {
+            // Two-state burst model: busier populations flip more often.
+            let flip = if task.runnable {
+                self.burstiness * 0.5
+            } else {
+                0.5
+            };
+            if self.rng.gen_bool(flip) {
+                task.runnable = !task.runnable;
+            }
+        }
+
+        let mut wants: HashMap<u32, f64> = HashMap::new();
+        for task in self.tasks.iter().filter(|task| task.runnable) {
+            let jitter = 1.0 + self.burstiness * self.rng.gen_range(-0.5..0.5);
+            wants.insert(task.pid, (task.demand * jitter).clamp(0.0, 1.0) * window_ns);
+        }
+
+        // Real-time and kernel work preempts the fair class.
+        let mut capacity = window_ns * self.cpus as f64;
+        let contention = wants.values().sum::<f64>() / capacity.max(1.0);
+        let mut granted: HashMap<u32, f64> = HashMap::new();
+        for task in &self.tasks {
+            if matches!(task.workload, Workload::RealTime | Workload::Kernel)
+                && let Some(&want) = wants.get(&task.pid)
+            {
+                let run = want.min(capacity);
+                capacity -= run;
+                granted.insert(task.pid, run);
+            }
+        }
+
+        // Water-fill the rest by tickets: tasks that want less than their
+        // share leave the remainder to the others.
+        let mut pending: Vec<&DemoTask> = self
+            .tasks
+            .iter()
+            .filter(|task| {
+                matches!(task.workload, Workload::CpuBound | Workload::Interactive)
+                    && wants.contains_key(&task.pid)
+            })
+            .collect();
+        while capacity > 1.0 && !pending.is_empty() {
+            let tickets: f64 = pending.iter().map(|task| task.info.tickets as f64).sum();
+            let mut handed_out = 0.0;
+            pending.retain(|task| {
+                let share = capacity * task.info.tickets as f64 / tickets;
+                let got = granted.entry(task.pid).or_default();
+                let run = share.min(wants[&task.pid] - *got);
+                *got += run;
+                handed_out += run;
+                wants[&task.pid] - *got > 1.0
+            });
+            capacity -= handed_out;
+            if handed_out < 1.0 {
+                break;
+            }
+        }
+
+        // A task is runnable, on the CPU it last ran on, for the share of
+        // the window it wanted: running, or waiting when the CPU is oversold.
+        let mut per_cpu = vec![0.0; self.cpus as usize];
+        for task in &self.tasks {
+            if let (Some(want), Some(cpu)) = (wants.get(&task.pid), task.meta.last_cpu)
+                && let Some(depth) = per_cpu.get_mut(cpu as usize)
+            {
+                *depth += want / window_ns.max(1.0);
+            }
+        }
+        self.depth = (window_ns > 0.0).then_some(RunQueueDepth {
+            per_cpu,
+            runnable: Some(wants.len() as u32),
+            estimated: false,
+        });
+        // A little hard-interrupt time everywhere, and softirq time that
+        // follows the switches, heaviest on CPU 0, which takes the NIC's
+        // receive queue.
+        let mut irq = vec![0.0; self.cpus as usize];
+        let mut softirq = vec![0.0; self.cpus as usize];
+        for cpu in 0..self.cpus as usize {
+            irq[cpu] = 0.002 + 0.004 * self.detail.r#gen::<f64>();
+            softirq[cpu] = (0.01 + 0.03 * contention.min(2.0)) * self.detail.gen_range(0.5..1.5);
+        }
+        softirq[0] += 0.05 * self.burstiness * self.detail.r#gen::<f64>();
+        self.irq = (window_ns > 0.0).then_some(IrqTime { irq, softirq });
+
+        for task in self.tasks.iter_mut() {
+            let run = granted.get(&task.pid).copied().unwrap_or_default();
+            // Still short of what it wanted, the task is left waiting with
+            // a pending wakeup, stamped when the wait began.
+            if wants.get(&task.pid).is_none_or(|want| want - run <= 1.0) {
+                task.latency.wakeup_ts = 0;
+            } else if task.latency.wakeup_ts == 0 {
+                task.latency.wakeup_ts = clock_ns;
+            }
+            if run <= 0.0 {
+                continue;
+            }
+            let switches = (run / task.slice_ns).ceil().max(1.0) as u64;
+            task.info.runtime_ns += run as u64;
+            task.info.switches += switches;
+            // CPU hogs mostly run out their slice and get preempted, the
+            // others mostly block; going short of what they wanted means
+            // more of their switches were forced.
+            let want = wants.get(&task.pid).copied().unwrap_or(run);
+            let starved = (1.0 - run / want.max(1.0)).clamp(0.0, 1.0);
+            let forced = match task.workload {
+                Workload::CpuBound => 0.7 + 0.3 * starved,
+                Workload::Interactive => 0.1 + 0.6 * starved,
+                Workload::RealTime => 0.02,
+                Workload::Kernel => 0.05 + 0.2 * starved,
+            };
+            let preempted = (switches as f64 * forced + self.detail.r#gen::<f64>()).floor() as u64;
+            task.info.preemptions += preempted.min(switches);
+            if let Some((.., cgroup)) = self
+                .cgroups
+                .iter_mut()
+                .find(|(id, ..)| *id == task.cgroup_id)
+            {
+                cgroup.runtime_ns += run as u64;
+                cgroup.switches += switches;
+                cgroup.preemptions += preempted.min(switches);
+                cgroup.ticket_us += task.info.tickets as u64 * run as u64 / 1_000;
+            }
+            // Every switch that blocked was woken again; a few more wakings
+            // found the task still runnable. Tasks that ran move to a random
+            // CPU, and more often in between when the CPUs are contended.
+            let woken = switches - preempted.min(switches);
+            let wakings = woken + self.detail.gen_range(0..=woken / 10);
+            let cpu = self.rng.gen_range(0..self.cpus);
+            let moved = if self.cpus > 1 {
+                u64::from(task.meta.last_cpu.is_some_and(|last| last != cpu))
+                    + (switches as f64 * 0.05 * contention * self.detail.r#gen::<f64>()) as u64
+            } else {
+                0
+            };
+            for (event, count) in [
+                (SchedEvent::Wakeup, woken),
+                (SchedEvent::Waking, wakings),
+                (SchedEvent::Migrate, moved),
+            ] {
+                task.events
+                    .set(event, Some(task.events.get(event).unwrap_or(0) + count));
+            }
+            task.meta.last_cpu = Some(cpu);
+            // Each switch-in ends a wait for the CPU: short for RT work,
+            // longer for fair tasks the busier the CPUs and the fewer
+            // their tickets, with an occasional long stall.
+            let mean_ns = match task.workload {
+                Workload::RealTime => 8_000.0,
+                Workload::Kernel => 20_000.0 * (1.0 + contention),
+                Workload::CpuBound | Workload::Interactive => {
+                    40_000.0
+                        * (1.0 + 4.0 * contention)
+                        * (100.0 / task.info.tickets.max(1) as f64).sqrt()
+                }
+            };
+            for _ in 0..switches {
+                let mut wait = -mean_ns * (1.0 - self.detail.r#gen::<f64>()).ln();
+                if self.detail.gen_bool(0.01) {
+                    wait *= 10.0;
+                }
+                task.latency.record(wait as u64);
+            }
+        }
+    }
+}
+
+fn pick_nice(distribution: TicketDistribution, rng: &mut StdRng) -> i32 {
+    match distribution {
+        TicketDistribution::Uniform => 0,
+        TicketDistribution::Mixed => match rng.gen_range(0..10) {
+            0 => rng.gen_range(-10..0),
+            1 | 2 => rng.gen_range(5..20),
+            _ => 0,
+        },
+        TicketDistribution::Skewed => match rng.gen_range(0..10) {
+            0 => rng.gen_range(-20..-10),
+            1 => 0,
+            _ => 19,
+        },
+    }
+}
+
+fn synth_meta(
+    template: &Template,
+    tgid: u32,
+    thread: usize,
+    nice: i32,
+    cpus: u32,
+    rng: &mut StdRng,
+) -> TaskMeta {
+    let kthread = matches!(template.workload, Workload::Kernel);
+    let sched = match template.workload {
+        Workload::RealTime => SchedAttr {
+            policy: SchedPolicy::Fifo,
+            nice: 0,
+            rt_priority: (-nice - 21) as u32,
+            dl_runtime_ns: 0,
+            dl_deadline_ns: 0,
+            dl_period_ns: 0,
+        },
+        _ => SchedAttr {
+            policy: SchedPolicy::Other,
+            nice,
+            rt_priority: 0,
+            dl_runtime_ns: 0,
+            dl_deadline_ns: 0,
+            dl_period_ns: 0,
+        },
+    };
+    let comm = if thread == 0 {
+        template.comm.to_string()
+    } else {
+        format!("{}:{thread}", template.comm)
+    };
+    TaskMeta {
+        cgroup: Some(template.cgroup.to_string()),
+        container_id: parse_container_id(template.cgroup),
+        pod_uid: None,
+        uid: Some(template.uid),
+        user: Some(template.user.to_string()),
+        cmdline: (!kthread).then(|| format!("/usr/bin/{}", template.comm)),
+        tgid: Some(tgid),
+        ppid: Some(if kthread { 2 } else { 1 }),
+        comm: Some(comm),
+        cpus_allowed: (0..cpus).collect(),
+        last_cpu: Some(rng.gen_range(0..cpus)),
+        sched: Some(sched),
+        kthread,
+        start_time: None,
+    }
+}
@@ -0,0 +1,128 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+
+use crate::error::RunnerError;
+
+const CAP_DAC_OVERRIDE: u32 = 1;
+pub(crate) const CAP_SYS_ADMIN: u32 = 21;
+pub(crate) const CAP_BPF: u32 = 39;
+
+/// Effective capabilities of this process, from `/proc/self/status`.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    effective: u64,
+}
+
+impl Capabilities {
+    pub fn current() -> Option<Self> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let hex = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))?
+            .trim();
+        let effective = u64::from_str_radix(hex, 16).ok()?;
+        Some(Self { effective })
+    }
+
+    pub fn has(&self, cap: u32) -> bool {
+        self.effective & (1 << cap) != 0
+    }
+
+    /// Whether bpf(2) map commands are allowed regardless of the
+    /// `unprivileged_bpf_disabled` sysctl.
+    pub fn bpf_privileged(&self) -> bool {
+        self.has(CAP_BPF) || self.has(CAP_SYS_ADMIN)
+    }
+}
+
+/// `kernel.unprivileged_bpf_disabled`: 0 lets unprivileged users call
+/// bpf(2), 1 forbids it until reboot, 2 forbids it until an admin resets it.
+pub fn unprivileged_bpf_disabled() -> Option<u8> {
+    fs::read_to_string("/proc/sys/kernel/unprivileged_bpf_disabled")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// `(major, minor)` of the running kernel.
+fn kernel_version() -> Option<(u32, u32)> {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    let mut parts = release.trim().split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Why a map could not be opened or read, and how to fix it, for a
+/// permission error on the map at `path`; empty for other errors. Checks
+/// the capabilities we run with, the unprivileged-BPF sysctl, and the pin's
+/// file permissions, since the bare errno cannot tell them apart.
+pub fn permission_hints(err: &RunnerError) -> Vec<String> {
+    let (path, source) = match err {
+        RunnerError::MapOpen { path, source } => (Some(path.as_str()), source),
+        RunnerError::MapIterate { source, .. } => (None, source),
+        _ => return Vec::new(),
+    };
+    if source.kind() != io::ErrorKind::PermissionDenied {
+        return Vec::new();
+    }
+
+    let mut hints = Vec::new();
+    let caps = Capabilities::current();
+    let privileged = caps.is_some_and(|caps| caps.bpf_privileged());
+    // CAP_BPF was split out of CAP_SYS_ADMIN in 5.8.
+    let cap_bpf = kernel_version().is_none_or(|version| version >= (5, 8));
+    let needed = if cap_bpf {
+        "CAP_BPF (or CAP_SYS_ADMIN)"
+    } else {
+        "CAP_SYS_ADMIN (this kernel predates CAP_BPF)"
+    };
+    let setcap = if cap_bpf {
+        "cap_bpf,cap_dac_override"
+    } else {
+        "cap_sys_admin,cap_dac_override"
+    };
+
+    match (caps, unprivileged_bpf_disabled()) {
+        (None, _) => hints.push(format!(
+            "could not read our capabilities from /proc/self/status; reading the map needs {needed}"
+        )),
+        (Some(_), Some(disabled @ 1..)) if !privileged => {
+            let sysctl = if disabled == 1 {
+                "it is 1, which cannot be undone before a reboot"
+            } else {
+                "an admin can allow unprivileged use with `sysctl kernel.unprivileged_bpf_disabled=0`"
+            };
+            hints.push(format!(
+                "kernel.unprivileged_bpf_disabled={disabled} restricts bpf(2) to processes with {needed}, which this one lacks; run it with sudo or grant it: `sudo setcap {setcap}+ep $(command -v rust-runner)` ({sysctl})"
+            ));
+        }
+        (Some(_), _) if !privileged => hints.push(format!(
+            "this process lacks {needed}; unless the pin's permissions allow our user, run with sudo or `sudo setcap {setcap}+ep $(command -v rust-runner)`"
+        )),
+        _ => {}
+    }
+
+    if let Some(path) = path
+        && !readable(path)
+        && !caps.is_some_and(|caps| caps.has(CAP_DAC_OVERRIDE))
+    {
+        hints.push(format!(
+            "{path} (or a directory above it) is not readable by uid {}; bpffs is mounted 0700 by default. `sudo ./scripts/run.sh load` relaxes the pin permissions, or grant CAP_DAC_OVERRIDE",
+            unsafe { libc::geteuid() }
+        ));
+    }
+
+    if hints.is_empty() {
+        hints.push(format!(
+            "we have {needed} and can reach the pin, so an LSM (SELinux, AppArmor, a BPF LSM program) probably refused access; check the audit log"
+        ));
+    }
+    hints
+}
+
+fn readable(path: &str) -> bool {
+    CString::new(path).is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::R_OK) } == 0)
+}
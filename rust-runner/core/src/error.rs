@@ -0,0 +1,135 @@
+use std::io;
+
+use thiserror::Error;
+
+/// Everything that can stop a sampling run, with enough context (pin path,
+/// pid, exporter) for callers to react without parsing messages.
+#[derive(Debug, Error)]
+pub enum RunnerError {
+    #[error("cannot open pinned map {path}: {source}")]
+    MapOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to read map {map}{}: {source}", pid.map(|pid| format!(" at pid {pid}")).unwrap_or_default())]
+    MapIterate {
+        map: String,
+        pid: Option<u32>,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error(
+        "map {path} has {key_size}-byte keys and {value_size}-byte values; expected 4-byte pids and {}-byte struct task_info values (or {} bytes for objects built before preemptions, {} before last_switch_in_ts); rebuild the BPF program and the runner from the same tree",
+        crate::bpf_map::ValueLayout::Current.size(),
+        crate::bpf_map::ValueLayout::NoPreemptions.size(),
+        crate::bpf_map::ValueLayout::NoSwitchIn.size()
+    )]
+    ValueSizeMismatch {
+        path: String,
+        key_size: u32,
+        value_size: u32,
+    },
+
+    #[error("exporter {name} failed: {source}")]
+    Export {
+        name: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("terminal error: {0}")]
+    Terminal(#[source] io::Error),
+
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("cannot adjust {target}: {source}")]
+    Control {
+        /// What was being changed, e.g. `pid 42` or `cgroup /system.slice`.
+        target: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("cannot switch to user {user} ({step}): {source}")]
+    PrivilegeDrop {
+        user: String,
+        /// The failing step, e.g. `setresuid` or `capset`.
+        step: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("remote agent {addr}: {source}")]
+    Remote {
+        addr: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("cannot attach the BPF program ({step}): {source}")]
+    Attach {
+        /// The failing step, e.g. `load sched_lottery.bpf.o` or
+        /// `pin map task_map at /sys/fs/bpf/task_map`.
+        step: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("thresholds violated: {0}")]
+    ThresholdViolated(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl RunnerError {
+    /// Stable, machine-readable tag for logs and wrapper scripts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RunnerError::MapOpen { source, .. } => match source.kind() {
+                io::ErrorKind::NotFound => "map_missing",
+                io::ErrorKind::PermissionDenied => "permission_denied",
+                _ => "map_open",
+            },
+            RunnerError::MapIterate { source, .. }
+                if source.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                "permission_denied"
+            }
+            RunnerError::MapIterate { .. } => "map_iterate",
+            RunnerError::ValueSizeMismatch { .. } => "value_size_mismatch",
+            RunnerError::Export { .. } => "export",
+            RunnerError::Terminal(_) => "terminal",
+            RunnerError::Config(_) => "config",
+            RunnerError::Control { source, .. } => match source.kind() {
+                io::ErrorKind::PermissionDenied => "permission_denied",
+                _ => "control",
+            },
+            RunnerError::PrivilegeDrop { .. } => "privilege_drop",
+            RunnerError::Remote { .. } => "remote",
+            RunnerError::Attach { source, .. } => match source.kind() {
+                io::ErrorKind::PermissionDenied => "permission_denied",
+                _ => "attach",
+            },
+            RunnerError::ThresholdViolated(_) => "threshold_violated",
+            RunnerError::Io(_) => "io",
+        }
+    }
+
+    pub(crate) fn in_map(self, label: &str) -> Self {
+        match self {
+            RunnerError::MapIterate { pid, source, .. } => RunnerError::MapIterate {
+                map: label.to_string(),
+                pid,
+                source,
+            },
+            other => other,
+        }
+    }
+}
+
+pub type Result<T, E = RunnerError> = std::result::Result<T, E>;
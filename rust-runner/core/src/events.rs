@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::Serialize;
+
+/// Scheduler tracepoints counted besides `sched_switch`, each into its own
+/// map pinned next to the task map.
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchedEvent {
+    /// `sched_wakeup` and `sched_wakeup_new`: the task became runnable.
+    Wakeup,
+    /// `sched_waking`: a wakeup was issued, also for tasks that were
+    /// already runnable.
+    Waking,
+    /// `sched_migrate_task`: the task moved to another CPU's run queue.
+    Migrate,
+}
+
+impl SchedEvent {
+    pub const ALL: [SchedEvent; 3] = [SchedEvent::Wakeup, SchedEvent::Waking, SchedEvent::Migrate];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SchedEvent::Wakeup => "wakeup",
+            SchedEvent::Waking => "waking",
+            SchedEvent::Migrate => "migrate",
+        }
+    }
+
+    /// Name of the BPF map, and of its pin next to the task map.
+    pub fn map_name(self) -> &'static str {
+        match self {
+            SchedEvent::Wakeup => "wakeup_events",
+            SchedEvent::Waking => "waking_events",
+            SchedEvent::Migrate => "migrate_events",
+        }
+    }
+
+    /// BPF program counting the event.
+    pub fn program(self) -> &'static str {
+        match self {
+            SchedEvent::Wakeup => "count_sched_wakeup",
+            SchedEvent::Waking => "count_sched_waking",
+            SchedEvent::Migrate => "count_sched_migrate_task",
+        }
+    }
+
+    /// Tracepoints the program is attached to, with the suffix their links
+    /// are pinned under next to the `sched_switch` link.
+    pub fn tracepoints(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            SchedEvent::Wakeup => &[
+                ("sched:sched_wakeup", "_wakeup_events"),
+                ("sched:sched_wakeup_new", "_wakeup_new_events"),
+            ],
+            SchedEvent::Waking => &[("sched:sched_waking", "_waking_events")],
+            SchedEvent::Migrate => &[("sched:sched_migrate_task", "_migrate_events")],
+        }
+    }
+}
+
+impl fmt::Display for SchedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Mirror of the BPF program's `struct sched_event_count`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EventCount {
+    pub count: u64,
+    /// `bpf_ktime_get_ns` of the latest event.
+    pub last_ts: u64,
+    /// CPU of the latest event: the wakeup target or migration destination.
+    pub cpu: u32,
+    pub _pad: u32,
+}
+
+/// A task's scheduler events over one window; `None` for events that are
+/// not monitored, and in the window a pid is first seen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SchedEvents {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wakeups: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wakings: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrations: Option<u64>,
+}
+
+impl SchedEvents {
+    pub fn get(&self, event: SchedEvent) -> Option<u64> {
+        match event {
+            SchedEvent::Wakeup => self.wakeups,
+            SchedEvent::Waking => self.wakings,
+            SchedEvent::Migrate => self.migrations,
+        }
+    }
+
+    pub fn set(&mut self, event: SchedEvent, count: Option<u64>) {
+        match event {
+            SchedEvent::Wakeup => self.wakeups = count,
+            SchedEvent::Waking => self.wakings = count,
+            SchedEvent::Migrate => self.migrations = count,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        SchedEvent::ALL
+            .iter()
+            .all(|event| self.get(*event).is_none())
+    }
+
+    /// Add `other`'s counts; an event counts as monitored when either side
+    /// has it.
+    pub fn merge(&mut self, other: &Self) {
+        for event in SchedEvent::ALL {
+            if let Some(count) = other.get(event) {
+                self.set(event, Some(self.get(event).unwrap_or(0) + count));
+            }
+        }
+    }
+
+    /// The events of every task together.
+    pub fn merged<'a>(events: impl IntoIterator<Item = &'a SchedEvents>) -> Self {
+        events
+            .into_iter()
+            .fold(Self::default(), |mut merged, events| {
+                merged.merge(events);
+                merged
+            })
+    }
+}
+
+/// Last [`EventCount`] of every monitored event and pid, to turn the
+/// cumulative counters into per-window [`SchedEvents`].
+#[derive(Debug, Default)]
+pub struct EventTracker {
+    last: HashMap<(SchedEvent, u32), u64>,
+}
+
+impl EventTracker {
+    /// Events of `pid` since its last reading; `None` for its first reading
+    /// and after its counter started over.
+    pub fn update(&mut self, event: SchedEvent, pid: u32, count: u64) -> Option<u64> {
+        let before = self.last.insert((event, pid), count)?;
+        count.checked_sub(before)
+    }
+
+    pub fn forget(&mut self, pid: u32) {
+        self.last.retain(|(_, known), _| *known != pid);
+    }
+
+    pub fn retain(&mut self, live: &HashSet<u32>) {
+        self.last.retain(|(_, pid), _| live.contains(pid));
+    }
+}
@@ -0,0 +1,1804 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+use crate::cgroup::CgroupSnapshot;
+use crate::compress::Compression;
+use crate::events::{SchedEvent, SchedEvents};
+use crate::inflation::InflationTracker;
+use crate::irq::IrqTime;
+use crate::latency::WakeupLatency;
+use crate::priority::PriorityReport;
+use crate::procfs::format_cpu_list;
+use crate::rotate::{RotatingFile, Rotation};
+use crate::runqueue::RunQueueDepth;
+use crate::sketch::QuantileSketch;
+use crate::stats::{
+    TaskSnapshot, pearson, per_cpu_utilization, preemption_rate, runtime_gini, runtime_lorenz,
+    system_utilization, window_fairness,
+};
+use crate::stream::{Clock, STALE_WINDOWS};
+
+/// One sampling window as handed to every registered [`Exporter`].
+#[derive(Debug, Clone)]
+pub struct SnapshotBatch {
+    /// Label of the source map; several maps may be sampled in lockstep.
+    pub map: String,
+    pub iteration: u32,
+    pub timestamp: f64,
+    pub total_tickets: u64,
+    pub window_ms: f64,
+    pub snapshots: Vec<TaskSnapshot>,
+    /// Tasks of the map that a selection or display filter left out of
+    /// `snapshots`; only filled when an exporter needs every task, like
+    /// [`AuditLog`].
+    pub filtered_out: Vec<TaskSnapshot>,
+    /// Scheduling changes made in this window.
+    pub events: Vec<ChangeEvent>,
+    /// See [`SnapshotWindow::consistent`](crate::stream::SnapshotWindow::consistent).
+    pub consistent: bool,
+    /// Clock of `timestamp`.
+    pub clock: Clock,
+    /// See [`SnapshotWindow::ktime_offset_s`](crate::stream::SnapshotWindow::ktime_offset_s).
+    pub ktime_offset_s: Option<f64>,
+    /// CPUs of the sampled host.
+    pub cpus: usize,
+    /// See [`SnapshotWindow::unchanged_windows`](crate::stream::SnapshotWindow::unchanged_windows).
+    pub unchanged_windows: u32,
+    /// See [`SnapshotWindow::runqueue`](crate::stream::SnapshotWindow::runqueue).
+    pub runqueue: Option<RunQueueDepth>,
+    /// See [`SnapshotWindow::irq`](crate::stream::SnapshotWindow::irq).
+    pub irq: Option<IrqTime>,
+    /// Lateness of the last few windows, this one included, and how many
+    /// windows that is; `None` when no horizon is kept.
+    pub lateness_horizon: Option<(usize, QuantileSketch)>,
+    /// See [`SnapshotWindow::cgroups`](crate::stream::SnapshotWindow::cgroups).
+    pub cgroups: Vec<CgroupSnapshot>,
+}
+
+impl SnapshotBatch {
+    /// Lateness of every task in the window.
+    pub fn lateness_sketch(&self) -> QuantileSketch {
+        self.snapshots.iter().map(|snap| snap.lateness_ms).collect()
+    }
+
+    /// The [`lateness_horizon`](Self::lateness_horizon) sketch, or the
+    /// window's own without one: what the metric sinks report as p95/p99.
+    pub fn lateness_horizon_sketch(&self) -> QuantileSketch {
+        self.lateness_horizon
+            .as_ref()
+            .map_or_else(|| self.lateness_sketch(), |(_, sketch)| sketch.clone())
+    }
+
+    /// See [`SnapshotWindow::stale`](crate::stream::SnapshotWindow::stale).
+    pub fn stale(&self) -> bool {
+        self.unchanged_windows >= STALE_WINDOWS
+    }
+
+    /// When the task was last switched in, in seconds on [`clock`](Self::clock);
+    /// `None` when the source does not record it or its kernel clock cannot
+    /// be placed on ours.
+    pub fn last_switch_in_secs(&self, entry: &TaskSnapshot) -> Option<f64> {
+        let ts = entry.info.last_switch_in_ts;
+        let offset = self.ktime_offset_s?;
+        (ts > 0).then(|| {
+            let secs = ts as f64 / 1_000_000_000.0 + offset;
+            (secs * 1_000_000.0).round() / 1_000_000.0
+        })
+    }
+
+    /// Run-queue depth of the CPU the task last ran on.
+    pub fn rq_depth(&self, entry: &TaskSnapshot) -> Option<f64> {
+        self.runqueue.as_ref()?.cpu(entry.meta.last_cpu?)
+    }
+
+    /// How the lateness of the window's tasks follows the run-queue depth
+    /// of their CPUs; see [`pearson`].
+    pub fn depth_lateness_correlation(&self) -> Option<f64> {
+        pearson(
+            self.snapshots
+                .iter()
+                .filter_map(|entry| self.rq_depth(entry).map(|depth| (depth, entry.lateness_ms))),
+        )
+    }
+
+    /// Share of the time the task's last CPU spent in hard and soft
+    /// interrupts.
+    pub fn cpu_interrupt(&self, entry: &TaskSnapshot) -> Option<f64> {
+        self.irq.as_ref()?.cpu(entry.meta.last_cpu?)
+    }
+
+    /// How the lateness of the window's tasks follows the interrupt time
+    /// of their CPUs; see [`pearson`].
+    pub fn interrupt_lateness_correlation(&self) -> Option<f64> {
+        pearson(self.snapshots.iter().filter_map(|entry| {
+            self.cpu_interrupt(entry)
+                .map(|share| (share, entry.lateness_ms))
+        }))
+    }
+
+    /// Window-wide aggregates shared by the metric sinks.
+    pub fn totals(&self) -> BatchTotals {
+        let mut totals = BatchTotals {
+            tasks: self.snapshots.len(),
+            active: 0,
+            overdue: 0,
+            starved: 0,
+            runtime_ms: 0.0,
+            utilization: 0.0,
+            system_utilization: system_utilization(&self.snapshots, self.window_ms, self.cpus),
+            runtime_gini: runtime_gini(&self.snapshots),
+            fairness_index: window_fairness(&self.snapshots),
+            worst_lateness_ms: None,
+            switches: 0,
+            preempt_delta: None,
+            latency: WakeupLatency::merged(
+                self.snapshots
+                    .iter()
+                    .filter_map(|snap| snap.latency.as_ref()),
+            ),
+            events: SchedEvents::merged(self.snapshots.iter().map(|snap| &snap.events)),
+        };
+        for snap in &self.snapshots {
+            totals.active += usize::from(snap.runtime_delta_ns > 0);
+            totals.overdue += usize::from(snap.deadline_missed());
+            totals.starved += usize::from(snap.starved);
+            totals.runtime_ms += snap.runtime_delta_ms();
+            totals.utilization += snap.utilization;
+            totals.switches += snap.switch_delta;
+            if let Some(preempted) = snap.preempt_delta {
+                *totals.preempt_delta.get_or_insert_default() += preempted;
+            }
+            totals.worst_lateness_ms = Some(
+                totals
+                    .worst_lateness_ms
+                    .map_or(snap.lateness_ms, |worst| worst.max(snap.lateness_ms)),
+            );
+        }
+        totals
+    }
+}
+
+/// See [`SnapshotBatch::totals`].
+#[derive(Debug, Clone, Copy)]
+pub struct BatchTotals {
+    pub tasks: usize,
+    /// Tasks that ran in the window.
+    pub active: usize,
+    /// Tasks past their deadline.
+    pub overdue: usize,
+    /// Tasks flagged as [`starved`](TaskSnapshot::starved).
+    pub starved: usize,
+    /// Runtime of all tasks in the window.
+    pub runtime_ms: f64,
+    /// Sum of the per-task utilizations, each relative to its own period.
+    pub utilization: f64,
+    /// Share of the host's CPU capacity used; see [`system_utilization`].
+    pub system_utilization: f64,
+    /// How unevenly the runtime was spread over the tasks; see
+    /// [`runtime_gini`].
+    pub runtime_gini: Option<f64>,
+    /// How closely runtime followed the ticket shares; see
+    /// [`window_fairness`].
+    pub fairness_index: Option<f64>,
+    /// `None` for an empty window.
+    pub worst_lateness_ms: Option<f64>,
+    /// Switches of all tasks in the window.
+    pub switches: u64,
+    /// Of `switches`, the preemptions; `None` when the map does not count
+    /// them.
+    pub preempt_delta: Option<u64>,
+    /// Wakeup latency of all tasks together; `None` when none waited or
+    /// the source records no latency.
+    pub latency: Option<WakeupLatency>,
+    /// Scheduler events of all tasks together.
+    pub events: SchedEvents,
+}
+
+impl BatchTotals {
+    /// See [`TaskSnapshot::preemption_rate`].
+    pub fn preemption_rate(&self) -> Option<f64> {
+        preemption_rate(self.preempt_delta, self.switches)
+    }
+}
+
+/// A nice or ticket change, exported next to the task records.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    pub pid: u32,
+    /// `nice`, `tickets`, or `cpu.weight`.
+    pub field: &'static str,
+    pub from: i64,
+    pub to: i64,
+    /// What made the change, e.g. `auto-nice`.
+    pub source: &'static str,
+    pub reason: String,
+}
+
+/// A sink for enriched windows. New outputs implement this and get
+/// registered by the CLI instead of being threaded through the dump loop.
+pub trait Exporter: Send {
+    /// Short label used in logs and error messages.
+    fn name(&self) -> &str;
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()>;
+
+    /// Called once after the last window (also on interrupt); flush or
+    /// finalize buffered output here.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub const CSV_COLUMNS: [&str; 24] = [
+    "iteration",
+    "timestamp_s",
+    "pid",
+    "runtime_ns",
+    "runtime_ms",
+    "delta_ns",
+    "delta_ms",
+    "rolling_runtime_ms",
+    "switches",
+    "nice",
+    "tickets",
+    "ticket_share",
+    "estimated_period_ms",
+    "lateness_ms",
+    "utilization",
+    "container_id",
+    "user",
+    "cmdline",
+    "last_cpu",
+    "cpus_allowed",
+    "policy",
+    "rt_priority",
+    "kthread",
+    "map",
+];
+
+/// Optional CSV columns, only written when selected explicitly (see
+/// [`CsvExporter::with_columns`]).
+pub const CSV_EXTRA_COLUMNS: [&str; 30] = [
+    "comm",
+    "tgid",
+    "cgroup",
+    "switch_delta",
+    "voluntary_switches",
+    "involuntary_switches",
+    "preemption_rate",
+    "deadline_ms",
+    "response_time_ms",
+    "dl_runtime_ms",
+    "dl_deadline_ms",
+    "dl_period_ms",
+    "counter_reset",
+    "starved_windows",
+    "starved",
+    "windows_observed",
+    "windows_missed",
+    "miss_rate",
+    "wakeups",
+    "latency_mean_ms",
+    "latency_p99_ms",
+    "latency_max_ms",
+    "sched_wakeups",
+    "sched_wakings",
+    "sched_migrations",
+    "rq_depth",
+    "cpu_interrupt",
+    "entity",
+    "cgroup_id",
+    "clock",
+];
+
+/// Columns of the per-window aggregate rows, appended to the task columns
+/// (see [`CsvExporter::with_columns`]). `record` tells the rows apart:
+/// `task` and `cgroup` rows leave the other aggregate columns empty, and
+/// `summary` rows fill only these, `iteration`, `timestamp_s`, and `map`.
+pub const CSV_SUMMARY_COLUMNS: [&str; 8] = [
+    "record",
+    "tasks",
+    "overdue_tasks",
+    "starved_tasks",
+    "total_runtime_ms",
+    "total_tickets",
+    "fairness_index",
+    "runtime_gini",
+];
+
+/// Field delimiter and decimal separator used for CSV rows.
+#[derive(Clone, Copy, Debug)]
+pub struct CsvFormat {
+    delimiter: char,
+    decimal: char,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            decimal: '.',
+        }
+    }
+}
+
+impl CsvFormat {
+    pub fn new(delimiter: char, decimal: char) -> io::Result<Self> {
+        if delimiter == decimal {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CSV delimiter and decimal separator must differ (both are '{delimiter}')"),
+            ));
+        }
+        // A digit or a minus sign on either side would make numbers ambiguous.
+        if matches!(delimiter, '"' | '\n' | '\r' | '-')
+            || delimiter.is_ascii_digit()
+            || decimal.is_ascii_digit()
+            || decimal == '-'
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported CSV delimiter/decimal combination",
+            ));
+        }
+        Ok(Self { delimiter, decimal })
+    }
+
+    pub fn float(&self, value: f64, precision: usize) -> String {
+        let text = format!("{value:.precision$}");
+        if self.decimal == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal.to_string())
+        }
+    }
+
+    /// Quote free-form text when it would otherwise break the row.
+    pub fn text(&self, value: &str) -> String {
+        if value.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn join(&self, fields: &[String]) -> String {
+        fields.join(&self.delimiter.to_string())
+    }
+}
+
+/// Appends one CSV row per task and window; the header is written only when
+/// the file is empty so repeated runs can share a file. Every rotated file
+/// starts with its own header. With the `entity` column selected, the
+/// window's [cgroups](SnapshotBatch::cgroups) follow as `cgroup` rows, and
+/// with the `record` column, every window also gets one aggregate row; see
+/// [`CSV_SUMMARY_COLUMNS`].
+pub struct CsvExporter {
+    file: RotatingFile,
+    format: CsvFormat,
+    columns: Vec<&'static str>,
+    cgroups: bool,
+    summary: bool,
+}
+
+impl CsvExporter {
+    pub fn open(
+        path: &Path,
+        format: CsvFormat,
+        rotation: Rotation,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        Self::with_columns(path, format, rotation, compression, &CSV_COLUMNS)
+    }
+
+    /// Like [`open`](Self::open) with a chosen subset and order of columns
+    /// from [`CSV_COLUMNS`], [`CSV_EXTRA_COLUMNS`], and
+    /// [`CSV_SUMMARY_COLUMNS`].
+    pub fn with_columns(
+        path: &Path,
+        format: CsvFormat,
+        rotation: Rotation,
+        compression: Compression,
+        columns: &[&str],
+    ) -> io::Result<Self> {
+        let columns = columns
+            .iter()
+            .map(|name| {
+                CSV_COLUMNS
+                    .iter()
+                    .chain(CSV_EXTRA_COLUMNS.iter())
+                    .chain(CSV_SUMMARY_COLUMNS.iter())
+                    .find(|known| *known == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("unknown CSV column `{name}`"),
+                        )
+                    })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let file = RotatingFile::open(path, rotation, compression)?;
+        let mut exporter = Self {
+            file,
+            format,
+            cgroups: columns.contains(&"entity"),
+            summary: columns.contains(&"record"),
+            columns,
+        };
+        if exporter.file.is_empty() {
+            exporter.write_header()?;
+        }
+        debug!(target: "exporter", path = %path.display(), "appending CSV rows");
+        Ok(exporter)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let header: Vec<String> = self.columns.iter().map(|c| c.to_string()).collect();
+        writeln!(self.file, "{}", self.format.join(&header))
+    }
+}
+
+/// Value of CSV column `name` for one task.
+fn csv_field(
+    format: &CsvFormat,
+    batch: &SnapshotBatch,
+    entry: &TaskSnapshot,
+    name: &str,
+) -> String {
+    let meta = &entry.meta;
+    match name {
+        "iteration" => (batch.iteration + 1).to_string(),
+        "timestamp_s" => format.float(batch.timestamp, 6),
+        "clock" => batch.clock.as_str().to_string(),
+        "pid" => entry.pid.to_string(),
+        "runtime_ns" => entry.info.runtime_ns.to_string(),
+        "runtime_ms" => format.float(entry.info.runtime_ms(), 3),
+        "delta_ns" => entry.runtime_delta_ns.to_string(),
+        "delta_ms" => format.float(entry.runtime_delta_ms(), 3),
+        "rolling_runtime_ms" => format.float(entry.rolling_runtime_ms, 3),
+        "switches" => entry.info.switches.to_string(),
+        "switch_delta" => entry.switch_delta.to_string(),
+        "nice" => entry.info.nice.to_string(),
+        "tickets" => entry.info.tickets.to_string(),
+        "ticket_share" => format.float(entry.ticket_share, 6),
+        "estimated_period_ms" => format.float(entry.estimated_period_ms, 3),
+        "deadline_ms" => format.float(entry.deadline_ms, 3),
+        "response_time_ms" => entry
+            .response_time_ms
+            .map(|ms| format.float(ms, 3))
+            .unwrap_or_default(),
+        "dl_runtime_ms" | "dl_deadline_ms" | "dl_period_ms" => {
+            match meta.sched.and_then(|attr| attr.dl_params_ms()) {
+                Some((runtime, deadline, period)) => format.float(
+                    match name {
+                        "dl_runtime_ms" => runtime,
+                        "dl_deadline_ms" => deadline,
+                        _ => period,
+                    },
+                    3,
+                ),
+                None => String::new(),
+            }
+        }
+        "lateness_ms" => format.float(entry.lateness_ms, 3),
+        "utilization" => format.float(entry.utilization, 3),
+        "container_id" => meta.container_id.clone().unwrap_or_default(),
+        "user" => format.text(&meta.user_label()),
+        "cmdline" => format.text(meta.cmdline.as_deref().unwrap_or_default()),
+        "comm" => format.text(meta.comm.as_deref().unwrap_or_default()),
+        "tgid" => meta.tgid.map(|tgid| tgid.to_string()).unwrap_or_default(),
+        "cgroup" => format.text(meta.cgroup.as_deref().unwrap_or_default()),
+        "last_cpu" => meta.last_cpu.map(|cpu| cpu.to_string()).unwrap_or_default(),
+        "cpus_allowed" => format.text(&format_cpu_list(&meta.cpus_allowed)),
+        "policy" => meta
+            .sched
+            .map(|attr| attr.policy.to_string())
+            .unwrap_or_default(),
+        "rt_priority" => meta
+            .sched
+            .map(|attr| attr.rt_priority.to_string())
+            .unwrap_or_default(),
+        "kthread" => (meta.kthread as u8).to_string(),
+        "counter_reset" => (entry.counter_reset as u8).to_string(),
+        "starved_windows" => entry.starved_windows.to_string(),
+        "starved" => (entry.starved as u8).to_string(),
+        "windows_observed" => entry.windows_observed.to_string(),
+        "windows_missed" => entry.windows_missed.to_string(),
+        "miss_rate" => entry
+            .miss_rate()
+            .map(|rate| format.float(rate, 4))
+            .unwrap_or_default(),
+        "voluntary_switches" => entry
+            .voluntary_delta()
+            .map(|switches| switches.to_string())
+            .unwrap_or_default(),
+        "involuntary_switches" => entry
+            .preempt_delta
+            .map(|switches| switches.to_string())
+            .unwrap_or_default(),
+        "preemption_rate" => entry
+            .preemption_rate()
+            .map(|rate| format.float(rate, 3))
+            .unwrap_or_default(),
+        "wakeups" => entry
+            .latency
+            .map(|lat| lat.wakeups.to_string())
+            .unwrap_or_default(),
+        "latency_mean_ms" | "latency_p99_ms" | "latency_max_ms" => match entry.latency {
+            Some(lat) => format.float(
+                match name {
+                    "latency_mean_ms" => lat.mean_ms(),
+                    "latency_p99_ms" => lat.quantile_ms(0.99),
+                    _ => lat.max_ms(),
+                },
+                3,
+            ),
+            None => String::new(),
+        },
+        "sched_wakeups" | "sched_wakings" | "sched_migrations" => {
+            let event = match name {
+                "sched_wakeups" => SchedEvent::Wakeup,
+                "sched_wakings" => SchedEvent::Waking,
+                _ => SchedEvent::Migrate,
+            };
+            entry
+                .events
+                .get(event)
+                .map(|count| count.to_string())
+                .unwrap_or_default()
+        }
+        "rq_depth" => batch
+            .rq_depth(entry)
+            .map(|depth| format.float(depth, 2))
+            .unwrap_or_default(),
+        "cpu_interrupt" => batch
+            .cpu_interrupt(entry)
+            .map(|share| format.float(share, 4))
+            .unwrap_or_default(),
+        "map" => format.text(&batch.map),
+        "record" | "entity" => "task".to_string(),
+        "total_tickets" => batch.total_tickets.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Value of CSV column `name` for one cgroup; columns that only apply to a
+/// task stay empty, and `tickets` is the cgroup's mean.
+fn csv_cgroup_field(
+    format: &CsvFormat,
+    batch: &SnapshotBatch,
+    cgroup: &CgroupSnapshot,
+    name: &str,
+) -> String {
+    match name {
+        "iteration" => (batch.iteration + 1).to_string(),
+        "timestamp_s" => format.float(batch.timestamp, 6),
+        "clock" => batch.clock.as_str().to_string(),
+        "record" | "entity" => "cgroup".to_string(),
+        "cgroup_id" => cgroup.id.to_string(),
+        "cgroup" => format.text(cgroup.path.as_deref().unwrap_or_default()),
+        "tasks" => cgroup.tasks.to_string(),
+        "runtime_ns" => cgroup.info.runtime_ns.to_string(),
+        "runtime_ms" => format.float(cgroup.info.runtime_ns as f64 / 1_000_000.0, 3),
+        "delta_ns" => cgroup.runtime_delta_ns.to_string(),
+        "delta_ms" => format.float(cgroup.runtime_delta_ms(), 3),
+        "switches" => cgroup.info.switches.to_string(),
+        "switch_delta" => cgroup.switch_delta.to_string(),
+        "voluntary_switches" => cgroup.voluntary_delta().to_string(),
+        "involuntary_switches" => cgroup.preempt_delta.to_string(),
+        "preemption_rate" => cgroup
+            .preemption_rate()
+            .map(|rate| format.float(rate, 3))
+            .unwrap_or_default(),
+        "tickets" => cgroup
+            .tickets
+            .map(|tickets| format.float(tickets, 1))
+            .unwrap_or_default(),
+        "utilization" => format.float(cgroup.utilization, 3),
+        "counter_reset" => (cgroup.counter_reset as u8).to_string(),
+        "map" => format.text(&batch.map),
+        _ => String::new(),
+    }
+}
+
+/// Value of CSV column `name` for the window's aggregate row; task columns
+/// other than the window's identity stay empty.
+fn csv_summary_field(
+    format: &CsvFormat,
+    batch: &SnapshotBatch,
+    totals: &BatchTotals,
+    name: &str,
+) -> String {
+    match name {
+        "iteration" => (batch.iteration + 1).to_string(),
+        "timestamp_s" => format.float(batch.timestamp, 6),
+        "clock" => batch.clock.as_str().to_string(),
+        "map" => format.text(&batch.map),
+        "record" => "summary".to_string(),
+        "tasks" => totals.tasks.to_string(),
+        "overdue_tasks" => totals.overdue.to_string(),
+        "starved_tasks" => totals.starved.to_string(),
+        "total_runtime_ms" => format.float(totals.runtime_ms, 3),
+        "total_tickets" => batch.total_tickets.to_string(),
+        "fairness_index" => totals
+            .fairness_index
+            .map(|index| format.float(index, 4))
+            .unwrap_or_default(),
+        "runtime_gini" => totals
+            .runtime_gini
+            .map(|gini| format.float(gini, 4))
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        if self.file.rotate_if_due()? {
+            self.write_header()?;
+        }
+        for entry in &batch.snapshots {
+            let fields: Vec<String> = self
+                .columns
+                .iter()
+                .map(|name| csv_field(&self.format, batch, entry, name))
+                .collect();
+            writeln!(self.file, "{}", self.format.join(&fields))?;
+        }
+        if self.cgroups {
+            for cgroup in &batch.cgroups {
+                let fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|name| csv_cgroup_field(&self.format, batch, cgroup, name))
+                    .collect();
+                writeln!(self.file, "{}", self.format.join(&fields))?;
+            }
+        }
+        if self.summary {
+            let totals = batch.totals();
+            let fields: Vec<String> = self
+                .columns
+                .iter()
+                .map(|name| csv_summary_field(&self.format, batch, &totals, name))
+                .collect();
+            writeln!(self.file, "{}", self.format.join(&fields))?;
+        }
+        self.file.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.finish()
+    }
+}
+
+/// Appends one NDJSON object per task (and [cgroup](SnapshotBatch::cgroups))
+/// and window.
+pub struct JsonExporter {
+    file: RotatingFile,
+    summary: bool,
+}
+
+impl JsonExporter {
+    pub fn open(path: &Path, rotation: Rotation, compression: Compression) -> io::Result<Self> {
+        let file = RotatingFile::open(path, rotation, compression)?;
+        debug!(target: "exporter", path = %path.display(), "appending NDJSON records");
+        Ok(Self {
+            file,
+            summary: false,
+        })
+    }
+
+    /// Also append a [`summary_json`] record per window.
+    pub fn with_summary_records(mut self, enabled: bool) -> Self {
+        self.summary = enabled;
+        self
+    }
+}
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &str {
+        "ndjson"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        self.file.rotate_if_due()?;
+        for entry in &batch.snapshots {
+            writeln!(self.file, "{}", snapshot_json(batch, entry))?;
+        }
+        for cgroup in &batch.cgroups {
+            writeln!(self.file, "{}", cgroup_json(batch, cgroup))?;
+        }
+        for event in &batch.events {
+            writeln!(self.file, "{}", event_json(batch, event))?;
+        }
+        if self.summary {
+            writeln!(self.file, "{}", summary_json(batch))?;
+        }
+        self.file.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.finish()
+    }
+}
+
+/// Streams the same NDJSON objects as [`JsonExporter`] to stdout, for piping
+/// into `jq`, `vector`, and friends.
+#[derive(Default)]
+pub struct StdoutJsonExporter {
+    summary: bool,
+}
+
+impl StdoutJsonExporter {
+    /// See [`JsonExporter::with_summary_records`].
+    pub fn with_summary_records(mut self, enabled: bool) -> Self {
+        self.summary = enabled;
+        self
+    }
+}
+
+impl Exporter for StdoutJsonExporter {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        for entry in &batch.snapshots {
+            writeln!(out, "{}", snapshot_json(batch, entry))?;
+        }
+        for cgroup in &batch.cgroups {
+            writeln!(out, "{}", cgroup_json(batch, cgroup))?;
+        }
+        for event in &batch.events {
+            writeln!(out, "{}", event_json(batch, event))?;
+        }
+        if self.summary {
+            writeln!(out, "{}", summary_json(batch))?;
+        }
+        out.flush()
+    }
+}
+
+/// Appends one NDJSON record per observed nice or ticket change. Changes are
+/// found by diffing each task, filtered out or not, against the previous
+/// sample of the same map; a change this process made itself (a
+/// [`ChangeEvent`] in an earlier batch) is attributed to its source,
+/// anything else to `external`.
+pub struct AuditLog {
+    file: RotatingFile,
+    /// Last seen (nice, tickets) per map and pid.
+    last: HashMap<String, HashMap<u32, (i32, u32)>>,
+    /// Changes made by this process, not yet observed: (map, pid, field, to) -> event.
+    pending: HashMap<(String, u32, &'static str, i64), ChangeEvent>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, rotation: Rotation, compression: Compression) -> io::Result<Self> {
+        let file = RotatingFile::open(path, rotation, compression)?;
+        debug!(target: "exporter", path = %path.display(), "appending audit records");
+        Ok(Self {
+            file,
+            last: HashMap::new(),
+            pending: HashMap::new(),
+        })
+    }
+
+    fn record(
+        &mut self,
+        batch: &SnapshotBatch,
+        entry: &TaskSnapshot,
+        field: &'static str,
+        from: i64,
+        to: i64,
+        cause: Option<(&'static str, String)>,
+    ) -> io::Result<&'static str> {
+        let made = self
+            .pending
+            .remove(&(batch.map.clone(), entry.pid, field, to));
+        let (source, reason) = match (made, cause) {
+            (Some(event), _) => (event.source, Some(event.reason)),
+            (None, Some((source, reason))) => (source, Some(reason)),
+            (None, None) => ("external", None),
+        };
+        let record = json!({
+            "map": batch.map,
+            "iteration": batch.iteration + 1,
+            "timestamp_s": batch.timestamp,
+            "pid": entry.pid,
+            "comm": entry.meta.comm,
+            "field": field,
+            "from": from,
+            "to": to,
+            "source": source,
+            "reason": reason,
+        });
+        writeln!(self.file, "{record}")?;
+        Ok(source)
+    }
+}
+
+impl Exporter for AuditLog {
+    fn name(&self) -> &str {
+        "audit"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        self.file.rotate_if_due()?;
+        let previous = self.last.remove(&batch.map).unwrap_or_default();
+        let mut current = HashMap::with_capacity(batch.snapshots.len() + batch.filtered_out.len());
+        for entry in batch.snapshots.iter().chain(&batch.filtered_out) {
+            let (nice, tickets) = (entry.info.nice, entry.info.tickets);
+            current.insert(entry.pid, (nice, tickets));
+            let Some(&(old_nice, old_tickets)) = previous.get(&entry.pid) else {
+                continue;
+            };
+            // Tickets that follow a nice change share its source.
+            let cause = if old_nice != nice {
+                let source =
+                    self.record(batch, entry, "nice", old_nice.into(), nice.into(), None)?;
+                Some((source, format!("nice {old_nice} -> {nice}")))
+            } else {
+                None
+            };
+            if old_tickets != tickets {
+                self.record(
+                    batch,
+                    entry,
+                    "tickets",
+                    old_tickets.into(),
+                    tickets.into(),
+                    cause,
+                )?;
+            }
+        }
+        // Made during this window, so they show up from the next sample on.
+        let live: HashSet<u32> = current.keys().copied().collect();
+        self.last.insert(batch.map.clone(), current);
+        self.pending
+            .retain(|(map, pid, ..), _| *map != batch.map || live.contains(pid));
+        // Only nice and ticket changes show up in the task map.
+        for event in batch
+            .events
+            .iter()
+            .filter(|event| matches!(event.field, "nice" | "tickets"))
+        {
+            self.pending.insert(
+                (batch.map.clone(), event.pid, event.field, event.to),
+                event.clone(),
+            );
+        }
+        self.file.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.finish()
+    }
+}
+
+/// NDJSON payload for one task in one window.
+pub fn snapshot_json(batch: &SnapshotBatch, entry: &TaskSnapshot) -> serde_json::Value {
+    json!({
+        "map": batch.map,
+        "iteration": batch.iteration + 1,
+        "timestamp_s": batch.timestamp,
+        "total_tickets": batch.total_tickets,
+        "consistent": batch.consistent,
+        "clock": batch.clock.as_str(),
+        "entity": "task",
+        "pid": entry.pid,
+        "runtime_ms": entry.info.runtime_ms(),
+        "delta_ms": entry.runtime_delta_ms(),
+        "rolling_runtime_ms": entry.rolling_runtime_ms,
+        "switch_delta": entry.switch_delta,
+        "voluntary_switches": entry.voluntary_delta(),
+        "involuntary_switches": entry.preempt_delta,
+        "preemption_rate": entry.preemption_rate(),
+        "estimated_period_ms": entry.estimated_period_ms,
+        "deadline_ms": entry.deadline_ms,
+        "response_time_ms": entry.response_time_ms,
+        "lateness_ms": entry.lateness_ms,
+        "wakeups": entry.latency.map(|lat| lat.wakeups),
+        "latency_mean_ms": entry.latency.map(|lat| lat.mean_ms()),
+        "latency_p50_ms": entry.latency.map(|lat| lat.quantile_ms(0.5)),
+        "latency_p99_ms": entry.latency.map(|lat| lat.quantile_ms(0.99)),
+        "latency_max_ms": entry.latency.map(|lat| lat.max_ms()),
+        "sched_events": (!entry.events.is_empty()).then_some(entry.events),
+        "utilization": entry.utilization,
+        "rq_depth": batch.rq_depth(entry),
+        "cpu_interrupt": batch.cpu_interrupt(entry),
+        "nice": entry.info.nice,
+        "tickets": entry.info.tickets,
+        "ticket_share": entry.ticket_share,
+        "counter_reset": entry.counter_reset,
+        "starved_windows": entry.starved_windows,
+        "starved": entry.starved,
+        "windows_observed": entry.windows_observed,
+        "windows_missed": entry.windows_missed,
+        "miss_rate": entry.miss_rate(),
+        "map_stale": batch.stale(),
+        "last_switch_in_s": batch.last_switch_in_secs(entry),
+        "cgroup": entry.meta.cgroup,
+        "container_id": entry.meta.container_id,
+        "pod_uid": entry.meta.pod_uid,
+        "uid": entry.meta.uid,
+        "user": entry.meta.user,
+        "cmdline": entry.meta.cmdline,
+        "last_cpu": entry.meta.last_cpu,
+        "cpus_allowed": format_cpu_list(&entry.meta.cpus_allowed),
+        "sched": entry.meta.sched,
+        "kthread": entry.meta.kthread,
+    })
+}
+
+/// NDJSON payload for one cgroup in one window; `entity` tells it apart
+/// from the task records, and `tickets` is the cgroup's mean.
+pub fn cgroup_json(batch: &SnapshotBatch, cgroup: &CgroupSnapshot) -> serde_json::Value {
+    json!({
+        "map": batch.map,
+        "iteration": batch.iteration + 1,
+        "timestamp_s": batch.timestamp,
+        "clock": batch.clock.as_str(),
+        "entity": "cgroup",
+        "cgroup_id": cgroup.id,
+        "cgroup": cgroup.path,
+        "tasks": cgroup.tasks,
+        "runtime_ms": cgroup.info.runtime_ns as f64 / 1_000_000.0,
+        "delta_ms": cgroup.runtime_delta_ms(),
+        "switch_delta": cgroup.switch_delta,
+        "voluntary_switches": cgroup.voluntary_delta(),
+        "involuntary_switches": cgroup.preempt_delta,
+        "preemption_rate": cgroup.preemption_rate(),
+        "tickets": cgroup.tickets,
+        "utilization": cgroup.utilization,
+        "counter_reset": cgroup.counter_reset,
+    })
+}
+
+/// p50/p95/p99 lateness of the window and, when kept, of the horizon.
+fn lateness_quantiles_json(batch: &SnapshotBatch) -> serde_json::Value {
+    let quantiles = |sketch: &QuantileSketch| {
+        json!({
+            "p50_ms": sketch.quantile(0.5),
+            "p95_ms": sketch.quantile(0.95),
+            "p99_ms": sketch.quantile(0.99),
+        })
+    };
+    json!({
+        "window": quantiles(&batch.lateness_sketch()),
+        "horizon": batch.lateness_horizon.as_ref().map(|(windows, sketch)| {
+            let mut horizon = quantiles(sketch);
+            horizon["windows"] = json!(windows);
+            horizon
+        }),
+    })
+}
+
+/// One whole window as a single JSON object: the task records of
+/// [`snapshot_json`] under `tasks`, the [`cgroup_json`] records under
+/// `cgroups`, and the change events under `events`.
+pub fn batch_json(batch: &SnapshotBatch) -> serde_json::Value {
+    let totals = batch.totals();
+    json!({
+        "map": batch.map,
+        "iteration": batch.iteration + 1,
+        "timestamp_s": batch.timestamp,
+        "total_tickets": batch.total_tickets,
+        "window_ms": batch.window_ms,
+        "cpus": batch.cpus,
+        "system_utilization": totals.system_utilization,
+        "preemption_rate": totals.preemption_rate(),
+        "starved_tasks": totals.starved,
+        "runtime_gini": totals.runtime_gini,
+        "lateness_quantiles": lateness_quantiles_json(batch),
+        "priority": PriorityReport::of(&batch.snapshots),
+        "runtime_lorenz": runtime_lorenz(&batch.snapshots)
+            .into_iter()
+            .map(|(population, runtime)| [population, runtime])
+            .collect::<Vec<_>>(),
+        "runqueue": batch.runqueue.as_ref().map(|depth| json!({
+            "mean": depth.mean(),
+            "max": depth.max(),
+            "per_cpu": depth.per_cpu,
+            "runnable": depth.runnable,
+            "estimated": depth.estimated,
+            "lateness_correlation": batch.depth_lateness_correlation(),
+        })),
+        "interrupts": batch.irq.as_ref().map(|irq| json!({
+            "irq": irq.irq_mean(),
+            "softirq": irq.softirq_mean(),
+            "per_cpu_irq": irq.irq,
+            "per_cpu_softirq": irq.softirq,
+            "per_cpu_task": irq.task_share(&per_cpu_utilization(
+                &batch.snapshots,
+                batch.window_ms,
+                batch.cpus,
+            )),
+            "lateness_correlation": batch.interrupt_lateness_correlation(),
+        })),
+        "wakeup_latency": totals.latency.map(|lat| json!({
+            "wakeups": lat.wakeups,
+            "mean_ms": lat.mean_ms(),
+            "p50_ms": lat.quantile_ms(0.5),
+            "p95_ms": lat.quantile_ms(0.95),
+            "p99_ms": lat.quantile_ms(0.99),
+            "max_ms": lat.max_ms(),
+        })),
+        "sched_events": (!totals.events.is_empty()).then_some(totals.events),
+        "consistent": batch.consistent,
+        "stale": batch.stale(),
+        "unchanged_windows": batch.unchanged_windows,
+        "clock": batch.clock.as_str(),
+        "tasks": batch.snapshots.iter().map(|entry| snapshot_json(batch, entry)).collect::<Vec<_>>(),
+        "cgroups": batch.cgroups.iter().map(|cgroup| cgroup_json(batch, cgroup)).collect::<Vec<_>>(),
+        "events": batch.events.iter().map(|event| event_json(batch, event)).collect::<Vec<_>>(),
+    })
+}
+
+/// One window's aggregates as an NDJSON record, tagged `"event": "summary"`
+/// like change events so consumers can split it from the task records
+/// without re-aggregating them.
+pub fn summary_json(batch: &SnapshotBatch) -> serde_json::Value {
+    let totals = batch.totals();
+    json!({
+        "map": batch.map,
+        "iteration": batch.iteration + 1,
+        "timestamp_s": batch.timestamp,
+        "event": "summary",
+        "window_ms": batch.window_ms,
+        "tasks": totals.tasks,
+        "active_tasks": totals.active,
+        "overdue_tasks": totals.overdue,
+        "starved_tasks": totals.starved,
+        "total_runtime_ms": totals.runtime_ms,
+        "total_tickets": batch.total_tickets,
+        "fairness_index": totals.fairness_index,
+        "runtime_gini": totals.runtime_gini,
+        "worst_lateness_ms": totals.worst_lateness_ms,
+        "system_utilization": totals.system_utilization,
+    })
+}
+
+/// NDJSON payload for one change event; the `event` key tells it apart from
+/// task records.
+pub fn event_json(batch: &SnapshotBatch, event: &ChangeEvent) -> serde_json::Value {
+    json!({
+        "map": batch.map,
+        "iteration": batch.iteration + 1,
+        "timestamp_s": batch.timestamp,
+        "event": "change",
+        "pid": event.pid,
+        "field": event.field,
+        "from": event.from,
+        "to": event.to,
+        "source": event.source,
+        "reason": event.reason,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(tag = "ph")]
+enum TraceEvent {
+    #[serde(rename = "M")]
+    Metadata {
+        name: &'static str,
+        cat: &'static str,
+        ts: f64,
+        pid: u32,
+        tid: u32,
+        args: MetadataArgs,
+    },
+    #[serde(rename = "X")]
+    Slice {
+        name: String,
+        cat: &'static str,
+        ts: f64,
+        dur: f64,
+        pid: u32,
+        tid: u32,
+        args: TraceArgs,
+    },
+}
+
+#[derive(Serialize)]
+struct MetadataArgs {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct TraceArgs {
+    ticket_share: f64,
+    deadline_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_time_ms: Option<f64>,
+    lateness_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_p99_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preemption_rate: Option<f64>,
+    runtime_ms: f64,
+    utilization: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_id: Option<String>,
+}
+
+/// Collects Chrome trace / Perfetto slices in memory and writes them on
+/// [`finish`](Exporter::finish). Wall-clock timestamps are made relative to
+/// the first window; monotonic and boottime ones are kept absolute so the
+/// trace lines up with perf or ftrace traces of the same boot.
+/// Each map becomes a trace process and each task a thread within it.
+pub struct TraceExporter {
+    path: PathBuf,
+    events: Vec<TraceEvent>,
+    start_ts: Option<f64>,
+    clock: Clock,
+    maps: Vec<String>,
+}
+
+impl TraceExporter {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            events: Vec::new(),
+            start_ts: None,
+            clock: Clock::default(),
+            maps: Vec::new(),
+        }
+    }
+}
+
+impl Exporter for TraceExporter {
+    fn name(&self) -> &str {
+        "trace"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        self.clock = batch.clock;
+        let origin = match batch.clock {
+            Clock::Realtime => batch.timestamp,
+            Clock::Monotonic | Clock::Boottime => 0.0,
+        };
+        let start = *self.start_ts.get_or_insert(origin);
+        let ts_us = (batch.timestamp - start) * 1_000_000.0;
+        let process = match self.maps.iter().position(|map| *map == batch.map) {
+            Some(index) => index as u32 + 1,
+            None => {
+                self.maps.push(batch.map.clone());
+                let process = self.maps.len() as u32;
+                self.events.push(TraceEvent::Metadata {
+                    name: "process_name",
+                    cat: "sched",
+                    ts: 0.0,
+                    pid: process,
+                    tid: 0,
+                    args: MetadataArgs {
+                        name: batch.map.clone(),
+                    },
+                });
+                process
+            }
+        };
+        for entry in &batch.snapshots {
+            let dur_us = entry.runtime_delta_ms() * 1000.0;
+            self.events.push(TraceEvent::Metadata {
+                name: "thread_name",
+                cat: "sched",
+                ts: 0.0,
+                pid: process,
+                tid: entry.pid,
+                args: MetadataArgs {
+                    name: format!("pid {}", entry.pid),
+                },
+            });
+            self.events.push(TraceEvent::Slice {
+                name: format!("pid {}", entry.pid),
+                cat: "sched",
+                ts: ts_us,
+                dur: dur_us.max(1.0),
+                pid: process,
+                tid: entry.pid,
+                args: TraceArgs {
+                    ticket_share: entry.ticket_share,
+                    deadline_ms: entry.deadline_ms,
+                    response_time_ms: entry.response_time_ms,
+                    lateness_ms: entry.lateness_ms,
+                    latency_p99_ms: entry.latency.map(|lat| lat.quantile_ms(0.99)),
+                    preemption_rate: entry.preemption_rate(),
+                    runtime_ms: entry.runtime_delta_ms(),
+                    utilization: entry.utilization,
+                    container_id: entry.meta.container_id.clone(),
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.events.is_empty() {
+            return Ok(());
+        }
+        let trace = json!({
+            "traceEvents": self.events,
+            "otherData": { "clock": self.clock.as_str() },
+        });
+        let data = serde_json::to_string_pretty(&trace).map_err(io::Error::other)?;
+        std::fs::write(&self.path, data)?;
+        info!(target: "exporter", path = %self.path.display(), events = self.events.len(), "trace exported");
+        Ok(())
+    }
+}
+
+/// Logs a warning per task whose lateness exceeds the threshold, worst first.
+pub struct DeadlineAlerts {
+    threshold_ms: f64,
+}
+
+impl DeadlineAlerts {
+    pub fn new(threshold_ms: f64) -> Self {
+        Self { threshold_ms }
+    }
+}
+
+impl Exporter for DeadlineAlerts {
+    fn name(&self) -> &str {
+        "deadline-alerts"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let mut flagged: Vec<&TaskSnapshot> = batch
+            .snapshots
+            .iter()
+            .filter(|e| e.lateness_ms > self.threshold_ms)
+            .collect();
+        flagged.sort_by(|a, b| b.lateness_ms.total_cmp(&a.lateness_ms));
+        for entry in flagged {
+            warn!(
+                target: "sampler",
+                pid = entry.pid,
+                lateness_ms = entry.lateness_ms,
+                utilization = entry.utilization,
+                tickets = entry.info.tickets,
+                nice = entry.info.nice,
+                "deadline alert: lateness {:.3} ms exceeds {:.3} ms",
+                entry.lateness_ms,
+                self.threshold_ms
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Logs a warning for every task whose 99th percentile wakeup latency in a
+/// window is over a threshold.
+pub struct LatencyAlerts {
+    threshold_ms: f64,
+}
+
+impl LatencyAlerts {
+    pub fn new(threshold_ms: f64) -> Self {
+        Self { threshold_ms }
+    }
+}
+
+impl Exporter for LatencyAlerts {
+    fn name(&self) -> &str {
+        "latency-alerts"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let mut flagged: Vec<(&TaskSnapshot, WakeupLatency)> = batch
+            .snapshots
+            .iter()
+            .filter_map(|e| e.latency.map(|lat| (e, lat)))
+            .filter(|(_, lat)| lat.quantile_ms(0.99) > self.threshold_ms)
+            .collect();
+        flagged.sort_by(|(_, a), (_, b)| b.quantile_ms(0.99).total_cmp(&a.quantile_ms(0.99)));
+        for (entry, latency) in flagged {
+            warn!(
+                target: "sampler",
+                pid = entry.pid,
+                wakeups = latency.wakeups,
+                mean_ms = latency.mean_ms(),
+                max_ms = latency.max_ms(),
+                tickets = entry.info.tickets,
+                nice = entry.info.nice,
+                "latency alert: p99 wakeup latency {:.3} ms exceeds {:.3} ms",
+                latency.quantile_ms(0.99),
+                self.threshold_ms
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Logs a warning when a task has held tickets without running for the
+/// stream's starvation threshold, once per starved stretch.
+pub struct StarvationAlerts {
+    windows: u32,
+}
+
+impl StarvationAlerts {
+    pub fn new(windows: u32) -> Self {
+        Self { windows }
+    }
+}
+
+impl Exporter for StarvationAlerts {
+    fn name(&self) -> &str {
+        "starvation-alerts"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        for entry in batch
+            .snapshots
+            .iter()
+            .filter(|e| e.starved_windows == self.windows)
+        {
+            warn!(
+                target: "sampler",
+                pid = entry.pid,
+                comm = entry.meta.comm.as_deref().unwrap_or(""),
+                tickets = entry.info.tickets,
+                ticket_share = entry.ticket_share,
+                nice = entry.info.nice,
+                "starvation alert: no runtime for {} windows despite {:.1}% of the tickets",
+                entry.starved_windows,
+                entry.ticket_share * 100.0
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Logs a warning when a task's or cgroup's tickets grow past the
+/// threshold of an [`InflationTracker`], once per crossing.
+pub struct InflationAlerts {
+    trackers: HashMap<String, InflationTracker>,
+    growth: f64,
+    windows: u32,
+}
+
+impl InflationAlerts {
+    pub fn new(growth: f64, windows: u32) -> Self {
+        Self {
+            trackers: HashMap::new(),
+            growth,
+            windows,
+        }
+    }
+}
+
+impl Exporter for InflationAlerts {
+    fn name(&self) -> &str {
+        "inflation-alerts"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let (growth, windows) = (self.growth, self.windows);
+        let tracker = self
+            .trackers
+            .entry(batch.map.clone())
+            .or_insert_with(|| InflationTracker::new(growth, windows));
+        for inflation in tracker.observe(&batch.snapshots, batch.total_tickets) {
+            warn!(
+                target: "sampler",
+                map = %batch.map,
+                holder = %inflation.holder,
+                from = inflation.from,
+                to = inflation.to,
+                windows = inflation.windows,
+                share = inflation.share,
+                total_tickets = batch.total_tickets,
+                "ticket inflation: {} went from {} to {} {} (+{:.0}%) in {} windows",
+                inflation.holder,
+                inflation.from,
+                inflation.to,
+                inflation.unit(),
+                inflation.growth() * 100.0,
+                inflation.windows
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How often a running alert hook is checked for having exited.
+const HOOK_POLL: Duration = Duration::from_millis(50);
+
+/// Runs a shell command whenever a window has tasks over the lateness
+/// threshold. The worst task is described in `NEO_ALERT_*` environment
+/// variables and every flagged task is passed as JSON on stdin. At most one
+/// command runs per `cooldown`, and never two at once; windows skipped that
+/// way are counted in `NEO_ALERT_SUPPRESSED` of the next run. The command
+/// runs on its own thread, so a slow hook never holds up the other exports,
+/// and is killed once it has run for `timeout`.
+pub struct AlertHook {
+    command: String,
+    threshold_ms: f64,
+    cooldown: Duration,
+    timeout: Duration,
+    last_run: Option<Instant>,
+    /// Thread that feeds and reaps the running hook.
+    running: Option<JoinHandle<()>>,
+    suppressed: u64,
+}
+
+impl AlertHook {
+    pub fn new(
+        command: impl Into<String>,
+        threshold_ms: f64,
+        cooldown: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            command: command.into(),
+            threshold_ms,
+            cooldown,
+            timeout,
+            last_run: None,
+            running: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Collect a finished hook; `true` while it is still running.
+    fn busy(&mut self) -> bool {
+        if self
+            .running
+            .as_ref()
+            .is_some_and(|hook| !hook.is_finished())
+        {
+            return true;
+        }
+        if let Some(hook) = self.running.take() {
+            // The thread reports the hook's outcome itself.
+            let _ = hook.join();
+        }
+        false
+    }
+
+    fn run(&mut self, batch: &SnapshotBatch, flagged: &[&TaskSnapshot]) -> io::Result<()> {
+        let worst = flagged[0];
+        let payload = json!({
+            "map": batch.map,
+            "iteration": batch.iteration,
+            "timestamp": batch.timestamp,
+            "threshold_ms": self.threshold_ms,
+            "suppressed": self.suppressed,
+            "alerts": flagged.iter().map(|entry| json!({
+                "pid": entry.pid,
+                "comm": entry.meta.comm,
+                "lateness_ms": entry.lateness_ms,
+                "utilization": entry.utilization,
+                "tickets": entry.info.tickets,
+                "nice": entry.info.nice,
+                "ticket_share": entry.ticket_share,
+            })).collect::<Vec<_>>(),
+        });
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&self.command)
+            .env("NEO_ALERT_MAP", &batch.map)
+            .env("NEO_ALERT_ITERATION", batch.iteration.to_string())
+            .env("NEO_ALERT_TIMESTAMP", format!("{:.6}", batch.timestamp))
+            .env(
+                "NEO_ALERT_THRESHOLD_MS",
+                format!("{:.3}", self.threshold_ms),
+            )
+            .env("NEO_ALERT_COUNT", flagged.len().to_string())
+            .env("NEO_ALERT_SUPPRESSED", self.suppressed.to_string())
+            .env("NEO_ALERT_PID", worst.pid.to_string())
+            .env("NEO_ALERT_COMM", worst.meta.comm.as_deref().unwrap_or(""))
+            .env("NEO_ALERT_LATENESS_MS", format!("{:.3}", worst.lateness_ms))
+            .env("NEO_ALERT_UTILIZATION", format!("{:.6}", worst.utilization))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            // Its own group, so a timeout also kills what the shell started.
+            .process_group(0);
+        let (label, timeout) = (self.command.clone(), self.timeout);
+        let (pid, alerts) = (worst.pid, flagged.len());
+        let hook = thread::Builder::new()
+            .name("alert-hook".into())
+            .spawn(move || {
+                let mut child = match command.spawn() {
+                    Ok(child) => child,
+                    Err(err) => {
+                        warn!(target: "exporter", command = %label, "cannot run alert hook: {err}");
+                        return;
+                    }
+                };
+                debug!(target: "exporter", command = %label, pid, alerts, "alert hook started");
+                let stdin = child.stdin.take();
+                // Detached: a hook that never reads stdin must not keep this
+                // thread from killing it.
+                if let Some(mut stdin) = stdin {
+                    thread::spawn(move || {
+                        // A hook that ignores stdin may exit before reading it.
+                        if let Err(err) = writeln!(stdin, "{payload}")
+                            && err.kind() != io::ErrorKind::BrokenPipe
+                        {
+                            warn!(target: "exporter", "cannot pass alerts to the hook: {err}");
+                        }
+                    });
+                }
+                let started = Instant::now();
+                let status = loop {
+                    match child.try_wait() {
+                        Ok(Some(status)) => break Ok(status),
+                        Ok(None) if started.elapsed() >= timeout => {
+                            warn!(target: "exporter", command = %label, ?timeout, "alert hook timed out; killing it");
+                            // It may have exited in the meantime.
+                            unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+                            break child.wait();
+                        }
+                        Ok(None) => thread::sleep(HOOK_POLL),
+                        Err(err) => break Err(err),
+                    }
+                };
+                match status {
+                    Ok(status) if !status.success() => {
+                        warn!(target: "exporter", command = %label, %status, "alert hook failed");
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        warn!(target: "exporter", command = %label, "cannot wait for alert hook: {err}");
+                    }
+                }
+            })?;
+        self.running = Some(hook);
+        self.last_run = Some(Instant::now());
+        self.suppressed = 0;
+        Ok(())
+    }
+}
+
+impl Exporter for AlertHook {
+    fn name(&self) -> &str {
+        "alert-hook"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let busy = self.busy();
+        let mut flagged: Vec<&TaskSnapshot> = batch
+            .snapshots
+            .iter()
+            .filter(|e| e.lateness_ms > self.threshold_ms)
+            .collect();
+        if flagged.is_empty() {
+            return Ok(());
+        }
+        let cooling = self
+            .last_run
+            .is_some_and(|last| last.elapsed() < self.cooldown);
+        if busy || cooling {
+            self.suppressed += 1;
+            return Ok(());
+        }
+        flagged.sort_by(|a, b| b.lateness_ms.total_cmp(&a.lateness_ms));
+        // A broken hook should not end the capture.
+        if let Err(err) = self.run(batch, &flagged) {
+            warn!(target: "exporter", command = %self.command, "cannot run alert hook: {err}");
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(hook) = self.running.take() {
+            // Bounded by the hook's timeout.
+            let _ = hook.join();
+        }
+        Ok(())
+    }
+}
+
+/// How [`StatsdExporter`] attaches tags to a metric line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum StatsdTags {
+    /// `name:1|g|#map:demo,pid:42` (DogStatsD; also read by Telegraf).
+    #[default]
+    Dogstatsd,
+    /// `name,map=demo,pid=42:1|g` (Telegraf/InfluxDB).
+    Influx,
+    /// No tags: values are folded into the name, `prefix.demo.42.name:1|g`.
+    Plain,
+}
+
+/// Largest datagram sent; stays below a typical path MTU.
+const STATSD_PACKET: usize = 1432;
+
+/// Sends gauges over UDP to a StatsD daemon: per-window aggregates and,
+/// optionally, one set per task that ran in the window. Fire-and-forget:
+/// undeliverable packets are dropped rather than failing the run.
+pub struct StatsdExporter {
+    socket: UdpSocket,
+    prefix: String,
+    tags: StatsdTags,
+    per_pid: bool,
+    packet: String,
+    dropped: u64,
+}
+
+impl StatsdExporter {
+    pub fn new(addr: &str, prefix: &str, tags: StatsdTags, per_pid: bool) -> io::Result<Self> {
+        let target = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{addr} did not resolve"))
+        })?;
+        let socket = UdpSocket::bind(if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: prefix.trim_end_matches('.').to_string(),
+            tags,
+            per_pid,
+            packet: String::with_capacity(STATSD_PACKET),
+            dropped: 0,
+        })
+    }
+
+    fn gauge(&mut self, metric: &str, value: f64, tags: &[(&str, &str)]) {
+        let mut line = String::new();
+        if !self.prefix.is_empty() {
+            line.push_str(&self.prefix);
+            line.push('.');
+        }
+        match self.tags {
+            StatsdTags::Dogstatsd => {
+                let _ = write!(line, "{metric}:{value}|g");
+                for (i, (key, tag)) in tags.iter().enumerate() {
+                    line.push_str(if i == 0 { "|#" } else { "," });
+                    let _ = write!(line, "{key}:{}", statsd_safe(tag, false));
+                }
+            }
+            StatsdTags::Influx => {
+                line.push_str(metric);
+                for (key, tag) in tags {
+                    let _ = write!(line, ",{key}={}", statsd_safe(tag, false));
+                }
+                let _ = write!(line, ":{value}|g");
+            }
+            StatsdTags::Plain => {
+                for (_, tag) in tags {
+                    line.push_str(&statsd_safe(tag, true));
+                    line.push('.');
+                }
+                let _ = write!(line, "{metric}:{value}|g");
+            }
+        }
+        if !self.packet.is_empty() && self.packet.len() + 1 + line.len() > STATSD_PACKET {
+            self.flush();
+        }
+        if !self.packet.is_empty() {
+            self.packet.push('\n');
+        }
+        self.packet.push_str(&line);
+    }
+
+    fn flush(&mut self) {
+        if self.packet.is_empty() {
+            return;
+        }
+        if let Err(err) = self.socket.send(self.packet.as_bytes()) {
+            // Nobody listening, or the socket buffer is full.
+            self.dropped += 1;
+            debug!(target: "exporter", dropped = self.dropped, "statsd packet dropped: {err}");
+        }
+        self.packet.clear();
+    }
+}
+
+/// Replace characters that delimit StatsD fields (and, for plain names, the
+/// `.` hierarchy separator).
+fn statsd_safe(value: &str, plain: bool) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | ',' | '#' | '=' | '@' => '_',
+            '.' | '/' if plain => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+impl Exporter for StatsdExporter {
+    fn name(&self) -> &str {
+        "statsd"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let map = [("map", batch.map.as_str())];
+        let totals = batch.totals();
+        self.gauge("tasks", totals.tasks as f64, &map);
+        self.gauge("active_tasks", totals.active as f64, &map);
+        self.gauge("overdue_tasks", totals.overdue as f64, &map);
+        self.gauge("starved_tasks", totals.starved as f64, &map);
+        self.gauge("total_utilization", totals.utilization, &map);
+        self.gauge("system_utilization", totals.system_utilization, &map);
+        if let Some(worst) = totals.worst_lateness_ms {
+            self.gauge("worst_lateness_ms", worst, &map);
+        }
+        let lateness = batch.lateness_horizon_sketch();
+        if let (Some(p95), Some(p99)) = (lateness.quantile(0.95), lateness.quantile(0.99)) {
+            self.gauge("lateness_p95_ms", p95, &map);
+            self.gauge("lateness_p99_ms", p99, &map);
+        }
+        if let Some(latency) = totals.latency {
+            self.gauge("latency_p99_ms", latency.quantile_ms(0.99), &map);
+        }
+        if let Some(rate) = totals.preemption_rate() {
+            self.gauge("preemption_rate", rate, &map);
+        }
+        for event in SchedEvent::ALL {
+            if let Some(count) = totals.events.get(event) {
+                self.gauge(&format!("sched_{event}_events"), count as f64, &map);
+            }
+        }
+        if let Some(gini) = totals.runtime_gini {
+            self.gauge("runtime_gini", gini, &map);
+        }
+        let priority = PriorityReport::of(&batch.snapshots);
+        if let Some(spearman) = priority.spearman {
+            self.gauge("priority_spearman", spearman, &map);
+        }
+        self.gauge("priority_outliers", priority.outliers.len() as f64, &map);
+        if let Some(depth) = &batch.runqueue {
+            self.gauge("rq_depth_mean", depth.mean(), &map);
+            self.gauge("rq_depth_max", depth.max(), &map);
+        }
+        if let Some(irq) = &batch.irq {
+            self.gauge("irq_share", irq.irq_mean(), &map);
+            self.gauge("softirq_share", irq.softirq_mean(), &map);
+        }
+        self.gauge("total_tickets", batch.total_tickets as f64, &map);
+        self.gauge("map_stale", f64::from(u8::from(batch.stale())), &map);
+        if self.per_pid {
+            for snap in batch
+                .snapshots
+                .iter()
+                .filter(|snap| snap.runtime_delta_ns > 0)
+            {
+                let pid = snap.pid.to_string();
+                let mut tags = vec![("map", batch.map.as_str()), ("pid", pid.as_str())];
+                // Plain names stay stable per pid; comm is only a tag.
+                if let Some(comm) = &snap.meta.comm
+                    && self.tags != StatsdTags::Plain
+                {
+                    tags.push(("comm", comm));
+                }
+                self.gauge("task.lateness_ms", snap.lateness_ms, &tags);
+                self.gauge("task.utilization", snap.utilization, &tags);
+                self.gauge("task.ticket_share", snap.ticket_share, &tags);
+                self.gauge("task.runtime_delta_ms", snap.runtime_delta_ms(), &tags);
+                if let Some(rate) = snap.preemption_rate() {
+                    self.gauge("task.preemption_rate", rate, &tags);
+                }
+            }
+        }
+        self.flush();
+        Ok(())
+    }
+}
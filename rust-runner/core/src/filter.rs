@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::PathBuf;
+
+use tracing::debug;
+
+use crate::procfs::TaskIdentity;
+use crate::stats::TaskSnapshot;
+
+/// Display/export filter over enriched snapshots. Filtering only affects what
+/// callers see; ticket shares stay relative to the whole map.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotFilter {
+    /// Keep only tasks owned by this user (name or numeric uid).
+    pub user: Option<String>,
+    /// Keep only tasks that last ran on these CPUs; tasks with an unknown CPU
+    /// are kept when their affinity overlaps the list.
+    pub cpus: Option<Vec<u32>>,
+    /// Drop kernel threads.
+    pub exclude_kthreads: bool,
+    /// Drop tasks that ran less than this many milliseconds in the window.
+    pub min_delta_ms: f64,
+    /// Drop tasks whose ticket share is below this fraction (0-1).
+    pub min_share: f64,
+}
+
+impl SnapshotFilter {
+    pub fn matches(&self, snapshot: &TaskSnapshot) -> bool {
+        if self.exclude_kthreads && snapshot.meta.kthread {
+            return false;
+        }
+        if snapshot.runtime_delta_ms() < self.min_delta_ms || snapshot.ticket_share < self.min_share
+        {
+            return false;
+        }
+        if let Some(user) = &self.user {
+            let by_name = snapshot.meta.user.as_deref() == Some(user.as_str());
+            let by_uid = snapshot
+                .meta
+                .uid
+                .is_some_and(|uid| uid.to_string() == *user);
+            if !by_name && !by_uid {
+                return false;
+            }
+        }
+        if let Some(cpus) = &self.cpus {
+            let on_cpu = match snapshot.meta.last_cpu {
+                Some(cpu) => cpus.contains(&cpu),
+                None => snapshot
+                    .meta
+                    .cpus_allowed
+                    .iter()
+                    .any(|cpu| cpus.contains(cpu)),
+            };
+            if !on_cpu {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn apply(&self, snapshots: Vec<TaskSnapshot>) -> Vec<TaskSnapshot> {
+        snapshots.into_iter().filter(|s| self.matches(s)).collect()
+    }
+}
+
+/// Selection applied to raw map entries before enrichment, so tasks outside
+/// it cost no `/proc` lookups, are never printed, and are never exported.
+/// Criteria of different kinds must all match; values of one kind are
+/// alternatives.
+#[derive(Clone, Debug, Default)]
+pub struct TaskSelector {
+    /// Keep these pids, or threads whose thread group (tgid) is one of them.
+    pub pids: Vec<u32>,
+    /// File holding one pid (e.g. a daemon pidfile), re-read every sample so
+    /// restarts are followed. Adds to [`pids`](Self::pids).
+    pub pid_file: Option<PathBuf>,
+    /// Glob patterns (`*`, `?`) over the task name from `/proc/<pid>/comm`.
+    pub comms: Vec<String>,
+    file_pids: Vec<u32>,
+}
+
+impl TaskSelector {
+    pub fn new(pids: Vec<u32>, pid_file: Option<PathBuf>, comms: Vec<String>) -> Self {
+        Self {
+            pids,
+            pid_file,
+            comms,
+            file_pids: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pids.is_empty() && self.pid_file.is_none() && self.comms.is_empty()
+    }
+
+    /// Re-read the pid file; an unreadable file keeps the previous pids so
+    /// a restart in progress does not drop every task.
+    pub fn refresh(&mut self) {
+        let Some(path) = &self.pid_file else {
+            return;
+        };
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                self.file_pids = text
+                    .split_whitespace()
+                    .filter_map(|token| token.parse().ok())
+                    .collect();
+            }
+            Err(err) => {
+                debug!(target: "sampler", path = %path.display(), "cannot read pid file: {err}");
+            }
+        }
+    }
+
+    pub fn matches(&self, pid: u32, identity: &TaskIdentity) -> bool {
+        if !self.pids.is_empty() || self.pid_file.is_some() {
+            let wanted = |candidate: u32| {
+                self.pids.contains(&candidate) || self.file_pids.contains(&candidate)
+            };
+            if !wanted(pid) && !identity.tgid.is_some_and(wanted) {
+                return false;
+            }
+        }
+        if !self.comms.is_empty() {
+            let Some(comm) = &identity.comm else {
+                return false;
+            };
+            if !self.comms.iter().any(|pattern| glob_match(pattern, comm)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Shell-style match supporting `*` (any run) and `?` (any one character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently absorbing up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    p = star + 1;
+                    t = absorbed + 1;
+                    backtrack = Some((star, absorbed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::events::SchedEvents;
+use crate::latency::WakeupLatency;
+use crate::stats::{TaskSnapshot, preemption_rate};
+
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    #[default]
+    Pid,
+    Tgid,
+    Cgroup,
+    User,
+}
+
+impl GroupBy {
+    pub fn key(self, snapshot: &TaskSnapshot) -> String {
+        match self {
+            GroupBy::Pid => snapshot.pid.to_string(),
+            GroupBy::Tgid => snapshot.meta.tgid.unwrap_or(snapshot.pid).to_string(),
+            GroupBy::Cgroup => snapshot
+                .meta
+                .cgroup
+                .clone()
+                .unwrap_or_else(|| "(unknown)".to_string()),
+            GroupBy::User => snapshot.meta.user_label(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroupSnapshot {
+    pub key: String,
+    pub tasks: usize,
+    pub runtime_ns: u64,
+    pub runtime_delta_ns: u64,
+    pub switch_delta: u64,
+    /// Preemptions among the members' switches; `None` when the map does
+    /// not count them.
+    pub preempt_delta: Option<u64>,
+    pub tickets: u64,
+    pub ticket_share: f64,
+    pub estimated_period_ms: f64,
+    pub lateness_ms: f64,
+    pub worst_lateness_ms: f64,
+    pub total_lateness_ms: f64,
+    pub overdue: usize,
+    /// Members' [`TaskSnapshot::windows_observed`] summed.
+    pub windows_observed: u64,
+    /// Members' [`TaskSnapshot::windows_missed`] summed.
+    pub windows_missed: u64,
+    pub utilization: f64,
+    /// Wakeup latency of the members together; `None` when none waited.
+    pub latency: Option<WakeupLatency>,
+    /// Scheduler events of the members together.
+    pub events: SchedEvents,
+}
+
+impl GroupSnapshot {
+    pub fn runtime_delta_ms(&self) -> f64 {
+        self.runtime_delta_ns as f64 / 1_000_000.0
+    }
+
+    pub fn preemption_rate(&self) -> Option<f64> {
+        preemption_rate(self.preempt_delta, self.switch_delta)
+    }
+
+    /// Share of the members' observed windows that missed the deadline;
+    /// see [`TaskSnapshot::miss_rate`].
+    pub fn miss_rate(&self) -> Option<f64> {
+        (self.windows_observed > 0)
+            .then(|| self.windows_missed as f64 / self.windows_observed as f64)
+    }
+
+    pub fn avg_lateness_ms(&self) -> f64 {
+        if self.tasks == 0 {
+            0.0
+        } else {
+            self.total_lateness_ms / self.tasks as f64
+        }
+    }
+}
+
+/// Aggregate per-task snapshots into groups, sorted by runtime delta. Group
+/// lateness applies the per-task heuristic to the summed runtime/switches.
+pub fn aggregate(
+    snapshots: &[TaskSnapshot],
+    group_by: GroupBy,
+    window_ms: f64,
+) -> Vec<GroupSnapshot> {
+    let mut groups: HashMap<String, GroupSnapshot> = HashMap::new();
+    for snap in snapshots {
+        let key = group_by.key(snap);
+        let group = groups.entry(key.clone()).or_insert_with(|| GroupSnapshot {
+            key,
+            worst_lateness_ms: f64::NEG_INFINITY,
+            ..GroupSnapshot::default()
+        });
+        group.tasks += 1;
+        group.runtime_ns = group.runtime_ns.saturating_add(snap.info.runtime_ns);
+        group.runtime_delta_ns = group.runtime_delta_ns.saturating_add(snap.runtime_delta_ns);
+        group.switch_delta = group.switch_delta.saturating_add(snap.switch_delta);
+        if let Some(preempted) = snap.preempt_delta {
+            *group.preempt_delta.get_or_insert_default() += preempted;
+        }
+        group.tickets += snap.info.tickets as u64;
+        group.ticket_share += snap.ticket_share;
+        group.worst_lateness_ms = group.worst_lateness_ms.max(snap.lateness_ms);
+        group.total_lateness_ms += snap.lateness_ms;
+        if snap.deadline_missed() {
+            group.overdue += 1;
+        }
+        group.windows_observed += snap.windows_observed;
+        group.windows_missed += snap.windows_missed;
+        group.utilization += snap.utilization;
+        if let Some(latency) = &snap.latency {
+            group.latency.get_or_insert_default().merge(latency);
+        }
+        group.events.merge(&snap.events);
+    }
+
+    let window_ms = window_ms.max(1.0);
+    let mut groups: Vec<GroupSnapshot> = groups
+        .into_values()
+        .map(|mut group| {
+            let period = if group.switch_delta > 0 {
+                window_ms / group.switch_delta as f64
+            } else {
+                window_ms
+            };
+            group.estimated_period_ms = period.max(0.1);
+            group.lateness_ms = group.runtime_delta_ms() - group.estimated_period_ms;
+            group
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.runtime_delta_ns
+            .cmp(&a.runtime_delta_ns)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    groups
+}
+
+pub fn members<'a>(
+    snapshots: &'a [TaskSnapshot],
+    group_by: GroupBy,
+    key: &'a str,
+) -> impl Iterator<Item = &'a TaskSnapshot> + 'a {
+    snapshots
+        .iter()
+        .filter(move |snap| group_by.key(snap) == key)
+}
@@ -0,0 +1,55 @@
+use crate::stats::TaskSnapshot;
+
+/// Inclusive upper edges (ms) of the lateness buckets: decades on either
+/// side of zero, so a few very late tasks and many slightly late ones both
+/// show up. Anything above the last edge falls into a final bucket.
+pub const LATENESS_EDGES_MS: [f64; 9] = [-100.0, -10.0, -1.0, -0.1, 0.0, 0.1, 1.0, 10.0, 100.0];
+
+/// Short bucket labels: the bucket's edge furthest from zero.
+pub const LATENESS_LABELS: [&str; LATENESS_BUCKETS] = [
+    "<-100", "-100", "-10", "-1", "-.1", ".1", "1", "10", "100", ">100",
+];
+
+pub const LATENESS_BUCKETS: usize = LATENESS_EDGES_MS.len() + 1;
+
+/// Counts of lateness values in log-scaled buckets; see
+/// [`LATENESS_EDGES_MS`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatenessHistogram {
+    pub counts: [u64; LATENESS_BUCKETS],
+}
+
+impl LatenessHistogram {
+    /// Lateness of the tasks that ran in a window; idle tasks only report
+    /// their lack of a switch-in as slack, which would drown the rest.
+    pub fn of(snapshots: &[TaskSnapshot]) -> Self {
+        let mut histogram = Self::default();
+        for snap in snapshots.iter().filter(|snap| snap.runtime_delta_ns > 0) {
+            histogram.record(snap.lateness_ms);
+        }
+        histogram
+    }
+
+    pub fn record(&mut self, lateness_ms: f64) {
+        let bucket = LATENESS_EDGES_MS
+            .iter()
+            .position(|&edge| lateness_ms <= edge)
+            .unwrap_or(LATENESS_BUCKETS - 1);
+        self.counts[bucket] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// `(label, count)` per bucket, from most slack to most late.
+    pub fn buckets(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        LATENESS_LABELS.iter().copied().zip(self.counts)
+    }
+}
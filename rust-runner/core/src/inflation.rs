@@ -0,0 +1,152 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::stats::TaskSnapshot;
+
+/// Growth (1.0 = +100%) over the horizon that counts as inflation.
+pub const DEFAULT_INFLATION_GROWTH: f64 = 1.0;
+
+/// Windows the growth is measured over.
+pub const DEFAULT_INFLATION_WINDOWS: u32 = 10;
+
+/// What holds the tickets that grew.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum TicketHolder {
+    Task(u32),
+    /// The tasks in a cgroup v2 path, per task, so the cgroup growing by
+    /// forks does not count.
+    Cgroup(String),
+}
+
+impl fmt::Display for TicketHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TicketHolder::Task(pid) => write!(f, "pid {pid}"),
+            TicketHolder::Cgroup(path) => write!(f, "cgroup {path}"),
+        }
+    }
+}
+
+/// Tickets of one holder that grew past the threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Inflation {
+    pub holder: TicketHolder,
+    /// Tickets at the start of the horizon; for a cgroup, the mean per
+    /// member task.
+    pub from: u64,
+    pub to: u64,
+    /// Windows the growth took, at most the horizon.
+    pub windows: u32,
+    /// Share of the ticket supply the holder has now.
+    pub share: f64,
+}
+
+impl Inflation {
+    /// Relative growth, 1.0 for a doubling.
+    pub fn growth(&self) -> f64 {
+        self.to as f64 / self.from as f64 - 1.0
+    }
+
+    /// What `from` and `to` count, for messages.
+    pub fn unit(&self) -> &'static str {
+        match self.holder {
+            TicketHolder::Task(_) => "tickets",
+            TicketHolder::Cgroup(_) => "tickets per task",
+        }
+    }
+}
+
+/// Ticket counts of the last few windows, oldest first.
+#[derive(Debug, Default)]
+struct Series {
+    tickets: VecDeque<u64>,
+    /// Over the threshold in the last window, so it is not reported again
+    /// until it drops back.
+    inflated: bool,
+}
+
+/// Watches ticket counts per task and per cgroup for growth beyond `growth`
+/// within `windows` windows: a policy agent handing out tickets in a loop,
+/// or a BPF program writing the wrong ones. Holders are reported once when
+/// they cross the threshold. Nice changes count too, so a renice to the top
+/// of the range can trip a tight threshold.
+#[derive(Debug)]
+pub struct InflationTracker {
+    growth: f64,
+    windows: u32,
+    holders: HashMap<TicketHolder, Series>,
+}
+
+impl InflationTracker {
+    pub fn new(growth: f64, windows: u32) -> Self {
+        Self {
+            growth,
+            windows: windows.max(1),
+            holders: HashMap::new(),
+        }
+    }
+
+    /// Record a window; returns the holders that crossed the threshold in
+    /// it, the largest growth first.
+    pub fn observe(&mut self, snapshots: &[TaskSnapshot], total_tickets: u64) -> Vec<Inflation> {
+        // Tickets to track and tickets held, which differ for cgroups.
+        let mut current: HashMap<TicketHolder, (u64, u64)> =
+            HashMap::with_capacity(snapshots.len());
+        let mut cgroups: HashMap<&str, (u64, u64)> = HashMap::new();
+        for snap in snapshots {
+            let tickets = u64::from(snap.info.tickets);
+            current.insert(TicketHolder::Task(snap.pid), (tickets, tickets));
+            if let Some(cgroup) = &snap.meta.cgroup {
+                let (sum, members) = cgroups.entry(cgroup).or_default();
+                *sum += tickets;
+                *members += 1;
+            }
+        }
+        // A cgroup of one task would only repeat that task's alert.
+        current.extend(
+            cgroups
+                .into_iter()
+                .filter(|(_, (_, members))| *members > 1)
+                .map(|(cgroup, (sum, members))| {
+                    let mean = (sum + members / 2) / members;
+                    (TicketHolder::Cgroup(cgroup.to_string()), (mean, sum))
+                }),
+        );
+        self.holders
+            .retain(|holder, _| current.contains_key(holder));
+        let mut crossed = Vec::new();
+        for (holder, (tickets, held)) in current {
+            let series = self.holders.entry(holder.clone()).or_default();
+            push_bounded(&mut series.tickets, tickets, self.windows + 1);
+            let from = series.tickets.iter().copied().min().unwrap_or(tickets);
+            let inflated = from > 0 && tickets as f64 > from as f64 * (1.0 + self.growth);
+            if inflated && !series.inflated {
+                let start = series
+                    .tickets
+                    .iter()
+                    .rposition(|count| *count == from)
+                    .unwrap_or(0);
+                crossed.push(Inflation {
+                    holder,
+                    from,
+                    to: tickets,
+                    windows: (series.tickets.len() - 1 - start) as u32,
+                    share: held as f64 / total_tickets.max(1) as f64,
+                });
+            }
+            series.inflated = inflated;
+        }
+        crossed.sort_by(|a, b| b.growth().total_cmp(&a.growth()));
+        crossed
+    }
+}
+
+fn push_bounded(series: &mut VecDeque<u64>, value: u64, capacity: u32) {
+    if series.len() >= capacity as usize {
+        series.pop_front();
+    }
+    series.push_back(value);
+}
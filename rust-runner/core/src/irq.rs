@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Serialize;
+use tracing::debug;
+
+/// Interrupt time of a host over one window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IrqTime {
+    /// Share of each CPU's time spent in hard interrupt handlers, indexed
+    /// by CPU number.
+    pub irq: Vec<f64>,
+    /// Share of each CPU's time spent in softirqs (network receive, timers,
+    /// block completions, RCU), indexed by CPU number.
+    pub softirq: Vec<f64>,
+}
+
+impl IrqTime {
+    /// Share of `cpu`'s time spent in either, when it was sampled.
+    pub fn cpu(&self, cpu: u32) -> Option<f64> {
+        let cpu = cpu as usize;
+        Some(self.irq.get(cpu)? + self.softirq.get(cpu)?)
+    }
+
+    /// Share of each CPU's time spent in either, indexed by CPU number.
+    pub fn per_cpu(&self) -> Vec<f64> {
+        (0..self.irq.len() as u32)
+            .map(|cpu| self.cpu(cpu).unwrap_or(0.0))
+            .collect()
+    }
+
+    /// Hard interrupt share of all CPUs together.
+    pub fn irq_mean(&self) -> f64 {
+        self.irq.iter().sum::<f64>() / self.irq.len().max(1) as f64
+    }
+
+    /// Softirq share of all CPUs together.
+    pub fn softirq_mean(&self) -> f64 {
+        self.softirq.iter().sum::<f64>() / self.softirq.len().max(1) as f64
+    }
+
+    /// The CPU that spent the most time in interrupts, and its share.
+    pub fn busiest(&self) -> Option<(u32, f64)> {
+        (0..self.irq.len().min(self.softirq.len()) as u32)
+            .filter_map(|cpu| Some((cpu, self.cpu(cpu)?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// `per_cpu` utilization from the task map with interrupt time taken
+    /// out. `sched_switch` charges a task the wall time it held the CPU,
+    /// interrupts that arrived meanwhile included, so heavy softirq load
+    /// otherwise reads as tasks running long. Only the interrupts that fell
+    /// into busy time are taken out, assuming they arrive evenly: those
+    /// taken while the CPU idled were never charged to a task. CPUs without
+    /// interrupt figures are left as they are.
+    pub fn task_share(&self, per_cpu: &[f64]) -> Vec<f64> {
+        per_cpu
+            .iter()
+            .enumerate()
+            .map(|(cpu, load)| {
+                let busy = load.clamp(0.0, 1.0);
+                (load - self.cpu(cpu as u32).unwrap_or(0.0) * busy).max(0.0)
+            })
+            .collect()
+    }
+}
+
+/// `cpu -> (total, irq, softirq)` in clock ticks.
+type CpuTicks = HashMap<u32, (u64, u64, u64)>;
+
+/// The `cpuN` lines of `/proc/stat`: user, nice, system, idle, iowait,
+/// irq, softirq, and steal time, then guest time that `user` already
+/// counts.
+fn parse_stat(text: &str) -> CpuTicks {
+    let mut cpus = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(cpu) = fields.next().and_then(|name| name.strip_prefix("cpu")) else {
+            continue;
+        };
+        // The aggregate `cpu` line has no number.
+        let Ok(cpu) = cpu.parse() else {
+            continue;
+        };
+        let ticks: Vec<u64> = fields.filter_map(|field| field.parse().ok()).collect();
+        if ticks.len() >= 7 {
+            let total = ticks.iter().take(8).sum();
+            cpus.insert(cpu, (total, ticks[5], ticks[6]));
+        }
+    }
+    cpus
+}
+
+/// Per-CPU interrupt time of this host from the `irq` and `softirq` ticks
+/// of `/proc/stat`, as a share of each CPU's ticks in between. Kernels
+/// without `CONFIG_IRQ_TIME_ACCOUNTING` charge whole ticks to whatever was
+/// running when the timer fired, so short windows are coarse there.
+#[derive(Debug, Default)]
+pub struct IrqSampler {
+    last: Option<CpuTicks>,
+    /// Set once `/proc/stat` turned out to be unreadable.
+    unreadable: bool,
+}
+
+impl IrqSampler {
+    /// The interrupt time since the previous call; `None` on the first
+    /// call and when `/proc/stat` cannot be read.
+    pub fn sample(&mut self) -> Option<IrqTime> {
+        if self.unreadable {
+            return None;
+        }
+        let ticks = fs::read_to_string("/proc/stat")
+            .ok()
+            .map(|text| parse_stat(&text))
+            .filter(|ticks| !ticks.is_empty());
+        let Some(ticks) = ticks else {
+            debug!(target: "sampler", "/proc/stat is unreadable; interrupt time is not reported");
+            self.unreadable = true;
+            return None;
+        };
+        let before = self.last.replace(ticks)?;
+        let ticks = self.last.as_ref()?;
+        let slots = ticks.keys().max().map_or(0, |cpu| *cpu as usize + 1);
+        let mut time = IrqTime {
+            irq: vec![0.0; slots],
+            softirq: vec![0.0; slots],
+        };
+        for (cpu, (total, irq, softirq)) in ticks {
+            let Some((was_total, was_irq, was_softirq)) = before.get(cpu) else {
+                continue;
+            };
+            let elapsed = total.saturating_sub(*was_total);
+            if elapsed == 0 {
+                continue;
+            }
+            time.irq[*cpu as usize] = irq.saturating_sub(*was_irq) as f64 / elapsed as f64;
+            time.softirq[*cpu as usize] =
+                softirq.saturating_sub(*was_softirq) as f64 / elapsed as f64;
+        }
+        Some(time)
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+
+use tracing::{info, warn};
+
+use crate::export::{ChangeEvent, Exporter, SnapshotBatch};
+use crate::inflation::{Inflation, InflationTracker, TicketHolder};
+use crate::stats::TaskSnapshot;
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Facility of the syslog messages (`daemon`).
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Syslog priority names, indexed by value.
+const PRIORITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Where [`AlertLog`] sends its messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum AlertTarget {
+    /// The systemd journal, with every detail as its own field.
+    Journald,
+    /// The local syslog daemon (`/dev/log`), details folded into the text.
+    Syslog,
+}
+
+/// How bad an alert is; mapped to a syslog priority by [`PriorityMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A scheduling change made by this runner.
+    Info,
+    /// Lateness over the alert threshold, or an anomaly: a starved task,
+    /// ticket inflation, or a map whose counters stopped moving.
+    Warning,
+    /// Lateness at least twice the alert threshold.
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "critical" => Ok(Severity::Critical),
+            other => Err(format!(
+                "unknown severity `{other}` (info, warning, critical)"
+            )),
+        }
+    }
+}
+
+/// A syslog priority given by name (`err`, `warning`, ...) or number (0-7).
+pub fn parse_priority(value: &str) -> Result<u8, String> {
+    if let Ok(number) = value.parse::<u8>()
+        && number < 8
+    {
+        return Ok(number);
+    }
+    PRIORITIES
+        .iter()
+        .position(|name| *name == value)
+        .map(|index| index as u8)
+        .ok_or_else(|| format!("unknown priority `{value}` ({})", PRIORITIES.join(", ")))
+}
+
+/// Syslog priority per [`Severity`].
+#[derive(Debug, Clone, Copy)]
+pub struct PriorityMap {
+    pub info: u8,
+    pub warning: u8,
+    pub critical: u8,
+}
+
+impl Default for PriorityMap {
+    fn default() -> Self {
+        Self {
+            info: 6,
+            warning: 4,
+            critical: 2,
+        }
+    }
+}
+
+impl PriorityMap {
+    pub fn set(&mut self, severity: Severity, priority: u8) {
+        match severity {
+            Severity::Info => self.info = priority,
+            Severity::Warning => self.warning = priority,
+            Severity::Critical => self.critical = priority,
+        }
+    }
+
+    fn get(&self, severity: Severity) -> u8 {
+        match severity {
+            Severity::Info => self.info,
+            Severity::Warning => self.warning,
+            Severity::Critical => self.critical,
+        }
+    }
+}
+
+/// Sends deadline alerts (tasks over `threshold_ms`), anomalies (starved
+/// tasks, ticket inflation, stale maps), and the change events of every
+/// window to journald or syslog, so they can be triaged with
+/// `journalctl -t <identifier>` rather than found in the dump output.
+/// Messages that cannot be delivered are logged and dropped.
+pub struct AlertLog {
+    socket: UnixDatagram,
+    target: AlertTarget,
+    identifier: String,
+    priorities: PriorityMap,
+    /// Deadline alerts are off at zero.
+    threshold_ms: f64,
+    /// Starvation alerts are off at zero.
+    starvation_windows: u32,
+    /// Growth and horizon of the inflation alerts; off when `None`.
+    inflation: Option<(f64, u32)>,
+    trackers: HashMap<String, InflationTracker>,
+    /// Maps already reported stale, until a counter moves again.
+    stale: HashSet<String>,
+    pid: u32,
+}
+
+impl AlertLog {
+    pub fn connect(
+        target: AlertTarget,
+        identifier: &str,
+        priorities: PriorityMap,
+        threshold_ms: f64,
+    ) -> io::Result<Self> {
+        let path = match target {
+            AlertTarget::Journald => JOURNALD_SOCKET,
+            AlertTarget::Syslog => SYSLOG_SOCKET,
+        };
+        let socket = UnixDatagram::unbound()?;
+        socket
+            .connect(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{path}: {err}")))?;
+        info!(target: "exporter", path, identifier, "sending alerts to {target:?}");
+        Ok(Self {
+            socket,
+            target,
+            identifier: identifier.to_string(),
+            priorities,
+            threshold_ms,
+            starvation_windows: 0,
+            inflation: None,
+            trackers: HashMap::new(),
+            stale: HashSet::new(),
+            pid: std::process::id(),
+        })
+    }
+
+    /// Also report tasks starved for `windows` windows, once per stretch.
+    pub fn with_starvation(mut self, windows: u32) -> Self {
+        self.starvation_windows = windows;
+        self
+    }
+
+    /// Also report tickets that grew by `growth` within `windows` windows;
+    /// see [`InflationTracker`].
+    pub fn with_inflation(mut self, growth: f64, windows: u32) -> Self {
+        self.inflation = Some((growth, windows));
+        self
+    }
+
+    fn send(&self, severity: Severity, message: &str, fields: &[(&str, String)]) -> io::Result<()> {
+        let priority = self.priorities.get(severity);
+        let datagram = match self.target {
+            AlertTarget::Journald => {
+                let mut datagram = Vec::new();
+                journal_field(&mut datagram, "MESSAGE", message);
+                journal_field(&mut datagram, "PRIORITY", &priority.to_string());
+                journal_field(&mut datagram, "SYSLOG_IDENTIFIER", &self.identifier);
+                journal_field(&mut datagram, "NEO_EBPF_SEVERITY", severity.as_str());
+                for (key, value) in fields {
+                    journal_field(&mut datagram, &format!("NEO_EBPF_{key}"), value);
+                }
+                datagram
+            }
+            AlertTarget::Syslog => {
+                let mut line = format!(
+                    "<{}>{}[{}]: {message}",
+                    SYSLOG_FACILITY * 8 + priority,
+                    self.identifier,
+                    self.pid
+                );
+                for (key, value) in fields {
+                    let _ = write!(line, " {}={value:?}", key.to_lowercase());
+                }
+                line.into_bytes()
+            }
+        };
+        self.socket.send(&datagram).map(|_| ())
+    }
+
+    fn alert(&self, batch: &SnapshotBatch, entry: &TaskSnapshot) -> io::Result<()> {
+        let severity = if entry.lateness_ms >= 2.0 * self.threshold_ms {
+            Severity::Critical
+        } else {
+            Severity::Warning
+        };
+        let comm = entry.meta.comm.as_deref().unwrap_or("?");
+        let message = format!(
+            "deadline alert: pid {} ({comm}) late by {:.3} ms (threshold {:.3} ms)",
+            entry.pid, entry.lateness_ms, self.threshold_ms
+        );
+        self.send(
+            severity,
+            &message,
+            &[
+                ("EVENT", "deadline".to_string()),
+                ("MAP", batch.map.clone()),
+                ("PID", entry.pid.to_string()),
+                ("COMM", comm.to_string()),
+                ("LATENESS_MS", format!("{:.3}", entry.lateness_ms)),
+                ("THRESHOLD_MS", format!("{:.3}", self.threshold_ms)),
+                ("UTILIZATION", format!("{:.4}", entry.utilization)),
+                ("NICE", entry.info.nice.to_string()),
+                ("TICKETS", entry.info.tickets.to_string()),
+            ],
+        )
+    }
+
+    fn starved(&self, batch: &SnapshotBatch, entry: &TaskSnapshot) -> io::Result<()> {
+        let comm = entry.meta.comm.as_deref().unwrap_or("?");
+        let message = format!(
+            "starvation alert: pid {} ({comm}) got no runtime for {} windows despite {:.1}% of the tickets",
+            entry.pid,
+            entry.starved_windows,
+            entry.ticket_share * 100.0
+        );
+        self.send(
+            Severity::Warning,
+            &message,
+            &[
+                ("EVENT", "starvation".to_string()),
+                ("MAP", batch.map.clone()),
+                ("PID", entry.pid.to_string()),
+                ("COMM", comm.to_string()),
+                ("WINDOWS", entry.starved_windows.to_string()),
+                ("TICKET_SHARE", format!("{:.4}", entry.ticket_share)),
+                ("NICE", entry.info.nice.to_string()),
+                ("TICKETS", entry.info.tickets.to_string()),
+            ],
+        )
+    }
+
+    fn inflated(&self, batch: &SnapshotBatch, inflation: &Inflation) -> io::Result<()> {
+        let message = format!(
+            "ticket inflation: {} went from {} to {} {} (+{:.0}%) in {} windows",
+            inflation.holder,
+            inflation.from,
+            inflation.to,
+            inflation.unit(),
+            inflation.growth() * 100.0,
+            inflation.windows
+        );
+        let mut fields = vec![
+            ("EVENT", "inflation".to_string()),
+            ("MAP", batch.map.clone()),
+        ];
+        match &inflation.holder {
+            TicketHolder::Task(pid) => fields.push(("PID", pid.to_string())),
+            TicketHolder::Cgroup(path) => fields.push(("CGROUP", path.clone())),
+        }
+        fields.extend([
+            ("FROM", inflation.from.to_string()),
+            ("TO", inflation.to.to_string()),
+            ("WINDOWS", inflation.windows.to_string()),
+            ("TICKET_SHARE", format!("{:.4}", inflation.share)),
+        ]);
+        self.send(Severity::Warning, &message, &fields)
+    }
+
+    fn stale_map(&self, batch: &SnapshotBatch) -> io::Result<()> {
+        let message = format!(
+            "stale map: no counter in {} moved for {} windows (BPF program detached?)",
+            batch.map, batch.unchanged_windows
+        );
+        self.send(
+            Severity::Warning,
+            &message,
+            &[
+                ("EVENT", "stale".to_string()),
+                ("MAP", batch.map.clone()),
+                ("WINDOWS", batch.unchanged_windows.to_string()),
+            ],
+        )
+    }
+
+    fn change(&self, batch: &SnapshotBatch, event: &ChangeEvent) -> io::Result<()> {
+        let message = format!(
+            "{}: pid {} {} {} -> {} ({})",
+            event.source, event.pid, event.field, event.from, event.to, event.reason
+        );
+        self.send(
+            Severity::Info,
+            &message,
+            &[
+                ("EVENT", "change".to_string()),
+                ("MAP", batch.map.clone()),
+                ("PID", event.pid.to_string()),
+                ("FIELD", event.field.to_string()),
+                ("FROM", event.from.to_string()),
+                ("TO", event.to.to_string()),
+                ("SOURCE", event.source.to_string()),
+                ("REASON", event.reason.clone()),
+            ],
+        )
+    }
+}
+
+/// One field of the native journal protocol; values with a newline use the
+/// length-prefixed form.
+fn journal_field(out: &mut Vec<u8>, key: &str, value: &str) {
+    out.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        out.push(b'\n');
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        out.push(b'=');
+    }
+    out.extend_from_slice(value.as_bytes());
+    out.push(b'\n');
+}
+
+impl Exporter for AlertLog {
+    fn name(&self) -> &str {
+        match self.target {
+            AlertTarget::Journald => "journald",
+            AlertTarget::Syslog => "syslog",
+        }
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        // A journal or syslog daemon that is down or being restarted must not
+        // end the other exports; the messages of this window are dropped.
+        let mut results = Vec::new();
+        if batch.stale() {
+            if self.stale.insert(batch.map.clone()) {
+                results.push(self.stale_map(batch));
+            }
+        } else {
+            self.stale.remove(&batch.map);
+        }
+        if self.threshold_ms > 0.0 {
+            let mut flagged: Vec<&TaskSnapshot> = batch
+                .snapshots
+                .iter()
+                .filter(|entry| entry.lateness_ms > self.threshold_ms)
+                .collect();
+            flagged.sort_by(|a, b| b.lateness_ms.total_cmp(&a.lateness_ms));
+            for entry in flagged {
+                results.push(self.alert(batch, entry));
+            }
+        }
+        if self.starvation_windows > 0 {
+            for entry in batch
+                .snapshots
+                .iter()
+                .filter(|entry| entry.starved_windows == self.starvation_windows)
+            {
+                results.push(self.starved(batch, entry));
+            }
+        }
+        if let Some((growth, windows)) = self.inflation {
+            let inflations = self
+                .trackers
+                .entry(batch.map.clone())
+                .or_insert_with(|| InflationTracker::new(growth, windows))
+                .observe(&batch.snapshots, batch.total_tickets);
+            for inflation in &inflations {
+                results.push(self.inflated(batch, inflation));
+            }
+        }
+        for event in &batch.events {
+            results.push(self.change(batch, event));
+        }
+        let sent = results.len();
+        let failed: Vec<io::Error> = results.into_iter().filter_map(Result::err).collect();
+        if let Some(err) = failed.first() {
+            warn!(
+                target: "exporter",
+                failed = failed.len(),
+                sent,
+                "{}: dropped alerts: {err}",
+                self.name()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+/// Log2 microsecond buckets of `struct task_latency`: slot 0 counts waits
+/// under 1 µs, slot `i` waits of `[2^(i-1), 2^i)` µs, and the last slot
+/// everything longer.
+pub const LATENCY_SLOTS: usize = 24;
+
+/// File name the latency map is pinned under, next to the task map.
+pub const LATENCY_PIN_NAME: &str = "task_latency";
+
+/// Mirror of the BPF program's `struct task_latency`: how long a pid waited
+/// between becoming runnable (woken, or preempted) and running.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TaskLatency {
+    /// `bpf_ktime_get_ns` of a wakeup that has not run yet, else 0.
+    pub wakeup_ts: u64,
+    pub total_ns: u64,
+    pub count: u64,
+    pub max_ns: u64,
+    pub slots: [u32; LATENCY_SLOTS],
+}
+
+impl TaskLatency {
+    /// Add one wait of `ns`, the way the BPF program does.
+    pub fn record(&mut self, ns: u64) {
+        self.total_ns += ns;
+        self.count += 1;
+        self.max_ns = self.max_ns.max(ns);
+        self.slots[slot_of(ns)] += 1;
+    }
+}
+
+/// Bucket of a wait of `ns`; see [`LATENCY_SLOTS`].
+fn slot_of(ns: u64) -> usize {
+    let us = ns / 1_000;
+    ((u64::BITS - us.leading_zeros()) as usize).min(LATENCY_SLOTS - 1)
+}
+
+/// Lower and upper edge of `slot` in nanoseconds; the last slot is open, so
+/// `max_ns` closes it.
+fn slot_edges_ns(slot: usize, max_ns: u64) -> (f64, f64) {
+    let lower = if slot == 0 {
+        0.0
+    } else {
+        (1_u64 << (slot - 1)) as f64 * 1_000.0
+    };
+    let upper = if slot == LATENCY_SLOTS - 1 {
+        (max_ns as f64).max(lower)
+    } else {
+        (1_u64 << slot) as f64 * 1_000.0
+    };
+    (lower, upper)
+}
+
+/// Wakeup-to-run latency over one window (or several merged), from the
+/// differences of two [`TaskLatency`] readings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct WakeupLatency {
+    /// Waits that ended in the window.
+    pub wakeups: u64,
+    pub total_ns: u64,
+    /// Longest wait: exact when it set the pid's record, otherwise the
+    /// upper edge of its bucket.
+    pub max_ns: u64,
+    #[serde(skip)]
+    pub slots: [u32; LATENCY_SLOTS],
+}
+
+impl WakeupLatency {
+    /// The waits recorded between `before` and `now`, or `None` when the
+    /// pid did not wait for the CPU in between.
+    pub fn between(before: &TaskLatency, now: &TaskLatency) -> Option<Self> {
+        let wakeups = now.count.checked_sub(before.count)?;
+        if wakeups == 0 {
+            return None;
+        }
+        let mut slots = [0; LATENCY_SLOTS];
+        for (slot, (now, before)) in slots.iter_mut().zip(now.slots.iter().zip(&before.slots)) {
+            *slot = now.wrapping_sub(*before);
+        }
+        let max_ns = if now.max_ns > before.max_ns {
+            now.max_ns
+        } else {
+            let top = slots.iter().rposition(|count| *count > 0).unwrap_or(0);
+            (slot_edges_ns(top, now.max_ns).1 as u64).min(now.max_ns)
+        };
+        Some(Self {
+            wakeups,
+            total_ns: now.total_ns.saturating_sub(before.total_ns),
+            max_ns,
+            slots,
+        })
+    }
+
+    /// The latency of every task of a window together, or `None` when none
+    /// waited.
+    pub fn merged<'a>(latencies: impl IntoIterator<Item = &'a WakeupLatency>) -> Option<Self> {
+        latencies
+            .into_iter()
+            .fold(None, |merged: Option<Self>, latency| {
+                let mut merged = merged.unwrap_or_default();
+                merged.merge(latency);
+                Some(merged)
+            })
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.wakeups += other.wakeups;
+        self.total_ns += other.total_ns;
+        self.max_ns = self.max_ns.max(other.max_ns);
+        for (slot, other) in self.slots.iter_mut().zip(other.slots) {
+            *slot += other;
+        }
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        self.total_ns as f64 / self.wakeups.max(1) as f64 / 1_000_000.0
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.max_ns as f64 / 1_000_000.0
+    }
+
+    /// The `q` quantile (0-1) in milliseconds, interpolated within its
+    /// log2 bucket and capped at the window's longest wait.
+    pub fn quantile_ms(&self, q: f64) -> f64 {
+        let counted: u64 = self.slots.iter().map(|count| *count as u64).sum();
+        if counted == 0 {
+            return 0.0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * counted as f64).max(1.0);
+        let mut below = 0.0;
+        for (slot, count) in self.slots.iter().enumerate() {
+            let count = *count as f64;
+            if count > 0.0 && below + count >= rank {
+                let (lower, upper) = slot_edges_ns(slot, self.max_ns);
+                let ns = lower + (upper - lower) * (rank - below) / count;
+                return ns.min(self.max_ns as f64) / 1_000_000.0;
+            }
+            below += count;
+        }
+        self.max_ms()
+    }
+}
+
+/// Last [`TaskLatency`] reading of every pid, to turn the cumulative
+/// counters into per-window [`WakeupLatency`].
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    last: HashMap<u32, TaskLatency>,
+}
+
+impl LatencyTracker {
+    /// The waits `pid` recorded since its last reading; `None` for its
+    /// first reading and after its counters started over.
+    pub fn update(&mut self, pid: u32, now: TaskLatency) -> Option<WakeupLatency> {
+        let before = self.last.insert(pid, now)?;
+        if now.count < before.count {
+            return None;
+        }
+        WakeupLatency::between(&before, &now)
+    }
+
+    pub fn forget(&mut self, pid: u32) {
+        self.last.remove(&pid);
+    }
+
+    pub fn retain(&mut self, live: &HashSet<u32>) {
+        self.last.retain(|pid, _| live.contains(pid));
+    }
+}
@@ -0,0 +1,112 @@
+//! Core of the eBPF lottery scheduler monitor: reading the pinned `task_map`,
+//! turning raw counters into enriched per-task snapshots, and exporting them.
+//!
+//! The `rust-runner` binary is a thin CLI/TUI on top of this crate. Embedders
+//! typically only need [`stream::SnapshotStream`]:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use neo_ebpf_core::stream::SnapshotStream;
+//!
+//! let stream = SnapshotStream::open("/sys/fs/bpf/task_map", Duration::from_secs(1), 0.5)?;
+//! for window in stream.take(3) {
+//!     let window = window?;
+//!     println!("{} tasks, {} tickets", window.snapshots.len(), window.total_tickets);
+//! }
+//! # Ok::<(), neo_ebpf_core::error::RunnerError>(())
+//! ```
+//!
+//! Modules:
+//! - [`bpf_map`]: libbpf FFI for opening/iterating (and, for benchmarks,
+//!   creating) the task map, and mmapping array task maps.
+//! - [`loader`]: loading the scheduler's BPF object from memory, pinning its
+//!   maps, and attaching it, like `loader/sched_loader`.
+//! - [`stats`]: the `struct task_info` mirror, ticket maths, EWMA/EDF
+//!   enrichment, and lottery draws.
+//! - [`procfs`] / [`sched_attr`]: per-pid metadata (cgroup, container, user,
+//!   cmdline, affinity, scheduling policy).
+//! - [`control`]: scheduling adjustments applied to live tasks (renice,
+//!   SCHED_DEADLINE reservations, cgroup `cpu.weight`/`cpu.max`, signals).
+//! - [`demo`]: a synthetic task population for running without root or a
+//!   loaded BPF program.
+//! - [`remote`]: the line protocol between `rust-runner agent` and clients
+//!   sampling it over TCP/TLS.
+//! - [`stream`]: the sampling loop as an `Iterator` (and a `Stream` behind the
+//!   `async` feature).
+//! - [`filter`], [`group`], [`tree`]: selecting and aggregating snapshots.
+//! - [`policy`]: declarative ticket assignments enforced through the
+//!   `ticket_override` map.
+//! - [`rebalance`]: a PI controller steering tickets toward nice-based
+//!   target shares.
+//! - [`autonice`]: nice-value feedback from heuristic deadline misses.
+//! - [`throttle`]: corrective actions for tasks that stay over a
+//!   utilization or lateness budget.
+//! - [`whatif`]: projected ticket shares under hypothetical ticket counts.
+//! - [`inflation`]: ticket growth per task and cgroup beyond a threshold.
+//! - [`priority`]: how well achieved CPU shares follow the ticket counts.
+//! - [`summary`]: end-of-run totals, fairness metrics, and per-task tail
+//!   lateness.
+//! - [`histogram`]: log-scaled lateness histograms; [`sketch`]: streaming
+//!   quantiles with bounded relative error.
+//! - [`latency`]: wakeup-to-run latency from the `task_latency` map.
+//! - [`events`]: per-task counts of the wakeup, waking, and migration
+//!   tracepoints from their own maps.
+//! - [`cgroup`]: runtime and tickets per cgroup from the `cgroup_map`.
+//! - [`runqueue`]: per-CPU run-queue depth from `/proc/schedstat`.
+//! - [`irq`]: per-CPU hard and soft interrupt time from `/proc/stat`.
+//! - [`export`]: the `Exporter` trait with CSV, NDJSON, Chrome trace,
+//!   StatsD, and deadline-alert sinks; [`rotate`] and [`compress`] handle
+//!   rotation and gzip/zstd compression of the CSV/NDJSON files; [`avro`]
+//!   encodes task records for message buses; [`journal`] sends deadline
+//!   alerts and change events to journald or syslog.
+//! - [`error`]: [`RunnerError`], the typed error returned by map access and
+//!   sampling; [`diagnose`] explains permission errors.
+//! - [`privilege`]: switching to an unprivileged user once the maps are
+//!   open.
+//!
+//! Features: `async` enables `futures_core::Stream` for `SnapshotStream`;
+//! `clap` derives `clap::ValueEnum` for [`group::GroupBy`],
+//! [`demo::TicketDistribution`], [`compress::Compression`],
+//! [`export::StatsdTags`], [`journal::AlertTarget`], [`stream::Clock`], and
+//! [`throttle::ThrottleAction`].
+
+// `export::snapshot_json` builds one `json!` object with every task field.
+#![recursion_limit = "256"]
+
+pub mod autonice;
+pub mod avro;
+pub mod bpf_map;
+pub mod cgroup;
+pub mod compress;
+pub mod control;
+pub mod demo;
+pub mod diagnose;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod filter;
+pub mod group;
+pub mod histogram;
+pub mod inflation;
+pub mod irq;
+pub mod journal;
+pub mod latency;
+pub mod loader;
+pub mod policy;
+pub mod priority;
+pub mod privilege;
+pub mod procfs;
+pub mod rebalance;
+pub mod remote;
+pub mod rotate;
+pub mod runqueue;
+pub mod sched_attr;
+pub mod sketch;
+pub mod stats;
+pub mod stream;
+pub mod summary;
+pub mod throttle;
+pub mod tree;
+pub mod whatif;
+
+pub use error::RunnerError;
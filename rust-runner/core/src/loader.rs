@@ -0,0 +1,407 @@
+use std::ffi::{CString, c_void};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use tracing::{debug, info};
+
+use crate::cgroup::CGROUP_PIN_NAME;
+use crate::error::{Result, RunnerError};
+use crate::events::SchedEvent;
+
+/// `libbpf_set_strict_mode(LIBBPF_STRICT_ALL)`: pointer-returning calls
+/// report errors through `errno`, as in libbpf 1.x.
+const LIBBPF_STRICT_ALL: libc::c_int = -1;
+
+/// First of libbpf's own error codes, past the errno range.
+const LIBBPF_ERRNO_START: i32 = 4000;
+
+#[repr(C)]
+struct BpfObject {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct BpfProgram {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct BpfMap {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct BpfLink {
+    _private: [u8; 0],
+}
+
+/// The leading fields of libbpf's `struct bpf_object_open_opts`, up to
+/// `btf_custom_path`; libbpf reads `sz` and takes the rest as zero.
+#[repr(C)]
+struct OpenOpts {
+    sz: usize,
+    object_name: *const libc::c_char,
+    relaxed_maps: bool,
+    pin_root_path: *const libc::c_char,
+    /// `attach_prog_fd` before libbpf 1.0, padding since.
+    _unused: u32,
+    kconfig: *const libc::c_char,
+    btf_custom_path: *const libc::c_char,
+}
+
+unsafe extern "C" {
+    fn libbpf_set_strict_mode(mode: libc::c_int) -> libc::c_int;
+    fn libbpf_get_error(ptr: *const c_void) -> libc::c_long;
+    fn libbpf_strerror(err: libc::c_int, buf: *mut libc::c_char, size: usize) -> libc::c_int;
+    fn bpf_object__open_mem(
+        obj_buf: *const c_void,
+        obj_buf_sz: usize,
+        opts: *const OpenOpts,
+    ) -> *mut BpfObject;
+    fn bpf_object__load(obj: *mut BpfObject) -> libc::c_int;
+    fn bpf_object__close(obj: *mut BpfObject);
+    fn bpf_object__find_program_by_name(
+        obj: *const BpfObject,
+        name: *const libc::c_char,
+    ) -> *mut BpfProgram;
+    fn bpf_object__find_map_by_name(
+        obj: *const BpfObject,
+        name: *const libc::c_char,
+    ) -> *mut BpfMap;
+    fn bpf_map__pin(map: *mut BpfMap, path: *const libc::c_char) -> libc::c_int;
+    fn bpf_map__unpin(map: *mut BpfMap, path: *const libc::c_char) -> libc::c_int;
+    fn bpf_program__pin(prog: *mut BpfProgram, path: *const libc::c_char) -> libc::c_int;
+    fn bpf_program__unpin(prog: *mut BpfProgram, path: *const libc::c_char) -> libc::c_int;
+    fn bpf_program__attach_tracepoint(
+        prog: *const BpfProgram,
+        category: *const libc::c_char,
+        name: *const libc::c_char,
+    ) -> *mut BpfLink;
+    fn bpf_link__pin(link: *mut BpfLink, path: *const libc::c_char) -> libc::c_int;
+    fn bpf_link__destroy(link: *mut BpfLink) -> libc::c_int;
+}
+
+/// Wakeup programs feeding `task_latency`, their tracepoints, and the
+/// suffix of their link pins next to the `sched_switch` one.
+const WAKEUP_PROGRAMS: [(&str, &str, &str); 2] = [
+    ("handle_sched_wakeup", "sched:sched_wakeup", "_wakeup"),
+    (
+        "handle_sched_wakeup_new",
+        "sched:sched_wakeup_new",
+        "_wakeup_new",
+    ),
+];
+
+/// Where [`attach`] pins what it loads. The defaults match
+/// `scripts/run.sh`, so `dump`/`tui` find the maps without flags.
+#[derive(Debug, Clone)]
+pub struct AttachConfig {
+    pub prog_pin: PathBuf,
+    pub map_pin: PathBuf,
+    pub link_pin: PathBuf,
+    /// `ticket_override` map; `None` leaves it unpinned.
+    pub override_pin: Option<PathBuf>,
+    /// `task_latency` map, whose wakeup programs are only attached when it
+    /// is pinned.
+    pub latency_pin: Option<PathBuf>,
+    /// `cgroup_map`, the per-cgroup totals; `None` leaves it unpinned.
+    pub cgroup_pin: Option<PathBuf>,
+    /// Scheduler events to count, none by default; each one's map is pinned
+    /// next to `map_pin` under its [`map_name`](SchedEvent::map_name).
+    pub events: Vec<SchedEvent>,
+    /// `category:name` of the tracepoint `handle_sched_switch` runs on.
+    pub trace_point: String,
+    /// BTF of the running kernel for CO-RE relocations, for kernels without
+    /// `/sys/kernel/btf/vmlinux`; `None` lets libbpf find it.
+    pub btf_path: Option<PathBuf>,
+}
+
+impl Default for AttachConfig {
+    fn default() -> Self {
+        Self {
+            prog_pin: PathBuf::from("/sys/fs/bpf/sched_lottery"),
+            map_pin: PathBuf::from("/sys/fs/bpf/task_map"),
+            link_pin: PathBuf::from("/sys/fs/bpf/sched_lottery_link"),
+            override_pin: Some(PathBuf::from("/sys/fs/bpf/ticket_override")),
+            latency_pin: Some(PathBuf::from("/sys/fs/bpf/task_latency")),
+            cgroup_pin: Some(PathBuf::from("/sys/fs/bpf/cgroup_map")),
+            events: Vec::new(),
+            trace_point: "sched:sched_switch".to_string(),
+            btf_path: None,
+        }
+    }
+}
+
+fn failed(step: impl Into<String>, source: io::Error) -> RunnerError {
+    RunnerError::Attach {
+        step: step.into(),
+        source,
+    }
+}
+
+/// libbpf's negative error code as an `io::Error`. Codes from
+/// `LIBBPF_ERRNO__START` up are libbpf's own (bad ELF, failed
+/// relocation, ...) and only `libbpf_strerror` knows their text.
+fn libbpf_error(code: libc::c_long) -> io::Error {
+    let errno = (-code) as i32;
+    if errno < LIBBPF_ERRNO_START {
+        return io::Error::from_raw_os_error(errno);
+    }
+    let mut buf = [0 as libc::c_char; 128];
+    unsafe { libbpf_strerror(errno, buf.as_mut_ptr(), buf.len()) };
+    let text = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    io::Error::other(text.to_string_lossy().into_owned())
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| RunnerError::Config(format!("path {} contains a NUL byte", path.display())))
+}
+
+fn c_name(name: &str) -> CString {
+    CString::new(name).expect("BPF object names contain no NUL bytes")
+}
+
+/// An opened `bpf_object`, closed (unloading whatever is not pinned) on
+/// drop.
+struct Object(*mut BpfObject);
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        unsafe { bpf_object__close(self.0) };
+    }
+}
+
+impl Object {
+    fn program(&self, name: &str) -> Result<*mut BpfProgram> {
+        let c_name = c_name(name);
+        let prog = unsafe { bpf_object__find_program_by_name(self.0, c_name.as_ptr()) };
+        if prog.is_null() {
+            return Err(failed(
+                format!("find program {name}"),
+                io::Error::from(io::ErrorKind::NotFound),
+            ));
+        }
+        Ok(prog)
+    }
+
+    fn map(&self, name: &str) -> Result<*mut BpfMap> {
+        let c_name = c_name(name);
+        let map = unsafe { bpf_object__find_map_by_name(self.0, c_name.as_ptr()) };
+        if map.is_null() {
+            return Err(failed(
+                format!("find map {name}"),
+                io::Error::from(io::ErrorKind::NotFound),
+            ));
+        }
+        Ok(map)
+    }
+}
+
+/// Pin `map` at `path`, replacing an earlier pin.
+fn repin_map(map: *mut BpfMap, name: &str, path: &Path) -> Result<()> {
+    let c_path = c_path(path)?;
+    let err = unsafe { bpf_map__unpin(map, c_path.as_ptr()) };
+    if err < 0 && err != -libc::ENOENT {
+        return Err(failed(
+            format!("unpin map {name} at {}", path.display()),
+            libbpf_error(err.into()),
+        ));
+    }
+    let err = unsafe { bpf_map__pin(map, c_path.as_ptr()) };
+    if err < 0 {
+        return Err(failed(
+            format!("pin map {name} at {}", path.display()),
+            libbpf_error(err.into()),
+        ));
+    }
+    debug!(target: "map", map = name, path = %path.display(), "pinned map");
+    Ok(())
+}
+
+/// Pin `prog` at `path`, replacing an earlier pin.
+fn repin_program(prog: *mut BpfProgram, path: &Path) -> Result<()> {
+    let c_path = c_path(path)?;
+    let err = unsafe { bpf_program__unpin(prog, c_path.as_ptr()) };
+    if err < 0 && err != -libc::ENOENT {
+        return Err(failed(
+            format!("unpin program at {}", path.display()),
+            libbpf_error(err.into()),
+        ));
+    }
+    let err = unsafe { bpf_program__pin(prog, c_path.as_ptr()) };
+    if err < 0 {
+        return Err(failed(
+            format!("pin program at {}", path.display()),
+            libbpf_error(err.into()),
+        ));
+    }
+    Ok(())
+}
+
+/// A tracepoint attachment, detached on drop unless pinned first.
+struct Link {
+    raw: *mut BpfLink,
+    trace: String,
+    pin: PathBuf,
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        unsafe { bpf_link__destroy(self.raw) };
+    }
+}
+
+impl Link {
+    /// Pin the link at its path, replacing an earlier pin, so the program
+    /// stays attached after this process exits.
+    fn pin(&self) -> Result<()> {
+        match fs::remove_file(&self.pin) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(failed(format!("remove {}", self.pin.display()), err));
+            }
+            _ => {}
+        }
+        let c_pin = c_path(&self.pin)?;
+        let err = unsafe { bpf_link__pin(self.raw, c_pin.as_ptr()) };
+        if err < 0 {
+            return Err(failed(
+                format!("pin link at {}", self.pin.display()),
+                libbpf_error(err.into()),
+            ));
+        }
+        debug!(target: "map", trace = %self.trace, link = %self.pin.display(), "pinned link");
+        Ok(())
+    }
+}
+
+/// Attach `prog` to `trace` (`category:name`); the link is pinned at
+/// `link_pin` by [`Link::pin`].
+fn attach_tracepoint(prog: *mut BpfProgram, trace: &str, link_pin: PathBuf) -> Result<Link> {
+    let (category, name) = trace
+        .split_once(':')
+        .ok_or_else(|| RunnerError::Config(format!("tracepoint {trace} is not category:name")))?;
+    let (category, name) = (c_name(category), c_name(name));
+    let raw = unsafe { bpf_program__attach_tracepoint(prog, category.as_ptr(), name.as_ptr()) };
+    let err = unsafe { libbpf_get_error(raw.cast_const().cast()) };
+    if err != 0 {
+        return Err(failed(format!("attach {trace}"), libbpf_error(err)));
+    }
+    debug!(target: "map", trace, "attached tracepoint");
+    Ok(Link {
+        raw,
+        trace: trace.to_string(),
+        pin: link_pin,
+    })
+}
+
+/// Load the scheduler's BPF object from `object` (an ELF image, e.g. the
+/// one embedded at build time), pin its maps and program, and attach it,
+/// like `loader/sched_loader`. `label` names the object in errors and logs.
+/// Nothing is pinned until every tracepoint is attached, so a failed attach
+/// leaves the running program and its maps in place. Needs `CAP_BPF`/`CAP_PERFMON` (or root) and a writable bpffs.
+pub fn attach(object: &[u8], label: &str, config: &AttachConfig) -> Result<()> {
+    unsafe { libbpf_set_strict_mode(LIBBPF_STRICT_ALL) };
+    raise_memlock_limit();
+
+    let btf_path = config.btf_path.as_deref().map(c_path).transpose()?;
+    let object_name = c_name(label);
+    let opts = OpenOpts {
+        sz: size_of::<OpenOpts>(),
+        object_name: object_name.as_ptr(),
+        relaxed_maps: false,
+        pin_root_path: ptr::null(),
+        _unused: 0,
+        kconfig: ptr::null(),
+        btf_custom_path: btf_path.as_ref().map_or(ptr::null(), |path| path.as_ptr()),
+    };
+    let raw = unsafe { bpf_object__open_mem(object.as_ptr().cast(), object.len(), &opts) };
+    let err = unsafe { libbpf_get_error(raw.cast_const().cast()) };
+    if err != 0 {
+        return Err(failed(format!("open {label}"), libbpf_error(err)));
+    }
+    let obj = Object(raw);
+
+    let prog = obj.program("handle_sched_switch")?;
+    let task_map = obj.map("task_map")?;
+    let err = unsafe { bpf_object__load(obj.0) };
+    if err < 0 {
+        return Err(failed(format!("load {label}"), libbpf_error(err.into())));
+    }
+
+    // Dropping the links detaches them again if anything below fails.
+    let mut links = vec![attach_tracepoint(
+        prog,
+        &config.trace_point,
+        config.link_pin.clone(),
+    )?];
+    if config.latency_pin.is_some() {
+        for (name, trace, suffix) in WAKEUP_PROGRAMS {
+            links.push(attach_tracepoint(
+                obj.program(name)?,
+                trace,
+                link_beside(config, suffix),
+            )?);
+        }
+    }
+    for event in &config.events {
+        let prog = obj.program(event.program())?;
+        for (trace, suffix) in event.tracepoints() {
+            links.push(attach_tracepoint(prog, trace, link_beside(config, suffix))?);
+        }
+    }
+
+    repin_map(task_map, "task_map", &config.map_pin)?;
+    if let Some(path) = &config.override_pin {
+        repin_map(obj.map("ticket_override")?, "ticket_override", path)?;
+    }
+    if let Some(path) = &config.cgroup_pin {
+        repin_map(obj.map(CGROUP_PIN_NAME)?, CGROUP_PIN_NAME, path)?;
+    }
+    if let Some(path) = &config.latency_pin {
+        repin_map(obj.map("task_latency")?, "task_latency", path)?;
+    }
+    for event in &config.events {
+        let name = event.map_name();
+        repin_map(obj.map(name)?, name, &config.map_pin.with_file_name(name))?;
+    }
+    repin_program(prog, &config.prog_pin)?;
+    for link in &links {
+        link.pin()?;
+    }
+    info!(
+        target: "map",
+        object = label,
+        prog = %config.prog_pin.display(),
+        map = %config.map_pin.display(),
+        link = %config.link_pin.display(),
+        "loaded and attached BPF program"
+    );
+    Ok(())
+}
+
+/// Pin path of a link next to the `sched_switch` one.
+fn link_beside(config: &AttachConfig, suffix: &str) -> PathBuf {
+    let mut link_pin = config.link_pin.clone().into_os_string();
+    link_pin.push(suffix);
+    link_pin.into()
+}
+
+/// Kernels before 5.11 charge BPF maps against `RLIMIT_MEMLOCK`; lift it
+/// where allowed and let the load report the error otherwise.
+fn raise_memlock_limit() {
+    let unlimited = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &unlimited) } != 0 {
+        debug!(
+            target: "map",
+            "cannot lift RLIMIT_MEMLOCK: {}",
+            io::Error::last_os_error()
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use crate::error::{Result, RunnerError};
+use crate::export::ChangeEvent;
+use crate::filter::glob_match;
+use crate::stats::{TaskSnapshot, nice_to_tickets};
+use crate::stream::{SnapshotStream, SnapshotWindow};
+
+/// One `[[rule]]` of a ticket policy file. Every criterion that is set must
+/// match; at least one is required.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    /// Glob over the task name (`/proc/<pid>/comm`), e.g. `postgres*`.
+    pub comm: Option<String>,
+    /// User name (or numeric uid when it has no name).
+    pub user: Option<String>,
+    /// Cgroup v2 path; tasks in nested cgroups match too.
+    pub cgroup: Option<String>,
+    pub tickets: u32,
+}
+
+impl PolicyRule {
+    fn matches(&self, snap: &TaskSnapshot) -> bool {
+        let comm = self.comm.as_deref().is_none_or(|pattern| {
+            snap.meta
+                .comm
+                .as_deref()
+                .is_some_and(|comm| glob_match(pattern, comm))
+        });
+        let user = self
+            .user
+            .as_deref()
+            .is_none_or(|user| snap.meta.user_label() == user);
+        let cgroup = self.cgroup.as_deref().is_none_or(|cgroup| {
+            let cgroup = cgroup.trim_end_matches('/');
+            snap.meta.cgroup.as_deref().is_some_and(|path| {
+                path == cgroup
+                    || path
+                        .strip_prefix(cgroup)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        });
+        comm && user && cgroup
+    }
+}
+
+/// Declarative ticket assignments, read from TOML:
+///
+/// ```toml
+/// [[rule]]
+/// comm = "postgres*"
+/// tickets = 400
+///
+/// [[rule]]
+/// cgroup = "/system.slice/backup.service"
+/// tickets = 20
+/// ```
+///
+/// The first matching rule wins; tasks no rule matches keep their
+/// nice-derived tickets.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TicketPolicy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<PolicyRule>,
+}
+
+impl TicketPolicy {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let policy: Self = toml::from_str(text).map_err(|err| err.to_string())?;
+        for (index, rule) in policy.rules.iter().enumerate() {
+            if rule.comm.is_none() && rule.user.is_none() && rule.cgroup.is_none() {
+                return Err(format!(
+                    "rule {} needs at least one of comm, user, cgroup",
+                    index + 1
+                ));
+            }
+            if rule.tickets == 0 {
+                return Err(format!("rule {} assigns 0 tickets", index + 1));
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))?;
+        Self::parse(&text).map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))
+    }
+
+    /// Tickets the policy assigns to `snap`, if any rule matches.
+    pub fn tickets_for(&self, snap: &TaskSnapshot) -> Option<u32> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(snap))
+            .map(|rule| rule.tickets)
+    }
+}
+
+/// Keeps the `ticket_override` map in line with a [`TicketPolicy`]: each
+/// [`reconcile`](Self::reconcile) assigns tickets to newly matching tasks
+/// (e.g. a restarted service) and drops overrides that no longer apply. The
+/// file is re-read when it changes.
+pub struct PolicyEnforcer {
+    path: PathBuf,
+    policy: TicketPolicy,
+    modified: Option<SystemTime>,
+    /// Overrides this enforcer wrote, by pid.
+    applied: HashMap<u32, u32>,
+}
+
+impl PolicyEnforcer {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            policy: TicketPolicy::load(path)?,
+            modified: modified(path),
+            applied: HashMap::new(),
+        })
+    }
+
+    /// Re-read the file after an edit; a broken edit keeps the last good policy.
+    fn reload_if_changed(&mut self) {
+        let current = modified(&self.path);
+        if current == self.modified {
+            return;
+        }
+        self.modified = current;
+        match TicketPolicy::load(&self.path) {
+            Ok(policy) => {
+                info!(target: "map", path = %self.path.display(), rules = policy.rules.len(), "ticket policy reloaded");
+                self.policy = policy;
+            }
+            Err(err) => warn!(target: "map", "keeping previous ticket policy: {err}"),
+        }
+    }
+
+    /// Bring the overrides for every task of `window` (read from `stream`)
+    /// in line with the policy, returning the ticket changes made. Tasks a
+    /// selector left out count too, so open the stream
+    /// [`with_unselected`](SnapshotStream::with_unselected).
+    pub fn reconcile(
+        &mut self,
+        stream: &mut SnapshotStream,
+        window: &SnapshotWindow,
+    ) -> Result<Vec<ChangeEvent>> {
+        self.reload_if_changed();
+        let (mut set, mut cleared) = (0, 0);
+        let mut events = Vec::new();
+        let live: HashSet<u32> = window.all_tasks().map(|snap| snap.pid).collect();
+        for snap in window.all_tasks() {
+            let wanted = self.policy.tickets_for(snap);
+            match (wanted, self.applied.get(&snap.pid).copied()) {
+                (Some(tickets), Some(current)) if tickets == current => {}
+                (Some(tickets), _) => {
+                    stream.set_ticket_override(snap.pid, Some(tickets))?;
+                    debug!(target: "map", pid = snap.pid, tickets, "ticket override set");
+                    self.applied.insert(snap.pid, tickets);
+                    set += 1;
+                    events.push(policy_event(snap, tickets, "a policy rule matches"));
+                }
+                (None, Some(_)) => {
+                    stream.set_ticket_override(snap.pid, None)?;
+                    self.applied.remove(&snap.pid);
+                    cleared += 1;
+                    let tickets = nice_to_tickets(snap.info.nice);
+                    events.push(policy_event(
+                        snap,
+                        tickets,
+                        "no policy rule matches any more",
+                    ));
+                }
+                (None, None) => {}
+            }
+        }
+        let gone: Vec<u32> = self
+            .applied
+            .keys()
+            .copied()
+            .filter(|pid| !live.contains(pid))
+            .collect();
+        for pid in gone {
+            stream.set_ticket_override(pid, None)?;
+            self.applied.remove(&pid);
+            cleared += 1;
+        }
+        if set + cleared > 0 {
+            info!(target: "map", set, cleared, active = self.applied.len(), "ticket policy reconciled");
+        }
+        Ok(events)
+    }
+
+    /// Remove every override this enforcer wrote, handing the tasks back to
+    /// nice-derived tickets.
+    pub fn release(&mut self, stream: &mut SnapshotStream) -> Result<()> {
+        for (pid, _) in self.applied.drain() {
+            stream.set_ticket_override(pid, None)?;
+        }
+        Ok(())
+    }
+}
+
+fn policy_event(snap: &TaskSnapshot, tickets: u32, reason: &str) -> ChangeEvent {
+    ChangeEvent {
+        pid: snap.pid,
+        field: "tickets",
+        from: snap.info.tickets.into(),
+        to: tickets.into(),
+        source: "policy",
+        reason: reason.to_string(),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
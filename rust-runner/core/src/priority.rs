@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+use crate::stats::{TaskSnapshot, spearman};
+
+/// Achieved/expected share ratio beyond which (either way) a task is an
+/// outlier.
+pub const OUTLIER_RATIO: f64 = 2.0;
+
+/// Outliers a [`PriorityReport`] lists at most.
+pub const MAX_OUTLIERS: usize = 5;
+
+/// A task whose achieved CPU share is far from what its tickets entitle it
+/// to, relative to the other tasks that ran.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PriorityOutlier {
+    pub pid: u32,
+    pub comm: Option<String>,
+    pub nice: i32,
+    pub tickets: u32,
+    /// Share of the compared tasks' tickets.
+    pub expected_share: f64,
+    /// Share of the compared tasks' smoothed runtime.
+    pub achieved_share: f64,
+}
+
+impl PriorityOutlier {
+    /// Achieved over expected share: above 1 the task ran more than its
+    /// tickets say, below 1 less.
+    pub fn ratio(&self) -> f64 {
+        self.achieved_share / self.expected_share
+    }
+}
+
+/// How well the achieved runtime follows the ticket counts over the tasks
+/// that ran: a rank correlation, and the tasks furthest off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PriorityReport {
+    /// Tasks compared: lottery tasks with tickets and smoothed runtime.
+    pub tasks: usize,
+    /// Spearman correlation of tickets and achieved share; 1 when more
+    /// tickets always means more CPU. `None` for fewer than three tasks or
+    /// equal tickets all round.
+    pub spearman: Option<f64>,
+    /// Tasks whose achieved/expected share ratio is beyond
+    /// [`OUTLIER_RATIO`] either way, furthest off first, at most
+    /// [`MAX_OUTLIERS`].
+    pub outliers: Vec<PriorityOutlier>,
+}
+
+impl PriorityReport {
+    /// Compare the tasks' tickets with their rolling runtime. Tasks that
+    /// did not run lately are left out: a sleeping task does not ask for
+    /// its share, so its absence says nothing about the scheduler.
+    pub fn of(snapshots: &[TaskSnapshot]) -> Self {
+        let compared: Vec<&TaskSnapshot> = snapshots
+            .iter()
+            .filter(|snap| snap.lottery_tickets() > 0 && snap.rolling_runtime_ms > 0.0)
+            .collect();
+        let tickets: f64 = compared
+            .iter()
+            .map(|snap| snap.lottery_tickets() as f64)
+            .sum();
+        let runtime: f64 = compared.iter().map(|snap| snap.rolling_runtime_ms).sum();
+        if compared.is_empty() || runtime <= 0.0 {
+            return Self::default();
+        }
+        let spearman = spearman(
+            compared
+                .iter()
+                .map(|snap| (snap.lottery_tickets() as f64, snap.rolling_runtime_ms)),
+        );
+        let limit = OUTLIER_RATIO.ln();
+        let mut outliers: Vec<PriorityOutlier> = compared
+            .iter()
+            .map(|snap| PriorityOutlier {
+                pid: snap.pid,
+                comm: snap.meta.comm.clone(),
+                nice: snap.info.nice,
+                tickets: snap.lottery_tickets(),
+                expected_share: snap.lottery_tickets() as f64 / tickets,
+                achieved_share: snap.rolling_runtime_ms / runtime,
+            })
+            .filter(|outlier| outlier.ratio().ln().abs() > limit)
+            .collect();
+        outliers.sort_by(|a, b| {
+            b.ratio()
+                .ln()
+                .abs()
+                .total_cmp(&a.ratio().ln().abs())
+                .then(a.pid.cmp(&b.pid))
+        });
+        outliers.truncate(MAX_OUTLIERS);
+        Self {
+            tasks: compared.len(),
+            spearman,
+            outliers,
+        }
+    }
+}
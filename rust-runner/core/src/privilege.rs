@@ -0,0 +1,190 @@
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::mem::MaybeUninit;
+
+use crate::diagnose::{CAP_BPF, CAP_SYS_ADMIN};
+use crate::error::{Result, RunnerError};
+
+const CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// An account to switch to with [`drop_to`].
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub name: String,
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+    /// Whether the account has a passwd entry, and so supplementary groups.
+    known: bool,
+}
+
+impl Account {
+    /// Look `user` up in the passwd database; a numeric uid without an
+    /// entry is accepted and gets the group with the same id.
+    pub fn lookup(user: &str) -> Result<Self> {
+        let invalid = |why: &str| RunnerError::Config(format!("--run-as-user {user}: {why}"));
+        let name = CString::new(user).map_err(|_| invalid("contains a NUL byte"))?;
+        let mut buf = vec![0 as libc::c_char; 16 * 1024];
+        let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut found = std::ptr::null_mut();
+        let numeric = user.parse::<libc::uid_t>().ok();
+        let rc = unsafe {
+            match numeric {
+                Some(uid) => libc::getpwuid_r(
+                    uid,
+                    pwd.as_mut_ptr(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut found,
+                ),
+                None => libc::getpwnam_r(
+                    name.as_ptr(),
+                    pwd.as_mut_ptr(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut found,
+                ),
+            }
+        };
+        if rc != 0 {
+            return Err(invalid(&io::Error::from_raw_os_error(rc).to_string()));
+        }
+        if found.is_null() {
+            return match numeric {
+                Some(uid) => Ok(Self {
+                    name: user.to_string(),
+                    uid,
+                    gid: uid,
+                    known: false,
+                }),
+                None => Err(invalid("no such user")),
+            };
+        }
+        let pwd = unsafe { pwd.assume_init() };
+        if pwd.pw_uid == 0 {
+            return Err(invalid("is root, so nothing would be dropped"));
+        }
+        Ok(Self {
+            name: unsafe { CStr::from_ptr(pwd.pw_name) }
+                .to_string_lossy()
+                .into_owned(),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+            known: true,
+        })
+    }
+}
+
+/// Switch this process to `account` for good: supplementary groups, real,
+/// effective and saved ids, no ambient or inheritable capabilities, and no
+/// way back through setuid binaries. With `keep_bpf` the thread keeps
+/// CAP_BPF (CAP_SYS_ADMIN on kernels that predate it) and nothing else, so
+/// already-open map fds stay readable under `unprivileged_bpf_disabled`.
+///
+/// Capabilities are per thread: call this before spawning threads that need
+/// the kept capability; threads spawned earlier lose every capability.
+pub fn drop_to(account: &Account, keep_bpf: bool) -> Result<()> {
+    let fail = |step: &str, source: io::Error| RunnerError::PrivilegeDrop {
+        user: account.name.clone(),
+        step: step.to_string(),
+        source,
+    };
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(fail(
+            "check",
+            io::Error::new(io::ErrorKind::PermissionDenied, "not running as root"),
+        ));
+    }
+    let kept = keep_bpf.then(|| match cap_last_cap() {
+        Some(last) if last >= CAP_BPF => CAP_BPF,
+        _ => CAP_SYS_ADMIN,
+    });
+
+    // Shrink the bounding set while we still have CAP_SETPCAP.
+    for cap in 0..=cap_last_cap().unwrap_or(CAP_BPF) {
+        if Some(cap) != kept && unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) } != 0 {
+            let err = io::Error::last_os_error();
+            // Capabilities this kernel does not know about.
+            if err.raw_os_error() != Some(libc::EINVAL) {
+                return Err(fail("bounding set", err));
+            }
+        }
+    }
+    if kept.is_some() && unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) } != 0 {
+        return Err(fail("keepcaps", io::Error::last_os_error()));
+    }
+
+    let groups = if account.known {
+        let name = CString::new(account.name.as_str()).expect("passwd names have no NUL");
+        unsafe { libc::initgroups(name.as_ptr(), account.gid) }
+    } else {
+        unsafe { libc::setgroups(1, &account.gid) }
+    };
+    if groups != 0 {
+        return Err(fail("setgroups", io::Error::last_os_error()));
+    }
+    if unsafe { libc::setresgid(account.gid, account.gid, account.gid) } != 0 {
+        return Err(fail("setresgid", io::Error::last_os_error()));
+    }
+    if unsafe { libc::setresuid(account.uid, account.uid, account.uid) } != 0 {
+        return Err(fail("setresuid", io::Error::last_os_error()));
+    }
+
+    // Leaving uid 0 cleared the effective set; keepcaps left the permitted
+    // set for us to trim to the one capability we need.
+    let mut data = [CapData::default(); 2];
+    if let Some(cap) = kept {
+        let word = &mut data[(cap / 32) as usize];
+        word.permitted = 1 << (cap % 32);
+        word.effective = word.permitted;
+    }
+    let mut header = CapHeader {
+        version: CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    if unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) } != 0 {
+        return Err(fail("capset", io::Error::last_os_error()));
+    }
+    if unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0) } != 0
+        || unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_CLEAR_ALL,
+                0,
+                0,
+                0,
+            )
+        } != 0
+        || unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0
+    {
+        return Err(fail("prctl", io::Error::last_os_error()));
+    }
+
+    if unsafe { libc::setuid(0) } == 0 {
+        return Err(fail("verify", io::Error::other("setuid(0) still succeeds")));
+    }
+    Ok(())
+}
+
+/// Highest capability number the running kernel knows.
+fn cap_last_cap() -> Option<u32> {
+    fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
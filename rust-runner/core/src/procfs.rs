@@ -0,0 +1,407 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sched_attr::{SchedAttr, sched_getattr};
+
+/// Per-task attributes resolved from `/proc`, cached across samples.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskMeta {
+    pub cgroup: Option<String>,
+    pub container_id: Option<String>,
+    pub pod_uid: Option<String>,
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    pub cmdline: Option<String>,
+    pub tgid: Option<u32>,
+    pub ppid: Option<u32>,
+    pub comm: Option<String>,
+    pub cpus_allowed: Vec<u32>,
+    pub last_cpu: Option<u32>,
+    pub sched: Option<SchedAttr>,
+    pub kthread: bool,
+    /// Start time in clock ticks since boot (field 22 of `/proc/<pid>/stat`);
+    /// tells a recycled pid from the task that had it before.
+    #[serde(default)]
+    pub start_time: Option<u64>,
+}
+
+impl TaskMeta {
+    pub fn resolve(pid: u32) -> Self {
+        let cgroup = cgroup_path(pid);
+        let container_id = cgroup.as_deref().and_then(parse_container_id);
+        let pod_uid = cgroup.as_deref().and_then(parse_pod_uid);
+        let cmdline = cmdline(pid);
+        Self {
+            cgroup,
+            container_id,
+            pod_uid,
+            uid: task_uid(pid),
+            user: None,
+            kthread: is_kthread(pid, cmdline.is_none()),
+            cmdline,
+            tgid: status_field(pid, "Tgid:"),
+            ppid: parent_pid(pid),
+            comm: comm(pid),
+            cpus_allowed: cpu_affinity(pid),
+            last_cpu: last_cpu(pid),
+            sched: sched_getattr(pid),
+            start_time: start_time(pid),
+        }
+    }
+
+    /// Re-read the fields that change between samples (placement/affinity),
+    /// and the start time, which changes when the pid is reused.
+    pub fn refresh_volatile(&mut self, pid: u32) {
+        self.cpus_allowed = cpu_affinity(pid);
+        let stat = stat_fields(pid);
+        self.last_cpu = stat.as_deref().and_then(|stat| nth_stat_field(stat, 39));
+        self.start_time = stat.as_deref().and_then(|stat| nth_stat_field(stat, 22));
+        self.sched = sched_getattr(pid);
+    }
+
+    pub fn policy_label(&self) -> String {
+        self.sched
+            .map(|attr| attr.label())
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// User name when it resolves, otherwise the numeric uid. Kernel threads
+    /// are labelled `[kernel]` so they group apart from root daemons.
+    pub fn user_label(&self) -> String {
+        if self.kthread {
+            return "[kernel]".to_string();
+        }
+        match (&self.user, self.uid) {
+            (Some(user), _) => user.clone(),
+            (None, Some(uid)) => uid.to_string(),
+            (None, None) => "-".to_string(),
+        }
+    }
+
+    pub fn last_cpu_label(&self) -> String {
+        self.last_cpu
+            .map(|cpu| cpu.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// Short (12 character) container id, as printed by `docker ps`.
+    pub fn short_container_id(&self) -> Option<&str> {
+        self.container_id
+            .as_deref()
+            .map(|id| &id[..id.len().min(12)])
+    }
+}
+
+/// Returns the cgroup v2 path of `pid` (the `0::` entry of `/proc/<pid>/cgroup`).
+pub fn cgroup_path(pid: u32) -> Option<String> {
+    let data = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    parse_cgroup_v2(&data)
+}
+
+fn parse_cgroup_v2(data: &str) -> Option<String> {
+    data.lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim().to_string())
+}
+
+const CONTAINER_SCOPE_PREFIXES: [&str; 5] = [
+    "docker-",
+    "cri-containerd-",
+    "crio-",
+    "libpod-",
+    "containerd-",
+];
+
+/// Extracts a docker/containerd/CRI-O/podman container id from a cgroup path.
+/// Handles both the systemd driver (`docker-<id>.scope`) and the cgroupfs
+/// driver (`/docker/<id>`, `/kubepods/.../pod<uid>/<id>`).
+pub fn parse_container_id(cgroup: &str) -> Option<String> {
+    cgroup.rsplit('/').find_map(|segment| {
+        let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+        let candidate = CONTAINER_SCOPE_PREFIXES
+            .iter()
+            .find_map(|prefix| segment.strip_prefix(prefix))
+            .unwrap_or(segment);
+        is_container_id(candidate).then(|| candidate.to_string())
+    })
+}
+
+fn is_container_id(candidate: &str) -> bool {
+    candidate.len() == 64 && candidate.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Extracts the Kubernetes pod UID from a kubepods cgroup path. The systemd
+/// driver encodes dashes as underscores (`kubepods-burstable-pod<uid>.slice`).
+pub fn parse_pod_uid(cgroup: &str) -> Option<String> {
+    if !cgroup.contains("kubepods") {
+        return None;
+    }
+    cgroup.split('/').find_map(|segment| {
+        let segment = segment.strip_suffix(".slice").unwrap_or(segment);
+        let uid = segment
+            .rsplit_once("-pod")
+            .map(|(_, uid)| uid)
+            .or_else(|| segment.strip_prefix("pod"))?;
+        let uid = uid.replace('_', "-");
+        (uid.len() == 36 && uid.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-')).then_some(uid)
+    })
+}
+
+/// Longest command line kept per task; worker pools rarely need more.
+pub const CMDLINE_MAX_CHARS: usize = 256;
+
+/// `/proc/<pid>/cmdline` with NUL separators turned into spaces, truncated to
+/// [`CMDLINE_MAX_CHARS`]. Kernel threads have an empty cmdline and yield `None`.
+pub fn cmdline(pid: u32) -> Option<String> {
+    let raw = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if args.is_empty() {
+        return None;
+    }
+    let joined = args.join(" ");
+    if joined.chars().count() > CMDLINE_MAX_CHARS {
+        let mut truncated: String = joined.chars().take(CMDLINE_MAX_CHARS - 3).collect();
+        truncated.push_str("...");
+        Some(truncated)
+    } else {
+        Some(joined)
+    }
+}
+
+/// This host's name from `/proc/sys/kernel/hostname`, or `localhost`.
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Task name from `/proc/<pid>/comm`.
+pub fn comm(pid: u32) -> Option<String> {
+    let data = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(data.trim_end().to_string())
+}
+
+/// Parent pid (field 4 of `/proc/<pid>/stat`).
+pub fn parent_pid(pid: u32) -> Option<u32> {
+    stat_field(pid, 4)
+}
+
+/// CPU the task last ran on (field 39 of `/proc/<pid>/stat`).
+pub fn last_cpu(pid: u32) -> Option<u32> {
+    stat_field(pid, 39)
+}
+
+/// Start time in clock ticks since boot (field 22 of `/proc/<pid>/stat`).
+pub fn start_time(pid: u32) -> Option<u64> {
+    stat_field(pid, 22)
+}
+
+/// Numeric field `field` (1-based, as in proc(5)) of `/proc/<pid>/stat`.
+fn stat_field<T: std::str::FromStr>(pid: u32, field: usize) -> Option<T> {
+    nth_stat_field(&stat_fields(pid)?, field)
+}
+
+/// `/proc/<pid>/stat` from field 3 on. The comm field may contain spaces and
+/// parentheses, so it starts after the last `)`, which ends field 2.
+fn stat_fields(pid: u32) -> Option<String> {
+    let mut data = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    data.drain(..data.rfind(')')? + 1);
+    Some(data)
+}
+
+/// Field `field` (1-based) of what [`stat_fields`] returned.
+fn nth_stat_field<T: std::str::FromStr>(fields: &str, field: usize) -> Option<T> {
+    fields
+        .split_whitespace()
+        .nth(field.checked_sub(3)?)?
+        .parse()
+        .ok()
+}
+
+const PF_KTHREAD: u64 = 0x0020_0000;
+
+/// Kernel threads carry PF_KTHREAD in the `flags` field (9) of
+/// `/proc/<pid>/stat`; when that cannot be read, fall back to the empty
+/// cmdline heuristic.
+pub fn is_kthread(pid: u32, empty_cmdline: bool) -> bool {
+    match stat_field::<u64>(pid, 9) {
+        Some(flags) => flags & PF_KTHREAD != 0,
+        None => empty_cmdline,
+    }
+}
+
+/// CPUs the task may run on, via sched_getaffinity(2).
+pub fn cpu_affinity(pid: u32) -> Vec<u32> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        libc::sched_getaffinity(
+            pid as libc::pid_t,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &mut set,
+        )
+    };
+    if ret != 0 {
+        return Vec::new();
+    }
+    (0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .map(|cpu| cpu as u32)
+        .collect()
+}
+
+/// CPUs the kernel has online, the capacity runtime is shared out of; falls
+/// back to the CPUs this process may run on.
+pub fn online_cpus() -> usize {
+    fs::read_to_string("/sys/devices/system/cpu/online")
+        .ok()
+        .and_then(|list| parse_cpu_list(&list).ok())
+        .map(|cpus| cpus.len())
+        .filter(|&cpus| cpus > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+/// Parses a kernel-style CPU list such as `0-3,8,10-11`.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<u32>, String> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid CPU '{v}' in list '{list}'"))
+        };
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(format!("invalid CPU range '{part}'"));
+                }
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// Formats CPUs back into the compact kernel list form.
+pub fn format_cpu_list(cpus: &[u32]) -> String {
+    let mut parts = Vec::new();
+    let mut iter = cpus.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap_or(end);
+        }
+        if start == end {
+            parts.push(start.to_string());
+        } else {
+            parts.push(format!("{start}-{end}"));
+        }
+    }
+    parts.join(",")
+}
+
+/// Real uid of `pid`, from the `Uid:` line of `/proc/<pid>/status`.
+pub fn task_uid(pid: u32) -> Option<u32> {
+    status_field(pid, "Uid:")
+}
+
+/// First numeric value of a `/proc/<pid>/status` line. Thread ids resolve too,
+/// even though they are not listed in `/proc`.
+fn status_field(pid: u32, key: &str) -> Option<u32> {
+    let data = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    data.lines()
+        .find_map(|line| line.strip_prefix(key))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Resolves a uid to a user name through NSS (`getpwuid_r`).
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() || pwd.pw_name.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// The few attributes needed to select a task before it is enriched.
+#[derive(Debug, Default, Clone)]
+pub struct TaskIdentity {
+    pub tgid: Option<u32>,
+    pub comm: Option<String>,
+}
+
+impl TaskIdentity {
+    pub fn resolve(pid: u32) -> Self {
+        Self {
+            tgid: status_field(pid, "Tgid:"),
+            comm: comm(pid),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProcCache {
+    entries: HashMap<u32, TaskMeta>,
+    identities: HashMap<u32, TaskIdentity>,
+    users: HashMap<u32, Option<String>>,
+}
+
+impl ProcCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lookup(&mut self, pid: u32) -> &TaskMeta {
+        let users = &mut self.users;
+        self.entries.entry(pid).or_insert_with(|| {
+            let mut meta = TaskMeta::resolve(pid);
+            meta.user = meta
+                .uid
+                .and_then(|uid| users.entry(uid).or_insert_with(|| user_name(uid)).clone());
+            meta
+        })
+    }
+
+    /// Cached [`TaskIdentity`]; much cheaper than a full [`lookup`](Self::lookup).
+    pub fn identity(&mut self, pid: u32) -> &TaskIdentity {
+        self.identities
+            .entry(pid)
+            .or_insert_with(|| TaskIdentity::resolve(pid))
+    }
+
+    /// Drop what is cached about `pid`, e.g. once it was reused.
+    pub fn forget(&mut self, pid: u32) {
+        self.entries.remove(&pid);
+        self.identities.remove(&pid);
+    }
+
+    /// Drop cached entries for pids that disappeared from the map.
+    pub fn retain_live(&mut self, live: &HashSet<u32>) {
+        self.entries.retain(|pid, _| live.contains(pid));
+        self.identities.retain(|pid, _| live.contains(pid));
+    }
+
+    /// Drop everything, so metadata is resolved afresh on the next lookup.
+    /// Returns how many cached entries were dropped.
+    pub fn clear(&mut self) -> usize {
+        let dropped = self.entries.len() + self.identities.len() + self.users.len();
+        *self = Self::default();
+        dropped
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+
+use tracing::debug;
+
+use crate::error::Result;
+use crate::export::ChangeEvent;
+use crate::stats::{SchedClass, TaskSnapshot, nice_to_tickets};
+use crate::stream::SnapshotStream;
+
+/// Bound on the accumulated relative error, so a task that cannot use its
+/// share (it sleeps, or is pinned to a busy CPU) does not wind up forever.
+const INTEGRAL_LIMIT: f64 = 4.0;
+/// Tickets stay within `[base / SPAN, base * SPAN]` of the nice-derived count.
+const TICKET_SPAN: f64 = 8.0;
+
+/// Gains and step bound of the [`Rebalancer`].
+#[derive(Debug, Clone, Copy)]
+pub struct RebalanceConfig {
+    /// Proportional gain on the relative share error.
+    pub kp: f64,
+    /// Integral gain on the accumulated relative share error.
+    pub ki: f64,
+    /// Largest relative ticket change per interval (0.25 = ±25 %).
+    pub max_step: f64,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            kp: 0.5,
+            ki: 0.1,
+            max_step: 0.25,
+        }
+    }
+}
+
+/// One proposed ticket change.
+#[derive(Debug, Clone, Copy)]
+pub struct Adjustment {
+    pub pid: u32,
+    /// Share the task's nice value entitles it to among the active fair tasks.
+    pub target_share: f64,
+    /// Share of the window's fair-class runtime it actually received.
+    pub achieved_share: f64,
+    pub from: u32,
+    pub to: u32,
+}
+
+impl Adjustment {
+    /// The adjustment as exported with the window's other changes.
+    pub fn change_event(&self) -> ChangeEvent {
+        ChangeEvent {
+            pid: self.pid,
+            field: "tickets",
+            from: self.from.into(),
+            to: self.to.into(),
+            source: "rebalance",
+            reason: format!(
+                "achieved {:.2}% of the runtime, entitled to {:.2}%",
+                self.achieved_share * 100.0,
+                self.target_share * 100.0
+            ),
+        }
+    }
+}
+
+/// PI controller that nudges per-task tickets (through the
+/// `ticket_override` map) until each active fair task's achieved CPU share
+/// matches the share its nice value entitles it to.
+pub struct Rebalancer {
+    config: RebalanceConfig,
+    integrals: HashMap<u32, f64>,
+    /// Pids with an override written by [`apply`](Self::apply).
+    written: HashSet<u32>,
+}
+
+impl Rebalancer {
+    pub fn new(config: RebalanceConfig) -> Self {
+        Self {
+            config,
+            integrals: HashMap::new(),
+            written: HashSet::new(),
+        }
+    }
+
+    /// Compare achieved and target shares for one window and propose ticket
+    /// changes. Tasks that did not run in the window are left alone.
+    pub fn step(&mut self, snapshots: &[TaskSnapshot]) -> Vec<Adjustment> {
+        let active: Vec<&TaskSnapshot> = snapshots
+            .iter()
+            .filter(|snap| snap.info.sched_class() == SchedClass::Fair && snap.runtime_delta_ns > 0)
+            .collect();
+        let live: HashSet<u32> = snapshots.iter().map(|snap| snap.pid).collect();
+        self.integrals.retain(|pid, _| live.contains(pid));
+
+        let total_runtime: u64 = active.iter().map(|snap| snap.runtime_delta_ns).sum();
+        let total_base: u64 = active
+            .iter()
+            .map(|snap| nice_to_tickets(snap.info.nice) as u64)
+            .sum();
+        if total_runtime == 0 || total_base == 0 {
+            return Vec::new();
+        }
+
+        let mut adjustments = Vec::new();
+        for snap in active {
+            let base = nice_to_tickets(snap.info.nice) as f64;
+            let target_share = base / total_base as f64;
+            let achieved_share = snap.runtime_delta_ns as f64 / total_runtime as f64;
+            let error = ((target_share - achieved_share) / target_share).clamp(-1.0, 1.0);
+            let integral = self.integrals.entry(snap.pid).or_default();
+            *integral = (*integral + error).clamp(-INTEGRAL_LIMIT, INTEGRAL_LIMIT);
+
+            let output = self.config.kp * error + self.config.ki * *integral;
+            let current = snap.info.tickets.max(1) as f64;
+            let wanted = (base * (1.0 + output))
+                .clamp(base / TICKET_SPAN, base * TICKET_SPAN)
+                .clamp(
+                    current * (1.0 - self.config.max_step),
+                    current * (1.0 + self.config.max_step),
+                );
+            let to = wanted.round().max(1.0) as u32;
+            if to != snap.info.tickets {
+                adjustments.push(Adjustment {
+                    pid: snap.pid,
+                    target_share,
+                    achieved_share,
+                    from: snap.info.tickets,
+                    to,
+                });
+            }
+        }
+        adjustments
+    }
+
+    /// Write `adjustments` to the override map behind `stream`.
+    pub fn apply(&mut self, stream: &mut SnapshotStream, adjustments: &[Adjustment]) -> Result<()> {
+        for adjustment in adjustments {
+            stream.set_ticket_override(adjustment.pid, Some(adjustment.to))?;
+            self.written.insert(adjustment.pid);
+            debug!(
+                target: "map",
+                pid = adjustment.pid,
+                from = adjustment.from,
+                to = adjustment.to,
+                "rebalanced tickets"
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove every override written so far, handing tasks back to
+    /// nice-derived tickets.
+    pub fn release(&mut self, stream: &mut SnapshotStream) -> Result<()> {
+        for pid in self.written.drain() {
+            stream.set_ticket_override(pid, None)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::procfs::TaskMeta;
+use crate::stats::TaskInfo;
+
+/// Line a client sends to ask an agent for one [`RemoteFrame`].
+pub const SAMPLE_REQUEST: &str = "sample";
+
+/// Longest request line an agent reads; anything longer is not a request.
+const MAX_REQUEST_LEN: u64 = 256;
+
+/// Longest frame line a client reads, far above what a 10k-task map with
+/// its metadata takes.
+const MAX_FRAME_LEN: u64 = 64 << 20;
+
+/// What an agent sends per request: the raw map entries plus the metadata it
+/// resolved from its own `/proc`. Enrichment (deltas, EWMA, shares) happens
+/// on the client, so each client keeps its own interval and filters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFrame {
+    pub entries: Vec<(u32, RemoteEntry)>,
+    pub meta: HashMap<u32, TaskMeta>,
+    /// Whether the agent's map held still while it was read; agents that
+    /// predate the field are taken as consistent.
+    #[serde(default = "consistent")]
+    pub consistent: bool,
+    /// Online CPUs of the agent's host; 0 from agents that predate it.
+    #[serde(default)]
+    pub cpus: usize,
+}
+
+fn consistent() -> bool {
+    true
+}
+
+/// A [`TaskInfo`] as agents send it. Counters added since the first agents
+/// are optional, so a frame says what its agent did not count.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RemoteEntry {
+    pub runtime_ns: u64,
+    pub switches: u64,
+    pub nice: i32,
+    pub tickets: u32,
+    pub last_switch_in_ts: u64,
+    /// `None` from agents that predate it or read a map without it.
+    #[serde(default)]
+    pub preemptions: Option<u64>,
+}
+
+impl RemoteEntry {
+    pub fn new(info: &TaskInfo, preemptions: bool) -> Self {
+        Self {
+            runtime_ns: info.runtime_ns,
+            switches: info.switches,
+            nice: info.nice,
+            tickets: info.tickets,
+            last_switch_in_ts: info.last_switch_in_ts,
+            preemptions: preemptions.then_some(info.preemptions),
+        }
+    }
+
+    fn info(&self) -> TaskInfo {
+        TaskInfo {
+            runtime_ns: self.runtime_ns,
+            switches: self.switches,
+            nice: self.nice,
+            tickets: self.tickets,
+            last_switch_in_ts: self.last_switch_in_ts,
+            preemptions: self.preemptions.unwrap_or_default(),
+        }
+    }
+}
+
+/// A byte stream to or from an agent: plain TCP or a TLS session.
+pub trait Transport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Transport for T {}
+
+/// Client side of the agent protocol: one JSON line per [`SAMPLE_REQUEST`].
+pub struct RemoteSource {
+    conn: BufReader<Box<dyn Transport>>,
+    meta: HashMap<u32, TaskMeta>,
+    consistent: bool,
+    cpus: usize,
+    preemptions: bool,
+    line: String,
+}
+
+impl RemoteSource {
+    pub fn new(transport: Box<dyn Transport>) -> Self {
+        Self {
+            conn: BufReader::new(transport),
+            meta: HashMap::new(),
+            consistent: true,
+            cpus: 0,
+            preemptions: true,
+            line: String::new(),
+        }
+    }
+
+    /// Ask for one frame and keep its metadata for [`meta`](Self::meta).
+    pub fn fetch(&mut self) -> io::Result<Vec<(u32, TaskInfo)>> {
+        let conn = self.conn.get_mut();
+        writeln!(conn, "{SAMPLE_REQUEST}")?;
+        conn.flush()?;
+        self.line.clear();
+        if read_line_within(&mut self.conn, &mut self.line, MAX_FRAME_LEN)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "agent closed the connection",
+            ));
+        }
+        let frame: RemoteFrame = serde_json::from_str(&self.line)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.meta = frame.meta;
+        self.consistent = frame.consistent;
+        self.cpus = frame.cpus;
+        self.preemptions = frame
+            .entries
+            .iter()
+            .all(|(_, entry)| entry.preemptions.is_some());
+        Ok(frame
+            .entries
+            .iter()
+            .map(|(pid, entry)| (*pid, entry.info()))
+            .collect())
+    }
+
+    /// Whether the agent counted preemptions in the last frame.
+    pub fn preemptions(&self) -> bool {
+        self.preemptions
+    }
+
+    /// Whether the agent read its map consistently for the last frame.
+    pub fn consistent(&self) -> bool {
+        self.consistent
+    }
+
+    /// Online CPUs of the agent's host, if it said.
+    pub fn cpus(&self) -> Option<usize> {
+        (self.cpus > 0).then_some(self.cpus)
+    }
+
+    /// Metadata the agent sent for `pid` with the last frame.
+    pub fn meta(&self, pid: u32) -> Option<&TaskMeta> {
+        self.meta.get(&pid)
+    }
+}
+
+/// Read one line of at most `max` bytes into `line`, so a peer that never
+/// sends a newline cannot grow it without bound. Returns 0 at end of file.
+fn read_line_within(reader: &mut impl BufRead, line: &mut String, max: u64) -> io::Result<usize> {
+    let read = reader.take(max).read_line(line)?;
+    if read as u64 == max && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line longer than {max} bytes"),
+        ));
+    }
+    Ok(read)
+}
+
+/// Agent side: answer each [`SAMPLE_REQUEST`] on `transport` with a frame
+/// from `frame` until the client hangs up.
+pub fn serve_requests<F>(transport: &mut dyn Transport, mut frame: F) -> io::Result<()>
+where
+    F: FnMut() -> io::Result<RemoteFrame>,
+{
+    let mut reader = BufReader::new(transport);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if read_line_within(&mut reader, &mut line, MAX_REQUEST_LEN)? == 0 {
+            return Ok(());
+        }
+        if line.trim() != SAMPLE_REQUEST {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected request `{}`", line.trim()),
+            ));
+        }
+        let payload = serde_json::to_string(&frame()?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let conn = reader.get_mut();
+        writeln!(conn, "{payload}")?;
+        conn.flush()?;
+    }
+}
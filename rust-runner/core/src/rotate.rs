@@ -0,0 +1,230 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::{debug, info, warn};
+
+use crate::compress::{Compression, Encoder};
+
+/// When an output file is closed and a fresh one started. The default never
+/// rotates.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rotation {
+    /// Rotate once this many bytes were written to the file (before
+    /// compression).
+    pub max_bytes: Option<u64>,
+    /// Rotate once the file has been written to for this long.
+    pub interval: Option<Duration>,
+    /// Keep at most this many rotated files, deleting the oldest.
+    pub max_files: Option<usize>,
+}
+
+/// Append-only file that rotates according to a [`Rotation`]. The live file
+/// always sits at the configured path; rotated files are renamed next to it
+/// with a UTC timestamp, e.g. `stats.csv` -> `stats-20250101T120000Z.csv`.
+/// Each file is written through the chosen [`Compression`].
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    compression: Compression,
+    /// `None` once [`finish`](Self::finish) has run.
+    file: Option<Encoder>,
+    written: u64,
+    opened: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, rotation: Rotation, compression: Compression) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            rotation,
+            compression,
+            file: Some(compression.encoder(file)?),
+            written,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Write the compression trailer and close the file; later writes fail.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(encoder) => encoder.finish().map(drop),
+            None => Ok(()),
+        }
+    }
+
+    fn encoder(&mut self) -> io::Result<&mut Encoder> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| io::Error::other(format!("{} is already closed", self.path.display())))
+    }
+
+    /// Nothing has been written to the current file yet.
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    /// Rotate when a limit is reached. Call between records so a record is
+    /// never split across files; returns `true` when a new file was started.
+    pub fn rotate_if_due(&mut self) -> io::Result<bool> {
+        if self.written == 0 {
+            return Ok(false);
+        }
+        let too_big = self
+            .rotation
+            .max_bytes
+            .is_some_and(|limit| self.written >= limit);
+        let too_old = self
+            .rotation
+            .interval
+            .is_some_and(|interval| self.opened.elapsed() >= interval);
+        if !too_big && !too_old {
+            return Ok(false);
+        }
+        // Rename under the open encoder first: if that fails, the current
+        // file simply keeps growing and nothing has been closed yet.
+        let rotated = self.rotated_path();
+        fs::rename(&self.path, &rotated)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|file| self.compression.encoder(file));
+        let file = match file {
+            Ok(file) => file,
+            Err(err) => {
+                // Put the current file back so writes keep landing in it.
+                if let Err(err) = fs::rename(&rotated, &self.path) {
+                    warn!(target: "exporter", path = %rotated.display(), %err, "could not move the output file back");
+                }
+                return Err(err);
+            }
+        };
+        let previous = self.file.replace(file);
+        self.written = 0;
+        self.opened = Instant::now();
+        info!(target: "exporter", from = %self.path.display(), to = %rotated.display(), "rotated output file");
+        self.prune();
+        if let Some(previous) = previous {
+            previous.finish()?;
+        }
+        Ok(true)
+    }
+
+    /// `<stem>-<UTC stamp>[.<n>].<ext>`, unique within the directory.
+    fn rotated_path(&self) -> PathBuf {
+        let (stem, ext) = self.split_name();
+        let stamp = utc_stamp(SystemTime::now());
+        let mut candidate = self.path.with_file_name(format!("{stem}-{stamp}{ext}"));
+        let mut n = 1;
+        while candidate.exists() {
+            candidate = self.path.with_file_name(format!("{stem}-{stamp}.{n}{ext}"));
+            n += 1;
+        }
+        candidate
+    }
+
+    /// File stem and extension (with its dot), e.g. `("stats", ".csv")`.
+    fn split_name(&self) -> (String, String) {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match name.find('.') {
+            Some(dot) if dot > 0 => (name[..dot].to_string(), name[dot..].to_string()),
+            _ => (name, String::new()),
+        }
+    }
+
+    /// Delete the oldest rotated files beyond `max_files`.
+    fn prune(&self) {
+        let Some(keep) = self.rotation.max_files else {
+            return;
+        };
+        let (stem, ext) = self.split_name();
+        let prefix = format!("{stem}-");
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut rotated: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.len() > prefix.len() + ext.len()
+                    && name.starts_with(&prefix)
+                    && name.ends_with(&ext)
+                    && name[prefix.len()..].starts_with(|c: char| c.is_ascii_digit())
+            })
+            .map(|entry| entry.path())
+            .collect();
+        if rotated.len() <= keep {
+            return;
+        }
+        // Oldest first: by stamp, then by the collision counter (none = 0).
+        rotated.sort_by_cached_key(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let middle = &name[prefix.len()..name.len() - ext.len()];
+            let (stamp, counter) = middle.split_once('.').unwrap_or((middle, "0"));
+            (stamp.to_string(), counter.parse::<u32>().unwrap_or(0))
+        });
+        for old in &rotated[..rotated.len() - keep] {
+            match fs::remove_file(old) {
+                Ok(()) => debug!(target: "exporter", path = %old.display(), "removed rotated file"),
+                Err(err) => {
+                    warn!(target: "exporter", path = %old.display(), "cannot remove rotated file: {err}")
+                }
+            }
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.encoder()?.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder()?.flush()
+    }
+}
+
+/// `YYYYMMDDTHHMMSSZ` for `time`.
+fn utc_stamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Proleptic Gregorian date for a day count since 1970-01-01 (Howard
+/// Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
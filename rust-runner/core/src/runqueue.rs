@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::procfs::online_cpus;
+
+/// Run-queue depth of a host over one window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunQueueDepth {
+    /// Mean runnable tasks (running plus waiting) on each CPU over the
+    /// window, indexed by CPU number.
+    pub per_cpu: Vec<f64>,
+    /// Tasks runnable host-wide when the window was read; `None` when the
+    /// source does not report it.
+    pub runnable: Option<u32>,
+    /// `per_cpu` is `runnable` spread evenly, for lack of per-CPU counters.
+    pub estimated: bool,
+}
+
+impl RunQueueDepth {
+    /// Mean depth over the CPUs.
+    pub fn mean(&self) -> f64 {
+        self.per_cpu.iter().sum::<f64>() / self.per_cpu.len().max(1) as f64
+    }
+
+    /// Depth of the busiest CPU.
+    pub fn max(&self) -> f64 {
+        self.per_cpu.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// Depth of `cpu`, when it was sampled.
+    pub fn cpu(&self, cpu: u32) -> Option<f64> {
+        self.per_cpu.get(cpu as usize).copied()
+    }
+}
+
+/// `cpu -> (rq_cpu_time, run_delay)` in nanoseconds.
+type CpuCounters = HashMap<u32, (u64, u64)>;
+
+/// The per-CPU counters of `/proc/schedstat`, or `None` for a format we do
+/// not know. Both have been at the same place of the `cpuN` lines since
+/// version 15.
+fn parse_schedstat(text: &str) -> Option<CpuCounters> {
+    let mut lines = text.lines();
+    let version: u32 = lines
+        .next()?
+        .strip_prefix("version ")?
+        .trim()
+        .parse()
+        .ok()?;
+    if version < 15 {
+        return None;
+    }
+    let mut cpus = HashMap::new();
+    for line in lines {
+        let mut fields = line.split_whitespace();
+        let Some(cpu) = fields.next().and_then(|name| name.strip_prefix("cpu")) else {
+            continue;
+        };
+        let Ok(cpu) = cpu.parse() else {
+            continue;
+        };
+        let counters: Vec<u64> = fields.filter_map(|field| field.parse().ok()).collect();
+        if let [.., running, waiting, _] = counters[..] {
+            cpus.insert(cpu, (running, waiting));
+        }
+    }
+    Some(cpus)
+}
+
+/// The runnable count of `/proc/loadavg` (`0.12 0.34 0.56 3/456 789`),
+/// less the task reading it.
+fn parse_loadavg(text: &str) -> Option<u32> {
+    let runnable: u32 = text
+        .split_whitespace()
+        .nth(3)?
+        .split_once('/')?
+        .0
+        .parse()
+        .ok()?;
+    Some(runnable.saturating_sub(1))
+}
+
+/// Per-CPU run-queue depth of this host from `/proc/schedstat`: the time a
+/// CPU spent running tasks plus the time tasks spent waiting for it, over
+/// the wall time in between, is its mean number of runnable tasks. Kernels
+/// without schedstats fall back to the runnable count of `/proc/loadavg`,
+/// spread evenly over the online CPUs.
+#[derive(Debug, Default)]
+pub struct RunQueueSampler {
+    last: Option<(Instant, CpuCounters)>,
+    /// Set once `/proc/schedstat` turned out to be unreadable.
+    no_schedstat: bool,
+}
+
+impl RunQueueSampler {
+    /// The depth since the previous call; `None` when neither file can be
+    /// read, and on the first call while `/proc/schedstat` can.
+    pub fn sample(&mut self) -> Option<RunQueueDepth> {
+        let runnable = fs::read_to_string("/proc/loadavg")
+            .ok()
+            .and_then(|text| parse_loadavg(&text));
+        let counters = (!self.no_schedstat)
+            .then(|| fs::read_to_string("/proc/schedstat").ok())
+            .flatten()
+            .and_then(|text| parse_schedstat(&text))
+            .filter(|counters| !counters.is_empty());
+        let Some(counters) = counters else {
+            if !self.no_schedstat {
+                debug!(target: "sampler", "/proc/schedstat is unreadable; run-queue depth comes from /proc/loadavg");
+                self.no_schedstat = true;
+            }
+            let cpus = online_cpus();
+            return runnable.map(|runnable| RunQueueDepth {
+                per_cpu: vec![runnable as f64 / cpus as f64; cpus],
+                runnable: Some(runnable),
+                estimated: true,
+            });
+        };
+        let now = Instant::now();
+        let (then, before) = self.last.replace((now, counters))?;
+        let counters = &self.last.as_ref()?.1;
+        let elapsed_ns = (now - then).as_nanos() as f64;
+        if elapsed_ns <= 0.0 {
+            return None;
+        }
+        let slots = counters.keys().max().map_or(0, |cpu| *cpu as usize + 1);
+        let mut per_cpu = vec![0.0; slots];
+        for (cpu, (running, waiting)) in counters {
+            if let Some((was_running, was_waiting)) = before.get(cpu) {
+                let busy =
+                    running.saturating_sub(*was_running) + waiting.saturating_sub(*was_waiting);
+                per_cpu[*cpu as usize] = busy as f64 / elapsed_ns;
+            }
+        }
+        Some(RunQueueDepth {
+            per_cpu,
+            runnable,
+            estimated: false,
+        })
+    }
+}
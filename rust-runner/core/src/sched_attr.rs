@@ -0,0 +1,170 @@
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+const SCHED_OTHER: u32 = 0;
+const SCHED_FIFO: u32 = 1;
+const SCHED_RR: u32 = 2;
+const SCHED_BATCH: u32 = 3;
+const SCHED_IDLE: u32 = 5;
+const SCHED_DEADLINE: u32 = 6;
+
+/// `struct sched_attr` from include/uapi/linux/sched/types.h (VER1 layout).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct RawSchedAttr {
+    size: u32,
+    sched_policy: u32,
+    sched_flags: u64,
+    sched_nice: i32,
+    sched_priority: u32,
+    sched_runtime: u64,
+    sched_deadline: u64,
+    sched_period: u64,
+    sched_util_min: u32,
+    sched_util_max: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedPolicy {
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    Rr,
+    Deadline,
+    Unknown(u32),
+}
+
+impl SchedPolicy {
+    fn from_raw(policy: u32) -> Self {
+        match policy {
+            SCHED_OTHER => SchedPolicy::Other,
+            SCHED_BATCH => SchedPolicy::Batch,
+            SCHED_IDLE => SchedPolicy::Idle,
+            SCHED_FIFO => SchedPolicy::Fifo,
+            SCHED_RR => SchedPolicy::Rr,
+            SCHED_DEADLINE => SchedPolicy::Deadline,
+            other => SchedPolicy::Unknown(other),
+        }
+    }
+
+    /// FIFO, RR and DEADLINE tasks preempt the fair class, so they sit
+    /// outside the lottery.
+    pub fn is_realtime(self) -> bool {
+        matches!(
+            self,
+            SchedPolicy::Fifo | SchedPolicy::Rr | SchedPolicy::Deadline
+        )
+    }
+}
+
+impl fmt::Display for SchedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedPolicy::Other => f.write_str("OTHER"),
+            SchedPolicy::Batch => f.write_str("BATCH"),
+            SchedPolicy::Idle => f.write_str("IDLE"),
+            SchedPolicy::Fifo => f.write_str("FIFO"),
+            SchedPolicy::Rr => f.write_str("RR"),
+            SchedPolicy::Deadline => f.write_str("DEADLINE"),
+            SchedPolicy::Unknown(raw) => write!(f, "?{raw}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedAttr {
+    pub policy: SchedPolicy,
+    pub nice: i32,
+    pub rt_priority: u32,
+    pub dl_runtime_ns: u64,
+    pub dl_deadline_ns: u64,
+    pub dl_period_ns: u64,
+}
+
+impl SchedAttr {
+    /// Short label such as `OTHER/n0`, `FIFO/50` or `DEADLINE/2ms`.
+    pub fn label(&self) -> String {
+        match self.policy {
+            SchedPolicy::Fifo | SchedPolicy::Rr => format!("{}/{}", self.policy, self.rt_priority),
+            SchedPolicy::Deadline => format!(
+                "{}/{:.1}ms",
+                self.policy,
+                self.dl_runtime_ns as f64 / 1_000_000.0
+            ),
+            policy => format!("{policy}/n{}", self.nice),
+        }
+    }
+
+    /// SCHED_DEADLINE runtime, deadline, and period in milliseconds; `None`
+    /// for other policies.
+    pub fn dl_params_ms(&self) -> Option<(f64, f64, f64)> {
+        (self.policy == SchedPolicy::Deadline).then(|| {
+            (
+                self.dl_runtime_ns as f64 / 1_000_000.0,
+                self.dl_deadline_ns as f64 / 1_000_000.0,
+                self.dl_period_ns as f64 / 1_000_000.0,
+            )
+        })
+    }
+}
+
+/// Query the scheduling policy and parameters of `pid` via sched_getattr(2).
+pub fn sched_getattr(pid: u32) -> Option<SchedAttr> {
+    let mut raw = RawSchedAttr::default();
+    let size = std::mem::size_of::<RawSchedAttr>() as libc::c_uint;
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_sched_getattr,
+            pid as libc::pid_t,
+            &mut raw as *mut RawSchedAttr,
+            size,
+            0 as libc::c_uint,
+        )
+    };
+    if ret != 0 {
+        return None;
+    }
+    Some(SchedAttr {
+        policy: SchedPolicy::from_raw(raw.sched_policy),
+        nice: raw.sched_nice,
+        rt_priority: raw.sched_priority,
+        dl_runtime_ns: raw.sched_runtime,
+        dl_deadline_ns: raw.sched_deadline,
+        dl_period_ns: raw.sched_period,
+    })
+}
+
+/// Switch `pid` to SCHED_DEADLINE via sched_setattr(2). Needs `CAP_SYS_NICE`
+/// and passes the kernel's admission control (runtime <= deadline <= period,
+/// total DL bandwidth within `sched_rt_runtime_us`).
+pub fn sched_setattr_deadline(
+    pid: u32,
+    runtime_ns: u64,
+    deadline_ns: u64,
+    period_ns: u64,
+) -> io::Result<()> {
+    let mut raw = RawSchedAttr {
+        size: std::mem::size_of::<RawSchedAttr>() as u32,
+        sched_policy: SCHED_DEADLINE,
+        sched_runtime: runtime_ns,
+        sched_deadline: deadline_ns,
+        sched_period: period_ns,
+        ..RawSchedAttr::default()
+    };
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_sched_setattr,
+            pid as libc::pid_t,
+            &mut raw as *mut RawSchedAttr,
+            0 as libc::c_uint,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// Relative accuracy of [`QuantileSketch`]: a quantile is off from the true
+/// value by at most this fraction of it.
+pub const SKETCH_ACCURACY: f64 = 0.01;
+
+/// Magnitudes (ms) below this are counted as zero: a nanosecond.
+const MIN_MAGNITUDE: f64 = 1e-6;
+
+/// Streaming quantiles of a series of values with bounded memory, in the
+/// manner of DDSketch: every value falls into a bucket of logarithmically
+/// growing width, so any quantile comes back within [`SKETCH_ACCURACY`] of
+/// the exact one while storing only a few hundred counters per decade
+/// actually seen. Negative values (slack) get buckets of their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileSketch {
+    /// `ln(gamma)`, where `gamma` is the ratio of neighbouring bucket edges.
+    ln_gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    /// Keyed by the bucket of the magnitude.
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    /// Exact, for [`mean`](Self::mean).
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for QuantileSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuantileSketch {
+    pub fn new() -> Self {
+        let gamma = (1.0 + SKETCH_ACCURACY) / (1.0 - SKETCH_ACCURACY);
+        Self {
+            ln_gamma: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    fn bucket(&self, magnitude: f64) -> i32 {
+        (magnitude.ln() / self.ln_gamma).ceil() as i32
+    }
+
+    /// Middle of `bucket` in relative terms, so either edge is within the
+    /// accuracy.
+    fn value(&self, bucket: i32) -> f64 {
+        let gamma = self.ln_gamma.exp();
+        2.0 * gamma.powi(bucket) / (gamma + 1.0)
+    }
+
+    pub fn record(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if value.abs() < MIN_MAGNITUDE {
+            self.zeros += 1;
+        } else if value > 0.0 {
+            *self.positive.entry(self.bucket(value)).or_default() += 1;
+        } else {
+            *self.negative.entry(self.bucket(-value)).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (bucket, count) in &other.positive {
+            *self.positive.entry(*bucket).or_default() += count;
+        }
+        for (bucket, count) in &other.negative {
+            *self.negative.entry(*bucket).or_default() += count;
+        }
+        self.zeros += other.zeros;
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Exact mean; `None` when empty.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Exact largest value; `None` when empty.
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Exact smallest value; `None` when empty.
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The `q` quantile (0-1), clamped to the values seen; `None` when
+    /// empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        // Most negative first: the largest magnitudes of the negative side.
+        for (bucket, count) in self.negative.iter().rev() {
+            seen += count;
+            if seen > rank {
+                return Some((-self.value(*bucket)).clamp(self.min, self.max));
+            }
+        }
+        seen += self.zeros;
+        if seen > rank {
+            return Some(0.0_f64.clamp(self.min, self.max));
+        }
+        for (bucket, count) in &self.positive {
+            seen += count;
+            if seen > rank {
+                return Some(self.value(*bucket).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+impl FromIterator<f64> for QuantileSketch {
+    fn from_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let mut sketch = Self::new();
+        for value in values {
+            sketch.record(value);
+        }
+        sketch
+    }
+}
+
+/// The sketches of the last `capacity` windows, so quantiles can be taken
+/// over a sliding horizon: merging a few hundred buckets per window is
+/// cheap, and memory stays bounded by the horizon rather than the values.
+#[derive(Debug, Clone)]
+pub struct SketchWindow {
+    capacity: usize,
+    sketches: VecDeque<QuantileSketch>,
+}
+
+impl SketchWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            sketches: VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    /// Add the latest window, dropping the oldest beyond the capacity.
+    pub fn push(&mut self, sketch: QuantileSketch) {
+        if self.sketches.len() == self.capacity {
+            self.sketches.pop_front();
+        }
+        self.sketches.push_back(sketch);
+    }
+
+    /// Windows held, at most the capacity.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    /// All held windows merged into one sketch.
+    pub fn merged(&self) -> QuantileSketch {
+        let mut merged = QuantileSketch::new();
+        for sketch in &self.sketches {
+            merged.merge(sketch);
+        }
+        merged
+    }
+}
@@ -0,0 +1,928 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::{panic, thread};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::events::SchedEvents;
+use crate::latency::WakeupLatency;
+use crate::procfs::TaskMeta;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub runtime_ns: u64,
+    pub switches: u64,
+    pub nice: i32,
+    pub tickets: u32,
+    pub last_switch_in_ts: u64,
+    /// Switches out while still runnable: preempted, or yielded. The other
+    /// `switches` are voluntary (the task blocked or slept).
+    #[serde(default)]
+    pub preemptions: u64,
+}
+
+/// Lowest nice value a CFS task can carry. The BPF side records `prio - 120`,
+/// so anything below this came from an RT/deadline priority (prio < 100).
+pub const MIN_NICE: i32 = -20;
+
+/// Highest nice value a CFS task can carry.
+pub const MAX_NICE: i32 = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedClass {
+    Fair,
+    RealTime,
+}
+
+impl TaskInfo {
+    /// An array-map slot no task has been recorded in yet.
+    pub fn is_empty(&self) -> bool {
+        self.switches == 0 && self.runtime_ns == 0
+    }
+
+    pub fn runtime_ms(&self) -> f64 {
+        self.runtime_ns as f64 / 1_000_000.0
+    }
+
+    pub fn sched_class(&self) -> SchedClass {
+        if self.nice < MIN_NICE {
+            SchedClass::RealTime
+        } else {
+            SchedClass::Fair
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskSnapshot {
+    pub pid: u32,
+    pub info: TaskInfo,
+    pub runtime_delta_ns: u64,
+    pub rolling_runtime_ms: f64,
+    pub switch_delta: u64,
+    pub estimated_period_ms: f64,
+    pub deadline_ms: f64,
+    /// Dispatch delay plus runtime per activation, from switch-in
+    /// timestamps; `None` when the window heuristic was used instead (see
+    /// [`enrich_entries`]) or the task was not switched in.
+    pub response_time_ms: Option<f64>,
+    pub lateness_ms: f64,
+    pub utilization: f64,
+    pub ticket_share: f64,
+    /// The BPF side's counters for this pid started over (program
+    /// reloaded, map recreated, entry deleted), so this window has no
+    /// deltas for it.
+    pub counter_reset: bool,
+    /// Of `switch_delta`, the switches out while still runnable; `None`
+    /// when the map predates the counter.
+    pub preempt_delta: Option<u64>,
+    /// Consecutive windows, this one included, in which the task held a
+    /// ticket share and waited for a CPU but got no runtime; 0 once it runs
+    /// or stops waiting.
+    pub starved_windows: u32,
+    /// `starved_windows` reached the stream's threshold; see
+    /// [`SnapshotStream::with_starvation_windows`](crate::stream::SnapshotStream::with_starvation_windows).
+    pub starved: bool,
+    /// Windows the stream has judged the task's deadline in since it first
+    /// saw the pid, this one included; 0 outside a stream.
+    pub windows_observed: u64,
+    /// Of `windows_observed`, those the task finished late in.
+    pub windows_missed: u64,
+    /// Wakeup-to-run latency over the window; `None` when the task did not
+    /// wait for a CPU or the source records no latency.
+    pub latency: Option<WakeupLatency>,
+    /// Scheduler events besides sched_switch over the window; each `None`
+    /// when not monitored or on the pid's first window.
+    pub events: SchedEvents,
+    pub meta: TaskMeta,
+}
+
+impl TaskSnapshot {
+    /// Scheduling class from the queried policy when available, otherwise
+    /// from the priority the BPF program recorded.
+    pub fn sched_class(&self) -> SchedClass {
+        match self.meta.sched {
+            Some(attr) if attr.policy.is_realtime() => SchedClass::RealTime,
+            Some(_) => SchedClass::Fair,
+            None => self.info.sched_class(),
+        }
+    }
+
+    /// Tickets that take part in the lottery; RT tasks preempt the lottery
+    /// entirely, so their tickets do not count.
+    pub fn lottery_tickets(&self) -> u32 {
+        match self.sched_class() {
+            SchedClass::Fair => self.info.tickets,
+            SchedClass::RealTime => 0,
+        }
+    }
+
+    pub fn runtime_delta_ms(&self) -> f64 {
+        self.runtime_delta_ns as f64 / 1_000_000.0
+    }
+
+    pub fn deadline_missed(&self) -> bool {
+        self.lateness_ms > 0.0
+    }
+
+    /// Share (0-1) of the observed windows the task missed its deadline
+    /// in; `None` before the first one.
+    pub fn miss_rate(&self) -> Option<f64> {
+        (self.windows_observed > 0)
+            .then(|| self.windows_missed as f64 / self.windows_observed as f64)
+    }
+
+    /// `STARVED`, `MISS`, or `OK`, for status columns.
+    pub fn status(&self) -> &'static str {
+        if self.starved {
+            "STARVED"
+        } else if self.deadline_missed() {
+            "MISS"
+        } else {
+            "OK"
+        }
+    }
+
+    /// Switches over the window the task gave up the CPU for itself.
+    pub fn voluntary_delta(&self) -> Option<u64> {
+        self.preempt_delta
+            .map(|preempted| self.switch_delta.saturating_sub(preempted))
+    }
+
+    /// Share (0-1) of the window's switches that were preemptions; `None`
+    /// without switches or a preemption counter.
+    pub fn preemption_rate(&self) -> Option<f64> {
+        preemption_rate(self.preempt_delta, self.switch_delta)
+    }
+}
+
+/// `preempted` switches as a share of `switches`; see
+/// [`TaskSnapshot::preemption_rate`].
+pub fn preemption_rate(preempted: Option<u64>, switches: u64) -> Option<f64> {
+    preempted
+        .filter(|_| switches > 0)
+        .map(|preempted| preempted.min(switches) as f64 / switches as f64)
+}
+
+/// Windows a pid may be missing from the map before its rolling state is
+/// dropped.
+pub const DEFAULT_ROLLING_TTL: u64 = 30;
+
+/// Windows in a row a task may hold tickets without running before it is
+/// flagged as starved.
+pub const DEFAULT_STARVATION_WINDOWS: u32 = 3;
+
+/// Most pids [`RollingStats`] keeps state for.
+pub const DEFAULT_ROLLING_CAPACITY: usize = 1 << 18;
+
+/// Entries from which enrichment (and the stream's `/proc` refresh) is
+/// split across threads.
+pub const PARALLEL_MIN_ENTRIES: usize = 8192;
+
+/// Least work worth handing one enrichment thread.
+const ENTRIES_PER_WORKER: usize = 4096;
+
+/// Shards of the per-pid state; parallel enrichment hands every thread its
+/// own shards, so none of them needs a lock.
+const SHARDS: usize = 16;
+
+fn shard(pid: u32) -> usize {
+    pid as usize % SHARDS
+}
+
+/// Threads to enrich `entries` entries with: one below
+/// [`PARALLEL_MIN_ENTRIES`], otherwise one per few thousand entries, up to
+/// the CPUs available.
+pub fn enrich_workers(entries: usize) -> usize {
+    if entries < PARALLEL_MIN_ENTRIES {
+        return 1;
+    }
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    (entries / ENTRIES_PER_WORKER).clamp(1, cpus.min(SHARDS))
+}
+
+/// Counters of one pid at its last sighting.
+#[derive(Debug, Clone, Copy)]
+struct Rolling {
+    runtime_ns: u64,
+    switches: u64,
+    last_switch_in_ts: u64,
+    preemptions: u64,
+    rolling_ms: f64,
+    /// EWMA of the time between switch-ins; 0 until two were seen.
+    period_ns: f64,
+    /// Window the pid was last seen in.
+    seen: u64,
+    /// [`RollingStats::rebase_all`] generation the counters belong to.
+    epoch: u64,
+}
+
+/// What [`RollingStats::update`] derived from one sighting of a pid.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rolled {
+    pub delta_ns: u64,
+    pub rolling_ms: f64,
+    pub switch_delta: u64,
+    pub preempt_delta: u64,
+    /// Timing from `last_switch_in_ts`; `None` while the pid has no two
+    /// switch-ins on record or the map does not record timestamps.
+    pub timing: Option<Timing>,
+    /// The counters started over; see [`TaskSnapshot::counter_reset`].
+    pub reset: bool,
+}
+
+/// Switch-in cadence of a pid, measured from the BPF timestamps.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    /// Expected time between switch-ins: the EWMA before this sighting, or
+    /// the first measured gap.
+    pub period_ns: f64,
+    /// Mean time between switch-ins since the last sighting; `None` when
+    /// the task was not switched in again.
+    pub inter_arrival_ns: Option<f64>,
+}
+
+/// Record a sighting of `info` under `pid` in `tasks`; see
+/// [`RollingStats::update`].
+fn roll(
+    tasks: &mut HashMap<u32, Rolling>,
+    (alpha, window, epoch): (f64, u64, u64),
+    pid: u32,
+    info: &TaskInfo,
+) -> Rolled {
+    let fresh = Rolling {
+        runtime_ns: info.runtime_ns,
+        switches: info.switches,
+        last_switch_in_ts: info.last_switch_in_ts,
+        preemptions: info.preemptions,
+        rolling_ms: 0.0,
+        period_ns: 0.0,
+        seen: window,
+        epoch,
+    };
+    let task = tasks.entry(pid).or_insert(fresh);
+    // Counters only grow, and the 64-bit ones never wrap in practice: lower
+    // ones, or a map swapped underneath, mean the BPF side started counting
+    // afresh. Start over from them rather than report a bogus delta; the
+    // pid may also belong to a new task whose start time was unknown.
+    let reset = task.epoch != epoch
+        || info.runtime_ns < task.runtime_ns
+        || info.switches < task.switches
+        || info.preemptions < task.preemptions
+        || info.last_switch_in_ts < task.last_switch_in_ts;
+    if reset {
+        *task = fresh;
+    }
+    let delta_ns = info.runtime_ns.saturating_sub(task.runtime_ns);
+    let switch_delta = info.switches.saturating_sub(task.switches);
+    let preempt_delta = info.preemptions.saturating_sub(task.preemptions);
+    let delta_ms = delta_ns as f64 / 1_000_000.0;
+    task.rolling_ms = alpha * delta_ms + (1.0 - alpha) * task.rolling_ms;
+
+    // Switch-outs since the last sighting stand in for the switch-ins
+    // between the two timestamps; a newer timestamp means at least one.
+    let inter_arrival_ns = (task.last_switch_in_ts > 0
+        && info.last_switch_in_ts > task.last_switch_in_ts)
+        .then(|| {
+            (info.last_switch_in_ts - task.last_switch_in_ts) as f64 / switch_delta.max(1) as f64
+        });
+    let expected_ns = task.period_ns;
+    if let Some(gap) = inter_arrival_ns {
+        task.period_ns = if expected_ns > 0.0 {
+            alpha * gap + (1.0 - alpha) * expected_ns
+        } else {
+            gap
+        };
+    }
+    let timing = (task.period_ns > 0.0).then_some(Timing {
+        period_ns: if expected_ns > 0.0 {
+            expected_ns
+        } else {
+            task.period_ns
+        },
+        inter_arrival_ns,
+    });
+
+    task.runtime_ns = info.runtime_ns;
+    task.switches = info.switches;
+    task.last_switch_in_ts = info.last_switch_in_ts;
+    task.preemptions = info.preemptions;
+    task.seen = window;
+    Rolled {
+        delta_ns,
+        rolling_ms: task.rolling_ms,
+        switch_delta,
+        preempt_delta,
+        timing,
+        reset,
+    }
+}
+
+/// Per-pid previous counters and EWMA runtime. Pids that drop out of the
+/// map are forgotten after `ttl` windows, and at most `capacity` pids are
+/// kept, so fork-heavy hosts do not grow it without bound.
+#[derive(Debug)]
+pub struct RollingStats {
+    alpha: f64,
+    /// Per-pid state, split by [`shard`].
+    shards: Vec<HashMap<u32, Rolling>>,
+    /// The current window; see [`end_window`](Self::end_window).
+    window: u64,
+    ttl: u64,
+    capacity: usize,
+    evicted: u64,
+    /// Bumped by [`rebase_all`](Self::rebase_all).
+    epoch: u64,
+}
+
+impl RollingStats {
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            shards: (0..SHARDS).map(|_| HashMap::new()).collect(),
+            window: 0,
+            ttl: DEFAULT_ROLLING_TTL,
+            capacity: DEFAULT_ROLLING_CAPACITY,
+            evicted: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Forget pids after `ttl` windows without a sighting (at least one) and
+    /// keep at most `capacity` pids, dropping the longest unseen first.
+    pub fn with_limits(mut self, ttl: u64, capacity: usize) -> Self {
+        self.ttl = ttl.max(1);
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn update(&mut self, pid: u32, info: &TaskInfo) -> Rolled {
+        let tasks = &mut self.shards[shard(pid)];
+        roll(tasks, (self.alpha, self.window, self.epoch), pid, info)
+    }
+
+    /// Treat every pid's next counters as a reset, e.g. after the task map
+    /// was replaced by a new one whose counters started from zero.
+    pub fn rebase_all(&mut self) {
+        self.epoch += 1;
+    }
+
+    /// Drop the state of `pid`, whose next sighting then starts afresh; for
+    /// a pid that now belongs to another task.
+    pub fn forget(&mut self, pid: u32) {
+        self.shards[shard(pid)].remove(&pid);
+    }
+
+    /// Close the current window: drop pids past the TTL, then the longest
+    /// unseen ones while over capacity. Returns how many pids were dropped.
+    pub fn end_window(&mut self) -> usize {
+        let (window, ttl) = (self.window, self.ttl);
+        self.window += 1;
+        let before = self.tracked();
+        for tasks in &mut self.shards {
+            tasks.retain(|_, task| window - task.seen < ttl);
+        }
+        let tracked = self.tracked();
+        if tracked > self.capacity {
+            let excess = tracked - self.capacity;
+            let mut by_age: Vec<(u64, u32)> = self
+                .shards
+                .iter()
+                .flatten()
+                .map(|(pid, task)| (task.seen, *pid))
+                .collect();
+            by_age.select_nth_unstable(excess - 1);
+            for (_, pid) in &by_age[..excess] {
+                self.shards[shard(*pid)].remove(pid);
+            }
+        }
+        // Give memory back after a burst of short-lived pids.
+        for tasks in &mut self.shards {
+            if tasks.capacity() > 4 * tasks.len().max(1024 / SHARDS) {
+                tasks.shrink_to_fit();
+            }
+        }
+        let dropped = before - self.tracked();
+        self.evicted += dropped as u64;
+        dropped
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Change the EWMA smoothing factor from the next update on; the
+    /// averages so far are kept and move toward new values at the new rate.
+    pub fn set_alpha(&mut self, alpha: f64) {
+        self.alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    /// Pids with rolling state.
+    pub fn tracked(&self) -> usize {
+        self.shards.iter().map(HashMap::len).sum()
+    }
+
+    /// Pids dropped by [`end_window`](Self::end_window) so far.
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Forget every pid not in `live` and release the freed capacity.
+    /// Returns how many pids were dropped.
+    pub fn retain(&mut self, live: &HashSet<u32>) -> usize {
+        let before = self.tracked();
+        for tasks in &mut self.shards {
+            tasks.retain(|pid, _| live.contains(pid));
+            tasks.shrink_to_fit();
+        }
+        before - self.tracked()
+    }
+}
+
+/// Mirror of `nice_to_tickets()` in `bpf/sched_lottery.bpf.c`.
+pub fn nice_to_tickets(nice: i32) -> u32 {
+    let nice = nice.clamp(MIN_NICE, 19);
+    (100 - 10 * nice).max(10) as u32
+}
+
+pub fn enrich_entries(
+    entries: &[(u32, TaskInfo)],
+    rolling: &mut RollingStats,
+    window_ms: f64,
+) -> Vec<TaskSnapshot> {
+    let mut snapshots = Vec::with_capacity(entries.len());
+    enrich_into(entries, rolling, window_ms, &mut snapshots);
+    snapshots
+}
+
+/// [`enrich_entries`] into `snapshots`, replacing its contents; a buffer
+/// kept across windows is only grown, never reallocated per tick. Maps of
+/// [`PARALLEL_MIN_ENTRIES`] entries or more are enriched on several
+/// threads, each owning some shards of the rolling state.
+pub fn enrich_into(
+    entries: &[(u32, TaskInfo)],
+    rolling: &mut RollingStats,
+    window_ms: f64,
+    snapshots: &mut Vec<TaskSnapshot>,
+) {
+    let window_ms = window_ms.max(1.0);
+    snapshots.clear();
+    let workers = enrich_workers(entries.len());
+    if workers == 1 {
+        snapshots.extend(entries.iter().map(|(pid, info)| {
+            let rolled = rolling.update(*pid, info);
+            enrich_one(*pid, info, rolled, window_ms)
+        }));
+    } else {
+        let per_worker = SHARDS.div_ceil(workers);
+        let state = (rolling.alpha, rolling.window, rolling.epoch);
+        let parts: Vec<Vec<TaskSnapshot>> = thread::scope(|scope| {
+            let handles: Vec<_> = rolling
+                .shards
+                .chunks_mut(per_worker)
+                .enumerate()
+                .map(|(worker, shards)| {
+                    scope.spawn(move || {
+                        let first = worker * per_worker;
+                        entries
+                            .iter()
+                            .filter(|(pid, _)| shard(*pid) / per_worker == worker)
+                            .map(|(pid, info)| {
+                                let tasks = &mut shards[shard(*pid) - first];
+                                let rolled = roll(tasks, state, *pid, info);
+                                enrich_one(*pid, info, rolled, window_ms)
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        // Each part is in map order already; interleave them back.
+        let mut parts: Vec<_> = parts.into_iter().map(Vec::into_iter).collect();
+        snapshots.extend(
+            entries
+                .iter()
+                .filter_map(|(pid, _)| parts[shard(*pid) / per_worker].next()),
+        );
+    }
+    rolling.end_window();
+    apply_class_aware_shares(snapshots);
+}
+
+/// The snapshot of one entry, given what [`RollingStats::update`] returned.
+///
+/// With switch-in timestamps each switch-in releases a job due one period
+/// later. Its response time is how much later than expected the task was
+/// switched in, plus its runtime per switch; lateness is that minus the
+/// period, and utilization the runtime per switch over the period; a task
+/// not switched in at all is late by the window less its period. Without
+/// timestamps the period is the window divided by the switches in it, and
+/// lateness and utilization set the window's runtime against that period.
+fn enrich_one(pid: u32, info: &TaskInfo, rolled: Rolled, window_ms: f64) -> TaskSnapshot {
+    let Rolled {
+        delta_ns,
+        rolling_ms,
+        switch_delta,
+        preempt_delta,
+        timing,
+        reset,
+    } = rolled;
+    let delta_ms = delta_ns as f64 / 1_000_000.0;
+    let (estimated_period_ms, response_time_ms) = match timing {
+        Some(timing) => {
+            let period_ms = (timing.period_ns / 1_000_000.0).max(0.1);
+            let response_ms = timing.inter_arrival_ns.map(|gap| {
+                let delay_ms = (gap / 1_000_000.0 - period_ms).max(0.0);
+                delay_ms + delta_ms / switch_delta.max(1) as f64
+            });
+            (period_ms, response_ms)
+        }
+        None => {
+            let period_ms = if switch_delta > 0 {
+                window_ms / switch_delta as f64
+            } else {
+                window_ms
+            };
+            (period_ms.max(0.1), None)
+        }
+    };
+    let deadline_ms = estimated_period_ms;
+    let lateness_ms = match (timing, response_time_ms) {
+        (Some(_), Some(response_ms)) => response_ms - deadline_ms,
+        // Not switched in since the last window: the job released at the
+        // last switch-in has waited at least the whole window, so a task
+        // that should have run by now is overdue.
+        (Some(_), None) => window_ms - deadline_ms,
+        (None, _) => delta_ms - deadline_ms,
+    };
+    // Timestamps give a real period, so the work per switch is set against
+    // it; the heuristic keeps comparing the whole window's runtime.
+    let demand_ms = match timing {
+        Some(_) => delta_ms / switch_delta.max(1) as f64,
+        None => delta_ms,
+    };
+    let utilization = if estimated_period_ms > 0.0 {
+        demand_ms / estimated_period_ms
+    } else {
+        0.0
+    };
+    TaskSnapshot {
+        pid,
+        info: *info,
+        runtime_delta_ns: delta_ns,
+        rolling_runtime_ms: rolling_ms,
+        switch_delta,
+        estimated_period_ms,
+        deadline_ms,
+        response_time_ms,
+        lateness_ms,
+        utilization,
+        ticket_share: 0.0,
+        counter_reset: reset,
+        preempt_delta: Some(preempt_delta),
+        starved_windows: 0,
+        starved: false,
+        windows_observed: 0,
+        windows_missed: 0,
+        latency: None,
+        events: SchedEvents::default(),
+        meta: TaskMeta::default(),
+    }
+}
+
+/// Share of the host's capacity the window's tasks used: their runtime over
+/// `window_ms` on each of `cpus` CPUs, so 1.0 means every CPU was busy.
+/// Unlike the per-task [`TaskSnapshot::utilization`], which is relative to
+/// each task's own period, this cannot exceed 1 on a real host.
+pub fn system_utilization(snapshots: &[TaskSnapshot], window_ms: f64, cpus: usize) -> f64 {
+    let capacity_ms = window_ms * cpus.max(1) as f64;
+    if capacity_ms <= 0.0 {
+        return 0.0;
+    }
+    let runtime_ms: f64 = snapshots.iter().map(TaskSnapshot::runtime_delta_ms).sum();
+    runtime_ms / capacity_ms
+}
+
+/// [`system_utilization`] broken down by CPU: each task's runtime is
+/// charged to the CPU it last ran on (`meta.last_cpu`), so a task that
+/// migrated mid-window lands on one CPU. Tasks whose CPU is unknown are left
+/// out. Indexed by CPU number; a CPU can exceed 1 when attribution piles
+/// migrated runtime onto it.
+pub fn per_cpu_utilization(snapshots: &[TaskSnapshot], window_ms: f64, cpus: usize) -> Vec<f64> {
+    let mut per_cpu = vec![0.0; cpus];
+    if window_ms <= 0.0 {
+        return per_cpu;
+    }
+    for snap in snapshots {
+        if let Some(load) = snap
+            .meta
+            .last_cpu
+            .and_then(|cpu| per_cpu.get_mut(cpu as usize))
+        {
+            *load += snap.runtime_delta_ms() / window_ms;
+        }
+    }
+    per_cpu
+}
+
+/// Herfindahl-Hirschman index of the ticket shares: the sum of squared
+/// shares, from 1/n when n tasks hold equal shares to 1.0 when one task
+/// holds every ticket. Antitrust practice calls above 0.25 highly
+/// concentrated and 0.15-0.25 moderately concentrated.
+pub fn ticket_concentration(snapshots: &[TaskSnapshot]) -> f64 {
+    snapshots
+        .iter()
+        .map(|snap| snap.ticket_share * snap.ticket_share)
+        .sum()
+}
+
+/// Jain's fairness index of `ratios`: 1.0 when all are equal, 1/n when one
+/// holds everything. 0 for no ratios or all zero.
+pub fn jain_index(ratios: impl IntoIterator<Item = f64>) -> f64 {
+    let (count, sum, sum_sq) = ratios.into_iter().fold(
+        (0_usize, 0.0_f64, 0.0_f64),
+        |(count, sum, sum_sq), ratio| (count + 1, sum + ratio, sum_sq + ratio * ratio),
+    );
+    if count == 0 || sum_sq == 0.0 {
+        0.0
+    } else {
+        (sum * sum) / (count as f64 * sum_sq)
+    }
+}
+
+/// [`jain_index`] of the achieved/expected share ratios of a window: each
+/// task's runtime share over its ticket share. `None` when nothing ran or
+/// no task holds a share.
+pub fn window_fairness(snapshots: &[TaskSnapshot]) -> Option<f64> {
+    let runtime: u64 = snapshots.iter().map(|snap| snap.runtime_delta_ns).sum();
+    let holders = snapshots.iter().filter(|snap| snap.ticket_share > 0.0);
+    (runtime > 0 && holders.clone().next().is_some()).then(|| {
+        jain_index(
+            holders.map(|snap| snap.runtime_delta_ns as f64 / runtime as f64 / snap.ticket_share),
+        )
+    })
+}
+
+/// Population steps of the Lorenz curves in exports: deciles.
+pub const LORENZ_POINTS: usize = 10;
+
+/// Gini coefficient of `values` (negative ones count as 0): 0 when all are
+/// equal, approaching 1 when one holds everything. `None` for fewer than
+/// two values or a zero total.
+pub fn gini(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+    let mut values: Vec<f64> = values.into_iter().map(|value| value.max(0.0)).collect();
+    let total: f64 = values.iter().sum();
+    if values.len() < 2 || total <= 0.0 {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let n = values.len() as f64;
+    let weighted: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(rank, value)| (2.0 * (rank + 1) as f64 - n - 1.0) * value)
+        .sum();
+    Some(weighted / (n * total))
+}
+
+/// Lorenz curve of `values`: for each population fraction `k / points`, the
+/// fraction of the total held by that share of the smallest values,
+/// interpolated between tasks. Starts at `(0, 0)` and ends at `(1, 1)`;
+/// empty under the same conditions as [`gini`].
+pub fn lorenz_curve(values: impl IntoIterator<Item = f64>, points: usize) -> Vec<(f64, f64)> {
+    let mut values: Vec<f64> = values.into_iter().map(|value| value.max(0.0)).collect();
+    let total: f64 = values.iter().sum();
+    if values.len() < 2 || total <= 0.0 || points == 0 {
+        return Vec::new();
+    }
+    values.sort_by(f64::total_cmp);
+    let mut cumulative = Vec::with_capacity(values.len() + 1);
+    cumulative.push(0.0);
+    for value in &values {
+        cumulative.push(cumulative.last().copied().unwrap_or(0.0) + value / total);
+    }
+    let n = values.len() as f64;
+    (0..=points)
+        .map(|step| {
+            let x = step as f64 / points as f64;
+            let position = x * n;
+            let below = (position.floor() as usize).min(values.len() - 1);
+            let within = position - below as f64;
+            let y = cumulative[below] + (cumulative[below + 1] - cumulative[below]) * within;
+            (x, y.min(1.0))
+        })
+        .collect()
+}
+
+/// [`gini`] of the runtime the tasks got in a window.
+pub fn runtime_gini(snapshots: &[TaskSnapshot]) -> Option<f64> {
+    gini(snapshots.iter().map(|snap| snap.runtime_delta_ns as f64))
+}
+
+/// [`lorenz_curve`] of the runtime the tasks got in a window, at
+/// [`LORENZ_POINTS`] steps.
+pub fn runtime_lorenz(snapshots: &[TaskSnapshot]) -> Vec<(f64, f64)> {
+    lorenz_curve(
+        snapshots.iter().map(|snap| snap.runtime_delta_ns as f64),
+        LORENZ_POINTS,
+    )
+}
+
+/// Pearson correlation of the `(x, y)` pairs, from -1 to 1; `None` for
+/// fewer than three pairs or when either side does not vary.
+pub fn pearson(pairs: impl IntoIterator<Item = (f64, f64)>) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = pairs
+        .into_iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    if pairs.len() < 3 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in &pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    let spread = (var_x * var_y).sqrt();
+    (spread > f64::EPSILON).then(|| (cov / spread).clamp(-1.0, 1.0))
+}
+
+/// Spearman rank correlation of the `(x, y)` pairs: [`pearson`] of their
+/// ranks, ties sharing the mean rank, so any monotonic relation scores 1
+/// (or -1). `None` under the same conditions as [`pearson`].
+pub fn spearman(pairs: impl IntoIterator<Item = (f64, f64)>) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = pairs
+        .into_iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    let xs = ranks(pairs.iter().map(|(x, _)| *x));
+    let ys = ranks(pairs.iter().map(|(_, y)| *y));
+    pearson(xs.into_iter().zip(ys))
+}
+
+/// 1-based rank of each value in input order; equal values get the mean of
+/// the ranks they span.
+fn ranks(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    let values: Vec<f64> = values.collect();
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for index in &order[start..end] {
+            ranks[*index] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+pub fn ticket_share(tickets: u32, total_tickets: u64) -> f64 {
+    if total_tickets == 0 {
+        0.0
+    } else {
+        tickets as f64 / total_tickets as f64
+    }
+}
+
+/// Scale lottery shares by the fraction of observed CPU time RT tasks left
+/// over, so SHARE% reflects what the lottery could actually hand out. Returns
+/// the number of tickets taking part in the lottery.
+pub fn apply_class_aware_shares(snapshots: &mut [TaskSnapshot]) -> u64 {
+    let lottery_tickets: u64 = snapshots.iter().map(|s| s.lottery_tickets() as u64).sum();
+    let total_delta: u64 = snapshots.iter().map(|s| s.runtime_delta_ns).sum();
+    let rt_delta: u64 = snapshots
+        .iter()
+        .filter(|s| s.sched_class() == SchedClass::RealTime)
+        .map(|s| s.runtime_delta_ns)
+        .sum();
+    let available = if total_delta == 0 {
+        1.0
+    } else {
+        1.0 - rt_delta as f64 / total_delta as f64
+    };
+    for snap in snapshots.iter_mut() {
+        snap.ticket_share = ticket_share(snap.lottery_tickets(), lottery_tickets) * available;
+    }
+    lottery_tickets
+}
+
+/// Draw `draws` lottery winners from `population`, e.g. a slice or
+/// [`Ranking::all`]; returns `(pid, wins)` by wins, most first.
+pub fn simulate_lottery_draws<'a, R, I>(rng: &mut R, population: I, draws: u32) -> Vec<(u32, u32)>
+where
+    R: Rng + ?Sized,
+    I: IntoIterator<Item = &'a TaskSnapshot> + Clone,
+{
+    let total_tickets: u64 = population
+        .clone()
+        .into_iter()
+        .map(|s| s.lottery_tickets() as u64)
+        .sum();
+    if draws == 0 || total_tickets == 0 {
+        return Vec::new();
+    }
+
+    let mut counts: HashMap<u32, u32> = HashMap::new();
+    for _ in 0..draws {
+        let mut target = rng.gen_range(0..total_tickets);
+        for snap in population.clone() {
+            let share = snap.lottery_tickets() as u64;
+            if share == 0 {
+                continue;
+            }
+
+            if target < share {
+                *counts.entry(snap.pid).or_insert(0) += 1;
+                break;
+            } else {
+                target -= share;
+            }
+        }
+    }
+
+    let mut pairs: Vec<(u32, u32)> = counts.into_iter().collect();
+    pairs.sort_by_key(|pair| std::cmp::Reverse(pair.1));
+    pairs
+}
+
+/// A sort order over a window's snapshots, kept as indices so tables can be
+/// ranked every tick without cloning the snapshots; the index buffer is
+/// reused from one window to the next.
+#[derive(Debug, Default)]
+pub struct Ranking {
+    order: Vec<usize>,
+    /// Leading entries of `order` that are sorted.
+    ranked: usize,
+}
+
+impl Ranking {
+    /// Rank `snapshots` by `cmp` (ascending; reverse it for "most first").
+    /// Only the first `limit` places are put in order, so picking a top-N
+    /// out of thousands of tasks is a selection rather than a full sort.
+    /// Ties keep map order.
+    pub fn rank<F>(&mut self, snapshots: &[TaskSnapshot], limit: usize, cmp: F)
+    where
+        F: Fn(&TaskSnapshot, &TaskSnapshot) -> Ordering,
+    {
+        self.order.clear();
+        self.order.extend(0..snapshots.len());
+        self.ranked = limit.min(snapshots.len());
+        if self.ranked == 0 {
+            return;
+        }
+        let by = |a: &usize, b: &usize| cmp(&snapshots[*a], &snapshots[*b]).then(a.cmp(b));
+        if self.ranked < self.order.len() {
+            self.order.select_nth_unstable_by(self.ranked - 1, by);
+        }
+        self.order[..self.ranked].sort_unstable_by(by);
+    }
+
+    /// Number of ranked places.
+    pub fn len(&self) -> usize {
+        self.ranked
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranked == 0
+    }
+
+    /// The ranked snapshots, first place first. `snapshots` must be the
+    /// slice last passed to [`rank`](Self::rank).
+    pub fn top<'a>(
+        &'a self,
+        snapshots: &'a [TaskSnapshot],
+    ) -> impl ExactSizeIterator<Item = &'a TaskSnapshot> + Clone + 'a {
+        self.order[..self.ranked]
+            .iter()
+            .map(|&index| &snapshots[index])
+    }
+
+    /// Every snapshot: the ranked places, then the rest in no set order.
+    pub fn all<'a>(
+        &'a self,
+        snapshots: &'a [TaskSnapshot],
+    ) -> impl Iterator<Item = &'a TaskSnapshot> + Clone + 'a {
+        self.order.iter().map(|&index| &snapshots[index])
+    }
+}
@@ -0,0 +1,1355 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use crate::bpf_map::{
+    BPF_MAP_TYPE_ARRAY, MapAccess, MappedArray, ValueLayout, clear_ticket_override, lookup_latency,
+    map_info, open_pinned_map, set_ticket_override, walk_cgroup_map, walk_event_map, walk_task_map,
+};
+use crate::cgroup::{CGROUP_PIN_NAME, CgroupInfo, CgroupPaths, CgroupSnapshot, CgroupTracker};
+use crate::control::{self, Renice};
+use crate::demo::{DemoConfig, DemoPopulation};
+use crate::error::{Result, RunnerError};
+use crate::events::{EventCount, EventTracker, SchedEvent};
+use crate::filter::TaskSelector;
+use crate::irq::{IrqSampler, IrqTime};
+use crate::latency::{LATENCY_PIN_NAME, LatencyTracker, TaskLatency};
+use crate::procfs::{ProcCache, TaskIdentity, TaskMeta, online_cpus};
+use crate::remote::{RemoteEntry, RemoteFrame, RemoteSource, Transport};
+use crate::runqueue::{RunQueueDepth, RunQueueSampler};
+use crate::stats::{
+    DEFAULT_STARVATION_WINDOWS, RollingStats, SchedClass, TaskInfo, TaskSnapshot,
+    apply_class_aware_shares, enrich_into, enrich_workers, per_cpu_utilization, system_utilization,
+};
+
+/// Consecutive windows without any counter moving before a map is
+/// considered stale. A live BPF program sees our own switches every window.
+pub const STALE_WINDOWS: u32 = 3;
+
+/// One enriched sampling window read from the task map.
+#[derive(Debug, Clone)]
+pub struct SnapshotWindow {
+    /// Label of the map this window was read from (see [`SnapshotStream::label`]).
+    pub map: String,
+    pub index: u64,
+    pub timestamp: f64,
+    pub total_tickets: u64,
+    pub window_ms: f64,
+    pub snapshots: Vec<TaskSnapshot>,
+    /// Tasks the selector left out of `snapshots`, enriched only with their
+    /// counters and `/proc` attributes; empty unless asked for with
+    /// [`SnapshotStream::with_unselected`].
+    pub unselected: Vec<TaskSnapshot>,
+    /// `false` when the map changed while it was walked, so some entries
+    /// may be missing or from a slightly different moment.
+    pub consistent: bool,
+    /// Clock of `timestamp`.
+    pub clock: Clock,
+    /// Seconds from the BPF program's timestamps (`last_switch_in_ts`) to
+    /// `clock`; see [`Clock::ktime_offset_secs`]. `None` for a remote agent,
+    /// whose kernel clock is not this host's.
+    pub ktime_offset_s: Option<f64>,
+    /// CPUs of the sampled host; see [`SnapshotStream::cpus`].
+    pub cpus: usize,
+    /// Consecutive samples, this one included, in which no task's
+    /// `runtime_ns` or `switches` changed anywhere in the map (selected or
+    /// not); 0 once something moves.
+    pub unchanged_windows: u32,
+    /// EWMA smoothing factor of the rolling runtime and period estimates.
+    pub alpha: f64,
+    /// Run-queue depth of the sampled host over the window; `None` when
+    /// the source does not report it (remote agents, the first local window).
+    pub runqueue: Option<RunQueueDepth>,
+    /// Interrupt time of the sampled host's CPUs over the window; `None`
+    /// when the source does not report it (remote agents, the first local
+    /// window).
+    pub irq: Option<IrqTime>,
+    /// Every cgroup of the `cgroup_map`, busiest first; empty unless asked
+    /// for with [`SnapshotStream::with_cgroups`].
+    pub cgroups: Vec<CgroupSnapshot>,
+}
+
+impl SnapshotWindow {
+    /// Whether the map looks frozen, e.g. because the BPF program was
+    /// detached while its pinned map lives on; see [`STALE_WINDOWS`].
+    pub fn stale(&self) -> bool {
+        self.unchanged_windows >= STALE_WINDOWS
+    }
+
+    /// Every task of the map, selected or not.
+    pub fn all_tasks(&self) -> impl Iterator<Item = &TaskSnapshot> {
+        self.snapshots.iter().chain(&self.unselected)
+    }
+
+    /// See [`system_utilization`].
+    pub fn system_utilization(&self) -> f64 {
+        system_utilization(&self.snapshots, self.window_ms, self.cpus)
+    }
+
+    /// See [`per_cpu_utilization`].
+    pub fn per_cpu_utilization(&self) -> Vec<f64> {
+        per_cpu_utilization(&self.snapshots, self.window_ms, self.cpus)
+    }
+
+    /// [`per_cpu_utilization`](Self::per_cpu_utilization) without the
+    /// interrupt time; see [`IrqTime::task_share`].
+    pub fn per_cpu_task_utilization(&self) -> Vec<f64> {
+        let per_cpu = self.per_cpu_utilization();
+        match &self.irq {
+            Some(irq) => irq.task_share(&per_cpu),
+            None => per_cpu,
+        }
+    }
+}
+
+/// Periodically samples a pinned task map and yields enriched windows, so
+/// callers can `for window in stream` instead of driving the sampling loop.
+pub struct SnapshotStream {
+    source: Source,
+    label: String,
+    rolling: RollingStats,
+    procs: ProcCache,
+    selector: TaskSelector,
+    /// See [`with_unselected`](Self::with_unselected).
+    keep_unselected: bool,
+    /// Pinned `ticket_override` map, for [`set_ticket_override`](Self::set_ticket_override).
+    overrides: Option<OwnedFd>,
+    /// Turns the source's cumulative latency counters into per-window ones.
+    latency: LatencyTracker,
+    /// See [`with_events`](Self::with_events); `None` reads whichever event
+    /// maps are pinned.
+    events: Option<Vec<SchedEvent>>,
+    /// Turns the source's cumulative event counters into per-window ones.
+    event_counts: EventTracker,
+    /// See [`with_cgroups`](Self::with_cgroups).
+    cgroups: bool,
+    /// Turns the source's cumulative cgroup counters into per-window ones.
+    cgroup_counts: CgroupTracker,
+    /// Paths of the cgroup ids of a local map.
+    cgroup_paths: CgroupPaths,
+    /// Run-queue depth of this host, for a local map.
+    runqueue: RunQueueSampler,
+    /// Interrupt time of this host, for a local map.
+    irq: IrqSampler,
+    /// Per-pid [`TaskSnapshot::starved_windows`] of the last sample.
+    starving: HashMap<u32, u32>,
+    /// See [`with_starvation_windows`](Self::with_starvation_windows).
+    starvation_windows: u32,
+    /// Per-pid [`TaskSnapshot::windows_observed`] and
+    /// [`TaskSnapshot::windows_missed`] so far.
+    misses: HashMap<u32, (u64, u64)>,
+    interval: Duration,
+    /// Pids in the last sample, for [`collect_garbage`](Self::collect_garbage).
+    live: HashSet<u32>,
+    /// Snapshot buffer handed back with [`recycle`](Self::recycle).
+    spare: Vec<TaskSnapshot>,
+    /// Metadata of the entries being sampled, kept for its buffer.
+    metas: Vec<TaskMeta>,
+    /// Start time of every pid in the last sample, to spot reused pids.
+    started: HashMap<u32, u64>,
+    reused: u64,
+    /// Summed `runtime_ns` and `switches` of the last sample's entries.
+    counter_sums: Option<(u64, u64)>,
+    unchanged_windows: u32,
+    /// Online CPUs of this host, read once.
+    cpus: usize,
+    clock: Clock,
+    /// When the source was last read; the next window spans from there.
+    last_read: Option<Instant>,
+    next_index: u64,
+    #[cfg(feature = "async")]
+    delay: Option<futures_timer::Delay>,
+}
+
+/// Where raw task entries come from.
+enum Source {
+    Map(LocalMap),
+    Demo(Box<DemoPopulation>),
+    Remote(Box<RemoteSource>),
+}
+
+/// A pinned task map on this host.
+struct LocalMap {
+    fd: OwnedFd,
+    access: MapAccess,
+    /// Set for an mmapable array map, which is then read without syscalls.
+    mapped: Option<MappedArray>,
+    /// Array maps have a slot for every pid; empty ones are skipped.
+    array: bool,
+    layout: ValueLayout,
+    /// Walk the map a second time when it changed during the first walk.
+    reread: bool,
+    /// Scratch space of [`walk_task_map`].
+    seen: HashSet<u32>,
+    /// Pin path and inode the map was opened from, to notice it being
+    /// replaced by a reloaded BPF program.
+    pin: Option<(String, u64)>,
+    /// Reopen the map when its pin is replaced; off once the process can
+    /// no longer open pinned maps.
+    follow_pin: bool,
+    /// The `task_latency` map pinned next to the task map, if any.
+    latency: Option<OwnedFd>,
+    /// The scheduler event maps pinned next to the task map; `None` until
+    /// the first read, so [`SnapshotStream::with_events`] can narrow them
+    /// down first.
+    events: Option<Vec<(SchedEvent, OwnedFd)>>,
+    /// The `cgroup_map` pinned next to the task map, once
+    /// [`SnapshotStream::with_cgroups`] opened it.
+    cgroups: Option<OwnedFd>,
+}
+
+impl LocalMap {
+    /// Check the map's key and value sizes before anything is read into a
+    /// [`TaskInfo`]; `path` names the map in errors.
+    fn new(path: &str, fd: OwnedFd, access: MapAccess) -> Result<Self> {
+        let info = map_info(fd.as_raw_fd()).map_err(|source| RunnerError::MapOpen {
+            path: path.to_string(),
+            source,
+        })?;
+        let layout = ValueLayout::of(path, &info)?;
+        match layout {
+            ValueLayout::Current => {}
+            ValueLayout::NoPreemptions => warn!(
+                target: "map",
+                path,
+                value_size = info.value_size,
+                "task map uses the older layout without preemptions; voluntary and involuntary switches are reported as unknown"
+            ),
+            ValueLayout::NoSwitchIn => warn!(
+                target: "map",
+                path,
+                value_size = info.value_size,
+                "task map uses the older layout without last_switch_in_ts; switch-in times and preemptions are reported as unknown"
+            ),
+        }
+        let mapped = if info.is_mmapable_array() {
+            MappedArray::map(fd.as_raw_fd(), &info, layout)
+                .inspect_err(|err| debug!(target: "map", "not mmapping the task map: {err}"))
+                .ok()
+        } else {
+            None
+        };
+        Ok(Self {
+            fd,
+            access,
+            mapped,
+            array: info.map_type == BPF_MAP_TYPE_ARRAY,
+            layout,
+            reread: false,
+            seen: HashSet::new(),
+            pin: None,
+            follow_pin: true,
+            latency: None,
+            events: None,
+            cgroups: None,
+        })
+    }
+
+    /// Open the map pinned at `path`, remembering the pin so a replaced map
+    /// is noticed by [`reopen_if_replaced`](Self::reopen_if_replaced).
+    fn open(path: &str) -> Result<Self> {
+        // Taken before opening, so a map swapped in between is re-read.
+        let inode = pin_inode(path);
+        // The task map is only ever read.
+        let (fd, access) = open_pinned_map(path, MapAccess::ReadOnly)?;
+        let map_fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut map = Self::new(path, map_fd, access)?;
+        map.pin = inode.map(|inode| (path.to_string(), inode));
+        map.latency = open_latency_beside(path);
+        Ok(map)
+    }
+
+    /// Switch to the map now pinned at our path if it is no longer the one
+    /// we hold, as after the BPF program was reloaded; our fd would
+    /// otherwise keep the old map alive, frozen. Returns whether it did.
+    fn reopen_if_replaced(&mut self) -> Result<bool> {
+        let Some((path, inode)) = &self.pin else {
+            return Ok(false);
+        };
+        // A missing pin means the program was unloaded; keep the last map.
+        if pin_inode(path).is_none_or(|now| now == *inode) {
+            return Ok(false);
+        }
+        if !self.follow_pin {
+            warn!(
+                target: "map",
+                path,
+                "task map was replaced (BPF program reloaded?), but it cannot be reopened without the dropped privileges; restart the runner to follow it"
+            );
+            self.pin = None;
+            return Ok(false);
+        }
+        let mut map = Self::open(&path.clone())?;
+        map.reread = self.reread;
+        if self.cgroups.is_some() {
+            map.cgroups = open_cgroups_beside(path);
+        }
+        *self = map;
+        Ok(true)
+    }
+
+    /// `pid`'s latency counters, when a latency map is open and has them.
+    fn latency(&self, pid: u32) -> Option<TaskLatency> {
+        let fd = self.latency.as_ref()?;
+        lookup_latency(fd.as_raw_fd(), pid)
+            .inspect_err(|err| debug!(target: "map", pid, "cannot read latency: {err}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Every pid's `event` counter, read in one walk of that event's map
+    /// when it is open; pids without an entry have not had the event yet.
+    fn event_counts(&self, event: SchedEvent) -> Option<HashMap<u32, u64>> {
+        let (_, fd) = self
+            .events
+            .iter()
+            .flatten()
+            .find(|(open, _)| *open == event)?;
+        match walk_event_map(fd.as_raw_fd()) {
+            Ok(entries) => Some(
+                entries
+                    .into_iter()
+                    .map(|(pid, count)| (pid, count.count))
+                    .collect(),
+            ),
+            Err(err) => {
+                debug!(target: "map", %event, "cannot read event counts: {err}");
+                None
+            }
+        }
+    }
+
+    /// The `cgroup_map` entries, when it is open and readable.
+    fn cgroups(&self) -> Vec<(u64, CgroupInfo)> {
+        let Some(fd) = &self.cgroups else {
+            return Vec::new();
+        };
+        walk_cgroup_map(fd.as_raw_fd())
+            .inspect_err(|err| debug!(target: "map", "cannot read the cgroup map: {err}"))
+            .unwrap_or_default()
+    }
+
+    /// The entries, and whether the map held still while they were read.
+    fn read(&mut self) -> Result<(Vec<(u32, TaskInfo)>, bool)> {
+        if let Some(mapped) = &self.mapped {
+            return Ok((mapped.read(), true));
+        }
+        let fd = self.fd.as_raw_fd();
+        let mut walk = walk_task_map(fd, self.layout, &mut self.seen)?;
+        if !walk.consistent() {
+            debug!(
+                target: "map",
+                restarts = walk.restarts,
+                vanished = walk.vanished,
+                truncated = walk.truncated,
+                reread = self.reread,
+                "task map changed while it was read"
+            );
+            if self.reread {
+                walk = walk_task_map(fd, self.layout, &mut self.seen)?.reconcile(walk);
+            }
+        }
+        let consistent = walk.consistent();
+        let mut entries = walk.entries;
+        if self.array {
+            entries.retain(|(pid, info)| *pid != 0 && !info.is_empty());
+        }
+        Ok((entries, consistent))
+    }
+}
+
+/// The `task_latency` map pinned next to the task map at `path`, when the
+/// BPF program was loaded with one. Its absence only leaves latency
+/// unreported; so does a map of another layout, which is warned about.
+fn open_latency_beside(path: &str) -> Option<OwnedFd> {
+    open_map_beside(
+        path,
+        LATENCY_PIN_NAME,
+        std::mem::size_of::<u32>(),
+        std::mem::size_of::<TaskLatency>(),
+        "struct task_latency",
+        "wakeup latency",
+        false,
+    )
+}
+
+/// The maps of `events` (`None`: of every event) pinned next to the task
+/// map at `path`. Events asked for by name are warned about when missing.
+fn open_events_beside(path: &str, events: Option<&[SchedEvent]>) -> Vec<(SchedEvent, OwnedFd)> {
+    events
+        .unwrap_or(&SchedEvent::ALL)
+        .iter()
+        .filter_map(|event| {
+            let fd = open_map_beside(
+                path,
+                event.map_name(),
+                std::mem::size_of::<u32>(),
+                std::mem::size_of::<EventCount>(),
+                "struct sched_event_count",
+                &format!("{event} events"),
+                events.is_some(),
+            )?;
+            Some((*event, fd))
+        })
+        .collect()
+}
+
+/// The `cgroup_map` pinned next to the task map at `path`; asked for
+/// explicitly, so a missing one is warned about.
+fn open_cgroups_beside(path: &str) -> Option<OwnedFd> {
+    open_map_beside(
+        path,
+        CGROUP_PIN_NAME,
+        std::mem::size_of::<u64>(),
+        std::mem::size_of::<CgroupInfo>(),
+        "struct cgroup_info",
+        "cgroups",
+        true,
+    )
+}
+
+/// A map pinned as `name` next to the task map at `path`, if it exists and
+/// holds `key_size`-byte keys and `value_size`-byte values (`value` names
+/// them in warnings). A missing map is logged at debug level, or as a
+/// warning when it was `required`; either way only `what` goes unreported.
+fn open_map_beside(
+    path: &str,
+    name: &str,
+    key_size: usize,
+    value_size: usize,
+    value: &str,
+    what: &str,
+    required: bool,
+) -> Option<OwnedFd> {
+    let pin = Path::new(path).with_file_name(name);
+    let pin = pin.to_str()?;
+    if !Path::new(pin).exists() {
+        if required {
+            warn!(target: "map", path = pin, "no {name} map; not reporting {what}");
+        } else {
+            debug!(target: "map", path = pin, "no {name} map; not reporting {what}");
+        }
+        return None;
+    }
+    let fd = match open_pinned_map(pin, MapAccess::ReadOnly) {
+        Ok((fd, _)) => unsafe { OwnedFd::from_raw_fd(fd) },
+        Err(err) => {
+            warn!(target: "map", "{err}; not reporting {what}");
+            return None;
+        }
+    };
+    match map_info(fd.as_raw_fd()) {
+        Ok(info)
+            if info.key_size as usize == key_size && info.value_size as usize == value_size =>
+        {
+            info!(target: "map", path = pin, "reading {what}");
+            Some(fd)
+        }
+        Ok(info) => {
+            warn!(
+                target: "map",
+                path = pin,
+                key_size = info.key_size,
+                value_size = info.value_size,
+                "{name} map does not hold {value} values; not reporting {what}"
+            );
+            None
+        }
+        Err(err) => {
+            warn!(target: "map", path = pin, "cannot inspect {name} map: {err}");
+            None
+        }
+    }
+}
+
+fn pin_inode(path: &str) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|meta| meta.ino())
+}
+
+/// Error for controls that only work on a local map or the demo population.
+fn remote_unsupported(what: &str) -> RunnerError {
+    RunnerError::Config(format!("{what} is not available on a --remote stream"))
+}
+
+/// Re-read the placement and start time of every entry's task into `metas`;
+/// a few syscalls per task, so large maps are split across the enrichment
+/// threads.
+fn refresh_volatile(entries: &[(u32, TaskInfo)], metas: &mut [TaskMeta]) {
+    let workers = enrich_workers(entries.len());
+    if workers == 1 {
+        for ((pid, _), meta) in entries.iter().zip(metas.iter_mut()) {
+            meta.refresh_volatile(*pid);
+        }
+        return;
+    }
+    let chunk = entries.len().div_ceil(workers);
+    thread::scope(|scope| {
+        for (entries, metas) in entries.chunks(chunk).zip(metas.chunks_mut(chunk)) {
+            scope.spawn(move || {
+                for ((pid, _), meta) in entries.iter().zip(metas.iter_mut()) {
+                    meta.refresh_volatile(*pid);
+                }
+            });
+        }
+    });
+}
+
+impl SnapshotStream {
+    pub fn open(path: &str, interval: Duration, alpha: f64) -> Result<Self> {
+        let label = Path::new(path).file_name().map_or_else(
+            || path.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let source = Source::Map(LocalMap::open(path)?);
+        Ok(Self::with_source(source, &label, interval, alpha))
+    }
+
+    /// Sample a map the caller opened read-write, e.g. one it created.
+    pub fn from_fd(map_fd: OwnedFd, interval: Duration, alpha: f64) -> Result<Self> {
+        let map = LocalMap::new("task_map", map_fd, MapAccess::ReadWrite)?;
+        Ok(Self::with_source(
+            Source::Map(map),
+            "task_map",
+            interval,
+            alpha,
+        ))
+    }
+
+    /// Sample a synthetic population instead of a pinned map; needs neither
+    /// root nor a loaded BPF program. Windows are labelled `demo`.
+    pub fn demo(config: &DemoConfig, interval: Duration, alpha: f64) -> Self {
+        let population = Box::new(DemoPopulation::new(config));
+        Self::with_source(Source::Demo(population), "demo", interval, alpha)
+    }
+
+    /// Sample a `rust-runner agent` over `transport` (TCP or TLS). Entries
+    /// and metadata come from the agent's host; enrichment happens here.
+    /// Windows are labelled `label`, e.g. the agent's address.
+    pub fn remote(
+        transport: Box<dyn Transport>,
+        label: &str,
+        interval: Duration,
+        alpha: f64,
+    ) -> Self {
+        let source = Box::new(RemoteSource::new(transport));
+        Self::with_source(Source::Remote(source), label, interval, alpha)
+    }
+
+    fn with_source(source: Source, label: &str, interval: Duration, alpha: f64) -> Self {
+        Self {
+            source,
+            label: label.to_string(),
+            rolling: RollingStats::new(alpha),
+            procs: ProcCache::new(),
+            selector: TaskSelector::default(),
+            keep_unselected: false,
+            overrides: None,
+            latency: LatencyTracker::default(),
+            events: None,
+            event_counts: EventTracker::default(),
+            cgroups: false,
+            cgroup_counts: CgroupTracker::default(),
+            cgroup_paths: CgroupPaths::default(),
+            runqueue: RunQueueSampler::default(),
+            irq: IrqSampler::default(),
+            starving: HashMap::new(),
+            starvation_windows: DEFAULT_STARVATION_WINDOWS,
+            misses: HashMap::new(),
+            interval,
+            live: HashSet::new(),
+            spare: Vec::new(),
+            metas: Vec::new(),
+            started: HashMap::new(),
+            reused: 0,
+            counter_sums: None,
+            unchanged_windows: 0,
+            cpus: online_cpus(),
+            clock: Clock::default(),
+            last_read: None,
+            next_index: 0,
+            #[cfg(feature = "async")]
+            delay: None,
+        }
+    }
+
+    /// How the pinned task map was opened; `None` for demo and remote
+    /// streams.
+    pub fn map_access(&self) -> Option<MapAccess> {
+        match &self.source {
+            Source::Map(map) => Some(map.access),
+            Source::Demo(_) | Source::Remote(_) => None,
+        }
+    }
+
+    /// CPUs the sampled tasks share: this host's online CPUs, the remote
+    /// agent's host's (when it reports them), or the demo's.
+    pub fn cpus(&self) -> usize {
+        match &self.source {
+            Source::Map(_) => self.cpus,
+            Source::Demo(population) => population.cpus().max(1) as usize,
+            Source::Remote(remote) => remote.cpus().unwrap_or(self.cpus),
+        }
+    }
+
+    /// Name used to tell maps apart in outputs; defaults to the pin file name.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Bound the per-pid rolling state; see [`RollingStats::with_limits`].
+    pub fn with_rolling_limits(mut self, ttl: u64, capacity: usize) -> Self {
+        let rolling = std::mem::replace(&mut self.rolling, RollingStats::new(0.0));
+        self.rolling = rolling.with_limits(ttl, capacity);
+        self
+    }
+
+    /// Read window timestamps from `clock` (wall clock by default).
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Walk a hash map a second time when it changed during the first walk,
+    /// and merge both walks; see
+    /// [`MapWalk::reconcile`](crate::bpf_map::MapWalk::reconcile).
+    pub fn with_reread(mut self, reread: bool) -> Self {
+        if let Source::Map(map) = &mut self.source {
+            map.reread = reread;
+        }
+        self
+    }
+
+    /// Flag tasks as [`starved`](TaskSnapshot::starved) once they held a
+    /// ticket share while runnable without running for `windows` windows in
+    /// a row; 0 never flags them.
+    pub fn with_starvation_windows(mut self, windows: u32) -> Self {
+        self.starvation_windows = windows;
+        self
+    }
+
+    /// Report only these scheduler events, and warn about those whose map
+    /// is not pinned next to the task map. Without it, whichever event maps
+    /// are pinned are read; remote streams report none.
+    pub fn with_events(mut self, events: Vec<SchedEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Also report the per-cgroup totals of the `cgroup_map` pinned next to
+    /// the task map (or the demo population's) in
+    /// [`SnapshotWindow::cgroups`], warning when it is not pinned. Remote
+    /// streams report none.
+    pub fn with_cgroups(mut self, cgroups: bool) -> Self {
+        self.cgroups = cgroups;
+        if cgroups
+            && let Source::Map(map) = &mut self.source
+            && let Some((path, _)) = &map.pin
+        {
+            map.cgroups = open_cgroups_beside(path);
+        }
+        self
+    }
+
+    /// Open the maps that are otherwise opened on first use (the event
+    /// maps) and stop following a replaced task map, before the process
+    /// gives up the privileges that opening pinned maps takes: bpffs is
+    /// owner-only, and CAP_BPF does not get past that.
+    pub fn prepare_privilege_drop(&mut self) {
+        if let Source::Map(map) = &mut self.source {
+            if map.events.is_none() {
+                map.events = Some(match &map.pin {
+                    Some((path, _)) => open_events_beside(path, self.events.as_deref()),
+                    None => Vec::new(),
+                });
+            }
+            map.follow_pin = false;
+        }
+    }
+
+    /// Only enrich and yield tasks matching `selector`. Ticket shares and
+    /// `total_tickets` stay relative to the whole map.
+    pub fn with_selector(mut self, selector: TaskSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Also yield the tasks the selector leaves out, as
+    /// [`SnapshotWindow::unselected`], for controllers that act on the whole
+    /// map. Costs a `/proc` lookup per task, as without a selector.
+    pub fn with_unselected(mut self, keep: bool) -> Self {
+        self.keep_unselected = keep;
+        self
+    }
+
+    /// Open the pinned `ticket_override` map next to the task map so
+    /// [`set_ticket_override`](Self::set_ticket_override) can write to it.
+    /// Demo streams keep overrides in the synthetic population instead.
+    pub fn with_ticket_overrides(mut self, path: &str) -> Result<Self> {
+        match self.source {
+            Source::Map(_) => {
+                let (fd, _) = open_pinned_map(path, MapAccess::ReadWrite)?;
+                self.overrides = Some(unsafe { OwnedFd::from_raw_fd(fd) });
+            }
+            Source::Demo(_) => {}
+            Source::Remote(_) => return Err(remote_unsupported("the ticket_override map")),
+        }
+        Ok(self)
+    }
+
+    /// Pin `pid`'s tickets (`Some`) or hand it back to the nice-derived
+    /// count (`None`); takes effect on the task's next switch-in.
+    pub fn set_ticket_override(&mut self, pid: u32, tickets: Option<u32>) -> Result<()> {
+        let failed = |source| RunnerError::Control {
+            target: format!("ticket override for pid {pid}"),
+            source,
+        };
+        match &mut self.source {
+            Source::Map(_) => {
+                let Some(fd) = &self.overrides else {
+                    return Err(RunnerError::Config(
+                        "no ticket_override map opened".to_string(),
+                    ));
+                };
+                match tickets {
+                    Some(tickets) => set_ticket_override(fd.as_raw_fd(), pid, tickets),
+                    None => clear_ticket_override(fd.as_raw_fd(), pid),
+                }
+                .map_err(failed)
+            }
+            Source::Demo(population) => {
+                if population.override_tickets(pid, tickets) || tickets.is_none() {
+                    Ok(())
+                } else {
+                    Err(failed(io::Error::from_raw_os_error(libc::ESRCH)))
+                }
+            }
+            Source::Remote(_) => Err(remote_unsupported("setting ticket overrides")),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Change the sampling interval; rolling state is kept. The next window
+    /// still spans whatever time passed since the previous sample.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+        #[cfg(feature = "async")]
+        {
+            self.delay = None;
+        }
+    }
+
+    /// Drop rolling state for tasks gone since the last sample and clear the
+    /// `/proc` metadata cache. Returns how many entries were released.
+    pub fn collect_garbage(&mut self) -> usize {
+        let rolling = self.rolling.retain(&self.live);
+        let cached = self.procs.clear();
+        debug!(target: "sampler", rolling, cached, "released cached state");
+        rolling + cached
+    }
+
+    /// Pids the stream keeps rolling state for.
+    pub fn tracked_pids(&self) -> usize {
+        self.rolling.tracked()
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.rolling.alpha()
+    }
+
+    /// Change the EWMA smoothing factor (clamped to 0-1) without losing the
+    /// rolling history; see [`RollingStats::set_alpha`].
+    pub fn set_alpha(&mut self, alpha: f64) {
+        self.rolling.set_alpha(alpha);
+    }
+
+    /// Window length used for the period/lateness heuristics: the time
+    /// since the previous read, or the interval before the first one. At
+    /// sub-second intervals timer slack is a sizeable part of a window, so
+    /// the nominal interval would skew utilization and lateness.
+    pub fn window_ms(&self) -> f64 {
+        let window = match self.last_read {
+            Some(last) => last.elapsed(),
+            None => self.interval,
+        };
+        if window.is_zero() {
+            1.0
+        } else {
+            window.as_secs_f64() * 1000.0
+        }
+    }
+
+    /// Change the nice value of a sampled task; the new ticket count shows
+    /// up from the next sample. Demo streams adjust the synthetic task.
+    pub fn renice(&mut self, pid: u32, nice: i32) -> Result<Renice> {
+        let nice = nice.clamp(-20, 19);
+        match &mut self.source {
+            Source::Map(_) => control::renice(pid, nice).map_err(|source| RunnerError::Control {
+                target: format!("pid {pid}"),
+                source,
+            }),
+            Source::Demo(population) => match population.renice(pid, nice) {
+                Some(old_nice) => Ok(Renice {
+                    pid,
+                    old_nice,
+                    new_nice: nice,
+                }),
+                None => Err(RunnerError::Control {
+                    target: format!("pid {pid}"),
+                    source: io::Error::from_raw_os_error(libc::ESRCH),
+                }),
+            },
+            Source::Remote(_) => Err(remote_unsupported("renice")),
+        }
+    }
+
+    /// The source's entries, and whether they were read consistently.
+    fn read_entries(&mut self) -> Result<(Vec<(u32, TaskInfo)>, bool)> {
+        match &mut self.source {
+            Source::Map(map) => {
+                if map.reopen_if_replaced()? {
+                    warn!(
+                        target: "map",
+                        map = %self.label,
+                        "task map was replaced (BPF program reloaded?); reopened it and restarted every task's counters"
+                    );
+                    self.rolling.rebase_all();
+                }
+                if map.events.is_none() {
+                    map.events = Some(match &map.pin {
+                        Some((path, _)) => open_events_beside(path, self.events.as_deref()),
+                        None => Vec::new(),
+                    });
+                }
+                map.read().map_err(|err| err.in_map(&self.label))
+            }
+            Source::Demo(population) => Ok((population.read(), true)),
+            Source::Remote(remote) => remote
+                .fetch()
+                .map(|entries| (entries, remote.consistent()))
+                .map_err(|source| RunnerError::Remote {
+                    addr: self.label.clone(),
+                    source,
+                }),
+        }
+    }
+
+    /// Metadata the source itself carries (demo population, remote agent);
+    /// `None` for a local map, which is resolved through `/proc`.
+    fn source_meta(&self, pid: u32) -> Option<Option<&TaskMeta>> {
+        match &self.source {
+            Source::Map(_) => None,
+            Source::Demo(population) => Some(population.meta(pid)),
+            Source::Remote(remote) => Some(remote.meta(pid)),
+        }
+    }
+
+    /// Whether the source counts preemptions; older task map layouts and
+    /// older agents do not.
+    fn counts_preemptions(&self) -> bool {
+        match &self.source {
+            Source::Map(map) => map.layout == ValueLayout::Current,
+            Source::Demo(_) => true,
+            Source::Remote(remote) => remote.preemptions(),
+        }
+    }
+
+    /// Read the source once without enriching, resolving metadata the way
+    /// [`sample`](Self::sample) does; what an agent sends to its clients.
+    pub fn frame(&mut self) -> Result<RemoteFrame> {
+        let (entries, consistent) = self.read_entries()?;
+        let mut meta = HashMap::with_capacity(entries.len());
+        for (pid, _) in &entries {
+            let task = match self.source_meta(*pid) {
+                Some(task) => task.cloned().unwrap_or_default(),
+                None => {
+                    let mut task = self.procs.lookup(*pid).clone();
+                    task.refresh_volatile(*pid);
+                    task
+                }
+            };
+            meta.insert(*pid, task);
+        }
+        if let Source::Map(_) = self.source {
+            let live: HashSet<u32> = entries.iter().map(|(pid, _)| *pid).collect();
+            self.procs.retain_live(&live);
+        }
+        let preemptions = self.counts_preemptions();
+        Ok(RemoteFrame {
+            entries: entries
+                .iter()
+                .map(|(pid, info)| (*pid, RemoteEntry::new(info, preemptions)))
+                .collect(),
+            meta,
+            consistent,
+            cpus: self.cpus(),
+        })
+    }
+
+    /// Hand back the snapshots of a window that is no longer needed; the
+    /// next sample enriches into their buffer instead of allocating one.
+    pub fn recycle(&mut self, mut snapshots: Vec<TaskSnapshot>) {
+        snapshots.clear();
+        if snapshots.capacity() > self.spare.capacity() {
+            self.spare = snapshots;
+        }
+    }
+
+    /// Reset pids whose start time changed since the last sample: their
+    /// counters and EWMA belong to the task that had the pid before, so the
+    /// new task starts from a fresh baseline, and a local map re-resolves
+    /// its `/proc` metadata.
+    fn forget_reused(&mut self, entries: &[(u32, TaskInfo)], metas: &mut [TaskMeta]) {
+        for ((pid, _), meta) in entries.iter().zip(metas.iter_mut()) {
+            let Some(start_time) = meta.start_time else {
+                continue;
+            };
+            let Some(before) = self.started.insert(*pid, start_time) else {
+                continue;
+            };
+            if before == start_time {
+                continue;
+            }
+            debug!(target: "sampler", pid, before, start_time, "pid was reused; resetting its state");
+            self.reused += 1;
+            self.rolling.forget(*pid);
+            self.latency.forget(*pid);
+            self.event_counts.forget(*pid);
+            self.starving.remove(pid);
+            self.misses.remove(pid);
+            if let Source::Map(_) = self.source {
+                self.procs.forget(*pid);
+                *meta = self.procs.lookup(*pid).clone();
+            }
+        }
+    }
+
+    /// `/proc` attributes of `entries`, in order, into `metas`.
+    fn lookup_metas(&mut self, entries: &[(u32, TaskInfo)], metas: &mut Vec<TaskMeta>) {
+        metas.clear();
+        if let Source::Map(_) = self.source {
+            metas.extend(
+                entries
+                    .iter()
+                    .map(|(pid, _)| self.procs.lookup(*pid).clone()),
+            );
+            refresh_volatile(entries, metas);
+        } else {
+            metas.extend(entries.iter().map(|(pid, _)| {
+                self.source_meta(*pid)
+                    .flatten()
+                    .cloned()
+                    .unwrap_or_default()
+            }));
+        }
+        self.forget_reused(entries, metas);
+    }
+
+    /// Read and enrich the map right now, without waiting for the interval.
+    pub fn sample(&mut self) -> Result<SnapshotWindow> {
+        let started = Instant::now();
+        let window_ms = self.window_ms();
+        let (mut entries, consistent) = self.read_entries()?;
+        self.last_read = Some(started);
+        let index = self.next_index;
+        self.track_staleness(&entries, index);
+        let mut live = std::mem::take(&mut self.live);
+        live.clear();
+        live.extend(entries.iter().map(|(pid, _)| *pid));
+        let mut unselected = Vec::new();
+        let map_tickets = if self.selector.is_empty() {
+            None
+        } else {
+            // Shares are computed over the selected tasks below, then scaled
+            // back to the whole map's (nice-derived) lottery pool, unless
+            // the unselected ones are kept and enriched with them.
+            let map_tickets: u64 = entries
+                .iter()
+                .filter(|(_, info)| info.sched_class() == SchedClass::Fair)
+                .map(|(_, info)| info.tickets as u64)
+                .sum();
+            self.selector.refresh();
+            let (selected, rest) = entries.into_iter().partition(|(pid, _)| {
+                let identity = match self.source_meta(*pid) {
+                    None => self.procs.identity(*pid).clone(),
+                    Some(meta) => meta
+                        .map(|meta| TaskIdentity {
+                            tgid: meta.tgid,
+                            comm: meta.comm.clone(),
+                        })
+                        .unwrap_or_default(),
+                };
+                self.selector.matches(*pid, &identity)
+            });
+            entries = selected;
+            if self.keep_unselected {
+                unselected = rest;
+            }
+            Some(map_tickets)
+        };
+        // Kept unselected tasks are enriched along with the selected ones,
+        // in one window and with shares over the whole map, and split off
+        // afterwards.
+        let selected = entries.len();
+        let whole_map = !unselected.is_empty();
+        entries.append(&mut unselected);
+        let mut metas = std::mem::take(&mut self.metas);
+        self.lookup_metas(&entries, &mut metas);
+        let mut snapshots = std::mem::take(&mut self.spare);
+        enrich_into(&entries, &mut self.rolling, window_ms, &mut snapshots);
+        for (snap, meta) in snapshots.iter_mut().zip(metas.iter_mut()) {
+            snap.meta = std::mem::take(meta);
+        }
+        self.metas = metas;
+        if !self.counts_preemptions() {
+            for snap in snapshots.iter_mut() {
+                snap.preempt_delta = None;
+            }
+        }
+        // Re-run with the queried policies now attached to each snapshot.
+        let mut total_tickets = apply_class_aware_shares(&mut snapshots);
+        let unselected = snapshots.split_off(selected);
+        let waiting = self.attach_latency(&mut snapshots);
+        self.latency.retain(&live);
+        self.attach_events(&mut snapshots);
+        self.event_counts.retain(&live);
+        if let Source::Map(_) = self.source {
+            self.procs.retain_live(&live);
+        }
+        self.started.retain(|pid, _| live.contains(pid));
+        if let Some(map_tickets) = map_tickets
+            && map_tickets > 0
+            && !whole_map
+        {
+            let scale = total_tickets as f64 / map_tickets as f64;
+            for snap in snapshots.iter_mut() {
+                snap.ticket_share *= scale;
+            }
+            total_tickets = map_tickets;
+        }
+        self.track_starvation(&mut snapshots, &waiting);
+        self.starving.retain(|pid, _| live.contains(pid));
+        self.track_misses(&mut snapshots);
+        self.misses.retain(|pid, _| live.contains(pid));
+        let cgroups = if self.cgroups {
+            self.sample_cgroups(window_ms, &snapshots)
+        } else {
+            Vec::new()
+        };
+        self.live = live;
+        self.next_index += 1;
+        let resets = snapshots.iter().filter(|snap| snap.counter_reset).count();
+        if resets > 1 && resets * 2 > snapshots.len() {
+            warn!(
+                target: "sampler",
+                map = %self.label,
+                index,
+                resets,
+                tasks = snapshots.len(),
+                "most task counters started over (BPF program reloaded?); their deltas restart next window"
+            );
+        }
+        debug!(
+            target: "sampler",
+            index,
+            tasks = snapshots.len(),
+            total_tickets,
+            tracked = self.rolling.tracked(),
+            evicted = self.rolling.evicted(),
+            reused = self.reused,
+            resets,
+            elapsed_us = started.elapsed().as_micros() as u64,
+            "sampled task map"
+        );
+        Ok(SnapshotWindow {
+            map: self.label.clone(),
+            index,
+            timestamp: self.clock.now_secs(),
+            total_tickets,
+            window_ms,
+            snapshots,
+            unselected,
+            consistent,
+            clock: self.clock,
+            ktime_offset_s: match &self.source {
+                Source::Remote(_) => None,
+                Source::Map(_) | Source::Demo(_) => Some(self.clock.ktime_offset_secs()),
+            },
+            cpus: self.cpus(),
+            unchanged_windows: self.unchanged_windows,
+            alpha: self.rolling.alpha(),
+            runqueue: match &self.source {
+                Source::Map(_) => self.runqueue.sample(),
+                Source::Demo(population) => population.runqueue(),
+                Source::Remote(_) => None,
+            },
+            irq: match &self.source {
+                Source::Map(_) => self.irq.sample(),
+                Source::Demo(population) => population.irq(),
+                Source::Remote(_) => None,
+            },
+            cgroups,
+        })
+    }
+
+    /// The cgroups of the `cgroup_map` beside a local task map, or of the
+    /// demo population, over the window.
+    fn sample_cgroups(
+        &mut self,
+        window_ms: f64,
+        snapshots: &[TaskSnapshot],
+    ) -> Vec<CgroupSnapshot> {
+        match &self.source {
+            Source::Map(map) => {
+                let entries = map.cgroups();
+                self.cgroup_paths.refresh(entries.iter().map(|(id, _)| *id));
+                let paths = &self.cgroup_paths;
+                self.cgroup_counts
+                    .update(entries, |id| paths.get(id), window_ms, snapshots)
+            }
+            Source::Demo(population) => self.cgroup_counts.update(
+                population.cgroups(),
+                |id| population.cgroup_path(id).map(str::to_string),
+                window_ms,
+                snapshots,
+            ),
+            Source::Remote(_) => Vec::new(),
+        }
+    }
+
+    /// Count each task's windows in a row with a ticket share, a pending
+    /// wakeup, and no runtime, and flag those that reached the threshold.
+    /// Sources without a latency map never flag a task.
+    fn track_starvation(&mut self, snapshots: &mut [TaskSnapshot], waiting: &HashSet<u32>) {
+        for snap in snapshots.iter_mut() {
+            // Without a pending wakeup the task may just be asleep.
+            let starving = snap.ticket_share > 0.0
+                && snap.runtime_delta_ns == 0
+                && !snap.counter_reset
+                && waiting.contains(&snap.pid);
+            let windows = match self.starving.entry(snap.pid) {
+                // The first sighting has no runtime delta to judge by.
+                Entry::Vacant(entry) => *entry.insert(0),
+                Entry::Occupied(mut entry) => {
+                    let windows = entry.get_mut();
+                    *windows = if starving { *windows + 1 } else { 0 };
+                    *windows
+                }
+            };
+            snap.starved_windows = windows;
+            snap.starved = self.starvation_windows > 0 && windows >= self.starvation_windows;
+        }
+    }
+
+    /// Count each task's windows and deadline misses since the pid was
+    /// first seen. Windows without deltas (first sighting, counter reset)
+    /// are not judged.
+    fn track_misses(&mut self, snapshots: &mut [TaskSnapshot]) {
+        for snap in snapshots.iter_mut() {
+            let (observed, missed) = match self.misses.entry(snap.pid) {
+                Entry::Vacant(entry) => entry.insert((0, 0)),
+                Entry::Occupied(entry) => {
+                    let counts = entry.into_mut();
+                    if !snap.counter_reset {
+                        counts.0 += 1;
+                        counts.1 += u64::from(snap.deadline_missed());
+                    }
+                    counts
+                }
+            };
+            snap.windows_observed = *observed;
+            snap.windows_missed = *missed;
+        }
+    }
+
+    /// Fill in each snapshot's wakeup latency over the window, from the
+    /// latency map beside a local task map or the demo population.
+    /// Returns the pids with a wakeup still pending, i.e. runnable tasks
+    /// waiting for a CPU.
+    fn attach_latency(&mut self, snapshots: &mut [TaskSnapshot]) -> HashSet<u32> {
+        let mut waiting = HashSet::new();
+        for snap in snapshots.iter_mut() {
+            let reading = match &self.source {
+                Source::Map(map) => map.latency(snap.pid),
+                Source::Demo(population) => population.latency(snap.pid),
+                Source::Remote(_) => None,
+            };
+            if reading.is_some_and(|reading| reading.wakeup_ts != 0) {
+                waiting.insert(snap.pid);
+            }
+            snap.latency = reading.and_then(|reading| self.latency.update(snap.pid, reading));
+        }
+        waiting
+    }
+
+    /// Fill in each snapshot's scheduler events over the window, from the
+    /// event maps beside a local task map or the demo population.
+    fn attach_events(&mut self, snapshots: &mut [TaskSnapshot]) {
+        let events = self.events.as_deref().unwrap_or(&SchedEvent::ALL);
+        for event in events {
+            let counts = match &self.source {
+                Source::Map(map) => map.event_counts(*event),
+                Source::Demo(_) | Source::Remote(_) => None,
+            };
+            for snap in snapshots.iter_mut() {
+                let count = match &self.source {
+                    Source::Map(_) => counts
+                        .as_ref()
+                        .map(|counts| counts.get(&snap.pid).copied().unwrap_or(0)),
+                    Source::Demo(population) => population.event_count(snap.pid, *event),
+                    Source::Remote(_) => None,
+                };
+                snap.events.set(
+                    *event,
+                    count.and_then(|count| self.event_counts.update(*event, snap.pid, count)),
+                );
+            }
+        }
+    }
+
+    /// Count samples in which no counter in the whole map moved, before the
+    /// selector narrows it down to tasks that may legitimately be idle.
+    fn track_staleness(&mut self, entries: &[(u32, TaskInfo)], index: u64) {
+        let sums = entries
+            .iter()
+            .fold((0_u64, 0_u64), |(runtime, switches), (_, info)| {
+                (
+                    runtime.wrapping_add(info.runtime_ns),
+                    switches.wrapping_add(info.switches),
+                )
+            });
+        let unchanged = !entries.is_empty() && self.counter_sums == Some(sums);
+        self.counter_sums = Some(sums);
+        if !unchanged {
+            if self.unchanged_windows >= STALE_WINDOWS {
+                info!(target: "sampler", map = %self.label, index, "task map counters are moving again");
+            }
+            self.unchanged_windows = 0;
+            return;
+        }
+        self.unchanged_windows += 1;
+        if self.unchanged_windows == STALE_WINDOWS {
+            warn!(
+                target: "sampler",
+                map = %self.label,
+                index,
+                windows = STALE_WINDOWS,
+                "no runtime or switch counter changed in {STALE_WINDOWS} windows; the map looks frozen (BPF program detached?)"
+            );
+        }
+    }
+}
+
+impl Iterator for SnapshotStream {
+    type Item = Result<SnapshotWindow>;
+
+    /// Sleeps for one interval, then samples. Never returns `None`; bound
+    /// the stream with `take` or stop on the first error.
+    fn next(&mut self) -> Option<Self::Item> {
+        thread::sleep(self.interval);
+        Some(self.sample())
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for SnapshotStream {
+    type Item = Result<SnapshotWindow>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::future::Future;
+        use std::task::Poll;
+
+        let this = self.get_mut();
+        if !this.interval.is_zero() {
+            let delay = this
+                .delay
+                .get_or_insert_with(|| futures_timer::Delay::new(this.interval));
+            if std::pin::Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+        }
+        Poll::Ready(Some(this.sample()))
+    }
+}
+
+/// Clock that window timestamps are read from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Clock {
+    /// Seconds since boot, not counting suspend; the domain of the BPF
+    /// program's `bpf_ktime_get_ns` and of perf/ftrace timestamps.
+    Monotonic,
+    /// Seconds since boot including suspend (`bpf_ktime_get_boot_ns`, the
+    /// Perfetto default).
+    Boottime,
+    /// Wall-clock seconds since the Unix epoch.
+    #[default]
+    Realtime,
+}
+
+impl Clock {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Clock::Monotonic => "monotonic",
+            Clock::Boottime => "boottime",
+            Clock::Realtime => "realtime",
+        }
+    }
+
+    fn id(self) -> libc::clockid_t {
+        match self {
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+            Clock::Realtime => libc::CLOCK_REALTIME,
+        }
+    }
+
+    pub(crate) fn now_ns(self) -> i128 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // Cannot fail for these clock ids.
+        unsafe { libc::clock_gettime(self.id(), &mut ts) };
+        ts.tv_sec as i128 * 1_000_000_000 + ts.tv_nsec as i128
+    }
+
+    /// Seconds on this clock, truncated to whole microseconds so every
+    /// output carries the same resolution.
+    pub fn now_secs(self) -> f64 {
+        (self.now_ns() / 1_000) as f64 / 1_000_000.0
+    }
+
+    /// Seconds to add to a `bpf_ktime_get_ns` value (monotonic) to place it
+    /// on this clock.
+    pub fn ktime_offset_secs(self) -> f64 {
+        if self == Clock::Monotonic {
+            return 0.0;
+        }
+        let monotonic = Clock::Monotonic.now_ns();
+        (self.now_ns() - monotonic) as f64 / 1_000_000_000.0
+    }
+}
+
+/// Wall-clock seconds since the epoch; see [`Clock::now_secs`].
+pub fn now_secs() -> f64 {
+    Clock::Realtime.now_secs()
+}
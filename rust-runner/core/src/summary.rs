@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sketch::QuantileSketch;
+use crate::stats::{TaskSnapshot, jain_index, spearman};
+
+/// Tail of one task's per-window lateness or response time over a run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TailSummary {
+    /// Windows the figure was known in.
+    pub samples: u64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: f64,
+}
+
+impl TailSummary {
+    /// `None` for an empty sketch.
+    pub fn of(sketch: &QuantileSketch) -> Option<Self> {
+        Some(Self {
+            samples: sketch.count(),
+            p99_ms: sketch.quantile(0.99)?,
+            p999_ms: sketch.quantile(0.999)?,
+            max_ms: sketch.max()?,
+        })
+    }
+}
+
+/// Total tickets of the map over a run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketSupply {
+    pub first: u64,
+    pub last: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+// Fields missing from summaries written by older versions load as their
+// defaults.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskTotals {
+    pub pid: u32,
+    pub samples: u32,
+    pub runtime_ns: u64,
+    pub misses: u32,
+    /// Samples in which the task was flagged as starved.
+    pub starved: u32,
+    pub worst_lateness_ms: f64,
+    pub expected_share: f64,
+    pub achieved_share: f64,
+    pub share_error: f64,
+    /// Lateness over the windows the task was sampled in.
+    pub lateness_tail: TailSummary,
+    /// Response time over the windows it was measured in; `None` when it
+    /// never was.
+    pub response_tail: Option<TailSummary>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunSummary {
+    pub iterations: u32,
+    pub total_runtime_ns: u64,
+    pub total_misses: u32,
+    /// Tasks flagged as starved in at least one sample.
+    pub starved_tasks: usize,
+    pub worst_lateness_ms: f64,
+    pub fairness_index: f64,
+    pub mean_abs_share_error: f64,
+    /// Spearman correlation of expected and achieved share over the tasks
+    /// that ran; see [`PriorityReport`](crate::priority::PriorityReport).
+    pub priority_spearman: Option<f64>,
+    /// `None` when the caller did not record it.
+    pub ticket_supply: Option<TicketSupply>,
+    pub tasks: Vec<TaskTotals>,
+}
+
+/// Accumulates per-task totals across a dump run so a conclusion can be
+/// printed once the per-iteration tables have scrolled away.
+#[derive(Debug, Default)]
+pub struct SummaryBuilder {
+    iterations: u32,
+    tasks: HashMap<u32, TaskTotals>,
+    share_sums: HashMap<u32, f64>,
+    lateness: HashMap<u32, QuantileSketch>,
+    response: HashMap<u32, QuantileSketch>,
+    supply: Option<TicketSupply>,
+}
+
+impl SummaryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, snapshots: &[TaskSnapshot]) {
+        self.iterations += 1;
+        for snap in snapshots {
+            let totals = self.tasks.entry(snap.pid).or_insert_with(|| TaskTotals {
+                pid: snap.pid,
+                worst_lateness_ms: f64::NEG_INFINITY,
+                ..TaskTotals::default()
+            });
+            totals.samples += 1;
+            totals.runtime_ns = totals.runtime_ns.saturating_add(snap.runtime_delta_ns);
+            if snap.deadline_missed() {
+                totals.misses += 1;
+            }
+            totals.starved += u32::from(snap.starved);
+            totals.worst_lateness_ms = totals.worst_lateness_ms.max(snap.lateness_ms);
+            *self.share_sums.entry(snap.pid).or_insert(0.0) += snap.ticket_share;
+            self.lateness
+                .entry(snap.pid)
+                .or_default()
+                .record(snap.lateness_ms);
+            if let Some(response_ms) = snap.response_time_ms {
+                self.response
+                    .entry(snap.pid)
+                    .or_default()
+                    .record(response_ms);
+            }
+        }
+    }
+
+    /// Track the map's total tickets, once per window.
+    pub fn record_supply(&mut self, total_tickets: u64) {
+        let supply = self.supply.get_or_insert(TicketSupply {
+            first: total_tickets,
+            last: total_tickets,
+            min: total_tickets,
+            max: total_tickets,
+        });
+        supply.last = total_tickets;
+        supply.min = supply.min.min(total_tickets);
+        supply.max = supply.max.max(total_tickets);
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn finish(&self) -> RunSummary {
+        let total_runtime_ns: u64 = self.tasks.values().map(|t| t.runtime_ns).sum();
+        let mut tasks: Vec<TaskTotals> = self
+            .tasks
+            .values()
+            .map(|totals| {
+                let mut totals = totals.clone();
+                let share_sum = self.share_sums.get(&totals.pid).copied().unwrap_or(0.0);
+                totals.expected_share = share_sum / self.iterations.max(1) as f64;
+                totals.achieved_share = if total_runtime_ns == 0 {
+                    0.0
+                } else {
+                    totals.runtime_ns as f64 / total_runtime_ns as f64
+                };
+                totals.share_error = totals.achieved_share - totals.expected_share;
+                if let Some(tail) = self.lateness.get(&totals.pid).and_then(TailSummary::of) {
+                    totals.lateness_tail = tail;
+                }
+                totals.response_tail = self.response.get(&totals.pid).and_then(TailSummary::of);
+                totals
+            })
+            .collect();
+        tasks.sort_by(|a, b| b.runtime_ns.cmp(&a.runtime_ns).then(a.pid.cmp(&b.pid)));
+
+        let worst_lateness_ms = tasks
+            .iter()
+            .map(|t| t.worst_lateness_ms)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let mean_abs_share_error = if tasks.is_empty() {
+            0.0
+        } else {
+            tasks.iter().map(|t| t.share_error.abs()).sum::<f64>() / tasks.len() as f64
+        };
+
+        RunSummary {
+            iterations: self.iterations,
+            total_runtime_ns,
+            total_misses: tasks.iter().map(|t| t.misses).sum(),
+            starved_tasks: tasks.iter().filter(|t| t.starved > 0).count(),
+            worst_lateness_ms: if worst_lateness_ms.is_finite() {
+                worst_lateness_ms
+            } else {
+                0.0
+            },
+            fairness_index: fairness_index(&tasks),
+            mean_abs_share_error,
+            priority_spearman: spearman(
+                tasks
+                    .iter()
+                    .filter(|t| t.expected_share > 0.0 && t.runtime_ns > 0)
+                    .map(|t| (t.expected_share, t.achieved_share)),
+            ),
+            ticket_supply: self.supply,
+            tasks,
+        }
+    }
+}
+
+/// Jain's fairness index over achieved/expected share ratios: 1.0 means every
+/// task received exactly its ticket share, 1/n means one task got everything.
+pub fn fairness_index(tasks: &[TaskTotals]) -> f64 {
+    jain_index(
+        tasks
+            .iter()
+            .filter(|t| t.expected_share > 0.0)
+            .map(|t| t.achieved_share / t.expected_share),
+    )
+}
+
+impl RunSummary {
+    pub fn print(&self, top_n: usize) {
+        println!("\n=== Run summary ({} iterations) ===", self.iterations);
+        println!(
+            "Observed runtime: {:.3} ms  Deadline misses: {}  Worst lateness: {:.3} ms",
+            self.total_runtime_ns as f64 / 1_000_000.0,
+            self.total_misses,
+            self.worst_lateness_ms
+        );
+        println!(
+            "Fairness index (Jain, achieved/expected share): {:.4}  Mean |share error|: {:.2}%",
+            self.fairness_index,
+            self.mean_abs_share_error * 100.0
+        );
+        if let Some(supply) = self.ticket_supply {
+            println!(
+                "Ticket supply: {} at start, {} at end ({:+.1}%), {}-{} over the run",
+                supply.first,
+                supply.last,
+                (supply.last as f64 / supply.first.max(1) as f64 - 1.0) * 100.0,
+                supply.min,
+                supply.max
+            );
+        }
+        if let Some(spearman) = self.priority_spearman {
+            println!(
+                "Priority fit (Spearman, expected vs achieved share of tasks that ran): {spearman:.3}"
+            );
+        }
+        if self.starved_tasks > 0 {
+            let mut starved: Vec<&TaskTotals> =
+                self.tasks.iter().filter(|t| t.starved > 0).collect();
+            starved.sort_by(|a, b| b.starved.cmp(&a.starved).then(a.pid.cmp(&b.pid)));
+            let listed: Vec<String> = starved
+                .iter()
+                .take(top_n.max(1))
+                .map(|t| format!("{} for {} windows", t.pid, t.starved))
+                .collect();
+            println!(
+                "Starved tasks: {}  ({})",
+                self.starved_tasks,
+                listed.join(", ")
+            );
+        }
+        if self.tasks.is_empty() {
+            return;
+        }
+        let limit = self.tasks.len().min(top_n.max(1));
+        println!(
+            "{:<8} {:>12} {:>7} {:>11} {:>10} {:>10} {:>9}",
+            "PID", "RUNTIME_MS", "MISSES", "WORST_LAT", "EXP_SHR%", "ACH_SHR%", "ERR%"
+        );
+        for task in self.tasks.iter().take(limit) {
+            println!(
+                "{:<8} {:>12.3} {:>7} {:>11.3} {:>10.2} {:>10.2} {:>+9.2}",
+                task.pid,
+                task.runtime_ns as f64 / 1_000_000.0,
+                task.misses,
+                task.worst_lateness_ms,
+                task.expected_share * 100.0,
+                task.achieved_share * 100.0,
+                task.share_error * 100.0
+            );
+        }
+        self.print_tails(top_n);
+    }
+
+    /// Per-task lateness and response-time tails, the tasks with the worst
+    /// p99.9 lateness first.
+    pub fn print_tails(&self, top_n: usize) {
+        if self.tasks.is_empty() {
+            return;
+        }
+        let mut tasks: Vec<&TaskTotals> = self.tasks.iter().collect();
+        tasks.sort_by(|a, b| {
+            b.lateness_tail
+                .p999_ms
+                .total_cmp(&a.lateness_tail.p999_ms)
+                .then(a.pid.cmp(&b.pid))
+        });
+        println!("\nTail lateness per task (ms over the run's windows):");
+        println!(
+            "{:<8} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "PID",
+            "WINDOWS",
+            "MISSES",
+            "LATE_P99",
+            "LATE_P999",
+            "LATE_MAX",
+            "RESP_P99",
+            "RESP_P999",
+            "RESP_MAX"
+        );
+        let dash = || "-".to_string();
+        for task in tasks.iter().take(top_n.max(1)) {
+            let response = task.response_tail;
+            println!(
+                "{:<8} {:>7} {:>7} {:>10.3} {:>10.3} {:>10.3} {:>10} {:>10} {:>10}",
+                task.pid,
+                task.samples,
+                task.misses,
+                task.lateness_tail.p99_ms,
+                task.lateness_tail.p999_ms,
+                task.lateness_tail.max_ms,
+                response.map_or_else(dash, |tail| format!("{:.3}", tail.p99_ms)),
+                response.map_or_else(dash, |tail| format!("{:.3}", tail.p999_ms)),
+                response.map_or_else(dash, |tail| format!("{:.3}", tail.max_ms)),
+            );
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, data)
+    }
+
+    /// Reports written by [`write_json`](Self::write_json), or by a `dump`
+    /// of several maps (keyed by map label), in label order; a single-map
+    /// report comes back with an empty label.
+    pub fn read_json(path: &Path) -> io::Result<Vec<(String, RunSummary)>> {
+        let data = fs::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&data).map_err(io::Error::other)?;
+        if value.get("iterations").is_some() {
+            let report = serde_json::from_value(value).map_err(io::Error::other)?;
+            return Ok(vec![(String::new(), report)]);
+        }
+        let reports: std::collections::BTreeMap<String, RunSummary> =
+            serde_json::from_value(value).map_err(io::Error::other)?;
+        Ok(reports.into_iter().collect())
+    }
+}
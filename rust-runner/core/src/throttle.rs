@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::control;
+use crate::error::{Result, RunnerError};
+use crate::export::ChangeEvent;
+use crate::procfs::{parent_pid, start_time};
+use crate::stats::{TaskSnapshot, nice_to_tickets};
+use crate::stream::SnapshotStream;
+
+/// Period of the SIGSTOP/SIGCONT duty cycle and of the `cpu.max` limit.
+const CYCLE: Duration = Duration::from_millis(100);
+
+/// Correction applied to a task that stays over its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ThrottleAction {
+    /// Cut its tickets through the `ticket_override` map.
+    #[default]
+    Tickets,
+    /// Duty-cycle it with SIGSTOP/SIGCONT.
+    Stop,
+    /// Limit its cgroup's `cpu.max` bandwidth.
+    Cgroup,
+}
+
+impl fmt::Display for ThrottleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ThrottleAction::Tickets => "tickets",
+            ThrottleAction::Stop => "stop",
+            ThrottleAction::Cgroup => "cgroup",
+        })
+    }
+}
+
+/// Budget and correction of a [`Throttler`].
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleConfig {
+    /// Utilization (runtime per estimated period) a task may not exceed.
+    pub max_utilization: Option<f64>,
+    /// EDF lateness in milliseconds a task may not exceed.
+    pub max_lateness_ms: Option<f64>,
+    /// Consecutive windows over budget before acting, and under budget
+    /// before lifting the correction.
+    pub windows: u32,
+    pub action: ThrottleAction,
+    /// Fraction of its CPU a throttled task keeps: its nice-derived tickets,
+    /// the running part of the stop/continue cycle, or the `cpu.max` quota
+    /// (of one CPU).
+    pub duty: f64,
+}
+
+/// Whether a [`ThrottleEvent`] started or lifted a correction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleState {
+    Throttled,
+    Released,
+}
+
+/// One correction started or lifted in a window.
+#[derive(Debug, Clone)]
+pub struct ThrottleEvent {
+    pub pid: u32,
+    pub comm: Option<String>,
+    pub action: ThrottleAction,
+    pub state: ThrottleState,
+    /// Why the task was throttled (or that it is back within budget).
+    pub reason: String,
+    /// What was changed, e.g. `tickets 100 -> 25`.
+    pub detail: String,
+    /// `tickets`, or `duty` (percent of its CPU the task keeps) for the
+    /// stop and cgroup actions.
+    pub field: &'static str,
+    pub from: i64,
+    pub to: i64,
+}
+
+impl ThrottleEvent {
+    /// The event as exported with the window's other changes.
+    pub fn change_event(&self) -> ChangeEvent {
+        ChangeEvent {
+            pid: self.pid,
+            field: self.field,
+            from: self.from,
+            to: self.to,
+            source: "throttle",
+            reason: format!("{}: {}", self.reason, self.detail),
+        }
+    }
+}
+
+/// A cgroup whose `cpu.max` this throttler lowered.
+struct CgroupLimit {
+    previous: String,
+    pids: HashSet<u32>,
+}
+
+/// Watches each window for tasks over a utilization or lateness budget and,
+/// after [`ThrottleConfig::windows`] consecutive breaches, applies the
+/// configured [`ThrottleAction`]. The correction is lifted once the task
+/// stays within budget for as many windows, or when it exits.
+pub struct Throttler {
+    config: ThrottleConfig,
+    /// Consecutive windows over budget (unthrottled tasks) or within budget
+    /// (throttled tasks), by pid.
+    streaks: HashMap<u32, u32>,
+    /// Throttled pids and the cgroup they were limited through, if any.
+    throttled: HashMap<u32, Option<String>>,
+    cgroups: HashMap<String, CgroupLimit>,
+    cycler: Option<DutyCycler>,
+    /// This process and its ancestors, which are never throttled: stopping
+    /// the shell or the runner itself would stall the correction loop.
+    protected: HashSet<u32>,
+    /// Pids without a cgroup to limit, warned about once.
+    unlimitable: HashSet<u32>,
+}
+
+impl Throttler {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config: ThrottleConfig {
+                windows: config.windows.max(1),
+                duty: config.duty.clamp(0.01, 1.0),
+                ..config
+            },
+            streaks: HashMap::new(),
+            throttled: HashMap::new(),
+            cgroups: HashMap::new(),
+            cycler: None,
+            protected: std::iter::successors(Some(std::process::id()), |pid| {
+                parent_pid(*pid).filter(|parent| *parent != 0)
+            })
+            .collect(),
+            unlimitable: HashSet::new(),
+        }
+    }
+
+    /// Tickets a throttled task with `nice` keeps.
+    fn cut_tickets(&self, nice: i32) -> u32 {
+        ((nice_to_tickets(nice) as f64 * self.config.duty).round() as u32).max(1)
+    }
+
+    /// `(field, from, to)` of the [`ThrottleEvent`] for throttling `snap`
+    /// or, without `throttling`, releasing it.
+    fn change(&self, snap: &TaskSnapshot, throttling: bool) -> (&'static str, i64, i64) {
+        match self.config.action {
+            ThrottleAction::Tickets => {
+                let to = if throttling {
+                    self.cut_tickets(snap.info.nice)
+                } else {
+                    nice_to_tickets(snap.info.nice)
+                };
+                ("tickets", snap.info.tickets.into(), to.into())
+            }
+            ThrottleAction::Stop | ThrottleAction::Cgroup => {
+                let duty = (self.config.duty * 100.0).round() as i64;
+                if throttling {
+                    ("duty", 100, duty)
+                } else {
+                    ("duty", duty, 100)
+                }
+            }
+        }
+    }
+
+    fn breach(&self, snap: &TaskSnapshot) -> Option<String> {
+        if let Some(max) = self.config.max_utilization
+            && snap.utilization > max
+        {
+            return Some(format!("utilization {:.2} > {max:.2}", snap.utilization));
+        }
+        if let Some(max) = self.config.max_lateness_ms
+            && snap.lateness_ms > max
+        {
+            return Some(format!("lateness {:.3} ms > {max:.3} ms", snap.lateness_ms));
+        }
+        None
+    }
+
+    /// Update the streaks with one window of `stream` and start or lift
+    /// corrections accordingly.
+    pub fn step(
+        &mut self,
+        stream: &mut SnapshotStream,
+        snapshots: &[TaskSnapshot],
+    ) -> Result<Vec<ThrottleEvent>> {
+        let mut events = Vec::new();
+        let live: HashSet<u32> = snapshots.iter().map(|snap| snap.pid).collect();
+        let gone: Vec<u32> = self
+            .throttled
+            .keys()
+            .copied()
+            .filter(|pid| !live.contains(pid))
+            .collect();
+        // The tasks exited; only their bookkeeping (and cgroup limits) remain.
+        self.lift_all(stream, gone)?;
+        self.streaks.retain(|pid, _| live.contains(pid));
+        self.unlimitable.retain(|pid| live.contains(pid));
+
+        for snap in snapshots {
+            if self.protected.contains(&snap.meta.tgid.unwrap_or(snap.pid))
+                || self.protected.contains(&snap.pid)
+            {
+                continue;
+            }
+            let breach = self.breach(snap);
+            let throttled = self.throttled.contains_key(&snap.pid);
+            // Throttled tasks count windows within budget, others over it.
+            if breach.is_some() == throttled {
+                self.streaks.remove(&snap.pid);
+                continue;
+            }
+            let streak = self.streaks.entry(snap.pid).or_default();
+            *streak += 1;
+            if *streak < self.config.windows {
+                continue;
+            }
+            self.streaks.remove(&snap.pid);
+            let event = match breach {
+                Some(reason) => {
+                    let (field, from, to) = self.change(snap, true);
+                    let Some(detail) = self.throttle(stream, snap)? else {
+                        continue;
+                    };
+                    ThrottleEvent {
+                        pid: snap.pid,
+                        comm: snap.meta.comm.clone(),
+                        action: self.config.action,
+                        state: ThrottleState::Throttled,
+                        reason: format!("{reason} for {} windows", self.config.windows),
+                        detail,
+                        field,
+                        from,
+                        to,
+                    }
+                }
+                None => {
+                    let (field, from, to) = self.change(snap, false);
+                    let detail = self.lift(stream, snap.pid)?;
+                    ThrottleEvent {
+                        pid: snap.pid,
+                        comm: snap.meta.comm.clone(),
+                        action: self.config.action,
+                        state: ThrottleState::Released,
+                        reason: format!("within budget for {} windows", self.config.windows),
+                        detail,
+                        field,
+                        from,
+                        to,
+                    }
+                }
+            };
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Apply the correction to `snap`; `None` when it cannot be applied to
+    /// this task and was skipped.
+    fn throttle(
+        &mut self,
+        stream: &mut SnapshotStream,
+        snap: &TaskSnapshot,
+    ) -> Result<Option<String>> {
+        let duty = self.config.duty;
+        let (cgroup, detail) = match self.config.action {
+            ThrottleAction::Tickets => {
+                let tickets = self.cut_tickets(snap.info.nice);
+                stream.set_ticket_override(snap.pid, Some(tickets))?;
+                (None, format!("tickets {} -> {tickets}", snap.info.tickets))
+            }
+            ThrottleAction::Stop => {
+                self.cycler
+                    .get_or_insert_with(|| DutyCycler::start(duty))
+                    .add(snap.pid);
+                (
+                    None,
+                    format!("running {:.0}% of every {CYCLE:?}", duty * 100.0),
+                )
+            }
+            ThrottleAction::Cgroup => {
+                // The root cgroup has no cpu.max.
+                let Some(cgroup) = snap.meta.cgroup.clone().filter(|cgroup| cgroup != "/") else {
+                    if self.unlimitable.insert(snap.pid) {
+                        warn!(target: "map", pid = snap.pid, "not throttling: the task has no cgroup v2 path to limit");
+                    }
+                    return Ok(None);
+                };
+                let failed = |source| RunnerError::Control {
+                    target: format!("cgroup {cgroup}"),
+                    source,
+                };
+                let limit = format!(
+                    "{} {}",
+                    (CYCLE.as_micros() as f64 * duty).round() as u64,
+                    CYCLE.as_micros()
+                );
+                if !self.cgroups.contains_key(&cgroup) {
+                    let previous = match control::cgroup_cpu_max(&cgroup) {
+                        Ok(previous) => previous,
+                        Err(err) => {
+                            // Gone already, or no cpu controller there.
+                            if self.unlimitable.insert(snap.pid) {
+                                warn!(target: "map", pid = snap.pid, cgroup = %cgroup, "not throttling: cannot read cpu.max: {err}");
+                            }
+                            return Ok(None);
+                        }
+                    };
+                    control::set_cgroup_cpu_max(&cgroup, &limit).map_err(failed)?;
+                    self.cgroups.insert(
+                        cgroup.clone(),
+                        CgroupLimit {
+                            previous,
+                            pids: HashSet::new(),
+                        },
+                    );
+                }
+                let entry = self.cgroups.get_mut(&cgroup).expect("inserted above");
+                entry.pids.insert(snap.pid);
+                let detail = format!("cgroup {cgroup} cpu.max {} -> {limit}", entry.previous);
+                (Some(cgroup), detail)
+            }
+        };
+        debug!(target: "map", pid = snap.pid, action = %self.config.action, "task throttled");
+        self.throttled.insert(snap.pid, cgroup);
+        Ok(Some(detail))
+    }
+
+    fn lift(&mut self, stream: &mut SnapshotStream, pid: u32) -> Result<String> {
+        let Some(cgroup) = self.throttled.remove(&pid) else {
+            return Ok(String::new());
+        };
+        match self.config.action {
+            ThrottleAction::Tickets => {
+                stream.set_ticket_override(pid, None)?;
+                Ok("tickets back to nice-derived".to_string())
+            }
+            ThrottleAction::Stop => {
+                if let Some(cycler) = &self.cycler {
+                    cycler.remove(pid);
+                }
+                Ok("stop/continue cycle ended".to_string())
+            }
+            ThrottleAction::Cgroup => {
+                let Some(cgroup) = cgroup else {
+                    return Ok(String::new());
+                };
+                let Some(limit) = self.cgroups.get_mut(&cgroup) else {
+                    return Ok(String::new());
+                };
+                limit.pids.remove(&pid);
+                if !limit.pids.is_empty() {
+                    return Ok(format!("cgroup {cgroup} stays limited for other tasks"));
+                }
+                let limit = self.cgroups.remove(&cgroup).expect("looked up above");
+                match control::set_cgroup_cpu_max(&cgroup, &limit.previous) {
+                    Ok(()) => Ok(format!("cgroup {cgroup} cpu.max -> {}", limit.previous)),
+                    // The cgroup went away with its limit.
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        warn!(target: "map", cgroup = %cgroup, "cgroup is gone; nothing to restore");
+                        Ok(format!("cgroup {cgroup} is gone"))
+                    }
+                    Err(source) => Err(RunnerError::Control {
+                        target: format!("cgroup {cgroup}"),
+                        source,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Lift the corrections of `pids`, trying every one of them even when
+    /// some fail; the first failure is returned once all were tried.
+    fn lift_all(&mut self, stream: &mut SnapshotStream, pids: Vec<u32>) -> Result<()> {
+        let mut first = None;
+        for pid in pids {
+            if let Err(err) = self.lift(stream, pid) {
+                warn!(target: "map", pid, "cannot lift throttle: {err}");
+                first.get_or_insert(err);
+            }
+        }
+        first.map_or(Ok(()), Err)
+    }
+
+    /// Lift every correction still in place.
+    pub fn release(&mut self, stream: &mut SnapshotStream) -> Result<()> {
+        let pids: Vec<u32> = self.throttled.keys().copied().collect();
+        let lifted = self.lift_all(stream, pids);
+        // Joining the cycler continues anything it stopped last.
+        self.cycler = None;
+        lifted
+    }
+}
+
+/// Background thread stopping a set of pids for part of every [`CYCLE`].
+/// Each pid is kept with its start time, so a pid reused after the task
+/// exited is left alone.
+struct DutyCycler {
+    pids: Arc<Mutex<HashMap<u32, Option<u64>>>>,
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DutyCycler {
+    fn start(duty: f64) -> Self {
+        let pids = Arc::new(Mutex::new(HashMap::new()));
+        let done = Arc::new(AtomicBool::new(false));
+        let running = CYCLE.mul_f64(duty);
+        let stopped = CYCLE - running;
+        let thread = {
+            let (pids, done) = (Arc::clone(&pids), Arc::clone(&done));
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    let current: Vec<(u32, Option<u64>)> = pids
+                        .lock()
+                        .map(|pids| pids.iter().map(|(pid, start)| (*pid, *start)).collect())
+                        .unwrap_or_default();
+                    let mut stopped_pids = Vec::with_capacity(current.len());
+                    for (pid, start) in current {
+                        if start_time(pid) != start {
+                            debug!(target: "map", pid, "not stopping: the pid was reused or exited");
+                            continue;
+                        }
+                        match control::signal(pid, libc::SIGSTOP) {
+                            Ok(()) => stopped_pids.push(pid),
+                            Err(err) => debug!(target: "map", pid, "cannot stop task: {err}"),
+                        }
+                    }
+                    thread::sleep(stopped);
+                    // Always continue exactly what was stopped, even if the
+                    // pid was removed meanwhile.
+                    for &pid in &stopped_pids {
+                        if let Err(err) = control::signal(pid, libc::SIGCONT) {
+                            debug!(target: "map", pid, "cannot continue task: {err}");
+                        }
+                    }
+                    thread::sleep(running);
+                }
+            })
+        };
+        Self {
+            pids,
+            done,
+            thread: Some(thread),
+        }
+    }
+
+    fn add(&self, pid: u32) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.insert(pid, start_time(pid));
+        }
+    }
+
+    fn remove(&self, pid: u32) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.remove(&pid);
+        }
+    }
+}
+
+impl Drop for DutyCycler {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!(target: "map", "duty cycler panicked; stopped tasks may need SIGCONT");
+        }
+    }
+}
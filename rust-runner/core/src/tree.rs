@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::group::{GroupBy, GroupSnapshot, aggregate};
+use crate::stats::TaskSnapshot;
+
+/// One process (thread group) in the parent/child tree.
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    pub tgid: u32,
+    pub name: String,
+    pub own: GroupSnapshot,
+    pub subtree_tasks: usize,
+    pub subtree_runtime_delta_ns: u64,
+    pub subtree_share: f64,
+    pub subtree_overdue: usize,
+    pub children: Vec<usize>,
+}
+
+impl TreeNode {
+    pub fn subtree_runtime_delta_ms(&self) -> f64 {
+        self.subtree_runtime_delta_ns as f64 / 1_000_000.0
+    }
+}
+
+/// Parent/child tree of the observed processes. Processes whose parent is not
+/// in the map become roots.
+#[derive(Debug, Default)]
+pub struct ProcessTree {
+    nodes: Vec<TreeNode>,
+    roots: Vec<usize>,
+}
+
+impl ProcessTree {
+    pub fn build(snapshots: &[TaskSnapshot], window_ms: f64) -> Self {
+        let mut parents: HashMap<u32, u32> = HashMap::new();
+        let mut names: HashMap<u32, String> = HashMap::new();
+        for snap in snapshots {
+            let tgid = snap.meta.tgid.unwrap_or(snap.pid);
+            if let Some(ppid) = snap.meta.ppid {
+                parents.entry(tgid).or_insert(ppid);
+            }
+            if snap.pid == tgid || !names.contains_key(&tgid) {
+                let name = snap.meta.comm.clone().unwrap_or_else(|| tgid.to_string());
+                names.insert(tgid, name);
+            }
+        }
+
+        let mut nodes: Vec<TreeNode> = aggregate(snapshots, GroupBy::Tgid, window_ms)
+            .into_iter()
+            .map(|group| {
+                let tgid = group.key.parse().unwrap_or_default();
+                TreeNode {
+                    tgid,
+                    name: names.remove(&tgid).unwrap_or_else(|| group.key.clone()),
+                    subtree_tasks: group.tasks,
+                    subtree_runtime_delta_ns: group.runtime_delta_ns,
+                    subtree_share: group.ticket_share,
+                    subtree_overdue: group.overdue,
+                    own: group,
+                    children: Vec::new(),
+                }
+            })
+            .collect();
+        let index: HashMap<u32, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (node.tgid, idx))
+            .collect();
+
+        let mut roots = Vec::new();
+        for idx in 0..nodes.len() {
+            let parent = parents
+                .get(&nodes[idx].tgid)
+                .and_then(|ppid| index.get(ppid))
+                .copied()
+                .filter(|&parent| parent != idx);
+            match parent {
+                Some(parent) => nodes[parent].children.push(idx),
+                None => roots.push(idx),
+            }
+        }
+
+        let mut tree = Self { nodes, roots };
+        let mut visited = HashSet::new();
+        for root in tree.roots.clone() {
+            tree.accumulate(root, &mut visited);
+        }
+        tree.sort();
+        tree
+    }
+
+    fn accumulate(&mut self, idx: usize, visited: &mut HashSet<usize>) {
+        if !visited.insert(idx) {
+            return;
+        }
+        for child in self.nodes[idx].children.clone() {
+            self.accumulate(child, visited);
+            let (tasks, runtime, share, overdue) = {
+                let c = &self.nodes[child];
+                (
+                    c.subtree_tasks,
+                    c.subtree_runtime_delta_ns,
+                    c.subtree_share,
+                    c.subtree_overdue,
+                )
+            };
+            let node = &mut self.nodes[idx];
+            node.subtree_tasks += tasks;
+            node.subtree_runtime_delta_ns = node.subtree_runtime_delta_ns.saturating_add(runtime);
+            node.subtree_share += share;
+            node.subtree_overdue += overdue;
+        }
+    }
+
+    fn sort(&mut self) {
+        let runtime: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|n| n.subtree_runtime_delta_ns)
+            .collect();
+        let by_runtime = |idx: &usize| std::cmp::Reverse(runtime[*idx]);
+        self.roots.sort_by_key(by_runtime);
+        for node in self.nodes.iter_mut() {
+            node.children.sort_by_key(by_runtime);
+        }
+    }
+
+    /// Depth-first rows, skipping the descendants of collapsed processes.
+    pub fn visible_rows(&self, collapsed: &HashSet<u32>) -> Vec<(usize, &TreeNode)> {
+        let mut rows = Vec::new();
+        let mut stack: Vec<(usize, usize)> = self.roots.iter().rev().map(|&r| (0, r)).collect();
+        let mut visited = HashSet::new();
+        while let Some((depth, idx)) = stack.pop() {
+            if !visited.insert(idx) {
+                continue;
+            }
+            let node = &self.nodes[idx];
+            rows.push((depth, node));
+            if !collapsed.contains(&node.tgid) {
+                stack.extend(node.children.iter().rev().map(|&c| (depth + 1, c)));
+            }
+        }
+        rows
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Result, RunnerError};
+use crate::stats::{TaskSnapshot, apply_class_aware_shares};
+
+/// Hypothetical ticket counts by pid, applied to a window without writing
+/// anything to the map.
+#[derive(Debug, Clone, Default)]
+pub struct WhatIf {
+    pub overrides: HashMap<u32, u32>,
+}
+
+/// Projected effect of a [`WhatIf`] on one task.
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub pid: u32,
+    pub comm: Option<String>,
+    pub tickets_before: u32,
+    pub tickets_after: u32,
+    pub share_before: f64,
+    pub share_after: f64,
+}
+
+impl Projection {
+    pub fn share_change(&self) -> f64 {
+        self.share_after - self.share_before
+    }
+}
+
+impl WhatIf {
+    /// Parse `pid=tickets` pairs separated by commas, whitespace, or
+    /// newlines; `#` starts a comment.
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut overrides = HashMap::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            for pair in line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|pair| !pair.is_empty())
+            {
+                let (pid, tickets) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected pid=tickets, got `{pair}`"))?;
+                let pid: u32 = pid
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid pid in `{pair}`"))?;
+                let tickets: u32 = tickets
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid ticket count in `{pair}`"))?;
+                if tickets == 0 {
+                    return Err(format!("`{pair}` assigns 0 tickets"));
+                }
+                overrides.insert(pid, tickets);
+            }
+        }
+        if overrides.is_empty() {
+            return Err("no pid=tickets pairs given".to_string());
+        }
+        Ok(Self { overrides })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))?;
+        Self::parse(&text).map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))
+    }
+
+    /// `snapshots` with the hypothetical tickets applied and shares
+    /// recomputed the way the sampler does (RT time excluded).
+    pub fn apply(&self, snapshots: &[TaskSnapshot]) -> Vec<TaskSnapshot> {
+        let mut projected = snapshots.to_vec();
+        for snap in &mut projected {
+            if let Some(&tickets) = self.overrides.get(&snap.pid) {
+                snap.info.tickets = tickets;
+            }
+        }
+        apply_class_aware_shares(&mut projected);
+        projected
+    }
+
+    /// Share before and after for every task of `population`, largest change
+    /// first. Both shares are recomputed over `population`, so pass every
+    /// task of the map rather than a selection: the sampler scales a
+    /// selection's shares to the whole map, which a subset alone cannot
+    /// reproduce.
+    pub fn project(&self, population: &[TaskSnapshot]) -> Vec<Projection> {
+        let mut before = population.to_vec();
+        apply_class_aware_shares(&mut before);
+        let mut projections: Vec<Projection> = before
+            .iter()
+            .zip(self.apply(population))
+            .map(|(before, after)| Projection {
+                pid: before.pid,
+                comm: before.meta.comm.clone(),
+                tickets_before: before.info.tickets,
+                tickets_after: after.info.tickets,
+                share_before: before.ticket_share,
+                share_after: after.ticket_share,
+            })
+            .collect();
+        projections.sort_by(|a, b| {
+            b.share_change()
+                .abs()
+                .total_cmp(&a.share_change().abs())
+                .then(a.pid.cmp(&b.pid))
+        });
+        projections
+    }
+
+    /// Overridden pids that are not in `snapshots`.
+    pub fn missing(&self, snapshots: &[TaskSnapshot]) -> Vec<u32> {
+        let mut missing: Vec<u32> = self
+            .overrides
+            .keys()
+            .copied()
+            .filter(|pid| !snapshots.iter().any(|snap| snap.pid == *pid))
+            .collect();
+        missing.sort_unstable();
+        missing
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::cgroup::CGROUP_PIN_NAME;
+use neo_ebpf_core::events::SchedEvent;
+use neo_ebpf_core::latency::LATENCY_PIN_NAME;
+use neo_ebpf_core::loader::{self, AttachConfig};
+
+/// File name of the scheduler's BPF object, also its label in logs.
+const OBJECT_NAME: &str = "sched_lottery.bpf.o";
+
+/// The object `build.rs` compiled with the `embed-bpf` feature.
+#[cfg(feature = "embed-bpf")]
+const EMBEDDED_OBJECT: Option<&[u8]> = Some(include_bytes!(concat!(
+    env!("OUT_DIR"),
+    "/sched_lottery.bpf.o"
+)));
+#[cfg(not(feature = "embed-bpf"))]
+const EMBEDDED_OBJECT: Option<&[u8]> = None;
+
+#[derive(Args, Clone)]
+pub struct AttachArgs {
+    /// BPF object to load instead of the one embedded at build time
+    #[arg(long, value_name = "PATH")]
+    obj: Option<PathBuf>,
+
+    /// Where to pin the task map
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map_pin: PathBuf,
+
+    /// Where to pin the ticket_override map
+    #[arg(long, default_value = "/sys/fs/bpf/ticket_override")]
+    override_pin: PathBuf,
+
+    /// Where to pin the task_latency map; defaults to next to --map-pin,
+    /// where dump and tui look for it
+    #[arg(long)]
+    latency_pin: Option<PathBuf>,
+
+    /// Where to pin the cgroup_map; defaults to next to --map-pin, where
+    /// dump and tui --cgroups look for it
+    #[arg(long)]
+    cgroup_pin: Option<PathBuf>,
+
+    /// Where to pin the sched_switch program
+    #[arg(long, default_value = "/sys/fs/bpf/sched_lottery")]
+    prog_pin: PathBuf,
+
+    /// Where to pin the sched_switch link; defaults to `<prog-pin>_link`,
+    /// with the wakeup and event links beside it
+    #[arg(long)]
+    link_pin: Option<PathBuf>,
+
+    /// Tracepoint (category:name) the sched_switch program runs on
+    #[arg(long, default_value = "sched:sched_switch")]
+    trace: String,
+
+    /// Kernel BTF for CO-RE relocations on kernels without
+    /// /sys/kernel/btf/vmlinux
+    #[arg(long, value_name = "PATH")]
+    btf: Option<PathBuf>,
+
+    /// Leave ticket_override unpinned (no --policy or --rebalance then)
+    #[arg(long)]
+    no_override: bool,
+
+    /// Neither pin task_latency nor attach the wakeup programs
+    #[arg(long)]
+    no_latency: bool,
+
+    /// Leave cgroup_map unpinned (no --cgroups then)
+    #[arg(long, conflicts_with = "cgroup_pin")]
+    no_cgroups: bool,
+
+    /// Scheduler events to count besides sched_switch (none by default;
+    /// sched_waking and sched_migrate_task fire often); each one's map is
+    /// pinned next to --map-pin as `<event>_events`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    events: Vec<SchedEvent>,
+
+    /// Octal mode of the pinned task_map, task_latency, cgroup_map, and
+    /// event maps, so dump and tui can read them without root (600 keeps
+    /// them root-only); a mode readable by others also makes the task
+    /// map's directory 755
+    #[arg(long, default_value = "644", value_parser = parse_mode)]
+    map_mode: u32,
+
+    /// Write the embedded object to PATH and exit, e.g. for
+    /// loader/sched_loader or llvm-objdump
+    #[arg(long, value_name = "PATH", conflicts_with = "obj")]
+    write_object: Option<PathBuf>,
+}
+
+fn parse_mode(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("`{value}` is not an octal file mode"))
+}
+
+fn embedded() -> Result<&'static [u8], RunnerError> {
+    EMBEDDED_OBJECT.ok_or_else(|| {
+        RunnerError::Config(
+            "this build embeds no BPF object; pass --obj PATH or rebuild with --features embed-bpf"
+                .to_string(),
+        )
+    })
+}
+
+/// Load the scheduler's BPF object (embedded, or `--obj`), pin its maps and
+/// program, and attach it, so a single binary replaces `bpf/` and
+/// `loader/` on the target host.
+pub fn run_attach(args: AttachArgs) -> Result<(), RunnerError> {
+    if let Some(path) = &args.write_object {
+        fs::write(path, embedded()?)?;
+        println!("Wrote {OBJECT_NAME} to {}", path.display());
+        return Ok(());
+    }
+    let (object, label) = match &args.obj {
+        Some(path) => (
+            fs::read(path).map_err(|err| {
+                RunnerError::Config(format!("cannot read {}: {err}", path.display()))
+            })?,
+            path.file_name()
+                .map_or(OBJECT_NAME.into(), |name| name.to_string_lossy()),
+        ),
+        None => (embedded()?.to_vec(), OBJECT_NAME.into()),
+    };
+    let beside_map = |name| args.map_pin.parent().unwrap_or(Path::new("/")).join(name);
+    let latency_pin = args
+        .latency_pin
+        .clone()
+        .unwrap_or_else(|| beside_map(LATENCY_PIN_NAME));
+    let cgroup_pin = args
+        .cgroup_pin
+        .clone()
+        .unwrap_or_else(|| beside_map(CGROUP_PIN_NAME));
+    let config = AttachConfig {
+        link_pin: args.link_pin.clone().unwrap_or_else(|| {
+            let mut link = args.prog_pin.clone().into_os_string();
+            link.push("_link");
+            link.into()
+        }),
+        prog_pin: args.prog_pin.clone(),
+        map_pin: args.map_pin.clone(),
+        override_pin: (!args.no_override).then(|| args.override_pin.clone()),
+        latency_pin: (!args.no_latency).then_some(latency_pin),
+        cgroup_pin: (!args.no_cgroups).then_some(cgroup_pin),
+        events: args.events.clone(),
+        trace_point: args.trace.clone(),
+        btf_path: args.btf.clone(),
+    };
+    loader::attach(&object, &label, &config)?;
+
+    let event_pins = config
+        .events
+        .iter()
+        .map(|event| config.map_pin.with_file_name(event.map_name()));
+    for path in std::iter::once(config.map_pin.clone())
+        .chain(config.latency_pin.clone())
+        .chain(config.cgroup_pin.clone())
+        .chain(event_pins)
+    {
+        fs::set_permissions(&path, fs::Permissions::from_mode(args.map_mode))?;
+    }
+    // bpffs mounts 0700; open the directory like scripts/run.sh does when
+    // the maps are meant to be read by others.
+    if args.map_mode & 0o044 != 0
+        && let Some(dir) = config.map_pin.parent()
+    {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o755))?;
+    }
+    println!(
+        "Attached {label} to {}: task_map at {}, program at {}, link at {}",
+        config.trace_point,
+        config.map_pin.display(),
+        config.prog_pin.display(),
+        config.link_pin.display()
+    );
+    Ok(())
+}
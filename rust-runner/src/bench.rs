@@ -0,0 +1,178 @@
+#[cfg(feature = "count-allocs")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::bpf_map::{
+    MappedArray, ValueLayout, create_task_array, create_task_map, iterate_task_info, map_info,
+    syscall_count, update_task_info,
+};
+use neo_ebpf_core::stats::{RollingStats, TaskInfo, enrich_into, nice_to_tickets};
+
+/// Thin wrapper around the system allocator that counts allocations so the
+/// bench can report per-tick churn without an external profiler. Only built
+/// with the `count-allocs` feature, since it counts for every subcommand.
+#[cfg(feature = "count-allocs")]
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "count-allocs")]
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[cfg(feature = "count-allocs")]
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[derive(Clone, Copy)]
+struct Counters {
+    wall: Instant,
+    cpu: Duration,
+    syscalls: u64,
+    allocations: u64,
+    bytes: u64,
+}
+
+impl Counters {
+    fn now() -> Self {
+        Self {
+            wall: Instant::now(),
+            cpu: process_cpu_time(),
+            syscalls: syscall_count(),
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn process_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+    let to_duration =
+        |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1_000);
+    to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+}
+
+fn populate(map_fd: &OwnedFd, size: u32, tick: u64) -> Result<(), RunnerError> {
+    for pid in 1..=size {
+        let nice = (pid % 40) as i32 - 20;
+        let info = TaskInfo {
+            runtime_ns: (pid as u64 + tick) * 1_000_000,
+            switches: pid as u64 + tick,
+            nice,
+            tickets: nice_to_tickets(nice),
+            last_switch_in_ts: tick,
+            preemptions: (pid as u64 + tick) / 2,
+        };
+        update_task_info(map_fd.as_raw_fd(), pid, &info)?;
+    }
+    Ok(())
+}
+
+/// Layout of the synthetic task map.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchMap {
+    /// A hash map read with one get-next-key and one lookup call per entry.
+    Hash,
+    /// An mmapable array map read straight from memory.
+    MmapArray,
+}
+
+pub fn run_bench(sizes: &[u32], ticks: u32, kind: BenchMap) -> Result<(), RunnerError> {
+    let ticks = ticks.max(1);
+    let label = match kind {
+        BenchMap::Hash => "hash map",
+        BenchMap::MmapArray => "mmapped array map",
+    };
+    println!("Observer overhead per sampling tick ({ticks} ticks per map size, synthetic {label})");
+    println!(
+        "{:>8} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "ENTRIES", "WALL_US", "CPU_US", "SYSCALLS", "ALLOCS", "ALLOC_KB"
+    );
+
+    for &size in sizes {
+        let fd = match kind {
+            BenchMap::Hash => create_task_map(size)?,
+            BenchMap::MmapArray => create_task_array(size)?,
+        };
+        let map_fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mapped = match kind {
+            BenchMap::Hash => None,
+            BenchMap::MmapArray => Some(MappedArray::map(
+                map_fd.as_raw_fd(),
+                &map_info(map_fd.as_raw_fd())?,
+                ValueLayout::Current,
+            )?),
+        };
+        populate(&map_fd, size, 0)?;
+        let mut rolling = RollingStats::new(0.5);
+        let mut snapshots = Vec::new();
+
+        let mut wall = Duration::ZERO;
+        let mut cpu = Duration::ZERO;
+        let (mut syscalls, mut allocations, mut bytes) = (0_u64, 0_u64, 0_u64);
+        for tick in 0..ticks {
+            // Refresh values between ticks so deltas are non-zero, but keep
+            // the writes out of the measured window.
+            populate(&map_fd, size, tick as u64 + 1)?;
+
+            let start = Counters::now();
+            let entries = match &mapped {
+                Some(mapped) => mapped.read(),
+                None => iterate_task_info(map_fd.as_raw_fd())?,
+            };
+            enrich_into(&entries, &mut rolling, 1000.0, &mut snapshots);
+            std::hint::black_box(&snapshots);
+            let end = Counters::now();
+
+            wall += end.wall - start.wall;
+            cpu += end.cpu.saturating_sub(start.cpu);
+            syscalls += end.syscalls - start.syscalls;
+            allocations += end.allocations - start.allocations;
+            bytes += end.bytes - start.bytes;
+        }
+
+        let per_tick = ticks as f64;
+        let (allocations, kib) = if cfg!(feature = "count-allocs") {
+            (
+                format!("{:.1}", allocations as f64 / per_tick),
+                format!("{:.1}", bytes as f64 / 1024.0 / per_tick),
+            )
+        } else {
+            ("-".to_string(), "-".to_string())
+        };
+        println!(
+            "{:>8} {:>12.1} {:>12.1} {:>12.1} {:>12} {:>12}",
+            size,
+            wall.as_secs_f64() * 1e6 / per_tick,
+            cpu.as_secs_f64() * 1e6 / per_tick,
+            syscalls as f64 / per_tick,
+            allocations,
+            kib
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use std::fmt::{Display, Write as _};
+use std::thread;
+use std::time::Duration;
+
+use clap::Args;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::stream::SnapshotStream;
+use neo_ebpf_core::summary::SummaryBuilder;
+
+use crate::demo::DemoArgs;
+use crate::parse_duration;
+
+#[derive(Args, Clone)]
+pub struct CheckArgs {
+    /// Path to the pinned task map
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map: String,
+
+    /// Length of one sampling window, e.g. `1s`, `500ms`
+    #[arg(long, value_parser = parse_duration, default_value = "1s")]
+    window: Duration,
+
+    /// Windows to evaluate; the worst one decides
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    windows: u32,
+
+    /// EWMA smoothing factor for rolling runtime (0-1)
+    #[arg(long, default_value_t = 0.5)]
+    alpha: f64,
+
+    /// WARNING when the worst lateness exceeds this many milliseconds
+    #[arg(long, value_name = "MS")]
+    warn_lateness: Option<f64>,
+
+    /// CRITICAL when the worst lateness exceeds this many milliseconds
+    #[arg(long, value_name = "MS")]
+    crit_lateness: Option<f64>,
+
+    /// WARNING when more than this many tasks are overdue in a window
+    #[arg(long, value_name = "N")]
+    warn_overdue: Option<usize>,
+
+    /// CRITICAL when more than this many tasks are overdue in a window
+    #[arg(long, value_name = "N")]
+    crit_overdue: Option<usize>,
+
+    /// WARNING when the Jain fairness index falls below this (0-1)
+    #[arg(long, value_name = "INDEX")]
+    warn_fairness: Option<f64>,
+
+    /// CRITICAL when the Jain fairness index falls below this (0-1)
+    #[arg(long, value_name = "INDEX")]
+    crit_fairness: Option<f64>,
+
+    #[command(flatten)]
+    demo: DemoArgs,
+}
+
+/// Nagios plugin states, in order of severity; the value is the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// The state of `value` against a warning and a critical threshold;
+    /// `breached(value, threshold)` says whether a threshold is crossed.
+    fn of<T: Copy>(
+        value: T,
+        warn: Option<T>,
+        crit: Option<T>,
+        breached: fn(T, T) -> bool,
+    ) -> Status {
+        if crit.is_some_and(|crit| breached(value, crit)) {
+            Status::Critical
+        } else if warn.is_some_and(|warn| breached(value, warn)) {
+            Status::Warning
+        } else {
+            Status::Ok
+        }
+    }
+}
+
+/// Sample the map, print one Nagios/Icinga status line with perfdata, and
+/// exit with the plugin's status code (3 when the map cannot be read).
+pub fn run_check(args: CheckArgs) -> ! {
+    let (status, line) = match check(&args) {
+        Ok(result) => result,
+        Err(err) => (Status::Unknown, err.to_string()),
+    };
+    println!("NEO-EBPF {} - {line}", status.label());
+    std::process::exit(status as i32)
+}
+
+fn check(args: &CheckArgs) -> Result<(Status, String), RunnerError> {
+    let mut stream = match args.demo.to_config() {
+        Some(config) => SnapshotStream::demo(&config, args.window, args.alpha),
+        None => SnapshotStream::open(&args.map, args.window, args.alpha)?,
+    };
+    // The first read only sets the baseline the window deltas start from.
+    stream.sample()?;
+    let mut summary = SummaryBuilder::new();
+    let mut tasks = 0;
+    let mut overdue = 0;
+    for _ in 0..args.windows {
+        thread::sleep(args.window);
+        let window = stream.sample()?;
+        summary.record(&window.snapshots);
+        tasks = tasks.max(window.snapshots.len());
+        overdue = overdue.max(
+            window
+                .snapshots
+                .iter()
+                .filter(|snap| snap.deadline_missed())
+                .count(),
+        );
+    }
+    let report = summary.finish();
+    // An idle map has no achieved shares to judge.
+    let fairness = (report.total_runtime_ns > 0).then_some(report.fairness_index);
+
+    let lateness_status = Status::of(
+        report.worst_lateness_ms,
+        args.warn_lateness,
+        args.crit_lateness,
+        |value, threshold| value > threshold,
+    );
+    let overdue_status = Status::of(
+        overdue,
+        args.warn_overdue,
+        args.crit_overdue,
+        |value, threshold| value > threshold,
+    );
+    let fairness_status = fairness.map_or(Status::Ok, |fairness| {
+        Status::of(
+            fairness,
+            args.warn_fairness,
+            args.crit_fairness,
+            |value, threshold| value < threshold,
+        )
+    });
+    let status = lateness_status.max(overdue_status).max(fairness_status);
+
+    let mut line = format!(
+        "worst lateness {:.3} ms, {overdue} of {tasks} tasks overdue, fairness {}",
+        report.worst_lateness_ms,
+        fairness.map_or_else(|| "n/a (idle)".to_string(), |index| format!("{index:.4}"))
+    );
+    let _ = write!(
+        line,
+        " | worst_lateness={:.3}ms;{};{};0 overdue={overdue};{};{};0;{tasks} tasks={tasks};;;0",
+        report.worst_lateness_ms,
+        range(args.warn_lateness, ""),
+        range(args.crit_lateness, ""),
+        range(args.warn_overdue, ""),
+        range(args.crit_overdue, ""),
+    );
+    if let Some(fairness) = fairness {
+        // A Nagios range `0.8:` alerts below 0.8.
+        let _ = write!(
+            line,
+            " fairness={fairness:.4};{};{};0;1",
+            range(args.warn_fairness, ":"),
+            range(args.crit_fairness, ":"),
+        );
+    }
+    Ok((status, line))
+}
+
+/// A perfdata threshold field; empty when the threshold is not set.
+fn range<T: Display>(threshold: Option<T>, suffix: &str) -> String {
+    threshold.map_or_else(String::new, |value| format!("{value}{suffix}"))
+}
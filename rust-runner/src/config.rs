@@ -0,0 +1,138 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use neo_ebpf_core::RunnerError;
+use toml::{Table, Value};
+
+/// `$XDG_CONFIG_HOME/neo-ebpf/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("neo-ebpf").join("config.toml"))
+}
+
+/// Load the explicit `--config` file, or the default one when it exists.
+pub fn load(explicit: Option<&Path>) -> Result<Option<Table>, RunnerError> {
+    let path = match explicit {
+        Some(path) => path.to_path_buf(),
+        None => match default_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(None),
+        },
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|err| RunnerError::Config(format!("failed to read {}: {err}", path.display())))?;
+    let table = text
+        .parse::<Table>()
+        .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))?;
+    Ok(Some(table))
+}
+
+/// Translate the config section for the invoked subcommand into extra
+/// command-line arguments. Options already given on the command line are
+/// skipped; see [`splice_args`] for how the rest are merged.
+pub fn extra_args(
+    config: &Table,
+    cli: &Command,
+    matches: &ArgMatches,
+) -> Result<Vec<OsString>, RunnerError> {
+    for name in config.keys() {
+        if cli.find_subcommand(name).is_none() {
+            return Err(RunnerError::Config(format!(
+                "unknown config section [{name}]"
+            )));
+        }
+    }
+    let Some((name, sub_matches)) = matches.subcommand() else {
+        return Ok(Vec::new());
+    };
+    let Some(section) = config.get(name) else {
+        return Ok(Vec::new());
+    };
+    let Value::Table(section) = section else {
+        return Err(RunnerError::Config(format!(
+            "config entry `{name}` must be a [{name}] table"
+        )));
+    };
+    let Some(command) = cli.find_subcommand(name) else {
+        return Ok(Vec::new());
+    };
+
+    let mut args = Vec::new();
+    for (key, value) in section {
+        let normalized = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == normalized.as_str() && arg.get_long().is_some())
+            .ok_or_else(|| {
+                RunnerError::Config(format!("unknown option `{key}` in config section [{name}]"))
+            })?;
+        if sub_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or_default());
+        if matches!(arg.get_action(), ArgAction::SetTrue) {
+            match value {
+                Value::Boolean(true) => args.push(flag.into()),
+                Value::Boolean(false) => {}
+                _ => {
+                    return Err(RunnerError::Config(format!(
+                        "config option `{key}` in [{name}] must be a boolean"
+                    )));
+                }
+            }
+            continue;
+        }
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        for value in values {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Integer(number) => number.to_string(),
+                Value::Float(number) => number.to_string(),
+                Value::Boolean(flag) => flag.to_string(),
+                _ => {
+                    return Err(RunnerError::Config(format!(
+                        "config option `{key}` in [{name}] has an unsupported type"
+                    )));
+                }
+            };
+            args.push(format!("{flag}={text}").into());
+        }
+    }
+    Ok(args)
+}
+
+/// Insert `extra` right after the subcommand name so clap still validates the
+/// config values, and flags typed on the command line come later and win
+/// (including `overrides_with` pairs such as `--include-kthreads`).
+pub fn splice_args(argv: Vec<OsString>, subcommand: &str, extra: Vec<OsString>) -> Vec<OsString> {
+    let mut position = None;
+    let mut skip_value = false;
+    for (index, arg) in argv.iter().enumerate().skip(1) {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        if arg == "--config" {
+            skip_value = true;
+        } else if arg == subcommand {
+            position = Some(index + 1);
+            break;
+        }
+    }
+    let Some(position) = position else {
+        return argv;
+    };
+    let mut merged = argv;
+    merged.splice(position..position, extra);
+    merged
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+use crate::filter::{CpuList, FilterArgs, parse_cpu_arg};
+use crate::parse_duration;
+
+const HELP: &str = "commands: interval <duration> | alpha <0-1> | filter [user <name|->] \
+                    [cpu <list|->] [kthreads <on|off>] [min-delta <ms>] [min-share <percent>] | \
+                    snapshot | gc | status | help";
+
+/// A command received on the `--control-socket`.
+#[derive(Debug)]
+pub enum ControlCommand {
+    /// Sample at a new interval from now on.
+    Interval(Duration),
+    /// Smooth rolling runtime and period with a new EWMA factor.
+    Alpha(f64),
+    /// Change filters (an empty list only reports them).
+    Filter(Vec<FilterChange>),
+    /// Sample and report a window right away.
+    Snapshot,
+    /// Release cached per-task state (see `SnapshotStream::collect_garbage`).
+    Gc,
+    Status,
+}
+
+#[derive(Debug)]
+pub enum FilterChange {
+    User(Option<String>),
+    Cpus(Option<CpuList>),
+    Kthreads(bool),
+    MinDelta(f64),
+    MinShare(f64),
+}
+
+impl FilterChange {
+    pub fn apply(self, filter: &mut FilterArgs) {
+        match self {
+            FilterChange::User(user) => filter.filter_user = user,
+            FilterChange::Cpus(cpus) => filter.cpu = cpus,
+            FilterChange::Kthreads(show) => {
+                filter.exclude_kthreads = !show;
+                filter.include_kthreads = show;
+            }
+            FilterChange::MinDelta(ms) => filter.min_delta_ms = ms,
+            FilterChange::MinShare(percent) => filter.min_share = percent,
+        }
+    }
+}
+
+/// One command and where its one-line answer goes.
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    pub reply: oneshot::Sender<Result<String, String>>,
+}
+
+fn parse(line: &str) -> Result<ControlCommand, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let rest: Vec<&str> = words.collect();
+    let no_args = |command| {
+        if rest.is_empty() {
+            Ok(command)
+        } else {
+            Err(format!("`{line}` takes no arguments"))
+        }
+    };
+    match command {
+        "interval" => match rest.as_slice() {
+            [value] => {
+                let interval = parse_duration(value)?;
+                if interval.is_zero() {
+                    return Err("interval must be positive".to_string());
+                }
+                Ok(ControlCommand::Interval(interval))
+            }
+            _ => Err("usage: interval <duration>, e.g. `interval 5s`".to_string()),
+        },
+        "alpha" => match rest.as_slice() {
+            [value] => match value.parse::<f64>() {
+                Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => Ok(ControlCommand::Alpha(alpha)),
+                _ => Err(format!("alpha must be in (0, 1], got `{value}`")),
+            },
+            _ => Err("usage: alpha <0-1>, e.g. `alpha 0.2`".to_string()),
+        },
+        "filter" => parse_filter(&rest).map(ControlCommand::Filter),
+        "snapshot" => no_args(ControlCommand::Snapshot),
+        "gc" => no_args(ControlCommand::Gc),
+        "status" => no_args(ControlCommand::Status),
+        other => Err(format!("unknown command `{other}`; {HELP}")),
+    }
+}
+
+fn parse_filter(words: &[&str]) -> Result<Vec<FilterChange>, String> {
+    if !words.len().is_multiple_of(2) {
+        return Err("usage: filter [<name> <value>]...".to_string());
+    }
+    let number = |value: &str| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|number| *number >= 0.0)
+            .ok_or_else(|| format!("expected a non-negative number, got `{value}`"))
+    };
+    words
+        .chunks(2)
+        .map(|pair| {
+            let (name, value) = (pair[0], pair[1]);
+            Ok(match name {
+                "user" => FilterChange::User((value != "-").then(|| value.to_string())),
+                "cpu" => FilterChange::Cpus(match value {
+                    "-" => None,
+                    list => Some(parse_cpu_arg(list)?),
+                }),
+                "kthreads" => FilterChange::Kthreads(match value {
+                    "on" => true,
+                    "off" => false,
+                    other => return Err(format!("kthreads takes on or off, got `{other}`")),
+                }),
+                "min-delta" => FilterChange::MinDelta(number(value)?),
+                "min-share" => FilterChange::MinShare(number(value)?),
+                other => return Err(format!("unknown filter `{other}`")),
+            })
+        })
+        .collect()
+}
+
+/// The bound socket; the file is removed again on drop.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Bind `path` (owner-only) and forward parsed commands to `requests`.
+/// A socket file left behind by an earlier run is replaced.
+pub fn listen(path: &Path, requests: mpsc::Sender<ControlRequest>) -> io::Result<ControlSocket> {
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = bind_private(path)?;
+    let socket = ControlSocket {
+        path: path.to_path_buf(),
+    };
+    info!(target: "sampler", path = %path.display(), "control socket listening");
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, requests.clone()));
+                }
+                Err(err) => {
+                    warn!(target: "sampler", "control socket accept failed: {err}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(socket)
+}
+
+/// Bind the socket inside a fresh owner-only directory next to `path`,
+/// restrict it there, and only then move it into place, so nobody else can
+/// connect in between.
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(
+        ".{}.{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join(name);
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    if bound.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    let _ = fs::remove_dir(&staging);
+    bound
+}
+
+/// Answer every line with `ok ...` or `error ...` until the client hangs up.
+async fn serve(stream: UnixStream, requests: mpsc::Sender<ControlRequest>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(err) => {
+                debug!(target: "sampler", "control client dropped: {err}");
+                return;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let answer = match line {
+            "help" => Ok(HELP.to_string()),
+            _ => match parse(line) {
+                Ok(command) => {
+                    debug!(target: "sampler", ?command, "control command");
+                    let (reply, answer) = oneshot::channel();
+                    if requests
+                        .send(ControlRequest { command, reply })
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    answer
+                        .await
+                        .unwrap_or_else(|_| Err("the run is over".to_string()))
+                }
+                Err(err) => Err(err),
+            },
+        };
+        let answer = match answer {
+            Ok(message) => format!("ok {message}\n"),
+            Err(message) => format!("error {message}\n"),
+        };
+        if write.write_all(answer.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
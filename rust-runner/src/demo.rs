@@ -0,0 +1,41 @@
+use clap::Args;
+
+use neo_ebpf_core::demo::{DemoConfig, TicketDistribution};
+
+/// Synthetic-data flags shared by `dump` and `tui`; see [`DemoConfig`].
+#[derive(Args, Clone, Debug)]
+pub struct DemoArgs {
+    /// Sample a generated task population instead of the pinned map (no root
+    /// or BPF program needed; --map is ignored)
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Number of synthetic tasks
+    #[arg(long, default_value_t = 24, requires = "demo")]
+    pub demo_tasks: usize,
+
+    /// How nice values, and so tickets, are spread across the synthetic tasks
+    #[arg(long, value_enum, default_value_t = TicketDistribution::Mixed, requires = "demo")]
+    pub demo_tickets: TicketDistribution,
+
+    /// 0 keeps synthetic tasks steadily runnable; up to 1 they alternate
+    /// between bursts and idle stretches
+    #[arg(long, default_value_t = 0.3, requires = "demo")]
+    pub demo_burstiness: f64,
+
+    /// Seed for a reproducible synthetic population
+    #[arg(long, requires = "demo")]
+    pub demo_seed: Option<u64>,
+}
+
+impl DemoArgs {
+    pub fn to_config(&self) -> Option<DemoConfig> {
+        self.demo.then(|| DemoConfig {
+            tasks: self.demo_tasks,
+            tickets: self.demo_tickets,
+            burstiness: self.demo_burstiness,
+            seed: self.demo_seed,
+            ..DemoConfig::default()
+        })
+    }
+}
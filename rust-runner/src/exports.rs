@@ -0,0 +1,74 @@
+use std::io;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::export::{Exporter, SnapshotBatch};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Windows the sampler may run ahead of a slow sink before it waits.
+const QUEUE_DEPTH: usize = 64;
+
+/// Exporters registered for a `dump` run. They run on a blocking worker fed
+/// through a bounded queue, so slow sinks delay exports rather than the
+/// sampling ticks.
+#[derive(Default)]
+pub struct Exports {
+    sinks: Vec<Box<dyn Exporter>>,
+}
+
+impl Exports {
+    pub fn register(&mut self, exporter: impl Exporter + 'static) {
+        debug!(target: "exporter", name = exporter.name(), "registered exporter");
+        self.sinks.push(Box::new(exporter));
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> Result<(), RunnerError> {
+        for sink in self.sinks.iter_mut() {
+            sink.on_iteration(batch).map_err(failed(sink.name()))?;
+        }
+        Ok(())
+    }
+
+    /// Finish every sink even if one fails, reporting the first error.
+    fn finish(&mut self) -> Result<(), RunnerError> {
+        let mut result = Ok(());
+        for sink in self.sinks.iter_mut() {
+            if let Err(err) = sink.finish()
+                && result.is_ok()
+            {
+                result = Err(failed(sink.name())(err));
+            }
+        }
+        result
+    }
+
+    /// Start the worker. Dropping the sender ends the queue; the handle then
+    /// resolves once every queued batch is written and all sinks finished.
+    pub fn spawn(
+        mut self,
+    ) -> (
+        mpsc::Sender<SnapshotBatch>,
+        JoinHandle<Result<(), RunnerError>>,
+    ) {
+        let (tx, mut rx) = mpsc::channel::<SnapshotBatch>(QUEUE_DEPTH);
+        let worker = tokio::task::spawn_blocking(move || {
+            let mut result = Ok(());
+            while let Some(batch) = rx.blocking_recv() {
+                result = self.on_iteration(&batch);
+                if result.is_err() {
+                    break;
+                }
+            }
+            // Keep whatever was recorded even when a later write failed.
+            result.and(self.finish())
+        });
+        (tx, worker)
+    }
+}
+
+/// Wrap an exporter's I/O error with the exporter's name.
+pub fn failed(name: &str) -> impl FnOnce(io::Error) -> RunnerError {
+    let name = name.to_string();
+    move |source| RunnerError::Export { name, source }
+}
@@ -0,0 +1,84 @@
+use clap::Args;
+
+use neo_ebpf_core::filter::SnapshotFilter;
+use neo_ebpf_core::procfs::parse_cpu_list;
+use neo_ebpf_core::stats::TaskSnapshot;
+
+#[derive(Clone, Debug, Default)]
+pub struct CpuList(pub Vec<u32>);
+
+pub fn parse_cpu_arg(value: &str) -> Result<CpuList, String> {
+    parse_cpu_list(value).map(CpuList)
+}
+
+/// Snapshot filter flags shared by `dump` and `tui`; see [`SnapshotFilter`].
+#[derive(Args, Clone, Debug, Default)]
+pub struct FilterArgs {
+    /// Only show tasks owned by this user (name or numeric uid)
+    #[arg(long)]
+    pub filter_user: Option<String>,
+
+    /// Only show tasks that last ran on these CPUs (e.g. `0-3,8`); tasks with
+    /// an unknown CPU are kept when their affinity overlaps the list
+    #[arg(long, value_parser = parse_cpu_arg)]
+    pub cpu: Option<CpuList>,
+
+    /// Hide kernel threads (kworkers, ksoftirqd, ...)
+    #[arg(long, overrides_with = "include_kthreads")]
+    pub exclude_kthreads: bool,
+
+    /// Keep kernel threads (default; overrides an earlier --exclude-kthreads)
+    #[arg(long, overrides_with = "exclude_kthreads")]
+    pub include_kthreads: bool,
+
+    /// Hide tasks that ran less than this many milliseconds in the window
+    /// (they still count towards ticket totals)
+    #[arg(long, default_value_t = 0.0)]
+    pub min_delta_ms: f64,
+
+    /// Hide tasks whose ticket share is below this percentage
+    #[arg(long, default_value_t = 0.0)]
+    pub min_share: f64,
+}
+
+impl FilterArgs {
+    pub fn to_filter(&self) -> SnapshotFilter {
+        SnapshotFilter {
+            user: self.filter_user.clone(),
+            cpus: self.cpu.as_ref().map(|CpuList(cpus)| cpus.clone()),
+            exclude_kthreads: self.exclude_kthreads,
+            min_delta_ms: self.min_delta_ms,
+            min_share: self.min_share / 100.0,
+        }
+    }
+
+    /// One-line summary of the active filters, e.g. `user=alice min-share=1%`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user) = &self.filter_user {
+            parts.push(format!("user={user}"));
+        }
+        if let Some(CpuList(cpus)) = &self.cpu {
+            let cpus: Vec<String> = cpus.iter().map(u32::to_string).collect();
+            parts.push(format!("cpu={}", cpus.join(",")));
+        }
+        if self.exclude_kthreads {
+            parts.push("kthreads=off".to_string());
+        }
+        if self.min_delta_ms > 0.0 {
+            parts.push(format!("min-delta={}ms", self.min_delta_ms));
+        }
+        if self.min_share > 0.0 {
+            parts.push(format!("min-share={}%", self.min_share));
+        }
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+
+    pub fn apply(&self, snapshots: Vec<TaskSnapshot>) -> Vec<TaskSnapshot> {
+        self.to_filter().apply(snapshots)
+    }
+}
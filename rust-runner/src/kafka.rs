@@ -0,0 +1,201 @@
+use clap::ValueEnum;
+
+/// Encoding of the Kafka messages.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KafkaFormat {
+    /// The NDJSON task/event records plus `host`.
+    Json,
+    /// Avro single-object encoding of task records (no change events).
+    Avro,
+}
+
+#[cfg(feature = "kafka")]
+pub use producer::KafkaExporter;
+
+#[cfg(feature = "kafka")]
+mod producer {
+    use std::io;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use neo_ebpf_core::avro;
+    use neo_ebpf_core::export::{Exporter, SnapshotBatch, event_json, snapshot_json};
+    use neo_ebpf_core::procfs::hostname;
+    use rdkafka::config::RDKafkaLogLevel;
+    use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+    use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+    use rdkafka::{ClientConfig, ClientContext};
+    use serde_json::json;
+    use tracing::{debug, info, warn};
+
+    use super::KafkaFormat;
+
+    /// How long `finish` waits for queued messages to be delivered.
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Counts messages the brokers did not take and routes librdkafka's
+    /// logs through tracing.
+    #[derive(Default)]
+    struct Delivery {
+        failed: Arc<AtomicU64>,
+        /// Client errors reported since the last successful delivery, so an
+        /// outage is logged once rather than on every reconnect attempt.
+        outage: Mutex<Vec<Option<RDKafkaErrorCode>>>,
+    }
+
+    impl ClientContext for Delivery {
+        fn log(&self, level: RDKafkaLogLevel, facility: &str, message: &str) {
+            debug!(target: "exporter", ?level, facility, "librdkafka: {message}");
+        }
+
+        fn error(&self, error: KafkaError, reason: &str) {
+            let code = error.rdkafka_error_code();
+            let Ok(mut outage) = self.outage.lock() else {
+                return;
+            };
+            if outage.contains(&code) {
+                debug!(target: "exporter", "kafka: {error}: {reason}");
+            } else {
+                warn!(target: "exporter", "kafka: {error}: {reason}");
+                outage.push(code);
+            }
+        }
+    }
+
+    impl ProducerContext for Delivery {
+        type DeliveryOpaque = ();
+
+        fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+            match result {
+                Ok(_) => {
+                    if let Ok(mut outage) = self.outage.lock() {
+                        outage.clear();
+                    }
+                }
+                Err((err, _)) => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    debug!(target: "exporter", "kafka delivery failed: {err}");
+                }
+            }
+        }
+    }
+
+    /// Publishes every task record (and, as JSON, every change event) of a
+    /// window to one topic, keyed `host:pid` so a task's records stay in
+    /// one partition.
+    pub struct KafkaExporter {
+        producer: BaseProducer<Delivery>,
+        topic: String,
+        format: KafkaFormat,
+        host: String,
+        failed: Arc<AtomicU64>,
+        reported: u64,
+        payload: Vec<u8>,
+    }
+
+    impl KafkaExporter {
+        /// `options` are extra librdkafka settings (`key=value`), e.g. for
+        /// SASL or compression.
+        pub fn new(
+            brokers: &str,
+            topic: &str,
+            format: KafkaFormat,
+            options: &[(String, String)],
+        ) -> io::Result<Self> {
+            let host = hostname();
+            let mut config = ClientConfig::new();
+            config
+                .set("bootstrap.servers", brokers)
+                .set("client.id", format!("neo-ebpf-{host}"))
+                // Errors arrive through `ClientContext::error`; keep
+                // librdkafka from also printing them to stderr.
+                .set_log_level(RDKafkaLogLevel::Critical);
+            for (key, value) in options {
+                config.set(key, value);
+            }
+            let context = Delivery::default();
+            let failed = Arc::clone(&context.failed);
+            let producer = config
+                .create_with_context(context)
+                .map_err(io::Error::other)?;
+            info!(target: "exporter", brokers, topic, ?format, "publishing to kafka");
+            Ok(Self {
+                producer,
+                topic: topic.to_string(),
+                format,
+                host,
+                failed,
+                reported: 0,
+                payload: Vec::new(),
+            })
+        }
+
+        fn send(&self, key: &str, payload: &[u8]) -> io::Result<()> {
+            let mut record = BaseRecord::to(&self.topic).key(key).payload(payload);
+            loop {
+                match self.producer.send(record) {
+                    Ok(()) => return Ok(()),
+                    // Wait for deliveries to make room rather than drop.
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), back)) => {
+                        record = back;
+                        self.producer.poll(Duration::from_millis(100));
+                    }
+                    Err((err, _)) => return Err(io::Error::other(err)),
+                }
+            }
+        }
+
+        fn report_failures(&mut self) {
+            let failed = self.failed.load(Ordering::Relaxed);
+            if failed > self.reported {
+                warn!(target: "exporter", failed = failed - self.reported, "kafka messages were not delivered");
+                self.reported = failed;
+            }
+        }
+    }
+
+    impl Exporter for KafkaExporter {
+        fn name(&self) -> &str {
+            "kafka"
+        }
+
+        fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+            let mut payload = std::mem::take(&mut self.payload);
+            for entry in &batch.snapshots {
+                let key = format!("{}:{}", self.host, entry.pid);
+                payload.clear();
+                match self.format {
+                    KafkaFormat::Json => {
+                        let mut record = snapshot_json(batch, entry);
+                        record["host"] = json!(self.host);
+                        serde_json::to_writer(&mut payload, &record)?;
+                    }
+                    KafkaFormat::Avro => avro::encode_task(&self.host, batch, entry, &mut payload),
+                }
+                self.send(&key, &payload)?;
+            }
+            if self.format == KafkaFormat::Json {
+                for event in &batch.events {
+                    let key = format!("{}:{}", self.host, event.pid);
+                    let mut record = event_json(batch, event);
+                    record["host"] = json!(self.host);
+                    payload.clear();
+                    serde_json::to_writer(&mut payload, &record)?;
+                    self.send(&key, &payload)?;
+                }
+            }
+            self.payload = payload;
+            // Serve delivery callbacks without blocking the next window.
+            self.producer.poll(Duration::ZERO);
+            self.report_failures();
+            Ok(())
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            let flushed = self.producer.flush(FLUSH_TIMEOUT);
+            self.report_failures();
+            flushed.map_err(io::Error::other)
+        }
+    }
+}
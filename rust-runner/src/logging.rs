@@ -0,0 +1,57 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use clap::ValueEnum;
+use neo_ebpf_core::RunnerError;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Install the global subscriber. Diagnostics go to stderr (or `file`), never
+/// stdout, so table/NDJSON output stays machine-readable. `interactive` means
+/// the TUI owns the terminal: without a log file, logging is switched off
+/// rather than scribbled over the dashboard.
+pub fn init(
+    directives: &str,
+    format: LogFormat,
+    file: Option<&Path>,
+    interactive: bool,
+) -> Result<(), RunnerError> {
+    let parse = |directives: &str| {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|err| RunnerError::Config(format!("--log-level {directives:?}: {err}")))?;
+        // rdkafka warns about every error event its producer hands back; the
+        // Kafka sink reports those itself.
+        #[cfg(feature = "kafka")]
+        let filter = if directives.contains("rdkafka") {
+            filter
+        } else {
+            filter.add_directive("rdkafka::producer=error".parse().expect("valid directive"))
+        };
+        Ok::<_, RunnerError>(filter)
+    };
+    let (filter, writer) = match file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            (parse(directives)?, BoxMakeWriter::new(file))
+        }
+        None if interactive => (EnvFilter::new("off"), BoxMakeWriter::new(io::sink)),
+        None => (parse(directives)?, BoxMakeWriter::new(io::stderr)),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(file.is_none() && io::stderr().is_terminal());
+    match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    }
+    .map_err(|err| RunnerError::Config(format!("logging already initialised: {err}")))
+}
@@ -1,74 +1,132 @@
-mod bpf_map;
-mod stats;
+mod attach;
+mod bench;
+mod check;
+mod config;
+mod ctl;
+mod demo;
+mod exports;
+mod filter;
+mod kafka;
+mod logging;
+mod mqtt;
+mod record;
+mod remote;
+mod renice;
+mod report;
+mod sampler;
+mod serve;
+mod shutdown;
+mod sort;
+mod table;
 mod tui;
+mod weight;
 
 use std::cmp::Ordering;
-use std::error::Error;
-use std::fs::OpenOptions;
-use std::io::{self, Write};
-use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, IsTerminal, Write};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use clap::{Args, Parser, Subcommand};
-use crossterm::event::{self, Event, KeyCode};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use crossterm::cursor::MoveTo;
+use crossterm::event::{Event, EventStream, KeyCode, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
-    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use rand::SeedableRng;
 use rand::rngs::StdRng;
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
-use serde::Serialize;
-use serde_json::json;
-
-use crate::bpf_map::{iterate_task_info, open_pinned_map};
-use crate::stats::{RollingStats, TaskInfo, TaskSnapshot, simulate_lottery_draws, ticket_share};
-use crate::tui::{HistorySample, HistoryWindow, draw_dashboard};
-
-#[derive(Serialize)]
-#[serde(tag = "ph")]
-enum TraceEvent {
-    #[serde(rename = "M")]
-    Metadata {
-        name: &'static str,
-        cat: &'static str,
-        ts: f64,
-        pid: u32,
-        tid: u32,
-        args: MetadataArgs,
-    },
-    #[serde(rename = "X")]
-    Slice {
-        name: String,
-        cat: &'static str,
-        ts: f64,
-        dur: f64,
-        pid: u32,
-        tid: u32,
-        args: TraceArgs,
-    },
-}
-
-#[derive(Serialize)]
-struct MetadataArgs {
-    thread_name: String,
-}
-
-#[derive(Serialize)]
-struct TraceArgs {
-    ticket_share: f64,
-    deadline_ms: f64,
-    lateness_ms: f64,
-    runtime_ms: f64,
-    utilization: f64,
-}
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, warn};
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::autonice::{AutoNice, AutoNiceConfig};
+use neo_ebpf_core::bpf_map::MapAccess;
+use neo_ebpf_core::cgroup::CgroupSnapshot;
+use neo_ebpf_core::compress::Compression;
+use neo_ebpf_core::control::{self, WeightWatcher};
+use neo_ebpf_core::demo::DemoConfig;
+use neo_ebpf_core::diagnose;
+use neo_ebpf_core::events::{SchedEvent, SchedEvents};
+use neo_ebpf_core::export::{
+    AlertHook, AuditLog, CSV_COLUMNS, CSV_SUMMARY_COLUMNS, ChangeEvent, CsvExporter, CsvFormat,
+    DeadlineAlerts, InflationAlerts, JsonExporter, LatencyAlerts, SnapshotBatch, StarvationAlerts,
+    StatsdExporter, StatsdTags, StdoutJsonExporter, TraceExporter, batch_json,
+};
+use neo_ebpf_core::filter::TaskSelector;
+use neo_ebpf_core::group::{GroupBy, GroupSnapshot, aggregate, members};
+use neo_ebpf_core::histogram::LatenessHistogram;
+use neo_ebpf_core::inflation::{DEFAULT_INFLATION_GROWTH, DEFAULT_INFLATION_WINDOWS};
+use neo_ebpf_core::irq::IrqTime;
+use neo_ebpf_core::journal::{self, AlertLog, AlertTarget, PriorityMap, Severity};
+use neo_ebpf_core::latency::WakeupLatency;
+use neo_ebpf_core::policy::PolicyEnforcer;
+use neo_ebpf_core::priority::{OUTLIER_RATIO, PriorityReport};
+use neo_ebpf_core::privilege::{self, Account};
+use neo_ebpf_core::rebalance::{Adjustment, RebalanceConfig, Rebalancer};
+use neo_ebpf_core::rotate::Rotation;
+use neo_ebpf_core::sketch::SketchWindow;
+use neo_ebpf_core::stats::{
+    DEFAULT_ROLLING_CAPACITY, DEFAULT_ROLLING_TTL, DEFAULT_STARVATION_WINDOWS, Ranking,
+    TaskSnapshot, preemption_rate, runtime_gini, runtime_lorenz, simulate_lottery_draws,
+    system_utilization, ticket_concentration,
+};
+use neo_ebpf_core::stream::{Clock, STALE_WINDOWS, SnapshotStream, SnapshotWindow};
+use neo_ebpf_core::summary::{RunSummary, SummaryBuilder};
+use neo_ebpf_core::throttle::{
+    ThrottleAction, ThrottleConfig, ThrottleEvent, ThrottleState, Throttler,
+};
+use neo_ebpf_core::whatif::{Projection, WhatIf};
+
+use crate::attach::AttachArgs;
+use crate::bench::BenchMap;
+use crate::check::CheckArgs;
+use crate::ctl::ControlCommand;
+use crate::demo::DemoArgs;
+use crate::exports::Exports;
+use crate::filter::FilterArgs;
+use crate::kafka::KafkaFormat;
+use crate::logging::LogFormat;
+use crate::mqtt::{MqttExporter, MqttSettings};
+use crate::record::{Recorder, TuiOutput};
+use crate::remote::{AgentArgs, RemoteArgs};
+use crate::report::ReportArgs;
+use crate::sampler::{Background, Samplers};
+use crate::sort::{SortKey, sort_rows};
+use crate::table::{ColumnKey, csv_columns, print_task_table, table_columns};
+use crate::tui::{
+    AlertHistory, ComparisonSide, HistorySample, HistoryWindow, KeyAction, Pane, PaneCache,
+    TuiState, draw_comparison, draw_dashboard, same_activity,
+};
 
 #[derive(Parser)]
 #[command(author, version, about = "Observe sched_switch activity and derive lottery stats", long_about = None)]
 struct Cli {
+    /// TOML file with per-subcommand defaults ([dump], [tui], [bench]);
+    /// defaults to ~/.config/neo-ebpf/config.toml when present
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Log filter: a level (error, warn, info, debug, trace) or per-target
+    /// directives such as `info,sampler=debug` (targets: map, sampler,
+    /// exporter, tui)
+    #[arg(long, global = true, default_value = "info")]
+    log_level: String,
+
+    /// Log line format on stderr (or --log-file)
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Append logs to this file instead of stderr (required to see logs in `tui`)
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -76,24 +134,71 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Dump the current BPF map contents at a fixed cadence
-    Dump(DumpArgs),
+    Dump(Box<DumpArgs>),
     /// Interactive terminal dashboard with live stats
     Tui(TuiArgs),
+    /// Print shell completions or a manpage to stdout
+    Completions(CompletionsArgs),
+    /// Measure the runner's own per-tick cost against synthetic maps
+    Bench(BenchArgs),
+    /// Change a task's nice value and show the resulting ticket change
+    Renice(ReniceArgs),
+    /// Write a cgroup's cpu.weight and compare its tasks before and after
+    SetWeight(SetWeightArgs),
+    /// Put a task under SCHED_DEADLINE with a runtime/deadline/period reservation
+    SetDeadline(SetDeadlineArgs),
+    /// Serve this host's task map to remote `dump`/`tui --remote` clients
+    Agent(AgentArgs),
+    /// Sample once and print a Nagios/Icinga status line with perfdata,
+    /// exiting 0 (OK), 1 (WARNING), 2 (CRITICAL) or 3 (UNKNOWN)
+    Check(CheckArgs),
+    /// Print the per-task tail latency report of a `dump --summary-output`
+    /// file
+    Report(ReportArgs),
+    /// Load the scheduler's BPF object (embedded with the `embed-bpf`
+    /// feature, or --obj), pin its maps and attach it to sched_switch
+    Attach(AttachArgs),
+    /// Sample until interrupted and serve the web dashboard, without
+    /// printing tables; takes every `dump` flag
+    Serve(Box<ServeArgs>),
 }
 
 #[derive(Args, Clone)]
 struct DumpArgs {
-    /// Path to the pinned task map
+    /// Path to a pinned task map; repeat to sample several maps in lockstep
+    /// (outputs are labelled with each map's file name)
     #[arg(long, default_value = "/sys/fs/bpf/task_map")]
-    map: String,
+    map: Vec<String>,
 
-    /// Seconds to sleep between samples
-    #[arg(long, default_value_t = 1)]
-    interval: u64,
+    /// Time between samples, e.g. `250ms`, `2s`; a bare number is seconds
+    /// (`0.5` works too)
+    #[arg(long, value_parser = parse_duration, default_value = "1s")]
+    interval: Duration,
 
-    /// Number of samples to capture
-    #[arg(long, default_value_t = 10)]
-    iterations: u32,
+    /// Number of samples to capture; 0 runs until interrupted. Defaults to 10,
+    /// or to no limit when --duration is given
+    #[arg(long)]
+    iterations: Option<u32>,
+
+    /// Stop after this much wall-clock time, e.g. `90s`, `5m`, `1h30m`
+    #[arg(long, value_parser = parse_duration)]
+    duration: Option<Duration>,
+
+    /// What to print on stdout: human-readable tables, or one NDJSON object
+    /// per task and iteration (tables and summaries are then suppressed)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// Clear the screen and redraw one table per interval, like watch(1);
+    /// on a terminal, values that rose since the last sample are green, ones
+    /// that fell red
+    #[arg(long)]
+    watch: bool,
+
+    /// Suppress tables and summaries; exit with status 10 if a deadline alert
+    /// (--deadline-warn) or fairness threshold (--min-fairness) was violated
+    #[arg(long, conflicts_with = "watch")]
+    quiet: bool,
 
     /// Optional CSV file to append results to
     #[arg(long)]
@@ -103,10 +208,38 @@ struct DumpArgs {
     #[arg(long, default_value_t = 0)]
     simulate_draws: u32,
 
+    /// Hypothetical tickets, e.g. `1234=400,5678=50`: print each task's
+    /// projected share (and draws) without writing anything to the map
+    #[arg(long, value_parser = WhatIf::parse, conflicts_with = "watch")]
+    what_if: Option<WhatIf>,
+
+    /// File of `pid=tickets` lines for --what-if
+    #[arg(long, conflicts_with_all = ["what_if", "watch"])]
+    what_if_file: Option<PathBuf>,
+
     /// EWMA smoothing factor for rolling runtime (0-1)
     #[arg(long, default_value_t = 0.5)]
     alpha: f64,
 
+    /// Forget the rolling state of a pid missing from the map for this many
+    /// windows
+    #[arg(long, value_name = "WINDOWS", default_value_t = DEFAULT_ROLLING_TTL)]
+    rolling_ttl: u64,
+
+    /// Most pids to keep rolling state for; the longest unseen go first
+    #[arg(long, value_name = "PIDS", default_value_t = DEFAULT_ROLLING_CAPACITY)]
+    rolling_capacity: usize,
+
+    /// Walk the map again when tasks were added or removed during a walk,
+    /// and merge both walks; windows read inconsistently are tagged either way
+    #[arg(long)]
+    reread_inconsistent: bool,
+
+    /// Clock for window timestamps; monotonic matches the BPF program's
+    /// bpf_ktime_get_ns and perf/ftrace traces
+    #[arg(long, value_enum, default_value_t = Clock::Realtime)]
+    clock: Clock,
+
     /// Optional RNG seed for reproducible lottery draws
     #[arg(long)]
     seed: Option<u64>,
@@ -119,198 +252,2183 @@ struct DumpArgs {
     #[arg(long)]
     json_output: Option<PathBuf>,
 
+    /// Add one aggregate record per window (tasks, overdue, runtime, tickets,
+    /// fairness) to the CSV, NDJSON, and `--format ndjson` streams, tagged
+    /// `record=summary` / `"event": "summary"`
+    #[arg(long)]
+    summary_records: bool,
+
+    /// Serve mode: push every window as one JSON message to WebSocket clients
+    /// connected to ws://ADDR/ws (e.g. 127.0.0.1:9000)
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<SocketAddr>,
+
+    /// Also let pages from ORIGIN (e.g. https://dash.example.com) open the
+    /// `--serve` WebSocket; the dashboard's own origin always may
+    #[arg(long, value_name = "ORIGIN")]
+    serve_origin: Vec<String>,
+
+    /// NDJSON audit log of every nice/ticket change seen between samples,
+    /// whether made by this runner or by anything else
+    #[arg(long)]
+    audit_log: Option<PathBuf>,
+
+    /// Unix socket accepting commands while the run is going (interval,
+    /// filter, snapshot, gc, status; `help` lists them)
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// Send gauges (total utilization, overdue tasks, worst lateness, ...)
+    /// to a StatsD daemon over UDP
+    #[arg(long, value_name = "HOST:PORT")]
+    statsd: Option<String>,
+
+    /// Prefix of every StatsD metric name
+    #[arg(long, default_value = "neo_ebpf", requires = "statsd")]
+    statsd_prefix: String,
+
+    /// How StatsD metrics carry their map/pid/comm tags
+    #[arg(long, value_enum, default_value_t = StatsdTags::Dogstatsd, requires = "statsd")]
+    statsd_tags: StatsdTags,
+
+    /// Also send per-task gauges for every task that ran in the window
+    #[arg(long, requires = "statsd")]
+    statsd_per_pid: bool,
+
+    /// Publish task records to Kafka (comma-separated bootstrap brokers;
+    /// needs a build with the `kafka` feature)
+    #[arg(long, value_name = "HOST:PORT,...")]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic; messages are keyed `host:pid`
+    #[arg(long, default_value = "neo-ebpf.tasks", requires = "kafka_brokers")]
+    kafka_topic: String,
+
+    /// Kafka message encoding
+    #[arg(long, value_enum, default_value_t = KafkaFormat::Json, requires = "kafka_brokers")]
+    kafka_format: KafkaFormat,
+
+    /// Extra librdkafka producer setting, e.g. `compression.type=zstd`;
+    /// repeatable
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value, requires = "kafka_brokers")]
+    kafka_option: Vec<(String, String)>,
+
+    /// Publish window summaries to an MQTT broker
+    #[arg(long, value_name = "HOST[:PORT]")]
+    mqtt_broker: Option<String>,
+
+    /// MQTT topic prefix (`{host}` is the host name); windows go to
+    /// `<prefix>/<map>/summary`, tasks to `<prefix>/<map>/task/<pid>`
+    #[arg(long, default_value = "neo-ebpf/{host}", requires = "mqtt_broker")]
+    mqtt_topic: String,
+
+    /// MQTT quality of service (0, 1 or 2)
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2), requires = "mqtt_broker")]
+    mqtt_qos: u8,
+
+    /// Retain the summaries so new subscribers see the latest window
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_retain: bool,
+
+    /// Also publish the record of every task that ran in the window
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_per_task: bool,
+
+    /// MQTT client id [default: neo-ebpf-<host>]
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_client_id: Option<String>,
+
+    /// MQTT user name
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_username: Option<String>,
+
+    /// File holding the MQTT password (first line)
+    #[arg(long, value_name = "PATH", requires = "mqtt_username")]
+    mqtt_password_file: Option<PathBuf>,
+
+    /// Compress the CSV/NDJSON outputs; by default `.gz`/`.zst` file names
+    /// select gzip/zstd
+    #[arg(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Rotate the CSV/NDJSON outputs once a file reaches this size, e.g.
+    /// `100M` (suffixes K, M, G; powers of 1024)
+    #[arg(long, value_parser = parse_size)]
+    rotate_size: Option<u64>,
+
+    /// Rotate the CSV/NDJSON outputs after this long, e.g. `1h`, `1d`
+    #[arg(long, value_parser = parse_duration)]
+    rotate_interval: Option<Duration>,
+
+    /// Keep at most this many rotated files per output, deleting the oldest
+    #[arg(long)]
+    rotate_keep: Option<usize>,
+
     /// Optional Chrome trace/Perfetto export path
     #[arg(long)]
     trace_output: Option<PathBuf>,
 
-    /// Emit warnings when lateness exceeds this many milliseconds
-    #[arg(long, default_value_t = 0.0)]
-    deadline_warn: f64,
-}
+    /// Emit warnings when lateness exceeds this many milliseconds
+    #[arg(long, default_value_t = 0.0)]
+    deadline_warn: f64,
+
+    /// Emit warnings when a task's 99th percentile wakeup-to-run latency
+    /// in a window exceeds this many milliseconds (0: off)
+    #[arg(long, default_value_t = 0.0)]
+    latency_warn: f64,
+
+    /// Flag a task as starved, and warn about it, once it held tickets and
+    /// waited for a CPU without running for this many windows in a row
+    /// (0: off)
+    #[arg(long, default_value_t = DEFAULT_STARVATION_WINDOWS)]
+    starve_windows: u32,
+
+    /// Scheduler events to report besides sched_switch (wakeup, waking,
+    /// migrate), warning about those whose map is not pinned next to
+    /// --map; by default every pinned event map is read
+    #[arg(long, value_enum, value_delimiter = ',')]
+    events: Vec<SchedEvent>,
+
+    /// Also report each cgroup's runtime from the cgroup_map pinned next to
+    /// --map: as `cgroup` rows below the task table, and in CSV (with
+    /// `entity` and `cgroup_id` columns) and NDJSON records
+    #[arg(long)]
+    cgroups: bool,
+
+    /// Every this many windows, report how well achieved CPU shares follow
+    /// the ticket counts, with the tasks furthest off (0: never)
+    #[arg(long, value_name = "WINDOWS", default_value_t = 10)]
+    priority_every: u32,
+
+    /// Windows the exported p95/p99 lateness is taken over (0: each window
+    /// on its own)
+    #[arg(long, value_name = "WINDOWS", default_value_t = 60)]
+    quantile_horizon: u32,
+
+    /// Warn when a task's or cgroup's tickets grow by more than this many
+    /// percent within --inflation-windows windows (0: off)
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_INFLATION_GROWTH * 100.0)]
+    inflation_warn: f64,
+
+    /// Windows --inflation-warn measures ticket growth over
+    #[arg(long, value_name = "WINDOWS", default_value_t = DEFAULT_INFLATION_WINDOWS)]
+    inflation_windows: u32,
+
+    /// Shell command run when a --deadline-warn alert fires; details arrive
+    /// in NEO_ALERT_* environment variables and as JSON on stdin
+    #[arg(long)]
+    on_alert: Option<String>,
+
+    /// Minimum time between two --on-alert runs, e.g. `30s`, `5m`
+    #[arg(long, value_parser = parse_duration, default_value = "60s")]
+    alert_cooldown: Duration,
+
+    /// Kill an --on-alert command that is still running after this long
+    #[arg(long, value_parser = parse_duration, default_value = "30s")]
+    alert_timeout: Duration,
+
+    /// Also send --deadline-warn alerts and change events to journald or
+    /// syslog
+    #[arg(long, value_enum, value_name = "TARGET")]
+    alert_log: Option<AlertTarget>,
+
+    /// Identifier (SYSLOG_IDENTIFIER / syslog tag) of --alert-log messages
+    #[arg(long, default_value = "neo-ebpf", requires = "alert_log")]
+    alert_identifier: String,
+
+    /// Priority of one alert severity, e.g. `warning=err`; severities are
+    /// info (change events), warning (over --deadline-warn) and critical
+    /// (over twice --deadline-warn); repeatable
+    #[arg(long, value_name = "SEVERITY=PRIORITY", value_parser = parse_alert_priority, requires = "alert_log")]
+    alert_priority: Vec<(Severity, u8)>,
+
+    /// Flag the run when the end-of-run Jain fairness index falls below this
+    /// (0-1)
+    #[arg(long)]
+    min_fairness: Option<f64>,
+
+    /// Field separator used in the CSV output (e.g. ';' for European locales)
+    #[arg(long, default_value_t = ',')]
+    csv_delimiter: char,
+
+    /// Decimal mark used for fractional CSV values (e.g. ',' for European locales)
+    #[arg(long, default_value_t = '.')]
+    csv_decimal: char,
+
+    /// Optional JSON file for the end-of-run summary report
+    #[arg(long)]
+    summary_output: Option<PathBuf>,
+
+    /// Aggregate the per-iteration table by pid, process (tgid), cgroup (v2 path), or user
+    #[arg(long, value_enum, default_value_t = GroupBy::Pid)]
+    group_by: GroupBy,
+
+    /// With --group-by, list each group's member threads/tasks below it
+    #[arg(long)]
+    expand_groups: bool,
+
+    /// Order of the per-iteration table (groups with --group-by; `pid` sorts
+    /// groups by key). Lottery/EDF summaries keep their own ranking
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// With --sort, put the largest values first
+    #[arg(long, requires = "sort")]
+    desc: bool,
+
+    /// Fields shown in the per-task table and written to the CSV output,
+    /// e.g. `pid,comm,share,lateness`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Vec<ColumnKey>,
+
+    /// Show and write every available field
+    #[arg(long, conflicts_with = "columns")]
+    wide: bool,
+
+    /// Only sample these pids (or threads of these processes); repeatable or
+    /// comma-separated
+    #[arg(long, value_delimiter = ',')]
+    pid: Vec<u32>,
+
+    /// Only sample the process whose pid is in this file (re-read every
+    /// iteration, so daemon restarts are followed)
+    #[arg(long)]
+    pid_file: Option<PathBuf>,
+
+    /// Only sample tasks whose name (/proc/<pid>/comm) matches this glob,
+    /// e.g. 'postgres*'; repeatable
+    #[arg(long)]
+    comm: Vec<String>,
+
+    /// Ticket policy file (TOML rules mapping comm globs, users, or cgroups
+    /// to ticket counts), reconciled into the override map every interval
+    #[arg(long)]
+    enforce_policy: Option<PathBuf>,
+
+    /// Pinned ticket_override map written by --enforce-policy and --rebalance
+    #[arg(long, default_value = "/sys/fs/bpf/ticket_override")]
+    override_map: String,
+
+    /// Steer tickets with a PI controller until each active task's achieved
+    /// CPU share matches the share its nice value entitles it to
+    #[arg(long, conflicts_with = "enforce_policy")]
+    rebalance: bool,
+
+    /// With --rebalance, print the proposed ticket changes without writing them
+    #[arg(long, requires = "rebalance")]
+    dry_run: bool,
+
+    /// Proportional gain of --rebalance
+    #[arg(long, default_value_t = 0.5, requires = "rebalance")]
+    rebalance_kp: f64,
+
+    /// Integral gain of --rebalance
+    #[arg(long, default_value_t = 0.1, requires = "rebalance")]
+    rebalance_ki: f64,
+
+    /// Largest relative ticket change per interval for --rebalance (0.25 = 25%)
+    #[arg(long, default_value_t = 0.25, requires = "rebalance")]
+    rebalance_step: f64,
+
+    /// Throttle tasks whose utilization (runtime per estimated period)
+    /// stays above this for --throttle-windows windows
+    #[arg(long)]
+    throttle_util: Option<f64>,
+
+    /// Throttle tasks whose EDF lateness (ms) stays above this for
+    /// --throttle-windows windows
+    #[arg(long)]
+    throttle_lateness: Option<f64>,
+
+    /// Consecutive windows over budget before throttling, and within budget
+    /// before lifting it
+    #[arg(long, default_value_t = 3)]
+    throttle_windows: u32,
+
+    /// Correction for a task over budget
+    #[arg(long, value_enum, default_value_t = ThrottleAction::Tickets)]
+    throttle_action: ThrottleAction,
+
+    /// Fraction of its CPU a throttled task keeps (tickets, running part of
+    /// the stop/continue cycle, or cpu.max quota of one CPU)
+    #[arg(long, default_value_t = 0.25)]
+    throttle_duty: f64,
+
+    /// Lower the nice value of tasks that keep missing their heuristic
+    /// deadline and raise it for tasks that keep meeting it
+    #[arg(long)]
+    auto_nice: bool,
+
+    /// Consecutive misses (or meets) before --auto-nice moves a task by one
+    #[arg(long, default_value_t = 3, requires = "auto_nice")]
+    auto_nice_windows: u32,
+
+    /// How far --auto-nice may move a task from its starting nice value
+    #[arg(long, default_value_t = 5, requires = "auto_nice")]
+    auto_nice_range: i32,
+
+    /// Lowest nice value --auto-nice assigns (below 0 needs CAP_SYS_NICE)
+    #[arg(long, default_value_t = -10, allow_hyphen_values = true, requires = "auto_nice")]
+    auto_nice_floor: i32,
+
+    /// Once the maps are open, switch to this user (name or uid) and keep
+    /// only CAP_BPF, so the long-running process and its output files are
+    /// not owned by root
+    #[arg(long, value_name = "USER")]
+    run_as_user: Option<String>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    #[command(flatten)]
+    remote: RemoteArgs,
+
+    #[command(flatten)]
+    demo: DemoArgs,
+}
+
+#[derive(Args, Clone)]
+struct ServeArgs {
+    /// Address of the dashboard (`/`) and its WebSocket stream (`/ws`)
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1:9000",
+        conflicts_with = "serve"
+    )]
+    listen: SocketAddr,
+
+    #[command(flatten)]
+    dump: DumpArgs,
+}
+
+#[derive(Args, Clone)]
+struct TuiArgs {
+    /// Path to the pinned task map
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map: String,
+
+    /// Refresh period in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    refresh_ms: u64,
+
+    /// EWMA smoothing factor for rolling runtime (0-1)
+    #[arg(long, default_value_t = 0.5)]
+    alpha: f64,
+
+    /// How many tasks to show in the dashboard table
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Initial table grouping (cycle with 'o' at runtime)
+    #[arg(long, value_enum, default_value_t = GroupBy::Pid)]
+    group_by: GroupBy,
+
+    /// List tasks in the alert pane when their lateness goes over this many
+    /// milliseconds (0: on every deadline miss)
+    #[arg(long, default_value_t = 0.0)]
+    deadline_warn: f64,
+
+    /// List tasks in the alert pane when their 99th percentile wakeup-to-run
+    /// latency goes over this many milliseconds (0: off)
+    #[arg(long, default_value_t = 0.0)]
+    latency_warn: f64,
+
+    /// Mark a task STARVED once it held tickets and waited for a CPU
+    /// without running for this many windows in a row (0: off)
+    #[arg(long, default_value_t = DEFAULT_STARVATION_WINDOWS)]
+    starve_windows: u32,
+
+    /// Scheduler events to report besides sched_switch (wakeup, waking,
+    /// migrate), warning about those whose map is not pinned next to
+    /// --map; by default every pinned event map is read
+    #[arg(long, value_enum, value_delimiter = ',')]
+    events: Vec<SchedEvent>,
+
+    /// Read each cgroup's runtime from the cgroup_map pinned next to --map,
+    /// shown as a BPF column of the cgroup group table
+    #[arg(long)]
+    cgroups: bool,
+
+    /// List a task or cgroup in the alert pane when its tickets grow by more
+    /// than this many percent within --inflation-windows windows (0: off)
+    #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_INFLATION_GROWTH * 100.0)]
+    inflation_warn: f64,
+
+    /// Windows --inflation-warn measures ticket growth over
+    #[arg(long, value_name = "WINDOWS", default_value_t = DEFAULT_INFLATION_WINDOWS)]
+    inflation_windows: u32,
+
+    /// Keep these pids at the top of the table, each with its own CPU
+    /// sparkline (toggle with 'p' at runtime)
+    #[arg(long, value_delimiter = ',', value_name = "PID")]
+    pin: Vec<u32>,
+
+    /// Sample this second pinned map as well (e.g. a baseline scheduler's)
+    /// and show both side by side with their differences. With --demo, the
+    /// same seed's population with every fair task at nice 0 stands in for it
+    #[arg(long, value_name = "PATH")]
+    compare: Option<String>,
+
+    /// Draw the trend panels as braille line charts (toggle with 'b')
+    #[arg(long)]
+    braille: bool,
+
+    /// Record the session's frames and timing to this asciinema v2 file
+    /// (`asciinema play FILE` replays it)
+    #[arg(long, value_name = "FILE")]
+    record_session: Option<PathBuf>,
+
+    /// Once the maps are open, switch to this user (name or uid) and keep
+    /// only CAP_BPF, so the long-running process and its output files are
+    /// not owned by root
+    #[arg(long, value_name = "USER")]
+    run_as_user: Option<String>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    #[command(flatten)]
+    remote: RemoteArgs,
+
+    #[command(flatten)]
+    demo: DemoArgs,
+}
+
+#[derive(Args, Clone)]
+struct BenchArgs {
+    /// Comma-separated map sizes (entries) to benchmark
+    #[arg(long, value_delimiter = ',', default_value = "100,1000,10000")]
+    sizes: Vec<u32>,
+
+    /// Sampling ticks to average over for each map size
+    #[arg(long, default_value_t = 20)]
+    ticks: u32,
+
+    /// Map layout to sample
+    #[arg(long, value_enum, default_value_t = BenchMap::Hash)]
+    map_type: BenchMap,
+}
+
+#[derive(Args, Clone)]
+struct ReniceArgs {
+    /// Task (thread id, as keyed in the map) to adjust
+    #[arg(long)]
+    pid: u32,
+
+    /// New nice value (-20..19); lowering it needs CAP_SYS_NICE
+    #[arg(long, allow_hyphen_values = true)]
+    nice: i32,
+
+    /// Pinned task map to watch for the new ticket count
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map: String,
+
+    /// Seconds between the samples that watch for the change
+    #[arg(long, default_value_t = 1)]
+    interval: u64,
+}
+
+#[derive(Args, Clone)]
+struct SetDeadlineArgs {
+    /// Task (thread id, as keyed in the map) to adjust
+    pid: u32,
+
+    /// CPU time reserved per period, e.g. `2ms`, `500us`
+    #[arg(long, value_parser = parse_duration)]
+    runtime: Duration,
+
+    /// Reservation period, e.g. `10ms`
+    #[arg(long, value_parser = parse_duration)]
+    period: Duration,
+
+    /// Relative deadline; defaults to the period
+    #[arg(long, value_parser = parse_duration)]
+    deadline: Option<Duration>,
+}
+
+#[derive(Args, Clone)]
+struct SetWeightArgs {
+    /// Cgroup v2 path as shown in the cgroup column, e.g. `/system.slice/db.service`
+    #[arg(long)]
+    cgroup: String,
+
+    /// New cpu.weight (1-10000; the kernel default is 100)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10_000))]
+    weight: u32,
+
+    /// Pinned task map sampled before and after the change
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map: String,
+
+    /// Samples taken before and after the change; 0 only writes the weight
+    #[arg(long, default_value_t = 3)]
+    samples: u32,
+
+    /// Seconds between samples
+    #[arg(long, default_value_t = 1)]
+    interval: u64,
+}
+
+#[derive(Args, Clone)]
+struct CompletionsArgs {
+    /// What to generate: a completion script for the given shell, or a roff manpage
+    #[arg(value_enum)]
+    target: CompletionTarget,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Ndjson,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum CompletionTarget {
+    Bash,
+    Zsh,
+    Fish,
+    Man,
+}
+
+fn main() {
+    if let Err(err) = entry() {
+        let hints = diagnose::permission_hints(&err);
+        if tracing::dispatcher::has_been_set() {
+            error!(kind = err.kind(), "{err}");
+            for hint in &hints {
+                error!(kind = err.kind(), "hint: {hint}");
+            }
+        } else {
+            eprintln!("Error: {err}");
+            for hint in &hints {
+                eprintln!("Hint: {hint}");
+            }
+        }
+        std::process::exit(exit_code(&err));
+    }
+}
+
+/// Exit status per failure class so wrapper scripts can branch without
+/// parsing messages (2 is left to clap's usage errors).
+fn exit_code(err: &RunnerError) -> i32 {
+    match err.kind() {
+        "map_missing" => 3,
+        "permission_denied" => 4,
+        "map_open" | "map_iterate" => 5,
+        "value_size_mismatch" => 6,
+        "export" => 7,
+        "terminal" => 8,
+        "config" => 9,
+        "threshold_violated" => 10,
+        "control" => 11,
+        "remote" => 12,
+        "privilege_drop" => 13,
+        "attach" => 14,
+        _ => 1,
+    }
+}
+
+fn entry() -> Result<(), RunnerError> {
+    let command = Cli::command();
+    let matches = command.clone().get_matches();
+    let explicit = matches.get_one::<PathBuf>("config").map(PathBuf::as_path);
+    let extra = match config::load(explicit)? {
+        Some(table) => config::extra_args(&table, &command, &matches)?,
+        None => Vec::new(),
+    };
+    let cli = match matches.subcommand_name() {
+        Some(name) if !extra.is_empty() => Cli::parse_from(config::splice_args(
+            std::env::args_os().collect(),
+            name,
+            extra,
+        )),
+        _ => Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit()),
+    };
+    logging::init(
+        &cli.log_level,
+        cli.log_format,
+        cli.log_file.as_deref(),
+        matches!(cli.command, Commands::Tui(_)),
+    )?;
+
+    match cli.command {
+        Commands::Dump(args) => run_dump(*args),
+        Commands::Tui(args) => run_tui(args),
+        Commands::Completions(args) => run_completions(args),
+        Commands::Bench(args) => bench::run_bench(&args.sizes, args.ticks, args.map_type),
+        Commands::Renice(args) => renice::run_renice(
+            &args.map,
+            args.pid,
+            args.nice,
+            Duration::from_secs(args.interval),
+        ),
+        Commands::SetWeight(args) => weight::run_set_weight(
+            &args.map,
+            &args.cgroup,
+            args.weight,
+            args.samples,
+            Duration::from_secs(args.interval),
+        ),
+        Commands::SetDeadline(args) => run_set_deadline(args),
+        Commands::Agent(args) => remote::run_agent(args),
+        Commands::Check(args) => check::run_check(args),
+        Commands::Report(args) => report::run_report(args),
+        Commands::Attach(args) => attach::run_attach(args),
+        Commands::Serve(args) => run_serve(*args),
+    }
+}
+
+fn run_serve(args: ServeArgs) -> Result<(), RunnerError> {
+    let mut dump = args.dump;
+    if dump.watch {
+        return Err(RunnerError::Config(
+            "serve prints no tables; drop --watch".into(),
+        ));
+    }
+    dump.serve = Some(args.listen);
+    dump.quiet = true;
+    if dump.iterations.is_none() && dump.duration.is_none() {
+        dump.iterations = Some(0);
+    }
+    run_dump(dump)
+}
+
+fn run_set_deadline(args: SetDeadlineArgs) -> Result<(), RunnerError> {
+    let deadline = args.deadline.unwrap_or(args.period);
+    let change =
+        control::set_deadline(args.pid, args.runtime, deadline, args.period).map_err(|source| {
+            RunnerError::Control {
+                target: format!("pid {}", args.pid),
+                source,
+            }
+        })?;
+    let ms = |value: Duration| value.as_secs_f64() * 1000.0;
+    println!(
+        "pid {}: {} -> DEADLINE runtime {:.3} ms, deadline {:.3} ms, period {:.3} ms ({:.1}% of a CPU)",
+        change.pid,
+        change
+            .previous
+            .map_or_else(|| "?".to_string(), |attr| attr.label()),
+        ms(change.runtime),
+        ms(change.deadline),
+        ms(change.period),
+        change.runtime.as_secs_f64() / change.period.as_secs_f64() * 100.0
+    );
+    Ok(())
+}
+
+fn run_completions(args: CompletionsArgs) -> Result<(), RunnerError> {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    let mut stdout = io::stdout();
+    let shell = match args.target {
+        CompletionTarget::Bash => Shell::Bash,
+        CompletionTarget::Zsh => Shell::Zsh,
+        CompletionTarget::Fish => Shell::Fish,
+        CompletionTarget::Man => {
+            clap_mangen::Man::new(cmd).render(&mut stdout)?;
+            return Ok(());
+        }
+    };
+    clap_complete::generate(shell, &mut cmd, name, &mut stdout);
+    Ok(())
+}
+
+/// Single-threaded runtime: sampling, input, and signals are multiplexed on
+/// one thread; file sinks get a blocking worker (see `exports`).
+fn runtime() -> io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got `{value}`")),
+    }
+}
+
+fn parse_alert_priority(value: &str) -> Result<(Severity, u8), String> {
+    let (severity, priority) = parse_key_value(value)?;
+    Ok((severity.parse()?, journal::parse_priority(&priority)?))
+}
+
+/// Parse `--duration`: a plain number of seconds or a sequence of
+/// `<number><unit>` parts with units `us`, `ms`, `s`, `m`, `h`, `d` (e.g.
+/// `1h30m`).
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs)
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| format!("invalid duration `{value}`"));
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("missing unit after `{rest}` in `{value}`"))?;
+        let number: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("expected a number in `{value}`"))?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "us" => Duration::from_micros(1),
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(3600),
+            "d" => Duration::from_secs(86_400),
+            other => {
+                return Err(format!(
+                    "unknown unit `{other}` in `{value}` (use us, ms, s, m, h, d)"
+                ));
+            }
+        };
+        total = u32::try_from(number)
+            .ok()
+            .and_then(|number| unit.checked_mul(number))
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(|| format!("duration `{value}` is too long"))?;
+        rest = &rest[unit_len..];
+    }
+    if total.is_zero() {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Ok(total)
+}
+
+/// Parse `--rotate-size`: bytes, or a number with a K/M/G suffix (1024-based).
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B').trim_end_matches('I');
+    let (number, unit) = match digits.char_indices().last() {
+        Some((index, 'K')) => (&digits[..index], 1_u64 << 10),
+        Some((index, 'M')) => (&digits[..index], 1 << 20),
+        Some((index, 'G')) => (&digits[..index], 1 << 30),
+        _ => (digits, 1),
+    };
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size `{value}` (e.g. 500K, 100M, 2G)"))?;
+    match number.checked_mul(unit) {
+        Some(0) => Err("size must be greater than zero".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("size `{value}` is too large")),
+    }
+}
+
+/// Fixed-rate ticker anchored to its start, so work done in a tick does not
+/// push later samples back. Overruns skip ticks instead of bursting.
+fn ticker(period: Duration, immediate: bool) -> Interval {
+    let period = period.max(Duration::from_millis(1));
+    let start = if immediate {
+        Instant::now()
+    } else {
+        Instant::now() + period
+    };
+    let mut ticker = time::interval_at(start, period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+fn run_dump(args: DumpArgs) -> Result<(), RunnerError> {
+    runtime()?.block_on(dump_loop(args))
+}
+
+async fn dump_loop(args: DumpArgs) -> Result<(), RunnerError> {
+    let period = args.interval;
+    let demo = args.demo.to_config();
+    let account = args
+        .run_as_user
+        .as_deref()
+        .map(Account::lookup)
+        .transpose()?;
+    let throttling = args.throttle_util.is_some() || args.throttle_lateness.is_some();
+    if (args.enforce_policy.is_some() || args.rebalance || throttling || args.auto_nice)
+        && !args.remote.remote.is_empty()
+    {
+        return Err(RunnerError::Config(
+            "--enforce-policy, --rebalance, --throttle-*, and --auto-nice change the local \
+             host and cannot steer a --remote agent"
+                .to_string(),
+        ));
+    }
+    let streams = match &demo {
+        Some(config) => vec![SnapshotStream::demo(config, period, args.alpha)],
+        None if !args.remote.remote.is_empty() => args.remote.connect(period, args.alpha)?,
+        None => open_streams(&args.map, period, args.alpha)?,
+    };
+    if (args.enforce_policy.is_some() || args.rebalance || throttling || args.auto_nice)
+        && streams.len() > 1
+    {
+        return Err(RunnerError::Config(
+            "--enforce-policy, --rebalance, --throttle-*, and --auto-nice work on a single --map"
+                .to_string(),
+        ));
+    }
+    let throttle_tickets = throttling && args.throttle_action == ThrottleAction::Tickets;
+    if args.run_as_user.is_some() && (args.auto_nice || (throttling && !throttle_tickets)) {
+        return Err(RunnerError::Config(
+            "--auto-nice and --throttle-action other than tickets change other users' tasks \
+             and need root; they cannot be combined with --run-as-user"
+                .to_string(),
+        ));
+    }
+    if throttle_tickets && (args.enforce_policy.is_some() || args.rebalance) {
+        return Err(RunnerError::Config(
+            "--throttle-action tickets would fight --enforce-policy/--rebalance over the override map"
+                .to_string(),
+        ));
+    }
+    if throttling && demo.is_some() && !throttle_tickets {
+        return Err(RunnerError::Config(format!(
+            "--throttle-action {} acts on real processes; use tickets with --demo",
+            args.throttle_action
+        )));
+    }
+    let mut enforcer = match &args.enforce_policy {
+        Some(path) => Some(PolicyEnforcer::open(path)?),
+        None => None,
+    };
+    let mut rebalancer = args.rebalance.then(|| {
+        Rebalancer::new(RebalanceConfig {
+            kp: args.rebalance_kp,
+            ki: args.rebalance_ki,
+            max_step: args.rebalance_step.clamp(0.0, 1.0),
+        })
+    });
+    let what_if = match (&args.what_if, &args.what_if_file) {
+        (Some(what_if), _) => Some(what_if.clone()),
+        (None, Some(path)) => Some(WhatIf::load(path)?),
+        (None, None) => None,
+    };
+    let audit = args.audit_log.is_some();
+    if what_if.is_some() && args.format != OutputFormat::Table {
+        return Err(RunnerError::Config(
+            "--what-if only prints tables; it needs --format table".to_string(),
+        ));
+    }
+    let mut auto_nice = args.auto_nice.then(|| {
+        AutoNice::new(AutoNiceConfig {
+            windows: args.auto_nice_windows,
+            range: args.auto_nice_range,
+            floor: args.auto_nice_floor,
+        })
+    });
+    // Demo and remote cgroups are not this host's.
+    let mut weights =
+        (demo.is_none() && args.remote.remote.is_empty()).then(WeightWatcher::default);
+    let mut throttler = throttling.then(|| {
+        Throttler::new(ThrottleConfig {
+            max_utilization: args.throttle_util,
+            max_lateness_ms: args.throttle_lateness,
+            windows: args.throttle_windows,
+            action: args.throttle_action,
+            duty: args.throttle_duty,
+        })
+    });
+    let writes_overrides =
+        enforcer.is_some() || (args.rebalance && !args.dry_run) || throttle_tickets;
+    let streams = if writes_overrides {
+        streams
+            .into_iter()
+            .map(|stream| stream.with_ticket_overrides(&args.override_map))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        streams
+    };
+    let selector = TaskSelector::new(args.pid.clone(), args.pid_file.clone(), args.comm.clone());
+    let selecting = !selector.is_empty();
+    let mut streams: Vec<SnapshotStream> = streams
+        .into_iter()
+        .map(|stream| {
+            stream
+                .with_selector(selector.clone())
+                // These act on the whole map, whatever is selected.
+                .with_unselected(enforcer.is_some() || what_if.is_some() || audit)
+                .with_rolling_limits(args.rolling_ttl, args.rolling_capacity)
+                .with_reread(args.reread_inconsistent)
+                .with_starvation_windows(args.starve_windows)
+                .with_cgroups(args.cgroups)
+                .with_clock(args.clock)
+        })
+        .map(|stream| {
+            if args.events.is_empty() {
+                stream
+            } else {
+                stream.with_events(args.events.clone())
+            }
+        })
+        .collect();
+    let multi = streams.len() > 1;
+    // Before the sampler threads start, so they inherit the kept CAP_BPF,
+    // and before any output file is created.
+    if let Some(account) = &account {
+        for stream in &mut streams {
+            stream.prepare_privilege_drop();
+        }
+        drop_privileges(account, demo.is_none() && args.remote.remote.is_empty())?;
+    }
+    let mut samplers = Samplers::new(streams)?;
+    let tables = args.format == OutputFormat::Table && !args.quiet;
+    let csv_format = CsvFormat::new(args.csv_delimiter, args.csv_decimal)
+        .map_err(|err| RunnerError::Config(err.to_string()))?;
+    let rotation = Rotation {
+        max_bytes: args.rotate_size,
+        interval: args.rotate_interval,
+        max_files: args.rotate_keep,
+    };
+    let mut exports = Exports::default();
+    let compression = |path: &Path| {
+        args.compress
+            .unwrap_or_else(|| Compression::from_path(path))
+    };
+    if let Some(path) = &args.output {
+        let columns = csv_columns(&args.columns, args.wide);
+        let columns = if args.cgroups {
+            let mut columns = columns.unwrap_or_else(|| CSV_COLUMNS.to_vec());
+            for name in ["entity", "cgroup_id", "cgroup"] {
+                if !columns.contains(&name) {
+                    columns.push(name);
+                }
+            }
+            Some(columns)
+        } else {
+            columns
+        };
+        // Timestamps on a boot-relative clock are meaningless without it.
+        let columns = if args.clock != Clock::Realtime {
+            let mut columns = columns.unwrap_or_else(|| CSV_COLUMNS.to_vec());
+            if !columns.contains(&"clock") {
+                columns.push("clock");
+            }
+            Some(columns)
+        } else {
+            columns
+        };
+        let columns = if args.summary_records {
+            let mut columns = columns.unwrap_or_else(|| CSV_COLUMNS.to_vec());
+            columns.extend(CSV_SUMMARY_COLUMNS);
+            Some(columns)
+        } else {
+            columns
+        };
+        exports.register(
+            match columns {
+                Some(columns) => CsvExporter::with_columns(
+                    path,
+                    csv_format,
+                    rotation,
+                    compression(path),
+                    &columns,
+                ),
+                None => CsvExporter::open(path, csv_format, rotation, compression(path)),
+            }
+            .map_err(exports::failed("csv"))?,
+        );
+    }
+    if let Some(path) = &args.json_output {
+        exports.register(
+            JsonExporter::open(path, rotation, compression(path))
+                .map_err(exports::failed("ndjson"))?
+                .with_summary_records(args.summary_records),
+        );
+    }
+    if args.format == OutputFormat::Ndjson {
+        exports.register(StdoutJsonExporter::default().with_summary_records(args.summary_records));
+    }
+    if let Some(addr) = args.serve {
+        exports.register(
+            serve::start(addr, args.serve_origin.clone())
+                .await
+                .map_err(exports::failed("serve"))?,
+        );
+    }
+    if let Some(brokers) = &args.kafka_brokers {
+        #[cfg(feature = "kafka")]
+        exports.register(
+            kafka::KafkaExporter::new(
+                brokers,
+                &args.kafka_topic,
+                args.kafka_format,
+                &args.kafka_option,
+            )
+            .map_err(exports::failed("kafka"))?,
+        );
+        #[cfg(not(feature = "kafka"))]
+        return Err(RunnerError::Config(format!(
+            "--kafka-brokers {brokers}: this build has no Kafka support (enable the `kafka` feature)"
+        )));
+    }
+    if let Some(broker) = &args.mqtt_broker {
+        exports.register(
+            MqttExporter::connect(&MqttSettings {
+                broker,
+                topic: &args.mqtt_topic,
+                qos: args.mqtt_qos,
+                retain: args.mqtt_retain,
+                per_task: args.mqtt_per_task,
+                client_id: args.mqtt_client_id.as_deref(),
+                username: args.mqtt_username.as_deref(),
+                password_file: args.mqtt_password_file.as_deref(),
+            })
+            .map_err(exports::failed("mqtt"))?,
+        );
+    }
+    if let Some(addr) = &args.statsd {
+        exports.register(
+            StatsdExporter::new(
+                addr,
+                &args.statsd_prefix,
+                args.statsd_tags,
+                args.statsd_per_pid,
+            )
+            .map_err(exports::failed("statsd"))?,
+        );
+    }
+    if let Some(path) = &args.audit_log {
+        exports.register(
+            AuditLog::open(path, rotation, compression(path)).map_err(exports::failed("audit"))?,
+        );
+    }
+    if let Some(path) = &args.trace_output {
+        exports.register(TraceExporter::new(path));
+    }
+    if args.deadline_warn > 0.0 {
+        exports.register(DeadlineAlerts::new(args.deadline_warn));
+    }
+    if args.latency_warn > 0.0 {
+        exports.register(LatencyAlerts::new(args.latency_warn));
+    }
+    if args.starve_windows > 0 {
+        exports.register(StarvationAlerts::new(args.starve_windows));
+    }
+    if args.inflation_warn > 0.0 {
+        exports.register(InflationAlerts::new(
+            args.inflation_warn / 100.0,
+            args.inflation_windows,
+        ));
+    }
+    if let Some(target) = args.alert_log {
+        let mut priorities = PriorityMap::default();
+        for &(severity, priority) in &args.alert_priority {
+            priorities.set(severity, priority);
+        }
+        let mut alert_log = AlertLog::connect(
+            target,
+            &args.alert_identifier,
+            priorities,
+            args.deadline_warn,
+        )
+        .map_err(exports::failed("alert-log"))?
+        .with_starvation(args.starve_windows);
+        if args.inflation_warn > 0.0 {
+            alert_log =
+                alert_log.with_inflation(args.inflation_warn / 100.0, args.inflation_windows);
+        }
+        exports.register(alert_log);
+    }
+    if let Some(command) = &args.on_alert {
+        if args.deadline_warn <= 0.0 {
+            return Err(RunnerError::Config(
+                "--on-alert needs a --deadline-warn threshold".to_string(),
+            ));
+        }
+        exports.register(AlertHook::new(
+            command.clone(),
+            args.deadline_warn,
+            args.alert_cooldown,
+            args.alert_timeout,
+        ));
+    }
+    let (export_tx, export_worker) = exports.spawn();
+    let mut ticks = ticker(period, false);
+    let shutdown = shutdown::signalled();
+    tokio::pin!(shutdown);
+    let mut lottery = Lottery {
+        rng: match args.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        },
+        ranking: Ranking::default(),
+    };
+    let mut summaries: Vec<SummaryBuilder> = samplers
+        .labels()
+        .iter()
+        .map(|_| SummaryBuilder::new())
+        .collect();
+    let mut previous: Vec<HashMap<u32, TaskSnapshot>> =
+        samplers.labels().iter().map(|_| HashMap::new()).collect();
+    let mut horizons: Vec<SketchWindow> = samplers
+        .labels()
+        .iter()
+        .map(|_| SketchWindow::new(args.quantile_horizon as usize))
+        .collect();
+    // Errors and signals end the loop but still fall through to the
+    // trace/summary finalization below.
+    let mut failure: Option<RunnerError> = None;
+
+    let limit = match (args.iterations, args.duration) {
+        (Some(0), _) => None,
+        (Some(iterations), _) => Some(iterations),
+        (None, Some(_)) => None,
+        (None, None) => Some(10),
+    };
+    let deadline = time::sleep(args.duration.unwrap_or(Duration::MAX));
+    tokio::pin!(deadline);
+    let mut taken = 0_u32;
+    let mut filter = args.filter.clone();
+    let (control_tx, mut control_rx) = mpsc::channel(8);
+    let _control_socket = match &args.control_socket {
+        Some(path) => Some(ctl::listen(path, control_tx).map_err(|err| {
+            RunnerError::Config(format!("--control-socket {}: {err}", path.display()))
+        })?),
+        None => None,
+    };
+
+    'ticks: while limit.is_none_or(|limit| taken < limit) {
+        taken += 1;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => break,
+                _ = &mut deadline, if args.duration.is_some() => {
+                    debug!(target: "sampler", "capture duration elapsed");
+                    break 'ticks;
+                }
+                signalled = &mut shutdown => {
+                    if let Err(err) = signalled {
+                        failure = Some(err.into());
+                    } else {
+                        info!(target: "sampler", "interrupted, finalizing exports");
+                    }
+                    break 'ticks;
+                }
+                Some(request) = control_rx.recv(), if args.control_socket.is_some() => {
+                    let answer = match request.command {
+                        ControlCommand::Interval(interval) => {
+                            samplers.set_interval(interval).await;
+                            ticks = ticker(interval, false);
+                            info!(target: "sampler", ?interval, "sampling interval changed");
+                            Ok(format!("interval {interval:?}"))
+                        }
+                        ControlCommand::Alpha(alpha) => {
+                            samplers.each(move |stream| stream.set_alpha(alpha)).await;
+                            info!(target: "sampler", alpha, "EWMA alpha changed");
+                            Ok(format!("alpha {alpha}"))
+                        }
+                        ControlCommand::Filter(changes) => {
+                            for change in changes {
+                                change.apply(&mut filter);
+                            }
+                            Ok(format!("filter {}", filter.describe()))
+                        }
+                        ControlCommand::Snapshot => {
+                            ticks.reset_immediately();
+                            Ok(format!("sampling iteration {taken} now"))
+                        }
+                        ControlCommand::Gc => {
+                            let released: usize =
+                                samplers.each(SnapshotStream::collect_garbage).await.into_iter().sum();
+                            Ok(format!("released {released} cached entries"))
+                        }
+                        ControlCommand::Status => {
+                            let tracked: usize =
+                                samplers.each(|stream| stream.tracked_pids()).await.into_iter().sum();
+                            let alpha = samplers.each(|stream| stream.alpha()).await;
+                            let access: Vec<&str> = samplers
+                                .each(|stream| stream.map_access())
+                                .await
+                                .into_iter()
+                                .map(|access| access.map_or("-", MapAccess::as_str))
+                                .collect();
+                            Ok(format!(
+                                "iterations {} interval {:?} alpha {} tracked pids {tracked} access {} filter {}",
+                                taken - 1,
+                                samplers.interval(),
+                                alpha.first().copied().unwrap_or(args.alpha),
+                                access.join(","),
+                                filter.describe()
+                            ))
+                        }
+                    };
+                    // The client may have hung up already.
+                    let _ = request.reply.send(answer);
+                }
+            }
+        }
+
+        // Read every map before reporting so all windows of a tick share
+        // one timestamp.
+        let windows = match samplers.sample().await {
+            Ok(windows) => windows,
+            Err(err) => {
+                failure = Some(err);
+                break 'ticks;
+            }
+        };
+        // Only a map that answered can be called empty: a tick where every
+        // sampler is still busy proves nothing. An empty selection may just
+        // mean the process is not up yet.
+        if windows.iter().any(Option::is_some)
+            && windows
+                .iter()
+                .flatten()
+                .all(|window| window.snapshots.is_empty())
+            && !selecting
+        {
+            warn!(target: "sampler", maps = ?args.map, "no task statistics in the map (is the BPF program loaded?)");
+            break;
+        }
+        let Some(timestamp) = windows
+            .iter()
+            .flatten()
+            .map(|window| window.timestamp)
+            .next()
+        else {
+            continue;
+        };
+        // The controllers need a single source, which is sampled inline and
+        // always has a window.
+        let controlled: &[TaskSnapshot] =
+            windows[0].as_ref().map_or(&[], |window| &window.snapshots);
+        let policy_events = match (enforcer.as_mut(), &windows[0]) {
+            (Some(enforcer), Some(window)) => {
+                match enforcer.reconcile(steered(&mut samplers), window) {
+                    Ok(events) => events,
+                    Err(err) => {
+                        failure = Some(err);
+                        break;
+                    }
+                }
+            }
+            _ => Vec::new(),
+        };
+        let adjustments = match rebalancer.as_mut() {
+            Some(rebalancer) => {
+                let adjustments = rebalancer.step(controlled);
+                if !args.dry_run
+                    && let Err(err) = rebalancer.apply(steered(&mut samplers), &adjustments)
+                {
+                    failure = Some(err);
+                    break;
+                }
+                adjustments
+            }
+            None => Vec::new(),
+        };
+        let throttle_events = match throttler.as_mut() {
+            Some(throttler) => match throttler.step(steered(&mut samplers), controlled) {
+                Ok(events) => events,
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            },
+            None => Vec::new(),
+        };
+        for event in &throttle_events {
+            warn!(
+                target: "sampler",
+                pid = event.pid,
+                comm = event.comm.as_deref().unwrap_or("-"),
+                action = %event.action,
+                state = ?event.state,
+                reason = %event.reason,
+                "{}",
+                event.detail
+            );
+        }
+        let mut events = match auto_nice.as_mut() {
+            Some(auto_nice) => match auto_nice.step(steered(&mut samplers), controlled) {
+                Ok(events) => events,
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            },
+            None => Vec::new(),
+        };
+        if let Some(weights) = weights.as_mut() {
+            events.extend(weights.step(controlled));
+        }
+        events.extend(policy_events);
+        if !args.dry_run {
+            events.extend(adjustments.iter().map(Adjustment::change_event));
+        }
+        // Logged above with their own fields.
+        events.extend(throttle_events.iter().map(ThrottleEvent::change_event));
+        for event in events.iter().filter(|event| !has_own_table(event)) {
+            info!(
+                target: "sampler",
+                pid = event.pid,
+                source = event.source,
+                "{} {} -> {}: {}",
+                event.field,
+                event.from,
+                event.to,
+                event.reason
+            );
+        }
+
+        if tables && args.watch {
+            // Best effort: a failed clear only leaves the previous frame visible.
+            let _ = execute!(io::stdout(), Clear(ClearType::All), MoveTo(0, 0));
+        }
+        for (((window, summary), previous), horizon) in windows
+            .into_iter()
+            .zip(summaries.iter_mut())
+            .zip(previous.iter_mut())
+            .zip(horizons.iter_mut())
+        {
+            let Some(window) = window else {
+                continue;
+            };
+            let SnapshotWindow {
+                map,
+                index,
+                total_tickets,
+                window_ms,
+                snapshots,
+                unselected,
+                consistent,
+                clock,
+                ktime_offset_s,
+                cpus,
+                unchanged_windows,
+                runqueue,
+                irq,
+                cgroups,
+                ..
+            } = window;
+            let iteration = index as u32;
+            let mut heading = if multi {
+                format!("Iteration {} [{map}]", iteration + 1)
+            } else {
+                format!("Iteration {}", iteration + 1)
+            };
+            if !consistent {
+                heading.push_str(" (map changed while read)");
+            }
+            heading.push_str(&format!(
+                " (CPU {:.1}% of {cpus})",
+                system_utilization(&snapshots, window_ms, cpus) * 100.0
+            ));
+            if let Some(depth) = &runqueue {
+                heading.push_str(&format!(
+                    " (run queue {:.2} avg / {:.2} max per CPU{})",
+                    depth.mean(),
+                    depth.max(),
+                    if depth.estimated {
+                        ", from loadavg"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            if let Some(irq) = &irq {
+                heading.push_str(&format!(
+                    " (irq {:.1}% + softirq {:.1}% of CPU time",
+                    irq.irq_mean() * 100.0,
+                    irq.softirq_mean() * 100.0
+                ));
+                if let Some((cpu, share)) = irq.busiest().filter(|_| cpus > 1) {
+                    heading.push_str(&format!(", most on cpu{cpu}: {:.1}%", share * 100.0));
+                }
+                heading.push(')');
+            }
+            let resets = snapshots.iter().filter(|snap| snap.counter_reset).count();
+            if resets > 0 {
+                heading.push_str(&format!(" (counters reset for {resets} tasks)"));
+            }
+            if unchanged_windows >= STALE_WINDOWS {
+                heading.push_str(&format!(
+                    " (STALE: no counter moved in {unchanged_windows} windows; BPF program detached?)"
+                ));
+            }
+            if snapshots.is_empty() {
+                if selecting {
+                    warn!(target: "sampler", map = %map, "no tasks match --pid/--pid-file/--comm");
+                } else {
+                    warn!(target: "sampler", map = %map, "no task statistics in the map");
+                }
+                continue;
+            }
+            // Projected over the whole map, like the sampler's own shares,
+            // then narrowed to the selected tasks.
+            let population: Vec<TaskSnapshot> = match &what_if {
+                Some(_) => snapshots.iter().chain(&unselected).cloned().collect(),
+                None => Vec::new(),
+            };
+            let what_if_report = what_if.as_ref().filter(|_| tables).map(|what_if| {
+                let selected: HashSet<u32> = snapshots.iter().map(|snap| snap.pid).collect();
+                let mut projections = what_if.project(&population);
+                projections.retain(|projection| selected.contains(&projection.pid));
+                let mut projected = what_if.apply(&population);
+                projected.retain(|snap| selected.contains(&snap.pid));
+                (projections, projected)
+            });
+            if let Some(what_if) = &what_if
+                && index == 0
+            {
+                for pid in what_if.missing(&population) {
+                    warn!(target: "sampler", map = %map, pid, "--what-if pid is not in the map");
+                }
+            }
+            let (snapshots, filtered_out) = if audit {
+                let all = snapshots.clone();
+                let shown = filter.apply(snapshots);
+                let kept: HashSet<u32> = shown.iter().map(|snap| snap.pid).collect();
+                let hidden = all.into_iter().filter(|snap| !kept.contains(&snap.pid));
+                (shown, hidden.chain(unselected).collect())
+            } else {
+                (filter.apply(snapshots), Vec::new())
+            };
+            summary.record(&snapshots);
+            summary.record_supply(total_tickets);
+            let lateness_horizon = (args.quantile_horizon > 0).then(|| {
+                horizon.push(snapshots.iter().map(|snap| snap.lateness_ms).collect());
+                (horizon.len(), horizon.merged())
+            });
+            if tables {
+                print_iteration(
+                    &args,
+                    &Iteration {
+                        heading: &heading,
+                        total_tickets,
+                        window_ms,
+                        snapshots: &snapshots,
+                        cgroups: &cgroups,
+                    },
+                    // Piped output gets no escape codes to strip.
+                    (args.watch && io::stdout().is_terminal()).then_some(&*previous),
+                    &mut lottery,
+                );
+                if args.rebalance {
+                    print_adjustments(&adjustments, args.dry_run);
+                }
+                if !throttle_events.is_empty() {
+                    print_throttle_events(&throttle_events);
+                }
+                print_change_events(&events);
+                if args.priority_every > 0
+                    && !args.watch
+                    && (index + 1) % u64::from(args.priority_every) == 0
+                {
+                    print_priority_report(&PriorityReport::of(&snapshots));
+                }
+                if let Some((projections, projected)) = &what_if_report {
+                    print_what_if(projections);
+                    if args.simulate_draws > 0 {
+                        let draws = lottery.draw(projected, args.simulate_draws);
+                        print_draw_results(args.simulate_draws, &draws, projected);
+                    }
+                }
+            }
+            if args.watch {
+                *previous = snapshots.iter().map(|s| (s.pid, s.clone())).collect();
+            }
+
+            let batch = SnapshotBatch {
+                map,
+                iteration,
+                timestamp,
+                total_tickets,
+                window_ms,
+                snapshots,
+                filtered_out,
+                // Controllers need a single map, so events belong to it.
+                events: std::mem::take(&mut events),
+                consistent,
+                clock,
+                ktime_offset_s,
+                cpus,
+                unchanged_windows,
+                runqueue,
+                irq,
+                lateness_horizon,
+                cgroups,
+            };
+            // A closed queue means the worker hit an error; it surfaces below.
+            if export_tx.send(batch).await.is_err() {
+                break 'ticks;
+            }
+        }
+    }
 
-#[derive(Args, Clone)]
-struct TuiArgs {
-    /// Path to the pinned task map
-    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
-    map: String,
+    if let Some(enforcer) = enforcer.as_mut()
+        && let Err(err) = enforcer.release(steered(&mut samplers))
+    {
+        warn!(target: "map", "cannot remove ticket overrides: {err}");
+    }
+    if let Some(rebalancer) = rebalancer.as_mut()
+        && let Err(err) = rebalancer.release(steered(&mut samplers))
+    {
+        warn!(target: "map", "cannot remove ticket overrides: {err}");
+    }
+    if let Some(auto_nice) = auto_nice.as_mut()
+        && let Err(err) = auto_nice.release(steered(&mut samplers))
+    {
+        warn!(target: "map", "cannot restore nice values: {err}");
+    }
+    if let Some(throttler) = throttler.as_mut()
+        && let Err(err) = throttler.release(steered(&mut samplers))
+    {
+        warn!(target: "map", "cannot lift throttling: {err}");
+    }
+    drop(export_tx);
+    let exported = export_worker
+        .await
+        .unwrap_or_else(|err| Err(exports::failed("worker")(io::Error::other(err))));
+    match exported {
+        // The reader of `--format ndjson` went away (e.g. `| head`).
+        Err(RunnerError::Export { source, .. }) if source.kind() == io::ErrorKind::BrokenPipe => {
+            debug!(target: "exporter", "stdout closed, stopping");
+        }
+        Err(err) if failure.is_none() => failure = Some(err),
+        _ => {}
+    }
 
-    /// Refresh period in milliseconds
-    #[arg(long, default_value_t = 1000)]
-    refresh_ms: u64,
+    let mut reports = BTreeMap::new();
+    let mut violations = Vec::new();
+    for (label, summary) in samplers.labels().iter().zip(&summaries) {
+        if summary.iterations() == 0 {
+            continue;
+        }
+        let report = summary.finish();
+        if tables {
+            if multi {
+                println!("\n[{label}]");
+            }
+            report.print(args.top);
+        }
+        for violation in threshold_violations(&args, &report) {
+            warn!(target: "sampler", map = %label, "{violation}");
+            violations.push(if multi {
+                format!("[{label}] {violation}")
+            } else {
+                violation
+            });
+        }
+        reports.insert(label.clone(), report);
+    }
+    if let Some(path) = &args.summary_output
+        && !reports.is_empty()
+    {
+        // A single map keeps the flat report; several are keyed by label.
+        if multi {
+            let data = serde_json::to_string_pretty(&reports).map_err(io::Error::other)?;
+            std::fs::write(path, data)?;
+        } else if let Some(report) = reports.values().next() {
+            report.write_json(path)?;
+        }
+        info!(target: "exporter", path = %path.display(), "summary written");
+    }
 
-    /// EWMA smoothing factor for rolling runtime (0-1)
-    #[arg(long, default_value_t = 0.5)]
-    alpha: f64,
+    if args.quiet && failure.is_none() && !violations.is_empty() {
+        failure = Some(RunnerError::ThresholdViolated(violations.join("; ")));
+    }
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
 
-    /// How many tasks to show in the dashboard table
-    #[arg(long, default_value_t = 10)]
-    top: usize,
+/// `--deadline-warn` / `--min-fairness` breaches over a whole run.
+fn threshold_violations(args: &DumpArgs, report: &RunSummary) -> Vec<String> {
+    let mut violations = Vec::new();
+    if args.deadline_warn > 0.0 && report.worst_lateness_ms > args.deadline_warn {
+        violations.push(format!(
+            "worst lateness {:.3} ms exceeds --deadline-warn {:.3} ms",
+            report.worst_lateness_ms, args.deadline_warn
+        ));
+    }
+    // An idle run has no achieved shares to judge.
+    if let Some(min) = args.min_fairness
+        && report.total_runtime_ns > 0
+        && report.fairness_index < min
+    {
+        violations.push(format!(
+            "fairness index {:.4} below --min-fairness {min:.4}",
+            report.fairness_index
+        ));
+    }
+    violations
 }
 
-fn main() {
-    if let Err(err) = entry() {
-        eprintln!("Error: {err}");
-        std::process::exit(1);
+/// The `--rebalance` ticket changes made (or, with `--dry-run`, proposed)
+/// this interval.
+fn print_adjustments(adjustments: &[Adjustment], dry_run: bool) {
+    let verb = if dry_run { "proposed" } else { "applied" };
+    if adjustments.is_empty() {
+        println!("\nRebalance: no ticket changes {verb}.");
+        return;
+    }
+    println!("\nRebalance ({} changes {verb}):", adjustments.len());
+    println!(
+        "{:<8} {:>8} {:>10} {:>8} {:>8}",
+        "PID", "TARGET%", "ACHIEVED%", "FROM", "TO"
+    );
+    for adjustment in adjustments {
+        println!(
+            "{:<8} {:>8.2} {:>10.2} {:>8} {:>8}",
+            adjustment.pid,
+            adjustment.target_share * 100.0,
+            adjustment.achieved_share * 100.0,
+            adjustment.from,
+            adjustment.to
+        );
     }
 }
 
-fn entry() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
+/// Throttling and rebalancing changes, which are logged and printed in
+/// tables of their own.
+fn has_own_table(event: &ChangeEvent) -> bool {
+    matches!(event.source, "throttle" | "rebalance")
+}
 
-    match cli.command {
-        Commands::Dump(args) => run_dump(args),
-        Commands::Tui(args) => run_tui(args),
+/// Nice/ticket, policy, and cgroup weight changes made this interval, one
+/// line each.
+fn print_change_events(events: &[ChangeEvent]) {
+    for event in events.iter().filter(|event| !has_own_table(event)) {
+        println!(
+            "{} pid {}: {} {} -> {} ({})",
+            event.source, event.pid, event.field, event.from, event.to, event.reason
+        );
     }
 }
 
-fn run_dump(args: DumpArgs) -> Result<(), Box<dyn Error>> {
-    let fd = open_pinned_map(&args.map)?;
-    let map_fd = unsafe { OwnedFd::from_raw_fd(fd) };
-    let mut writer = match args.output {
-        Some(path) => Some(prepare_csv(&path)?),
-        None => None,
-    };
-    let mut json_writer = match args.json_output {
-        Some(path) => Some(prepare_json(&path)?),
-        None => None,
-    };
-    let mut rolling = RollingStats::new(args.alpha);
-    let mut rng = match args.seed {
-        Some(seed) => StdRng::seed_from_u64(seed),
-        None => StdRng::from_entropy(),
-    };
-    let mut trace_events: Vec<TraceEvent> = Vec::new();
-    let mut trace_start_ts: Option<f64> = None;
+/// Projected `--what-if` shares for every task, largest change first.
+fn print_what_if(projections: &[Projection]) {
+    println!("\nWhat-if (nothing written to the map):");
+    println!(
+        "{:<8} {:<16} {:>8} {:>8} {:>9} {:>9} {:>9}",
+        "PID", "COMM", "TICKETS", "WHAT_IF", "SHARE%", "PROJ%", "CHANGE"
+    );
+    for projection in projections {
+        println!(
+            "{:<8} {:<16} {:>8} {:>8} {:>9.2} {:>9.2} {:>+9.2}",
+            projection.pid,
+            projection.comm.as_deref().unwrap_or("-"),
+            projection.tickets_before,
+            projection.tickets_after,
+            projection.share_before * 100.0,
+            projection.share_after * 100.0,
+            projection.share_change() * 100.0
+        );
+    }
+}
 
-    for iteration in 0..args.iterations {
-        if args.interval > 0 {
-            thread::sleep(Duration::from_secs(args.interval));
-        }
+/// Throttling corrections started or lifted this interval.
+fn print_throttle_events(events: &[ThrottleEvent]) {
+    println!("\nThrottle ({} actions):", events.len());
+    println!(
+        "{:<8} {:<16} {:<9} {:<8} {:<40} DETAIL",
+        "PID", "COMM", "STATE", "ACTION", "REASON"
+    );
+    for event in events {
+        let state = match event.state {
+            ThrottleState::Throttled => "throttled",
+            ThrottleState::Released => "released",
+        };
+        println!(
+            "{:<8} {:<16} {:<9} {:<8} {:<40} {}",
+            event.pid,
+            event.comm.as_deref().unwrap_or("-"),
+            state,
+            event.action,
+            event.reason,
+            event.detail
+        );
+    }
+}
 
-        let entries = iterate_task_info(map_fd.as_raw_fd())?;
-        if entries.is_empty() {
-            println!("No task statistics available in the map (is the BPF program loaded?).");
-            return Ok(());
-        }
+/// The single stream the controllers steer; they are rejected when several
+/// sources are sampled.
+fn steered(samplers: &mut Samplers) -> &mut SnapshotStream {
+    samplers
+        .single()
+        .expect("controllers run on a single source")
+}
 
-        let window_ms = if args.interval == 0 {
-            1.0
-        } else {
-            (args.interval as f64).max(0.001) * 1000.0
-        };
-        let total_tickets: u64 = entries.iter().map(|(_, info)| info.tickets as u64).sum();
-        let snapshots = enrich_entries(&entries, total_tickets, &mut rolling, window_ms);
-        let timestamp = now_secs();
-        if trace_start_ts.is_none() {
-            trace_start_ts = Some(timestamp);
-        }
-        print_table(iteration, total_tickets, &snapshots);
-
-        if !snapshots.is_empty() {
-            let mut ranking = snapshots.clone();
-            ranking.sort_by(|a, b| {
-                b.ticket_share
-                    .partial_cmp(&a.ticket_share)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
-            print_lottery_summary(&ranking, args.top);
-            if args.simulate_draws > 0 {
-                let draws = simulate_lottery_draws(&mut rng, &ranking, args.simulate_draws);
-                print_draw_results(args.simulate_draws, &draws, &ranking);
+/// Open every `--map`, falling back to full paths as labels when two pins
+/// share a file name.
+fn open_streams(
+    paths: &[String],
+    interval: Duration,
+    alpha: f64,
+) -> Result<Vec<SnapshotStream>, RunnerError> {
+    let streams = paths
+        .iter()
+        .map(|path| SnapshotStream::open(path, interval, alpha))
+        .collect::<Result<Vec<_>, _>>()?;
+    let labels: Vec<String> = streams.iter().map(|s| s.label().to_string()).collect();
+    let duplicate = |label: &str| {
+        labels
+            .iter()
+            .filter(|other| other.as_str() == label)
+            .count()
+            > 1
+    };
+    Ok(streams
+        .into_iter()
+        .zip(paths)
+        .map(|(stream, path)| {
+            if duplicate(stream.label()) {
+                stream.with_label(path.clone())
+            } else {
+                stream
             }
-            print_edf_summary(&ranking, args.top);
-        }
+        })
+        .collect())
+}
 
-        if args.deadline_warn > 0.0 {
-            emit_deadline_alerts(args.deadline_warn, &snapshots);
-        }
+/// Switch to `account` once the sources are open, keeping CAP_BPF only when
+/// a local map is read.
+fn drop_privileges(account: &Account, local_map: bool) -> Result<(), RunnerError> {
+    privilege::drop_to(account, local_map)?;
+    info!(
+        user = account.name,
+        uid = account.uid,
+        gid = account.gid,
+        "dropped root privileges"
+    );
+    Ok(())
+}
 
-        if let Some(file) = writer.as_mut() {
-            write_csv(file, iteration, timestamp, &snapshots)?;
+fn run_tui(args: TuiArgs) -> Result<(), RunnerError> {
+    let refresh = Duration::from_millis(args.refresh_ms.max(100));
+    if args.remote.remote.len() > 1 {
+        return Err(RunnerError::Config(
+            "tui shows a single --remote agent".to_string(),
+        ));
+    }
+    let account = args
+        .run_as_user
+        .as_deref()
+        .map(Account::lookup)
+        .transpose()?;
+    let demo = args.demo.demo;
+    let mut demo_config = args.demo.to_config();
+    // Both sides of a demo comparison must draw the same pids.
+    if args.compare.is_some()
+        && let Some(config) = demo_config.as_mut()
+    {
+        config.seed.get_or_insert_with(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+    }
+    let stream = match demo_config.clone() {
+        Some(config) => SnapshotStream::demo(&config, refresh, args.alpha),
+        None if !args.remote.remote.is_empty() => {
+            args.remote.connect(refresh, args.alpha)?.remove(0)
         }
-        if let Some(file) = json_writer.as_mut() {
-            write_json(file, iteration, timestamp, total_tickets, &snapshots)?;
+        None => SnapshotStream::open(&args.map, refresh, args.alpha)?,
+    }
+    .with_starvation_windows(args.starve_windows)
+    .with_cgroups(args.cgroups);
+    let mut stream = if args.events.is_empty() {
+        stream
+    } else {
+        stream.with_events(args.events.clone())
+    };
+    let mut baseline = match (&args.compare, demo_config) {
+        (None, _) => None,
+        (Some(_), Some(config)) => {
+            let config = DemoConfig {
+                level_nice: true,
+                ..config
+            };
+            Some(SnapshotStream::demo(&config, refresh, args.alpha).with_label("demo-uniform"))
         }
-        if args.trace_output.is_some() {
-            let rel_ts = timestamp - trace_start_ts.unwrap_or(timestamp);
-            collect_trace_events(&mut trace_events, iteration, rel_ts, &snapshots);
+        (Some(path), None) => {
+            let baseline = SnapshotStream::open(path, refresh, args.alpha)?;
+            Some(if baseline.label() == stream.label() {
+                baseline.with_label(path.clone())
+            } else {
+                baseline
+            })
         }
+    };
+    if let Some(account) = &account {
+        stream.prepare_privilege_drop();
+        if let Some(baseline) = baseline.as_mut() {
+            baseline.prepare_privilege_drop();
+        }
+        drop_privileges(account, !demo && args.remote.remote.is_empty())?;
     }
-
-    if let Some(path) = args.trace_output {
-        flush_trace(&path, &trace_events)?;
-    }
-
-    Ok(())
-}
-
-fn run_tui(args: TuiArgs) -> Result<(), Box<dyn Error>> {
-    let fd = open_pinned_map(&args.map)?;
-    let map_fd = unsafe { OwnedFd::from_raw_fd(fd) };
-
-    enable_raw_mode()?;
+    let label = stream.label().to_string();
+    let labels = [
+        label.clone(),
+        baseline
+            .as_ref()
+            .map(|baseline| baseline.label().to_string())
+            .unwrap_or_default(),
+    ];
+    let runtime = runtime()?;
+
+    // Leave raw mode/alternate screen even if rendering panics, so the shell
+    // is usable afterwards.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        default_hook(info);
+    }));
+
+    let recorder = args
+        .record_session
+        .as_deref()
+        .map(Recorder::create)
+        .transpose()
+        .map_err(exports::failed("asciicast"))?;
+    enable_raw_mode().map_err(RunnerError::Terminal)?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
-
-    let result = tui_loop(&mut terminal, &map_fd, &args);
+    execute!(stdout, EnterAlternateScreen).map_err(RunnerError::Terminal)?;
+    let backend = CrosstermBackend::new(TuiOutput::new(stdout, recorder));
+    let mut terminal = Terminal::new(backend).map_err(RunnerError::Terminal)?;
+    terminal.clear().map_err(RunnerError::Terminal)?;
+
+    debug!(target: "tui", map = label, refresh_ms = refresh.as_millis() as u64, "dashboard started");
+    // The sampler must start inside the runtime its wake-ups are awaited on.
+    let result = runtime.block_on(async {
+        let sampler = Background::spawn(stream)?;
+        match baseline {
+            Some(baseline) => {
+                let baseline = Background::spawn(baseline)?;
+                compare_loop(&mut terminal, [&sampler, &baseline], &labels, &args).await
+            }
+            None => tui_loop(&mut terminal, &sampler, &args).await,
+        }
+    });
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    disable_raw_mode().map_err(RunnerError::Terminal)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen).map_err(RunnerError::Terminal)?;
+    terminal.show_cursor().map_err(RunnerError::Terminal)?;
 
     result
 }
 
-fn tui_loop(
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    map_fd: &OwnedFd,
+/// The `tui --compare` view: two maps, each sampled on its own thread,
+/// drawn side by side whenever either has a new window.
+async fn compare_loop(
+    terminal: &mut Terminal<CrosstermBackend<TuiOutput>>,
+    samplers: [&Background; 2],
+    labels: &[String; 2],
     args: &TuiArgs,
-) -> Result<(), Box<dyn Error>> {
-    let mut rolling = RollingStats::new(args.alpha);
-    let refresh = Duration::from_millis(args.refresh_ms.max(100));
-    let mut history = HistoryWindow::new(120);
-
+) -> Result<(), RunnerError> {
+    let mut sides = labels.clone().map(ComparisonSide::new);
+    let mut events = EventStream::new();
+    let shutdown = shutdown::signalled();
+    tokio::pin!(shutdown);
     loop {
-        let entries = iterate_task_info(map_fd.as_raw_fd())?;
-        let total_tickets: u64 = entries.iter().map(|(_, info)| info.tickets as u64).sum();
-        let window_ms = refresh.as_secs_f64() * 1000.0;
-        let snapshots = enrich_entries(&entries, total_tickets, &mut rolling, window_ms);
-
-        history.push(make_history_sample(&snapshots));
+        let (index, mut sampled) = tokio::select! {
+            sampled = samplers[0].next() => (0, sampled?),
+            sampled = samplers[1].next() => (1, sampled?),
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => {
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                        break;
+                    }
+                    continue;
+                }
+                Some(Ok(Event::Resize(..))) => {
+                    terminal
+                        .draw(|f| draw_comparison(f, &sides))
+                        .map_err(RunnerError::Terminal)?;
+                    continue;
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(RunnerError::Terminal(err)),
+                None => break,
+            },
+            signalled = &mut shutdown => {
+                signalled?;
+                break;
+            }
+        };
+        sampled.snapshots = args.filter.apply(sampled.snapshots);
+        let summary = make_history_sample(&sampled, &[]);
+        if let Some(old) = sides[index].update(sampled, summary, args.top) {
+            samplers[index].recycle(old);
+        }
+        terminal
+            .draw(|f| draw_comparison(f, &sides))
+            .map_err(RunnerError::Terminal)?;
+    }
+    Ok(())
+}
 
-        terminal.draw(|f| {
-            draw_dashboard(f, &snapshots, total_tickets, &history, args.top);
-        })?;
+async fn tui_loop(
+    terminal: &mut Terminal<CrosstermBackend<TuiOutput>>,
+    sampler: &Background,
+    args: &TuiArgs,
+) -> Result<(), RunnerError> {
+    let mut history = HistoryWindow::new(120);
+    let mut alerts = AlertHistory::new(args.deadline_warn, args.latency_warn);
+    if args.inflation_warn > 0.0 {
+        alerts = alerts.with_inflation(args.inflation_warn / 100.0, args.inflation_windows);
+    }
+    let mut state = TuiState {
+        group_by: args.group_by,
+        alpha: args.alpha.clamp(0.0, 1.0),
+        refresh: Duration::from_millis(args.refresh_ms.max(100)),
+        filters: args.filter.describe(),
+        pinned: args.pin.clone(),
+        braille: args.braille,
+        ..TuiState::default()
+    };
+    let mut events = EventStream::new();
+    let (renice_done, mut renices) =
+        mpsc::unbounded_channel::<Result<control::Renice, RunnerError>>();
+    let (alpha_done, mut alphas) = mpsc::unbounded_channel::<f64>();
+    let (refresh_done, mut refreshes) = mpsc::unbounded_channel::<Duration>();
+    let shutdown = shutdown::signalled();
+    tokio::pin!(shutdown);
+    let mut window: Option<SnapshotWindow> = None;
+    // The sample before `:filter`, kept while one is set so a new pattern
+    // applies at once instead of on the next sample.
+    let mut unfiltered: Vec<TaskSnapshot> = Vec::new();
+    let mut panes = PaneCache::default();
 
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => break,
-                    _ => {}
+    loop {
+        tokio::select! {
+            sampled = sampler.next() => {
+                let mut sampled = sampled?;
+                sampled.snapshots = args.filter.apply(sampled.snapshots);
+                if state.task_filter.is_some() {
+                    unfiltered.clone_from(&sampled.snapshots);
+                    sampled.snapshots.retain(|entry| state.matches_filter(entry));
+                } else {
+                    unfiltered.clear();
+                }
+                // An idle map keeps its table; only the trends move on.
+                if window
+                    .as_ref()
+                    .is_none_or(|old| !same_activity(&old.snapshots, &sampled.snapshots))
+                {
+                    panes.invalidate(Pane::Table);
+                    panes.invalidate(Pane::Summary);
+                }
+                // A frozen map is idle too, but its warning counts windows.
+                if sampled.stale() || window.as_ref().is_some_and(SnapshotWindow::stale) {
+                    panes.invalidate(Pane::Summary);
+                }
+                panes.invalidate(Pane::Detail);
+                panes.invalidate(Pane::History);
+                panes.invalidate(Pane::Pinned);
+                history.push(make_history_sample(&sampled, &state.pinned));
+                let added = alerts.observe(&sampled);
+                if added > 0 {
+                    state.alerts_added(added);
+                    panes.invalidate(Pane::Alerts);
+                }
+                state.rank(&sampled.snapshots, args.top);
+                state.refresh_focus(&sampled.snapshots);
+                state.observe_renice(&sampled.snapshots);
+                if let Some(old) = window.replace(sampled) {
+                    sampler.recycle(old);
+                }
+            }
+            Some(change) = renices.recv() => {
+                match change {
+                    Ok(change) => state.renice_applied(change),
+                    Err(err) => {
+                        warn!(target: "tui", kind = err.kind(), "{err}");
+                        state.status = Some(err.to_string());
+                    }
+                }
+                panes.invalidate(Pane::Summary);
+            }
+            Some(refresh) = refreshes.recv() => {
+                state.refresh_applied(refresh);
+                panes.invalidate(Pane::Summary);
+            }
+            Some(alpha) = alphas.recv() => {
+                state.alpha_applied(alpha);
+                panes.invalidate(Pane::Summary);
+                panes.invalidate(Pane::Detail);
+            }
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => {
+                    // Keys work before the first (possibly slow) sample too.
+                    let (snapshots, window_ms) = window
+                        .as_ref()
+                        .map_or((&[][..], 0.0), |current| (&current.snapshots[..], current.window_ms));
+                    // Raw mode swallows SIGINT, so treat Ctrl-C as a quit key.
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    let trends = (state.show_histogram, state.braille);
+                    let action = if ctrl_c {
+                        KeyAction::Quit
+                    } else {
+                        state.handle_key(key, snapshots, window_ms, args.top)
+                    };
+                    match action {
+                        KeyAction::Quit => break,
+                        KeyAction::Redraw => {
+                            panes.invalidate(Pane::Table);
+                            panes.invalidate(Pane::Pinned);
+                            panes.invalidate(Pane::Detail);
+                            panes.invalidate(Pane::Summary);
+                            panes.invalidate(Pane::Alerts);
+                        }
+                        KeyAction::Ignore => {}
+                    }
+                    if (state.show_histogram, state.braille) != trends {
+                        panes.invalidate(Pane::History);
+                    }
+                    if state.filter_changed {
+                        state.filter_changed = false;
+                        if let Some(current) = window.as_mut() {
+                            if !unfiltered.is_empty() {
+                                current.snapshots = std::mem::take(&mut unfiltered);
+                            }
+                            if state.task_filter.is_some() {
+                                unfiltered.clone_from(&current.snapshots);
+                                current.snapshots.retain(|entry| state.matches_filter(entry));
+                            }
+                            state.rank(&current.snapshots, args.top);
+                            state.refresh_focus(&current.snapshots);
+                        }
+                    }
+                    if let Some(path) = state.write_request.take() {
+                        state.status = Some(match window.as_ref() {
+                            Some(current) => match write_window_json(current, &history, &path) {
+                                Ok(()) => format!(
+                                    "wrote {} tasks to {}",
+                                    current.snapshots.len(),
+                                    path.display()
+                                ),
+                                Err(err) => format!("cannot write {}: {err}", path.display()),
+                            },
+                            None => "nothing sampled yet".to_string(),
+                        });
+                    }
+                    if let Some((pid, nice)) = state.renice_request.take() {
+                        sampler.run(move |stream| stream.renice(pid, nice), renice_done.clone());
+                    }
+                    if let Some(refresh) = state.refresh_request.take() {
+                        sampler.run(
+                            move |stream| {
+                                stream.set_interval(refresh);
+                                stream.interval()
+                            },
+                            refresh_done.clone(),
+                        );
+                    }
+                    if let Some(alpha) = state.alpha_request.take() {
+                        sampler.run(
+                            move |stream| {
+                                stream.set_alpha(alpha);
+                                stream.alpha()
+                            },
+                            alpha_done.clone(),
+                        );
+                    }
                 }
+                Some(Ok(Event::Resize(..))) => panes.invalidate_all(),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(RunnerError::Terminal(err)),
+                None => break,
+            },
+            signalled = &mut shutdown => {
+                signalled?;
+                break;
             }
         }
 
-        thread::sleep(refresh);
+        // Redraw after samples and key presses alike, so navigation does not
+        // wait for the next refresh; nothing to do when no pane changed.
+        if let Some(current) = window.as_ref()
+            && panes.needs_redraw()
+        {
+            terminal
+                .draw(|f| {
+                    draw_dashboard(f, current, &history, &alerts, args.top, &state, &mut panes)
+                })
+                .map_err(RunnerError::Terminal)?;
+        }
     }
 
     Ok(())
 }
 
-fn make_history_sample(snapshots: &[TaskSnapshot]) -> HistorySample {
+/// Save a TUI window as one pretty-printed `batch_json` document.
+fn write_window_json(
+    window: &SnapshotWindow,
+    history: &HistoryWindow,
+    path: &Path,
+) -> io::Result<()> {
+    let batch = SnapshotBatch {
+        map: window.map.clone(),
+        iteration: window.index as u32,
+        timestamp: window.timestamp,
+        total_tickets: window.total_tickets,
+        window_ms: window.window_ms,
+        snapshots: window.snapshots.clone(),
+        filtered_out: Vec::new(),
+        events: Vec::new(),
+        consistent: window.consistent,
+        clock: window.clock,
+        ktime_offset_s: window.ktime_offset_s,
+        cpus: window.cpus,
+        unchanged_windows: window.unchanged_windows,
+        runqueue: window.runqueue.clone(),
+        irq: window.irq.clone(),
+        lateness_horizon: Some(history.lateness_horizon()),
+        cgroups: window.cgroups.clone(),
+    };
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut out, &batch_json(&batch))?;
+    writeln!(out)?;
+    out.flush()
+}
+
+fn make_history_sample(window: &SnapshotWindow, pinned: &[u32]) -> HistorySample {
+    let snapshots = &window.snapshots;
     if snapshots.is_empty() {
-        return HistorySample::default();
+        return HistorySample {
+            total_tickets: window.total_tickets,
+            cpus: window.cpus,
+            per_cpu: window.per_cpu_task_utilization(),
+            per_cpu_irq: window.irq.as_ref().map_or_else(Vec::new, IrqTime::per_cpu),
+            irq: window
+                .irq
+                .as_ref()
+                .map(|irq| (irq.irq_mean(), irq.softirq_mean())),
+            unchanged_windows: window.unchanged_windows,
+            stale: window.stale(),
+            rq_depth: window
+                .runqueue
+                .as_ref()
+                .map(|depth| (depth.mean(), depth.max())),
+            ..HistorySample::default()
+        };
     }
 
-    let (sum_lateness, max_lateness, overdue, runtime_ms, sum_util) = snapshots.iter().fold(
-        (0.0_f64, 0.0_f64, 0_usize, 0.0_f64, 0.0_f64),
-        |(sum, max, overdue, runtime, util_sum), entry| {
+    let (overdue, runtime_ms, sum_util) = snapshots.iter().fold(
+        (0_usize, 0.0_f64, 0.0_f64),
+        |(overdue, runtime, util_sum), entry| {
             (
-                sum + entry.lateness_ms,
-                max.max(entry.lateness_ms),
                 overdue + if entry.lateness_ms > 0.0 { 1 } else { 0 },
                 runtime + entry.runtime_delta_ms(),
                 util_sum + entry.utilization,
@@ -327,193 +2445,269 @@ fn make_history_sample(snapshots: &[TaskSnapshot]) -> HistorySample {
         .map(|entry| (Some(entry.pid), entry.ticket_share))
         .unwrap_or((None, 0.0));
     let avg_utilization = sum_util / snapshots.len() as f64;
+    let priority = PriorityReport::of(snapshots);
 
     HistorySample {
-        avg_lateness: sum_lateness / snapshots.len() as f64,
-        max_lateness,
+        lateness_quantiles: snapshots.iter().map(|entry| entry.lateness_ms).collect(),
         total_tasks: snapshots.len(),
+        total_tickets: window.total_tickets,
         overdue_tasks: overdue,
+        starved_tasks: snapshots.iter().filter(|entry| entry.starved).count(),
         total_runtime_ms: runtime_ms,
         avg_utilization,
+        system_utilization: window.system_utilization(),
+        cpus: window.cpus,
+        per_cpu: window.per_cpu_task_utilization(),
+        per_cpu_irq: window.irq.as_ref().map_or_else(Vec::new, IrqTime::per_cpu),
+        irq: window
+            .irq
+            .as_ref()
+            .map(|irq| (irq.irq_mean(), irq.softirq_mean())),
+        unchanged_windows: window.unchanged_windows,
+        stale: window.stale(),
         top_pid,
         top_share,
+        lateness: LatenessHistogram::of(snapshots),
+        ticket_hhi: ticket_concentration(snapshots),
+        pinned: snapshots
+            .iter()
+            .filter(|entry| pinned.contains(&entry.pid))
+            .map(|entry| (entry.pid, entry.utilization))
+            .collect(),
+        latency: WakeupLatency::merged(snapshots.iter().filter_map(|entry| entry.latency.as_ref())),
+        preemption_rate: preemption_rate(
+            snapshots
+                .iter()
+                .filter_map(|entry| entry.preempt_delta)
+                .reduce(|a, b| a + b),
+            snapshots.iter().map(|entry| entry.switch_delta).sum(),
+        ),
+        events: SchedEvents::merged(snapshots.iter().map(|entry| &entry.events)),
+        rq_depth: window
+            .runqueue
+            .as_ref()
+            .map(|depth| (depth.mean(), depth.max())),
+        runtime_gini: runtime_gini(snapshots),
+        priority_spearman: priority.spearman,
+        priority_outliers: priority.outliers.len(),
     }
 }
 
-fn print_table(iteration: u32, total_tickets: u64, entries: &[TaskSnapshot]) {
-    println!("\nIteration {}:", iteration + 1);
-    println!(
-        "{:<8} {:>11} {:>11} {:>11} {:>11} {:>10} {:>8} {:>9} {:>6} {:>8} {:>8}",
-        "PID",
-        "RUN_MS",
-        "DELTA",
-        "ROLL",
-        "PERIOD",
-        "LATENESS",
-        "UTIL%",
-        "SW_DELTA",
-        "NICE",
-        "TICKETS",
-        "SHARE%"
-    );
-    for entry in entries {
-        println!(
-            "{:<8} {:>11.3} {:>11.3} {:>11.3} {:>11.3} {:>10.3} {:>8.2} {:>9} {:>6} {:>8} {:>7.2}",
-            entry.pid,
-            entry.info.runtime_ms(),
-            entry.runtime_delta_ms(),
-            entry.rolling_runtime_ms,
-            entry.estimated_period_ms,
-            entry.lateness_ms,
-            entry.utilization * 100.0,
-            entry.switch_delta,
-            entry.info.nice,
-            entry.info.tickets,
-            entry.ticket_share * 100.0
-        );
-    }
-    if total_tickets == 0 {
-        println!("Total tickets: 0 (all tasks currently inactive).");
-    } else {
-        println!("Total tickets: {total_tickets}");
-    }
+/// The parts of a (filtered) window [`print_iteration`] shows.
+struct Iteration<'a> {
+    heading: &'a str,
+    total_tickets: u64,
+    window_ms: f64,
+    snapshots: &'a [TaskSnapshot],
+    cgroups: &'a [CgroupSnapshot],
 }
 
-fn prepare_csv(path: &Path) -> io::Result<std::fs::File> {
-    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+/// Human-readable output for one window: the (grouped) task table, then the
+/// lottery ranking, optional simulated draws, and EDF summary.
+/// In `--watch` mode only the table is shown, highlighted against `previous`.
+fn print_iteration(
+    args: &DumpArgs,
+    iteration: &Iteration<'_>,
+    previous: Option<&HashMap<u32, TaskSnapshot>>,
+    lottery: &mut Lottery,
+) {
+    let &Iteration {
+        heading,
+        total_tickets,
+        window_ms,
+        snapshots,
+        cgroups,
+    } = iteration;
+    let columns = table_columns(&args.columns, args.wide, snapshots, !cgroups.is_empty());
+    match (args.group_by, args.sort) {
+        (GroupBy::Pid, Some(key)) => {
+            let ranking = &mut lottery.ranking;
+            ranking.rank(snapshots, snapshots.len(), |a, b| {
+                let order = key.tasks(a, b);
+                if args.desc { order.reverse() } else { order }
+            });
+            print_task_table(
+                heading,
+                &columns,
+                total_tickets,
+                ranking.top(snapshots),
+                cgroups,
+                previous,
+            );
+        }
+        (GroupBy::Pid, None) => print_task_table(
+            heading,
+            &columns,
+            total_tickets,
+            snapshots,
+            cgroups,
+            previous,
+        ),
+        (group_by, _) => {
+            let mut groups = aggregate(snapshots, group_by, window_ms);
+            sort_rows(&mut groups, args.sort, args.desc, SortKey::groups);
+            print_group_table(
+                heading,
+                total_tickets,
+                &groups,
+                args.expand_groups.then_some((group_by, snapshots)),
+            );
+        }
+    }
 
-    if file.metadata()?.len() == 0 {
-        writeln!(
-            file,
-            "iteration,timestamp_s,pid,runtime_ns,runtime_ms,delta_ns,delta_ms,rolling_runtime_ms,switches,nice,tickets,ticket_share,estimated_period_ms,lateness_ms,utilization"
-        )?;
+    if !snapshots.is_empty() && !args.watch {
+        let top = args.top.max(1);
+        print_runtime_inequality(snapshots);
+        lottery.ranking.rank(snapshots, top, by_share);
+        print_lottery_summary(lottery.ranking.top(snapshots));
+        if args.simulate_draws > 0 {
+            let draws = lottery.draw(snapshots, args.simulate_draws);
+            print_draw_results(args.simulate_draws, &draws, snapshots);
+        }
+        lottery.ranking.rank(snapshots, top, |a, b| {
+            b.lateness_ms
+                .total_cmp(&a.lateness_ms)
+                .then_with(|| by_share(a, b))
+        });
+        print_edf_summary(lottery.ranking.top(snapshots));
     }
+}
 
-    Ok(file)
+/// Highest ticket share first.
+fn by_share(a: &TaskSnapshot, b: &TaskSnapshot) -> Ordering {
+    b.ticket_share.total_cmp(&a.ticket_share)
 }
 
-fn prepare_json(path: &Path) -> io::Result<std::fs::File> {
-    OpenOptions::new().create(true).append(true).open(path)
+/// What the human-readable output keeps across windows: the generator of
+/// simulated draws and the sort order reused to rank each window.
+struct Lottery {
+    rng: StdRng,
+    ranking: Ranking,
 }
 
-fn write_csv(
-    file: &mut std::fs::File,
-    iteration: u32,
-    timestamp: f64,
-    entries: &[TaskSnapshot],
-) -> io::Result<()> {
-    for entry in entries {
-        writeln!(
-            file,
-            "{},{:.6},{},{},{:.3},{},{:.3},{:.3},{},{},{},{:.6},{:.3},{:.3},{:.3}",
-            iteration + 1,
-            timestamp,
-            entry.pid,
-            entry.info.runtime_ns,
-            entry.info.runtime_ms(),
-            entry.runtime_delta_ns,
-            entry.runtime_delta_ms(),
-            entry.rolling_runtime_ms,
-            entry.info.switches,
-            entry.info.nice,
-            entry.info.tickets,
-            entry.ticket_share,
-            entry.estimated_period_ms,
-            entry.lateness_ms,
-            entry.utilization
-        )?;
+impl Lottery {
+    /// Simulate `draws` lottery draws over `snapshots`. The population is
+    /// walked in share order, so a `--seed` run picks the same winners.
+    fn draw(&mut self, snapshots: &[TaskSnapshot], draws: u32) -> Vec<(u32, u32)> {
+        self.ranking.rank(snapshots, snapshots.len(), by_share);
+        simulate_lottery_draws(&mut self.rng, self.ranking.all(snapshots), draws)
     }
-
-    file.flush()
 }
 
-fn write_json(
-    file: &mut std::fs::File,
-    iteration: u32,
-    timestamp: f64,
+fn print_group_table(
+    heading: &str,
     total_tickets: u64,
-    entries: &[TaskSnapshot],
-) -> io::Result<()> {
-    for entry in entries {
-        let payload = json!({
-            "iteration": iteration + 1,
-            "timestamp_s": timestamp,
-            "total_tickets": total_tickets,
-            "pid": entry.pid,
-            "runtime_ms": entry.info.runtime_ms(),
-            "delta_ms": entry.runtime_delta_ms(),
-            "rolling_runtime_ms": entry.rolling_runtime_ms,
-            "switch_delta": entry.switch_delta,
-            "estimated_period_ms": entry.estimated_period_ms,
-            "deadline_ms": entry.deadline_ms,
-            "lateness_ms": entry.lateness_ms,
-            "utilization": entry.utilization,
-            "nice": entry.info.nice,
-            "tickets": entry.info.tickets,
-            "ticket_share": entry.ticket_share,
-        });
-        writeln!(file, "{}", payload)?;
+    groups: &[GroupSnapshot],
+    expand: Option<(GroupBy, &[TaskSnapshot])>,
+) {
+    println!("\n{heading}:");
+    println!(
+        "{:>6} {:>11} {:>9} {:>10} {:>10} {:>10} {:>8} {:>8} {:>8} {:>8}  GROUP",
+        "TASKS",
+        "DELTA",
+        "SW_DELTA",
+        "LATENESS",
+        "WORST_LAT",
+        "AVG_LAT",
+        "OVERDUE",
+        "UTIL%",
+        "TICKETS",
+        "SHARE%"
+    );
+    for group in groups {
+        println!(
+            "{:>6} {:>11.3} {:>9} {:>10.3} {:>10.3} {:>10.3} {:>8} {:>8.2} {:>8} {:>8.2}  {}",
+            group.tasks,
+            group.runtime_delta_ms(),
+            group.switch_delta,
+            group.lateness_ms,
+            group.worst_lateness_ms,
+            group.avg_lateness_ms(),
+            group.overdue,
+            group.utilization * 100.0,
+            group.tickets,
+            group.ticket_share * 100.0,
+            group.key
+        );
+        if let Some((group_by, snapshots)) = expand {
+            for member in members(snapshots, group_by, &group.key) {
+                println!(
+                    "{:>6} {:>11.3} {:>9} {:>10.3} {:>10} {:>10} {:>8} {:>8.2} {:>8} {:>8.2}    - pid {}",
+                    "",
+                    member.runtime_delta_ms(),
+                    member.switch_delta,
+                    member.lateness_ms,
+                    "",
+                    "",
+                    if member.deadline_missed() { "MISS" } else { "" },
+                    member.utilization * 100.0,
+                    member.info.tickets,
+                    member.ticket_share * 100.0,
+                    member.pid
+                );
+            }
+        }
     }
-    file.flush()
+    println!("Total tickets: {total_tickets}");
 }
 
-fn enrich_entries(
-    entries: &[(u32, TaskInfo)],
-    total_tickets: u64,
-    rolling: &mut RollingStats,
-    window_ms: f64,
-) -> Vec<TaskSnapshot> {
-    let window_ms = window_ms.max(1.0);
-    entries
+/// How unevenly the window's runtime went round: the Gini coefficient and
+/// the share the busiest tenth of the tasks got.
+fn print_runtime_inequality(snapshots: &[TaskSnapshot]) {
+    let Some(gini) = runtime_gini(snapshots) else {
+        return;
+    };
+    let lorenz = runtime_lorenz(snapshots);
+    let bottom = lorenz
         .iter()
-        .map(|(pid, info)| {
-            let (delta_ns, rolling_ms, switch_delta) =
-                rolling.update(*pid, info.runtime_ns, info.switches);
-            let delta_ms = delta_ns as f64 / 1_000_000.0;
-            let mut estimated_period_ms = if switch_delta > 0 {
-                window_ms / switch_delta as f64
-            } else {
-                window_ms
-            };
-            estimated_period_ms = estimated_period_ms.max(0.1);
-            let deadline_ms = estimated_period_ms;
-            let lateness_ms = delta_ms - deadline_ms;
-            let utilization = if estimated_period_ms > 0.0 {
-                delta_ms / estimated_period_ms
-            } else {
-                0.0
-            };
-            TaskSnapshot {
-                pid: *pid,
-                info: *info,
-                runtime_delta_ns: delta_ns,
-                rolling_runtime_ms: rolling_ms,
-                switch_delta,
-                estimated_period_ms,
-                deadline_ms,
-                lateness_ms,
-                utilization,
-                ticket_share: ticket_share(info.tickets, total_tickets),
-            }
-        })
-        .collect()
+        .rev()
+        .nth(1)
+        .map_or(1.0, |(_, runtime)| *runtime);
+    println!(
+        "\nRuntime inequality: Gini {gini:.3}; the busiest 10% of tasks got {:.1}% of the runtime",
+        (1.0 - bottom) * 100.0
+    );
 }
 
-fn now_secs() -> f64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs_f64()
+/// How well the achieved shares follow the tickets; see [`PriorityReport`].
+fn print_priority_report(report: &PriorityReport) {
+    let Some(spearman) = report.spearman else {
+        return;
+    };
+    println!(
+        "\nPriority fit over {} running tasks: Spearman {spearman:.3} (tickets vs achieved share)",
+        report.tasks
+    );
+    if report.outliers.is_empty() {
+        println!("No task is more than {OUTLIER_RATIO}x off its ticket share.");
+        return;
+    }
+    println!("Outliers (achieved/expected share beyond {OUTLIER_RATIO}x):");
+    println!(
+        "{:<8} {:<16} {:>5} {:>8} {:>9} {:>9} {:>7}",
+        "PID", "COMM", "NICE", "TICKETS", "EXP_SHR%", "ACH_SHR%", "RATIO"
+    );
+    for outlier in &report.outliers {
+        println!(
+            "{:<8} {:<16} {:>5} {:>8} {:>9.2} {:>9.2} {:>7.2}",
+            outlier.pid,
+            outlier.comm.as_deref().unwrap_or("-"),
+            outlier.nice,
+            outlier.tickets,
+            outlier.expected_share * 100.0,
+            outlier.achieved_share * 100.0,
+            outlier.ratio()
+        );
+    }
 }
 
-fn print_lottery_summary(entries: &[TaskSnapshot], top_n: usize) {
-    if entries.is_empty() {
+fn print_lottery_summary<'a>(ranked: impl ExactSizeIterator<Item = &'a TaskSnapshot>) {
+    if ranked.len() == 0 {
         return;
     }
-    let limit = entries.len().min(top_n.max(1));
-    println!("\nTop {} candidates by ticket share:", limit);
+    println!("\nTop {} candidates by ticket share:", ranked.len());
     println!("{:<8} {:>10} {:>9}", "PID", "TICKETS", "SHARE%");
-    for entry in entries.iter().take(limit) {
+    for entry in ranked {
         println!(
             "{:<8} {:>10} {:>8.2}",
             entry.pid,
@@ -523,25 +2717,13 @@ fn print_lottery_summary(entries: &[TaskSnapshot], top_n: usize) {
     }
 }
 
-fn print_edf_summary(entries: &[TaskSnapshot], top_n: usize) {
-    if entries.is_empty() {
+fn print_edf_summary<'a>(ranked: impl ExactSizeIterator<Item = &'a TaskSnapshot>) {
+    if ranked.len() == 0 {
         return;
     }
-    let mut ranked = entries.to_vec();
-    ranked.sort_by(|a, b| {
-        b.lateness_ms
-            .partial_cmp(&a.lateness_ms)
-            .unwrap_or(Ordering::Equal)
-    });
-    let limit = ranked.len().min(top_n.max(1));
-    println!("\nEDF lateness (top {limit}):");
+    println!("\nEDF lateness (top {}):", ranked.len());
     let mut any_positive = false;
-    for entry in ranked.iter().take(limit) {
-        let status = if entry.deadline_missed() {
-            "MISS"
-        } else {
-            "OK"
-        };
+    for entry in ranked {
         println!(
             "{:<8} lateness={:>8.3} ms period={:>8.3} ms util={:>6.2}% share={:>6.2}% status={}",
             entry.pid,
@@ -549,7 +2731,7 @@ fn print_edf_summary(entries: &[TaskSnapshot], top_n: usize) {
             entry.estimated_period_ms,
             entry.utilization * 100.0,
             entry.ticket_share * 100.0,
-            status
+            entry.status()
         );
         if entry.lateness_ms > 0.0 {
             any_positive = true;
@@ -589,80 +2771,3 @@ fn print_draw_results(draws: u32, results: &[(u32, u32)], snapshots: &[TaskSnaps
         );
     }
 }
-
-fn emit_deadline_alerts(threshold_ms: f64, entries: &[TaskSnapshot]) {
-    let mut flagged = entries
-        .iter()
-        .filter(|e| e.lateness_ms > threshold_ms)
-        .collect::<Vec<_>>();
-    if flagged.is_empty() {
-        return;
-    }
-    flagged.sort_by(|a, b| {
-        b.lateness_ms
-            .partial_cmp(&a.lateness_ms)
-            .unwrap_or(Ordering::Equal)
-    });
-    println!(
-        "\n[!] Deadline alerts (>{:.3} ms over budget):",
-        threshold_ms
-    );
-    for entry in flagged {
-        println!(
-            "  pid {:>6}: lateness={:>8.3}ms util={:>6.2}% tickets={} nice={}",
-            entry.pid,
-            entry.lateness_ms,
-            entry.utilization * 100.0,
-            entry.info.tickets,
-            entry.info.nice
-        );
-    }
-}
-
-fn collect_trace_events(
-    events: &mut Vec<TraceEvent>,
-    _iteration: u32,
-    rel_timestamp: f64,
-    entries: &[TaskSnapshot],
-) {
-    let ts_us = rel_timestamp * 1_000_000.0;
-    for entry in entries {
-        let dur_us = entry.runtime_delta_ms() * 1000.0;
-        events.push(TraceEvent::Metadata {
-            name: "thread_name",
-            cat: "sched",
-            ts: 0.0,
-            pid: entry.pid,
-            tid: entry.pid,
-            args: MetadataArgs {
-                thread_name: format!("pid {}", entry.pid),
-            },
-        });
-        events.push(TraceEvent::Slice {
-            name: format!("pid {}", entry.pid),
-            cat: "sched",
-            ts: ts_us,
-            dur: dur_us.max(1.0),
-            pid: entry.pid,
-            tid: entry.pid,
-            args: TraceArgs {
-                ticket_share: entry.ticket_share,
-                deadline_ms: entry.deadline_ms,
-                lateness_ms: entry.lateness_ms,
-                runtime_ms: entry.runtime_delta_ms(),
-                utilization: entry.utilization,
-            },
-        });
-    }
-}
-
-fn flush_trace(path: &Path, events: &[TraceEvent]) -> Result<(), Box<dyn Error>> {
-    if events.is_empty() {
-        return Ok(());
-    }
-    let trace = json!({ "traceEvents": events });
-    let data = serde_json::to_string_pretty(&trace)?;
-    std::fs::write(path, data)?;
-    println!("[+] Trace exported to {}", path.display());
-    Ok(())
-}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use neo_ebpf_core::export::{Exporter, SnapshotBatch, snapshot_json};
+use neo_ebpf_core::priority::PriorityReport;
+use neo_ebpf_core::procfs::hostname;
+use rumqttc::{Client, ClientError, Connection, Event, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+/// Default MQTT port when `--mqtt-broker` has none.
+const DEFAULT_PORT: u16 = 1883;
+
+/// Requests queued between the exporter and the connection thread.
+const QUEUE: usize = 1024;
+
+/// How long `finish` waits for queued messages to go out.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause between reconnect attempts while the broker is unreachable.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Connection settings gathered from the `--mqtt-*` flags.
+pub struct MqttSettings<'a> {
+    pub broker: &'a str,
+    /// Topic prefix; `{host}` is replaced with the host name.
+    pub topic: &'a str,
+    pub qos: u8,
+    pub retain: bool,
+    pub per_task: bool,
+    pub client_id: Option<&'a str>,
+    pub username: Option<&'a str>,
+    pub password_file: Option<&'a Path>,
+}
+
+/// Publishes a JSON summary of every window to `<topic>/<map>/summary`
+/// and, optionally, the record of every task that ran to
+/// `<topic>/<map>/task/<pid>`. Messages are queued without blocking the
+/// run: while the broker is away they are dropped and counted instead.
+pub struct MqttExporter {
+    client: Client,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+    per_task: bool,
+    host: String,
+    dropped: u64,
+    done: mpsc::Receiver<()>,
+}
+
+impl MqttExporter {
+    pub fn connect(settings: &MqttSettings) -> io::Result<Self> {
+        let (address, port) = match settings.broker.rsplit_once(':') {
+            Some((address, port)) if !address.ends_with(':') => {
+                let port = port.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid port in `{}`", settings.broker),
+                    )
+                })?;
+                (address.trim_matches(['[', ']']), port)
+            }
+            _ => (settings.broker, DEFAULT_PORT),
+        };
+        let host = hostname();
+        let client_id = settings
+            .client_id
+            .map_or_else(|| format!("neo-ebpf-{host}"), str::to_string);
+        let mut options = MqttOptions::new(client_id, address, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = settings.username {
+            let password = match settings.password_file {
+                Some(path) => fs::read_to_string(path)?.trim_end().to_string(),
+                None => String::new(),
+            };
+            options.set_credentials(username, password);
+        }
+        let (client, connection) = Client::new(options, QUEUE);
+        let (finished, done) = mpsc::channel();
+        let broker = settings.broker.to_string();
+        thread::Builder::new()
+            .name("mqtt".to_string())
+            .spawn(move || {
+                drive(connection, &broker);
+                let _ = finished.send(());
+            })?;
+        let topic = settings
+            .topic
+            .replace("{host}", &topic_safe(&host))
+            .trim_end_matches('/')
+            .to_string();
+        info!(target: "exporter", broker = settings.broker, topic, "publishing to mqtt");
+        Ok(Self {
+            client,
+            topic,
+            qos: rumqttc::qos(settings.qos).map_err(io::Error::other)?,
+            retain: settings.retain,
+            per_task: settings.per_task,
+            host,
+            dropped: 0,
+            done,
+        })
+    }
+
+    fn publish(&mut self, topic: String, payload: &serde_json::Value, retain: bool) {
+        let payload = payload.to_string();
+        match self.client.try_publish(topic, self.qos, retain, payload) {
+            Ok(()) => {}
+            Err(ClientError::TryRequest(_)) => self.dropped += 1,
+            Err(err) => debug!(target: "exporter", "mqtt publish failed: {err}"),
+        }
+    }
+}
+
+/// Run the connection until the client disconnects, reconnecting after
+/// errors. An outage is logged once, not on every attempt.
+fn drive(mut connection: Connection, broker: &str) {
+    let mut down = false;
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                if down {
+                    info!(target: "exporter", broker, "mqtt reconnected");
+                }
+                down = false;
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(err) => {
+                if down {
+                    debug!(target: "exporter", broker, "mqtt: {err}");
+                } else {
+                    warn!(target: "exporter", broker, "mqtt: {err}; retrying");
+                    down = true;
+                }
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// `+`, `#` and `/` would change the topic structure.
+fn topic_safe(value: &str) -> String {
+    value.replace(['+', '#', '/'], "_")
+}
+
+impl Exporter for MqttExporter {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        let base = format!("{}/{}", self.topic, topic_safe(&batch.map));
+        let totals = batch.totals();
+        let lateness = batch.lateness_horizon_sketch();
+        let summary = json!({
+            "host": self.host,
+            "map": batch.map,
+            "iteration": batch.iteration + 1,
+            "timestamp_s": batch.timestamp,
+            "window_ms": batch.window_ms,
+            "tasks": totals.tasks,
+            "active_tasks": totals.active,
+            "overdue_tasks": totals.overdue,
+            "starved_tasks": totals.starved,
+            "total_utilization": totals.utilization,
+            "system_utilization": totals.system_utilization,
+            "runtime_gini": totals.runtime_gini,
+            "priority_spearman": PriorityReport::of(&batch.snapshots).spearman,
+            "cpus": batch.cpus,
+            "worst_lateness_ms": totals.worst_lateness_ms,
+            "lateness_p95_ms": lateness.quantile(0.95),
+            "lateness_p99_ms": lateness.quantile(0.99),
+            "latency_p99_ms": totals.latency.map(|lat| lat.quantile_ms(0.99)),
+            "preemption_rate": totals.preemption_rate(),
+            "rq_depth_mean": batch.runqueue.as_ref().map(|depth| depth.mean()),
+            "rq_depth_max": batch.runqueue.as_ref().map(|depth| depth.max()),
+            "irq_share": batch.irq.as_ref().map(|irq| irq.irq_mean()),
+            "softirq_share": batch.irq.as_ref().map(|irq| irq.softirq_mean()),
+            "total_tickets": batch.total_tickets,
+            "stale": batch.stale(),
+        });
+        self.publish(format!("{base}/summary"), &summary, self.retain);
+        if self.per_task {
+            for entry in batch
+                .snapshots
+                .iter()
+                .filter(|entry| entry.runtime_delta_ns > 0)
+            {
+                let mut record = snapshot_json(batch, entry);
+                record["host"] = json!(self.host);
+                self.publish(format!("{base}/task/{}", entry.pid), &record, false);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.dropped > 0 {
+            warn!(target: "exporter", dropped = self.dropped, "mqtt messages were dropped while the broker was unreachable");
+        }
+        // The disconnect is queued behind the pending publishes, so they go
+        // out first when the broker is reachable.
+        if self.client.try_disconnect().is_ok() && self.done.recv_timeout(FLUSH_TIMEOUT).is_err() {
+            warn!(target: "exporter", "mqtt: gave up waiting for queued messages");
+        }
+        Ok(())
+    }
+}
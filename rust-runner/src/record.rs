@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::terminal;
+use serde_json::json;
+use tracing::warn;
+
+/// asciinema v2 recording (`.cast`) of what the TUI writes to the
+/// terminal: a JSON header line, then one `[seconds, "o", text]` event per
+/// flushed frame and `[seconds, "r", "COLSxROWS"]` when the size changes.
+/// `asciinema play` replays it with the original timing.
+pub struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+    size: (u16, u16),
+    /// Bytes written since the last frame, including an incomplete UTF-8
+    /// sequence carried over from it.
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let size = terminal::size()?;
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let header = json!({
+            "version": 2,
+            "width": size.0,
+            "height": size.1,
+            "timestamp": timestamp,
+            "title": "rust-runner tui",
+            "env": {
+                "TERM": std::env::var("TERM").unwrap_or_default(),
+                "SHELL": std::env::var("SHELL").unwrap_or_default(),
+            },
+        });
+        writeln!(file, "{header}")?;
+        Ok(Self {
+            file,
+            start: Instant::now(),
+            size,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Record the output written since the last frame as one event.
+    fn frame(&mut self) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if let Ok(size) = terminal::size()
+            && size != self.size
+        {
+            self.size = size;
+            let resize = json!([elapsed, "r", format!("{}x{}", size.0, size.1)]);
+            writeln!(self.file, "{resize}")?;
+        }
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        // A frame may end inside a multi-byte character; keep its start
+        // for the next one.
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let text = String::from_utf8_lossy(&self.pending[..complete]);
+        writeln!(self.file, "{}", json!([elapsed, "o", text]))?;
+        self.file.flush()?;
+        self.pending.drain(..complete);
+        Ok(())
+    }
+}
+
+/// The TUI's terminal output, teed into a [`Recorder`] when one is set.
+/// A recording that fails to write is stopped with a warning; the
+/// dashboard itself carries on.
+pub struct TuiOutput {
+    stdout: Stdout,
+    recorder: Option<Recorder>,
+}
+
+impl TuiOutput {
+    pub fn new(stdout: Stdout, recorder: Option<Recorder>) -> Self {
+        Self { stdout, recorder }
+    }
+}
+
+impl Write for TuiOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(buf)?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        if let Some(recorder) = &mut self.recorder
+            && let Err(err) = recorder.frame()
+        {
+            warn!(target: "tui", "session recording stopped: {err}");
+            self.recorder = None;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use clap::Args;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+use tracing::{debug, info, warn};
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::remote::{Transport, serve_requests};
+use neo_ebpf_core::stream::SnapshotStream;
+
+use crate::demo::DemoArgs;
+use crate::parse_duration;
+
+/// Longest a client waits for an agent's answer before giving up.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client flags shared by `dump` and `tui` for sampling `rust-runner agent`s.
+#[derive(Args, Clone, Debug)]
+pub struct RemoteArgs {
+    /// Sample the agent at HOST:PORT instead of a local map (--map is
+    /// ignored); `dump` accepts it repeatedly
+    #[arg(long, value_name = "HOST:PORT")]
+    pub remote: Vec<String>,
+
+    /// Connect with TLS, trusting agents whose certificate chains to this CA
+    /// (PEM)
+    #[arg(long, requires = "remote")]
+    pub tls_ca: Option<PathBuf>,
+
+    /// Client certificate (PEM) for agents started with --client-ca
+    #[arg(long, requires_all = ["tls_ca", "tls_key"])]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Private key (PEM) of --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+}
+
+impl RemoteArgs {
+    /// Connect to every `--remote` agent; windows are labelled with the
+    /// address as given.
+    pub fn connect(
+        &self,
+        interval: Duration,
+        alpha: f64,
+    ) -> Result<Vec<SnapshotStream>, RunnerError> {
+        let tls = match &self.tls_ca {
+            Some(ca) => Some(Arc::new(client_config(
+                ca,
+                self.tls_cert.as_deref().zip(self.tls_key.as_deref()),
+            )?)),
+            None => None,
+        };
+        self.remote
+            .iter()
+            .map(|addr| {
+                let transport =
+                    connect(addr, tls.clone()).map_err(|source| RunnerError::Remote {
+                        addr: addr.clone(),
+                        source,
+                    })?;
+                Ok(SnapshotStream::remote(transport, addr, interval, alpha))
+            })
+            .collect()
+    }
+}
+
+fn connect(addr: &str, tls: Option<Arc<ClientConfig>>) -> io::Result<Box<dyn Transport>> {
+    let tcp = addr
+        .to_socket_addrs()?
+        .map(|resolved| TcpStream::connect_timeout(&resolved, REPLY_TIMEOUT))
+        .reduce(|first, next| first.or(next))
+        .unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "address resolved to nothing",
+            ))
+        })?;
+    tcp.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    tcp.set_write_timeout(Some(REPLY_TIMEOUT))?;
+    tcp.set_nodelay(true)?;
+    let Some(config) = tls else {
+        debug!(target: "map", addr, "connected to agent");
+        return Ok(Box::new(tcp));
+    };
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let session = ClientConnection::new(config, name).map_err(io::Error::other)?;
+    debug!(target: "map", addr, "connected to agent over TLS");
+    Ok(Box::new(rustls::StreamOwned::new(session, tcp)))
+}
+
+fn client_config(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<ClientConfig, RunnerError> {
+    let builder = ClientConfig::builder().with_root_certificates(root_store(ca)?);
+    match identity {
+        Some((cert, key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+            .map_err(|err| RunnerError::Config(format!("{}: {err}", cert.display()))),
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+fn server_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: Option<&Path>,
+) -> Result<ServerConfig, RunnerError> {
+    let builder = match client_ca {
+        Some(ca) => {
+            let verifier = WebPkiClientVerifier::builder(Arc::new(root_store(ca)?))
+                .build()
+                .map_err(|err| RunnerError::Config(format!("{}: {err}", ca.display())))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(load_certs(cert)?, load_key(key)?)
+        .map_err(|err| RunnerError::Config(format!("{}: {err}", cert.display())))
+}
+
+fn pem_reader(path: &Path) -> Result<BufReader<File>, RunnerError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, RunnerError> {
+    let certs = rustls_pemfile::certs(&mut pem_reader(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))?;
+    if certs.is_empty() {
+        return Err(RunnerError::Config(format!(
+            "{}: no PEM certificates",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, RunnerError> {
+    rustls_pemfile::private_key(&mut pem_reader(path)?)
+        .map_err(|err| RunnerError::Config(format!("{}: {err}", path.display())))?
+        .ok_or_else(|| RunnerError::Config(format!("{}: no PEM private key", path.display())))
+}
+
+fn root_store(ca: &Path) -> Result<RootCertStore, RunnerError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca)? {
+        roots
+            .add(cert)
+            .map_err(|err| RunnerError::Config(format!("{}: {err}", ca.display())))?;
+    }
+    Ok(roots)
+}
+
+#[derive(Args, Clone)]
+pub struct AgentArgs {
+    /// Address to accept clients on; use a routable address (with TLS) to
+    /// reach the agent from other hosts
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    listen: SocketAddr,
+
+    /// Path to the pinned task map served to clients
+    #[arg(long, default_value = "/sys/fs/bpf/task_map")]
+    map: String,
+
+    /// Serve TLS with this certificate chain (PEM)
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// Private key (PEM) of --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Only accept clients presenting a certificate signed by this CA (PEM)
+    #[arg(long, requires = "tls_cert")]
+    client_ca: Option<PathBuf>,
+
+    /// Drop a client that sends no request for this long, e.g. `15m`; keep
+    /// it above the clients' sampling interval
+    #[arg(long, value_parser = parse_duration, default_value = "15m")]
+    idle_timeout: Duration,
+
+    /// Clients served at once, each reading the map on a thread of its
+    /// own; further connections are closed right away
+    #[arg(long, default_value_t = 64)]
+    max_clients: usize,
+
+    #[command(flatten)]
+    demo: DemoArgs,
+}
+
+/// Accept clients until interrupted. Each connection reads the map (or its
+/// own demo population) on every request from a thread of its own.
+pub fn run_agent(args: AgentArgs) -> Result<(), RunnerError> {
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(Arc::new(server_config(
+            cert,
+            key,
+            args.client_ca.as_deref(),
+        )?)),
+        _ => None,
+    };
+    // Fail early on a missing map rather than on the first client.
+    if args.demo.to_config().is_none() {
+        SnapshotStream::open(&args.map, Duration::ZERO, 0.5)?;
+    }
+    let listener = TcpListener::bind(args.listen)?;
+    if tls.is_none() && !args.listen.ip().is_loopback() {
+        warn!(target: "map", addr = %args.listen, "serving task statistics without TLS on a non-loopback address");
+    }
+    info!(target: "map", addr = %args.listen, tls = tls.is_some(), "agent listening");
+    let active = Arc::new(AtomicUsize::new(0));
+    for conn in listener.incoming() {
+        let tcp = match conn {
+            Ok(tcp) => tcp,
+            Err(err) => {
+                warn!(target: "map", "accept failed: {err}");
+                continue;
+            }
+        };
+        let peer = tcp
+            .peer_addr()
+            .map_or_else(|_| "?".to_string(), |addr| addr.to_string());
+        if active.load(Ordering::Relaxed) >= args.max_clients {
+            warn!(target: "map", peer, max_clients = args.max_clients, "too many clients; connection closed");
+            continue;
+        }
+        let stream = match args.demo.to_config() {
+            Some(config) => SnapshotStream::demo(&config, Duration::ZERO, 0.5),
+            None => match SnapshotStream::open(&args.map, Duration::ZERO, 0.5) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!(target: "map", peer, "cannot serve client: {err}");
+                    continue;
+                }
+            },
+        };
+        let tls = tls.clone();
+        let idle_timeout = args.idle_timeout;
+        let slot = ClientSlot::take(&active);
+        thread::spawn(move || {
+            let _slot = slot;
+            info!(target: "map", peer, "client connected");
+            match serve_client(tcp, tls, stream, idle_timeout) {
+                Ok(()) => info!(target: "map", peer, "client disconnected"),
+                // TLS clients that exit without close_notify.
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    info!(target: "map", peer, "client disconnected")
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    info!(target: "map", peer, "client idle or stalled; dropped")
+                }
+                Err(err) => warn!(target: "map", peer, "client dropped: {err}"),
+            }
+        });
+    }
+    Ok(())
+}
+
+/// One of the agent's `--max-clients`, given back when dropped.
+struct ClientSlot(Arc<AtomicUsize>);
+
+impl ClientSlot {
+    fn take(active: &Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(active))
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn serve_client(
+    tcp: TcpStream,
+    tls: Option<Arc<ServerConfig>>,
+    mut stream: SnapshotStream,
+    idle_timeout: Duration,
+) -> io::Result<()> {
+    tcp.set_nodelay(true)?;
+    // Neither a silent client nor one that stops reading holds its thread
+    // forever.
+    tcp.set_read_timeout(Some(idle_timeout))?;
+    tcp.set_write_timeout(Some(REPLY_TIMEOUT))?;
+    let mut transport: Box<dyn Transport> = match tls {
+        Some(config) => {
+            let session = ServerConnection::new(config).map_err(io::Error::other)?;
+            Box::new(rustls::StreamOwned::new(session, tcp))
+        }
+        None => Box::new(tcp),
+    };
+    serve_requests(transport.as_mut(), || {
+        stream.frame().map_err(io::Error::other)
+    })
+}
@@ -0,0 +1,64 @@
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::control;
+use neo_ebpf_core::filter::TaskSelector;
+use neo_ebpf_core::stream::SnapshotStream;
+
+/// Samples to wait for the BPF program to pick up the new nice value; it
+/// only rewrites a task's tickets when the task is switched in.
+const OBSERVE_SAMPLES: u32 = 5;
+
+/// Change `pid`'s nice value, then watch `map` until the new ticket count
+/// shows up (or give up after a few samples).
+pub fn run_renice(map: &str, pid: u32, nice: i32, interval: Duration) -> Result<(), RunnerError> {
+    let mut stream = match SnapshotStream::open(map, interval, 0.5) {
+        Ok(stream) => Some(stream.with_selector(TaskSelector::new(vec![pid], None, Vec::new()))),
+        Err(err) => {
+            warn!(target: "map", "not observing the ticket change: {err}");
+            None
+        }
+    };
+    let change = match stream.as_mut() {
+        Some(stream) => stream.renice(pid, nice)?,
+        None => control::renice(pid, nice).map_err(|source| RunnerError::Control {
+            target: format!("pid {pid}"),
+            source,
+        })?,
+    };
+    println!(
+        "pid {pid}: nice {} -> {}, tickets {} -> {}",
+        change.old_nice,
+        change.new_nice,
+        change.old_tickets(),
+        change.new_tickets()
+    );
+    let Some(mut stream) = stream else {
+        return Ok(());
+    };
+    for sample in 1..=OBSERVE_SAMPLES {
+        thread::sleep(interval);
+        let window = stream.sample()?;
+        let Some(task) = window.snapshots.iter().find(|task| task.pid == pid) else {
+            continue;
+        };
+        if task.info.tickets == change.new_tickets() {
+            println!(
+                "{} sample {sample}: tickets {} ({:.2}% of {} map tickets)",
+                stream.label(),
+                task.info.tickets,
+                task.ticket_share * 100.0,
+                window.total_tickets
+            );
+            return Ok(());
+        }
+    }
+    println!(
+        "{}: tickets not updated after {OBSERVE_SAMPLES} samples (the task has not been scheduled since)",
+        stream.label()
+    );
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::summary::RunSummary;
+
+#[derive(Args, Clone)]
+pub struct ReportArgs {
+    /// Summary written by `dump --summary-output`
+    summary: PathBuf,
+
+    /// How many tasks to list, worst p99.9 lateness first
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Print the whole run summary (totals, fairness, shares), not only the
+    /// tail report
+    #[arg(long)]
+    full: bool,
+}
+
+/// Print the per-task tail report of a finished `dump` run, one block per
+/// map for multi-map summaries.
+pub fn run_report(args: ReportArgs) -> Result<(), RunnerError> {
+    let reports = RunSummary::read_json(&args.summary).map_err(|err| {
+        RunnerError::Config(format!(
+            "cannot read summary {}: {err}",
+            args.summary.display()
+        ))
+    })?;
+    for (label, report) in &reports {
+        if !label.is_empty() {
+            println!("\n[{label}]");
+        }
+        if args.full {
+            report.print(args.top);
+        } else {
+            println!(
+                "Run of {} iterations: {} deadline misses, worst lateness {:.3} ms",
+                report.iterations, report.total_misses, report.worst_lateness_ms
+            );
+            report.print_tails(args.top);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::io;
+use std::sync::mpsc as std_mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::stream::{SnapshotStream, SnapshotWindow};
+use tokio::sync::{Notify, mpsc, oneshot};
+use tokio::time;
+use tracing::{debug, warn};
+
+type Job = Box<dyn FnOnce(&mut SnapshotStream) + Send>;
+
+enum Request {
+    /// Take the sample of tick `n`.
+    Sample(u64),
+    Run(Job),
+}
+
+/// One source sampled on its own thread.
+struct Worker {
+    requests: std_mpsc::Sender<Request>,
+    /// A sample was requested and has not come back yet.
+    busy: bool,
+}
+
+/// The dump's sources. A single source is sampled inline; several each get
+/// a dedicated thread feeding one merge channel, so a slow map or agent
+/// only costs its own window instead of delaying every other source.
+pub struct Samplers {
+    local: Option<SnapshotStream>,
+    workers: Vec<Worker>,
+    results: mpsc::UnboundedReceiver<(usize, u64, Result<SnapshotWindow, RunnerError>)>,
+    labels: Vec<String>,
+    interval: Duration,
+    tick: u64,
+}
+
+impl Samplers {
+    pub fn new(mut streams: Vec<SnapshotStream>) -> Result<Self, RunnerError> {
+        let labels: Vec<String> = streams.iter().map(|s| s.label().to_string()).collect();
+        let interval = streams
+            .first()
+            .map_or(Duration::ZERO, SnapshotStream::interval);
+        let (done, results) = mpsc::unbounded_channel();
+        if streams.len() == 1 {
+            return Ok(Self {
+                local: streams.pop(),
+                workers: Vec::new(),
+                results,
+                labels,
+                interval,
+                tick: 0,
+            });
+        }
+        let mut workers = Vec::with_capacity(streams.len());
+        for (index, mut stream) in streams.into_iter().enumerate() {
+            let (requests, inbox) = std_mpsc::channel();
+            let done = done.clone();
+            thread::Builder::new()
+                .name(format!("sample-{}", stream.label()))
+                .spawn(move || {
+                    for request in inbox {
+                        match request {
+                            Request::Sample(tick) => {
+                                if done.send((index, tick, stream.sample())).is_err() {
+                                    return;
+                                }
+                            }
+                            Request::Run(job) => job(&mut stream),
+                        }
+                    }
+                })?;
+            workers.push(Worker {
+                requests,
+                busy: false,
+            });
+        }
+        Ok(Self {
+            local: None,
+            workers,
+            results,
+            labels,
+            interval,
+            tick: 0,
+        })
+    }
+
+    /// Labels of the sources, in `--map`/`--remote` order.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// The stream itself when there is a single source; the controllers
+    /// that steer tasks (policy, rebalance, throttle, auto-nice) need it.
+    pub fn single(&mut self) -> Option<&mut SnapshotStream> {
+        self.local.as_mut()
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// One window per source, in source order. Threaded sources get until
+    /// the next tick is due; one that is still sampling by then has no
+    /// window this tick and is not asked again until its sample is in,
+    /// which is then reported with the tick it arrives in.
+    pub async fn sample(&mut self) -> Result<Vec<Option<SnapshotWindow>>, RunnerError> {
+        if let Some(mut stream) = self.local.take() {
+            // A map walk or an agent's reply may take a while; the runtime
+            // keeps serving signals and the control socket meanwhile.
+            let (stream, window) = tokio::task::spawn_blocking(move || {
+                let window = stream.sample();
+                (stream, window)
+            })
+            .await
+            .map_err(|err| RunnerError::Io(io::Error::other(err)))?;
+            self.local = Some(stream);
+            return Ok(vec![Some(window?)]);
+        }
+        self.tick += 1;
+        for worker in self.workers.iter_mut().filter(|worker| !worker.busy) {
+            worker.busy = worker.requests.send(Request::Sample(self.tick)).is_ok();
+        }
+        let mut windows: Vec<Option<SnapshotWindow>> = self.workers.iter().map(|_| None).collect();
+        let grace = time::sleep(self.interval.max(Duration::from_millis(10)));
+        tokio::pin!(grace);
+        while self.workers.iter().any(|worker| worker.busy) {
+            tokio::select! {
+                result = self.results.recv() => {
+                    let Some((index, tick, window)) = result else {
+                        break;
+                    };
+                    self.workers[index].busy = false;
+                    if tick != self.tick {
+                        debug!(target: "sampler", map = %self.labels[index], tick, "window of an earlier tick arrived");
+                    }
+                    windows[index] = Some(window?);
+                }
+                _ = &mut grace => break,
+            }
+        }
+        for (index, worker) in self.workers.iter().enumerate() {
+            if worker.busy {
+                warn!(target: "sampler", map = %self.labels[index], "still sampling; skipping its window this tick");
+            }
+        }
+        Ok(windows)
+    }
+
+    /// Run `job` on every source and collect what it returns; waits for a
+    /// source that is in the middle of a sample.
+    pub async fn each<R, F>(&mut self, job: F) -> Vec<R>
+    where
+        R: Send + 'static,
+        F: Fn(&mut SnapshotStream) -> R + Clone + Send + 'static,
+    {
+        if let Some(stream) = self.local.as_mut() {
+            return vec![job(stream)];
+        }
+        let mut replies = Vec::with_capacity(self.workers.len());
+        for worker in &self.workers {
+            let (reply, answer) = oneshot::channel();
+            let job = job.clone();
+            let run: Job = Box::new(move |stream| {
+                let _ = reply.send(job(stream));
+            });
+            if worker.requests.send(Request::Run(run)).is_ok() {
+                replies.push(answer);
+            }
+        }
+        let mut results = Vec::with_capacity(replies.len());
+        for answer in replies {
+            if let Ok(result) = answer.await {
+                results.push(result);
+            }
+        }
+        results
+    }
+
+    pub async fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+        self.each(move |stream| stream.set_interval(interval)).await;
+    }
+}
+
+/// The newest window of a [`Background`] sampler, or the error it stopped
+/// on; replaced on every sample whether or not it was taken.
+type Latest = Mutex<Option<Result<SnapshotWindow, RunnerError>>>;
+
+/// One stream sampled on its own thread at its interval, for the TUI: the
+/// newest window is swapped into a slot the UI takes from, so drawing and
+/// key handling never wait for a slow map read. The lock only guards the
+/// swap. A window the UI never took is recycled by the next sample.
+pub struct Background {
+    latest: Arc<Latest>,
+    ready: Arc<Notify>,
+    jobs: std_mpsc::Sender<Job>,
+}
+
+impl Background {
+    pub fn spawn(mut stream: SnapshotStream) -> Result<Self, RunnerError> {
+        let latest: Arc<Latest> = Arc::default();
+        let ready = Arc::new(Notify::new());
+        let (jobs, inbox) = std_mpsc::channel::<Job>();
+        let (slot, notify) = (Arc::clone(&latest), Arc::clone(&ready));
+        thread::Builder::new()
+            .name(format!("sample-{}", stream.label()))
+            .spawn(move || {
+                let mut due = Instant::now();
+                loop {
+                    // Run jobs (renice, recycling) until the next tick is due.
+                    match inbox.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                        Ok(job) => {
+                            let before = stream.interval();
+                            job(&mut stream);
+                            // A new interval also moves the tick in progress,
+                            // so backing off from 2s to 100ms is immediate.
+                            let after = stream.interval();
+                            if after != before
+                                && let Some(last) = due.checked_sub(before)
+                            {
+                                due = last + after;
+                            }
+                            continue;
+                        }
+                        Err(std_mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(std_mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                    let sampled = stream.sample();
+                    let failed = sampled.is_err();
+                    let Ok(mut latest) = slot.lock() else { return };
+                    if let Some(Ok(unread)) = latest.replace(sampled) {
+                        debug!(target: "sampler", map = stream.label(), "window was not drawn; replaced by a newer one");
+                        stream.recycle(unread.snapshots);
+                    }
+                    drop(latest);
+                    notify.notify_one();
+                    if failed {
+                        return;
+                    }
+                    // Skip ticks a slow read overran instead of catching up.
+                    let interval = stream.interval().max(Duration::from_millis(1));
+                    due += interval;
+                    let now = Instant::now();
+                    if due < now {
+                        due = now + interval;
+                    }
+                }
+            })?;
+        Ok(Self {
+            latest,
+            ready,
+            jobs,
+        })
+    }
+
+    /// Wait for the next window, or the error sampling stopped on. Windows
+    /// that arrive faster than they are taken are dropped, not queued.
+    pub async fn next(&self) -> Result<SnapshotWindow, RunnerError> {
+        loop {
+            self.ready.notified().await;
+            if let Ok(mut latest) = self.latest.lock()
+                && let Some(window) = latest.take()
+            {
+                return window;
+            }
+        }
+    }
+
+    /// Run `job` on the sampling thread between two samples; what it
+    /// returns arrives on `reply` without blocking the caller.
+    pub fn run<R, F>(&self, job: F, reply: mpsc::UnboundedSender<R>)
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut SnapshotStream) -> R + Send + 'static,
+    {
+        let _ = self.jobs.send(Box::new(move |stream| {
+            let _ = reply.send(job(stream));
+        }));
+    }
+
+    /// Hand the snapshots of a window that was replaced back to the stream.
+    pub fn recycle(&self, window: SnapshotWindow) {
+        let _ = self
+            .jobs
+            .send(Box::new(move |stream| stream.recycle(window.snapshots)));
+    }
+}
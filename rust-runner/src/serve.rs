@@ -0,0 +1,242 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use neo_ebpf_core::export::{Exporter, SnapshotBatch, batch_json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{
+    Callback, ErrorResponse, Request, Response,
+};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tracing::{debug, info, warn};
+
+/// Batches a slow WebSocket client may fall behind before it skips ahead.
+const CLIENT_BACKLOG: usize = 16;
+/// Longest request head read before deciding what the client wants.
+const MAX_HEAD: usize = 8192;
+/// Keeps the dashboard to its own origin: scripts, styles, and the `/ws`
+/// stream load from the serving host only, and no other page may frame it.
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self'; connect-src 'self'; frame-ancestors 'none'; base-uri 'none'";
+
+/// Dashboard assets, compiled in so `--serve` needs nothing on disk.
+const ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "/",
+        "text/html; charset=utf-8",
+        include_str!("web/index.html"),
+    ),
+    ("/app.js", "text/javascript", include_str!("web/app.js")),
+    ("/style.css", "text/css", include_str!("web/style.css")),
+];
+
+/// Hands every batch to the `--serve` clients as one JSON text message.
+pub struct BroadcastExporter {
+    tx: broadcast::Sender<Arc<str>>,
+}
+
+impl Exporter for BroadcastExporter {
+    fn name(&self) -> &str {
+        "serve"
+    }
+
+    fn on_iteration(&mut self, batch: &SnapshotBatch) -> io::Result<()> {
+        // No subscribers is not an error; nobody is watching yet.
+        if self.tx.receiver_count() == 0 {
+            return Ok(());
+        }
+        let _ = self.tx.send(batch_json(batch).to_string().into());
+        Ok(())
+    }
+}
+
+/// Bind `addr` and serve the dashboard and `/ws` on the current runtime. Returns the exporter
+/// that feeds connected clients. Browsers may open `/ws` only from the
+/// dashboard's own origin or one of `origins`.
+pub async fn start(addr: SocketAddr, origins: Vec<String>) -> io::Result<BroadcastExporter> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(target: "exporter", addr = %listener.local_addr()?, "serving dashboard on / and WebSocket stream on /ws");
+    let (tx, _) = broadcast::channel(CLIENT_BACKLOG);
+    let clients = tx.clone();
+    let origins: Arc<[String]> = origins.into();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    tokio::spawn(handle(
+                        stream,
+                        peer,
+                        clients.subscribe(),
+                        Arc::clone(&origins),
+                    ));
+                }
+                Err(err) => {
+                    warn!(target: "exporter", "accept failed: {err}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+    Ok(BroadcastExporter { tx })
+}
+
+/// Path of the request line, peeked so a WebSocket handshake can still
+/// read the full request.
+async fn peek_path(stream: &TcpStream) -> io::Result<Option<String>> {
+    let mut buf = vec![0; MAX_HEAD];
+    for _ in 0..50 {
+        let n = stream.peek(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if let Some(end) = buf[..n].windows(2).position(|w| w == b"\r\n") {
+            let line = String::from_utf8_lossy(&buf[..end]);
+            return Ok(line.split_whitespace().nth(1).map(str::to_string));
+        }
+        if n == buf.len() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    Ok(None)
+}
+
+async fn handle(
+    stream: TcpStream,
+    peer: SocketAddr,
+    rx: broadcast::Receiver<Arc<str>>,
+    origins: Arc<[String]>,
+) {
+    let path = match peek_path(&stream).await {
+        Ok(Some(path)) => path,
+        Ok(None) => return,
+        Err(err) => {
+            debug!(target: "exporter", %peer, "cannot read request: {err}");
+            return;
+        }
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = if path == "/index.html" { "/" } else { path };
+    let result = if path == "/ws" {
+        stream_batches(stream, peer, rx, &origins).await
+    } else if let Some((_, content_type, body)) = ASSETS.iter().find(|(asset, ..)| *asset == path) {
+        respond(stream, "200 OK", content_type, body).await
+    } else {
+        respond(
+            stream,
+            "404 Not Found",
+            "text/plain",
+            "open / for the dashboard or connect a WebSocket to /ws\n",
+        )
+        .await
+    };
+    if let Err(err) = result {
+        debug!(target: "exporter", %peer, path, "client dropped: {err}");
+    }
+}
+
+/// Whether a handshake's `Origin` may read the stream: requests without
+/// one come from outside a browser, and pages may only read it from the
+/// host they were served by or an allowed origin, so that no other site
+/// open in the operator's browser can.
+fn origin_allowed(request: &Request, origins: &[String]) -> bool {
+    let Some(origin) = request.headers().get("origin") else {
+        return true;
+    };
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    if origins
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    {
+        return true;
+    }
+    let host = request
+        .headers()
+        .get("host")
+        .and_then(|host| host.to_str().ok());
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    matches!((origin_host, host), (Some(origin), Some(host)) if origin.eq_ignore_ascii_case(host))
+}
+
+/// Handshake callback refusing origins [`origin_allowed`] does not allow.
+struct OriginCheck<'a>(&'a [String]);
+
+impl Callback for OriginCheck<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        if origin_allowed(request, self.0) {
+            return Ok(response);
+        }
+        let mut refused = ErrorResponse::new(Some("cross-origin WebSocket refused\n".into()));
+        *refused.status_mut() = StatusCode::FORBIDDEN;
+        Err(refused)
+    }
+}
+
+async fn stream_batches(
+    stream: TcpStream,
+    peer: SocketAddr,
+    mut rx: broadcast::Receiver<Arc<str>>,
+    origins: &[String],
+) -> io::Result<()> {
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, OriginCheck(origins))
+        .await
+        .map_err(io::Error::other)?;
+    debug!(target: "exporter", %peer, "WebSocket client connected");
+    loop {
+        tokio::select! {
+            batch = rx.recv() => match batch {
+                Ok(batch) => ws
+                    .send(Message::text(batch.as_ref()))
+                    .await
+                    .map_err(io::Error::other)?,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!(target: "exporter", %peer, skipped, "slow WebSocket client skipped batches");
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    // The run is over.
+                    let _ = ws.close(None).await;
+                    return Ok(());
+                }
+            },
+            incoming = ws.next() => match incoming {
+                // Pings are answered by tungstenite; other input is ignored.
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(io::Error::other(err)),
+            },
+        }
+    }
+}
+
+/// Consume the request head and send a small plain HTTP response.
+async fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nContent-Security-Policy: {CONTENT_SECURITY_POLICY}\r\nX-Content-Type-Options: nosniff\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
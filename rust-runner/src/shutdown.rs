@@ -0,0 +1,25 @@
+use std::io;
+
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::warn;
+
+/// Resolves on the first SIGINT or SIGTERM so sampling loops can wind down
+/// and finalize exporters, the trace file, and the terminal. A second signal
+/// while finalizing exits immediately, so a wedged sink can still be killed.
+pub async fn signalled() -> io::Result<()> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = interrupt.recv() => {}
+        _ = terminate.recv() => {}
+    }
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = interrupt.recv() => {}
+            _ = terminate.recv() => {}
+        }
+        warn!(target: "sampler", "second signal, exiting without finalizing");
+        std::process::exit(130);
+    });
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::cmp::Ordering;
+
+use clap::ValueEnum;
+
+use neo_ebpf_core::events::{SchedEvent, SchedEvents};
+use neo_ebpf_core::group::GroupSnapshot;
+use neo_ebpf_core::latency::WakeupLatency;
+use neo_ebpf_core::stats::TaskSnapshot;
+
+/// Table order for `dump --sort` and the TUI's `:sort`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum SortKey {
+    #[default]
+    Share,
+    Lateness,
+    /// Share of the windows since first sight that missed the deadline.
+    MissRate,
+    /// 99th percentile wakeup latency.
+    Latency,
+    Delta,
+    Util,
+    Switches,
+    /// Share of the switches that were preemptions.
+    Preemption,
+    /// sched_wakeup events in the window.
+    Wakeups,
+    /// sched_migrate_task events in the window.
+    Migrations,
+    Pid,
+}
+
+impl SortKey {
+    pub fn tasks(self, a: &TaskSnapshot, b: &TaskSnapshot) -> Ordering {
+        match self {
+            SortKey::Share => a.ticket_share.total_cmp(&b.ticket_share),
+            SortKey::Lateness => a.lateness_ms.total_cmp(&b.lateness_ms),
+            SortKey::MissRate => a
+                .miss_rate()
+                .unwrap_or_default()
+                .total_cmp(&b.miss_rate().unwrap_or_default()),
+            SortKey::Latency => latency_p99(&a.latency).total_cmp(&latency_p99(&b.latency)),
+            SortKey::Delta => a.runtime_delta_ns.cmp(&b.runtime_delta_ns),
+            SortKey::Util => a.utilization.total_cmp(&b.utilization),
+            SortKey::Switches => a.switch_delta.cmp(&b.switch_delta),
+            SortKey::Preemption => a
+                .preemption_rate()
+                .unwrap_or_default()
+                .total_cmp(&b.preemption_rate().unwrap_or_default()),
+            SortKey::Wakeups => event_count(&a.events, SchedEvent::Wakeup)
+                .cmp(&event_count(&b.events, SchedEvent::Wakeup)),
+            SortKey::Migrations => event_count(&a.events, SchedEvent::Migrate)
+                .cmp(&event_count(&b.events, SchedEvent::Migrate)),
+            SortKey::Pid => a.pid.cmp(&b.pid),
+        }
+    }
+
+    pub fn groups(self, a: &GroupSnapshot, b: &GroupSnapshot) -> Ordering {
+        match self {
+            SortKey::Share => a.ticket_share.total_cmp(&b.ticket_share),
+            SortKey::Lateness => a.lateness_ms.total_cmp(&b.lateness_ms),
+            SortKey::MissRate => a
+                .miss_rate()
+                .unwrap_or_default()
+                .total_cmp(&b.miss_rate().unwrap_or_default()),
+            SortKey::Latency => latency_p99(&a.latency).total_cmp(&latency_p99(&b.latency)),
+            SortKey::Delta => a.runtime_delta_ns.cmp(&b.runtime_delta_ns),
+            SortKey::Util => a.utilization.total_cmp(&b.utilization),
+            SortKey::Switches => a.switch_delta.cmp(&b.switch_delta),
+            SortKey::Preemption => a
+                .preemption_rate()
+                .unwrap_or_default()
+                .total_cmp(&b.preemption_rate().unwrap_or_default()),
+            SortKey::Wakeups => event_count(&a.events, SchedEvent::Wakeup)
+                .cmp(&event_count(&b.events, SchedEvent::Wakeup)),
+            SortKey::Migrations => event_count(&a.events, SchedEvent::Migrate)
+                .cmp(&event_count(&b.events, SchedEvent::Migrate)),
+            SortKey::Pid => a.key.cmp(&b.key),
+        }
+    }
+}
+
+/// Rows without latency sort as if they never waited.
+fn latency_p99(latency: &Option<WakeupLatency>) -> f64 {
+    latency.map_or(0.0, |latency| latency.quantile_ms(0.99))
+}
+
+/// Rows without the event counted sort as if it never happened.
+fn event_count(events: &SchedEvents, event: SchedEvent) -> u64 {
+    events.get(event).unwrap_or_default()
+}
+
+/// Apply `--sort`/`--desc`; without `--sort` rows keep their natural order.
+pub fn sort_rows<T>(
+    rows: &mut [T],
+    sort: Option<SortKey>,
+    desc: bool,
+    cmp: fn(SortKey, &T, &T) -> Ordering,
+) {
+    if let Some(key) = sort {
+        rows.sort_by(|a, b| {
+            let order = cmp(key, a, b);
+            if desc { order.reverse() } else { order }
+        });
+    }
+}
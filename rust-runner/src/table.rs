@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use crossterm::style::Stylize;
+
+use neo_ebpf_core::cgroup::CgroupSnapshot;
+use neo_ebpf_core::events::SchedEvent;
+use neo_ebpf_core::procfs::format_cpu_list;
+use neo_ebpf_core::stats::TaskSnapshot;
+
+/// Names accepted by `--columns`. Each selects a table column and the
+/// matching CSV column(s).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKey {
+    /// `task`, or `cgroup` for the `--cgroups` rows.
+    Entity,
+    Pid,
+    Tgid,
+    Comm,
+    User,
+    Cpu,
+    Affinity,
+    Runtime,
+    Delta,
+    Roll,
+    Period,
+    Deadline,
+    /// Response time from switch-in timestamps.
+    Response,
+    /// SCHED_DEADLINE runtime/deadline/period of deadline tasks.
+    Dl,
+    Lateness,
+    /// Share of the windows since the task was first seen that it missed
+    /// its deadline in.
+    Miss,
+    /// Mean wakeup-to-run latency and the wakeups it covers.
+    Lat,
+    /// 99th percentile wakeup-to-run latency.
+    LatP99,
+    /// Longest wakeup-to-run latency.
+    LatMax,
+    Util,
+    Switches,
+    /// Switches the task gave up the CPU for itself (blocked or slept).
+    Voluntary,
+    /// Share of the switches that were preemptions.
+    Preempt,
+    /// sched_wakeup/sched_wakeup_new events in the window.
+    Wakeups,
+    /// sched_waking events in the window.
+    Wakings,
+    /// sched_migrate_task events in the window.
+    Migrations,
+    /// Whether the task's BPF counters started over this window.
+    Reset,
+    /// `STARVED`, `MISS`, or `OK`.
+    Status,
+    Nice,
+    Policy,
+    Tickets,
+    Share,
+    Kthread,
+    Container,
+    Cgroup,
+    Cmdline,
+}
+
+impl ColumnKey {
+    fn csv_names(self) -> &'static [&'static str] {
+        match self {
+            ColumnKey::Entity => &["entity", "cgroup_id"],
+            ColumnKey::Pid => &["pid"],
+            ColumnKey::Tgid => &["tgid"],
+            ColumnKey::Comm => &["comm"],
+            ColumnKey::User => &["user"],
+            ColumnKey::Cpu => &["last_cpu"],
+            ColumnKey::Affinity => &["cpus_allowed"],
+            ColumnKey::Runtime => &["runtime_ns", "runtime_ms"],
+            ColumnKey::Delta => &["delta_ns", "delta_ms"],
+            ColumnKey::Roll => &["rolling_runtime_ms"],
+            ColumnKey::Period => &["estimated_period_ms"],
+            ColumnKey::Deadline => &["deadline_ms"],
+            ColumnKey::Response => &["response_time_ms"],
+            ColumnKey::Dl => &["dl_runtime_ms", "dl_deadline_ms", "dl_period_ms"],
+            ColumnKey::Lateness => &["lateness_ms"],
+            ColumnKey::Miss => &["windows_observed", "windows_missed", "miss_rate"],
+            ColumnKey::Lat => &["wakeups", "latency_mean_ms"],
+            ColumnKey::LatP99 => &["latency_p99_ms"],
+            ColumnKey::LatMax => &["latency_max_ms"],
+            ColumnKey::Util => &["utilization"],
+            ColumnKey::Switches => &["switches", "switch_delta"],
+            ColumnKey::Voluntary => &["voluntary_switches", "involuntary_switches"],
+            ColumnKey::Preempt => &["preemption_rate"],
+            ColumnKey::Wakeups => &["sched_wakeups"],
+            ColumnKey::Wakings => &["sched_wakings"],
+            ColumnKey::Migrations => &["sched_migrations"],
+            ColumnKey::Reset => &["counter_reset"],
+            ColumnKey::Status => &["starved_windows", "starved"],
+            ColumnKey::Nice => &["nice"],
+            ColumnKey::Policy => &["policy", "rt_priority"],
+            ColumnKey::Tickets => &["tickets"],
+            ColumnKey::Share => &["ticket_share"],
+            ColumnKey::Kthread => &["kthread"],
+            ColumnKey::Container => &["container_id"],
+            ColumnKey::Cgroup => &["cgroup"],
+            ColumnKey::Cmdline => &["cmdline"],
+        }
+    }
+}
+
+/// Table columns shown without `--columns`/`--wide`.
+pub const DEFAULT_COLUMNS: &[ColumnKey] = &[
+    ColumnKey::Pid,
+    ColumnKey::User,
+    ColumnKey::Cpu,
+    ColumnKey::Affinity,
+    ColumnKey::Runtime,
+    ColumnKey::Delta,
+    ColumnKey::Roll,
+    ColumnKey::Period,
+    ColumnKey::Lateness,
+    ColumnKey::Miss,
+    ColumnKey::Util,
+    ColumnKey::Switches,
+    ColumnKey::Nice,
+    ColumnKey::Policy,
+    ColumnKey::Tickets,
+    ColumnKey::Share,
+];
+
+/// Table columns for `--columns` / `--wide` (every column, in catalog order),
+/// or the default set. The default set gains the `DL_MS` column while any of
+/// `snapshots` runs under SCHED_DEADLINE, so heuristic and real deadlines
+/// line up, the `LAT_MS`/`LAT_P99` columns while the source records
+/// wakeup latency, the `VOL_SW`/`PREEMPT%` columns while it counts
+/// preemptions, a `WAKEUPS`/`WAKINGS`/`MIGR` column for each scheduler
+/// event it counts, the `STATUS` column while a task is starved, and the
+/// `ENTITY` and `CGROUP` columns with `cgroups` rows.
+pub fn table_columns(
+    selected: &[ColumnKey],
+    wide: bool,
+    snapshots: &[TaskSnapshot],
+    cgroups: bool,
+) -> Vec<&'static Column> {
+    if wide {
+        return TASK_COLUMNS.iter().collect();
+    }
+    let mut keys = if selected.is_empty() {
+        DEFAULT_COLUMNS.to_vec()
+    } else {
+        selected.to_vec()
+    };
+    let deadline_tasks = snapshots.iter().any(|s| {
+        s.meta
+            .sched
+            .is_some_and(|attr| attr.dl_params_ms().is_some())
+    });
+    if selected.is_empty() && deadline_tasks {
+        let after = keys.iter().position(|key| *key == ColumnKey::Lateness);
+        keys.insert(after.map_or(keys.len(), |index| index + 1), ColumnKey::Dl);
+    }
+    if selected.is_empty() && snapshots.iter().any(|s| s.latency.is_some()) {
+        let after = keys.iter().position(|key| *key == ColumnKey::Util);
+        let at = after.unwrap_or(keys.len());
+        keys.splice(at..at, [ColumnKey::Lat, ColumnKey::LatP99]);
+    }
+    if selected.is_empty() && snapshots.iter().any(|s| s.preempt_delta.is_some()) {
+        let after = keys.iter().position(|key| *key == ColumnKey::Switches);
+        let at = after.map_or(keys.len(), |index| index + 1);
+        keys.splice(at..at, [ColumnKey::Voluntary, ColumnKey::Preempt]);
+    }
+    if selected.is_empty() {
+        let counted: Vec<ColumnKey> = [
+            (SchedEvent::Wakeup, ColumnKey::Wakeups),
+            (SchedEvent::Waking, ColumnKey::Wakings),
+            (SchedEvent::Migrate, ColumnKey::Migrations),
+        ]
+        .into_iter()
+        .filter(|(event, _)| snapshots.iter().any(|s| s.events.get(*event).is_some()))
+        .map(|(_, key)| key)
+        .collect();
+        let after = keys
+            .iter()
+            .rposition(|key| matches!(key, ColumnKey::Switches | ColumnKey::Preempt));
+        let at = after.map_or(keys.len(), |index| index + 1);
+        keys.splice(at..at, counted);
+    }
+    if selected.is_empty() && snapshots.iter().any(|s| s.starved) {
+        keys.push(ColumnKey::Status);
+    }
+    if selected.is_empty() && cgroups {
+        keys.insert(0, ColumnKey::Entity);
+        keys.push(ColumnKey::Cgroup);
+    }
+    keys.iter()
+        .filter_map(|key| TASK_COLUMNS.iter().find(|column| column.key == *key))
+        .collect()
+}
+
+/// CSV columns for an explicit `--columns` / `--wide` selection, or `None`
+/// to keep the default CSV layout. `iteration`, `timestamp_s`, and `map`
+/// are always included so rows stay identifiable.
+pub fn csv_columns(selected: &[ColumnKey], wide: bool) -> Option<Vec<&'static str>> {
+    if selected.is_empty() && !wide {
+        return None;
+    }
+    let keys: Vec<ColumnKey> = if wide {
+        TASK_COLUMNS.iter().map(|column| column.key).collect()
+    } else {
+        selected.to_vec()
+    };
+    let mut names = vec!["iteration", "timestamp_s"];
+    for key in keys {
+        for name in key.csv_names() {
+            if !names.contains(name) {
+                names.push(name);
+            }
+        }
+    }
+    names.push("map");
+    Some(names)
+}
+
+/// One column of the per-task dump table.
+pub struct Column {
+    key: ColumnKey,
+    header: &'static str,
+    width: usize,
+    left: bool,
+    text: fn(&TaskSnapshot) -> String,
+    /// Numeric value compared between samples in `--watch` mode.
+    value: Option<fn(&TaskSnapshot) -> f64>,
+}
+
+impl Column {
+    fn pad(&self, text: &str) -> String {
+        if self.left {
+            format!("{text:<width$}", width = self.width)
+        } else {
+            format!("{text:>width$}", width = self.width)
+        }
+    }
+}
+
+/// Every table column, in `--wide` order.
+const TASK_COLUMNS: &[Column] = &[
+    Column {
+        key: ColumnKey::Entity,
+        header: "ENTITY",
+        width: 6,
+        left: true,
+        text: |_| "task".to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Pid,
+        header: "PID",
+        width: 8,
+        left: true,
+        text: |s| s.pid.to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Tgid,
+        header: "TGID",
+        width: 8,
+        left: true,
+        text: |s| {
+            s.meta
+                .tgid
+                .map_or_else(|| "-".to_string(), |tgid| tgid.to_string())
+        },
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Comm,
+        header: "COMM",
+        width: 16,
+        left: true,
+        text: |s| s.meta.comm.clone().unwrap_or_else(|| "-".to_string()),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::User,
+        header: "USER",
+        width: 10,
+        left: true,
+        text: |s| s.meta.user_label(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Cpu,
+        header: "CPU",
+        width: 4,
+        left: false,
+        text: |s| s.meta.last_cpu_label(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Affinity,
+        header: "AFFINITY",
+        width: 10,
+        left: true,
+        text: |s| format_cpu_list(&s.meta.cpus_allowed),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Runtime,
+        header: "RUN_MS",
+        width: 11,
+        left: false,
+        text: |s| format!("{:.3}", s.info.runtime_ms()),
+        // Cumulative, so it would always read as "up".
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Delta,
+        header: "DELTA",
+        width: 11,
+        left: false,
+        text: |s| format!("{:.3}", s.runtime_delta_ms()),
+        value: Some(|s| s.runtime_delta_ms()),
+    },
+    Column {
+        key: ColumnKey::Roll,
+        header: "ROLL",
+        width: 11,
+        left: false,
+        text: |s| format!("{:.3}", s.rolling_runtime_ms),
+        value: Some(|s| s.rolling_runtime_ms),
+    },
+    Column {
+        key: ColumnKey::Period,
+        header: "PERIOD",
+        width: 11,
+        left: false,
+        text: |s| format!("{:.3}", s.estimated_period_ms),
+        value: Some(|s| s.estimated_period_ms),
+    },
+    Column {
+        key: ColumnKey::Deadline,
+        header: "DEADLINE",
+        width: 11,
+        left: false,
+        text: |s| format!("{:.3}", s.deadline_ms),
+        value: Some(|s| s.deadline_ms),
+    },
+    Column {
+        key: ColumnKey::Response,
+        header: "RESPONSE",
+        width: 11,
+        left: false,
+        text: |s| {
+            s.response_time_ms
+                .map_or("-".to_string(), |ms| format!("{ms:.3}"))
+        },
+        // NaN when unknown, which --watch leaves uncoloured.
+        value: Some(|s| s.response_time_ms.unwrap_or(f64::NAN)),
+    },
+    Column {
+        key: ColumnKey::Dl,
+        header: "DL_MS",
+        width: 20,
+        left: false,
+        text: |s| match s.meta.sched.and_then(|attr| attr.dl_params_ms()) {
+            Some((runtime, deadline, period)) => {
+                format!("{runtime:.1}/{deadline:.1}/{period:.1}")
+            }
+            None => "-".to_string(),
+        },
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Lateness,
+        header: "LATENESS",
+        width: 10,
+        left: false,
+        text: |s| format!("{:.3}", s.lateness_ms),
+        value: Some(|s| s.lateness_ms),
+    },
+    Column {
+        key: ColumnKey::Miss,
+        header: "MISS%",
+        width: 6,
+        left: false,
+        text: |s| {
+            s.miss_rate()
+                .map_or("-".to_string(), |rate| format!("{:.1}", rate * 100.0))
+        },
+        value: Some(|s| s.miss_rate().unwrap_or(f64::NAN)),
+    },
+    Column {
+        key: ColumnKey::Lat,
+        header: "LAT_MS",
+        width: 9,
+        left: false,
+        text: |s| {
+            s.latency
+                .map_or("-".to_string(), |lat| format!("{:.3}", lat.mean_ms()))
+        },
+        value: Some(|s| s.latency.map_or(f64::NAN, |lat| lat.mean_ms())),
+    },
+    Column {
+        key: ColumnKey::LatP99,
+        header: "LAT_P99",
+        width: 9,
+        left: false,
+        text: |s| {
+            s.latency.map_or("-".to_string(), |lat| {
+                format!("{:.3}", lat.quantile_ms(0.99))
+            })
+        },
+        value: Some(|s| s.latency.map_or(f64::NAN, |lat| lat.quantile_ms(0.99))),
+    },
+    Column {
+        key: ColumnKey::LatMax,
+        header: "LAT_MAX",
+        width: 9,
+        left: false,
+        text: |s| {
+            s.latency
+                .map_or("-".to_string(), |lat| format!("{:.3}", lat.max_ms()))
+        },
+        value: Some(|s| s.latency.map_or(f64::NAN, |lat| lat.max_ms())),
+    },
+    Column {
+        key: ColumnKey::Util,
+        header: "UTIL%",
+        width: 8,
+        left: false,
+        text: |s| format!("{:.2}", s.utilization * 100.0),
+        value: Some(|s| s.utilization),
+    },
+    Column {
+        key: ColumnKey::Switches,
+        header: "SW_DELTA",
+        width: 9,
+        left: false,
+        text: |s| s.switch_delta.to_string(),
+        value: Some(|s| s.switch_delta as f64),
+    },
+    Column {
+        key: ColumnKey::Voluntary,
+        header: "VOL_SW",
+        width: 9,
+        left: false,
+        text: |s| {
+            s.voluntary_delta()
+                .map_or("-".to_string(), |switches| switches.to_string())
+        },
+        value: Some(|s| {
+            s.voluntary_delta()
+                .map_or(f64::NAN, |switches| switches as f64)
+        }),
+    },
+    Column {
+        key: ColumnKey::Preempt,
+        header: "PREEMPT%",
+        width: 8,
+        left: false,
+        text: |s| {
+            s.preemption_rate()
+                .map_or("-".to_string(), |rate| format!("{:.1}", rate * 100.0))
+        },
+        value: Some(|s| s.preemption_rate().unwrap_or(f64::NAN)),
+    },
+    Column {
+        key: ColumnKey::Wakeups,
+        header: "WAKEUPS",
+        width: 8,
+        left: false,
+        text: |s| event_label(s, SchedEvent::Wakeup),
+        value: Some(|s| event_value(s, SchedEvent::Wakeup)),
+    },
+    Column {
+        key: ColumnKey::Wakings,
+        header: "WAKINGS",
+        width: 8,
+        left: false,
+        text: |s| event_label(s, SchedEvent::Waking),
+        value: Some(|s| event_value(s, SchedEvent::Waking)),
+    },
+    Column {
+        key: ColumnKey::Migrations,
+        header: "MIGR",
+        width: 6,
+        left: false,
+        text: |s| event_label(s, SchedEvent::Migrate),
+        value: Some(|s| event_value(s, SchedEvent::Migrate)),
+    },
+    Column {
+        key: ColumnKey::Reset,
+        header: "RESET",
+        width: 5,
+        left: false,
+        text: |s| if s.counter_reset { "yes" } else { "no" }.to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Status,
+        header: "STATUS",
+        width: 7,
+        left: true,
+        text: |s| s.status().to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Nice,
+        header: "NICE",
+        width: 6,
+        left: false,
+        text: |s| s.info.nice.to_string(),
+        value: Some(|s| s.info.nice as f64),
+    },
+    Column {
+        key: ColumnKey::Policy,
+        header: "POLICY",
+        width: 14,
+        left: true,
+        text: |s| s.meta.policy_label(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Tickets,
+        header: "TICKETS",
+        width: 8,
+        left: false,
+        text: |s| s.info.tickets.to_string(),
+        value: Some(|s| s.info.tickets as f64),
+    },
+    Column {
+        key: ColumnKey::Share,
+        header: "SHARE%",
+        width: 8,
+        left: false,
+        text: |s| format!("{:.2}", s.ticket_share * 100.0),
+        value: Some(|s| s.ticket_share),
+    },
+    Column {
+        key: ColumnKey::Kthread,
+        header: "KTHREAD",
+        width: 7,
+        left: false,
+        text: |s| if s.meta.kthread { "yes" } else { "no" }.to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Container,
+        header: "CONTAINER",
+        width: 12,
+        left: true,
+        text: |s| s.meta.short_container_id().unwrap_or("-").to_string(),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Cgroup,
+        header: "CGROUP",
+        width: 32,
+        left: true,
+        text: |s| s.meta.cgroup.clone().unwrap_or_else(|| "-".to_string()),
+        value: None,
+    },
+    Column {
+        key: ColumnKey::Cmdline,
+        header: "CMDLINE",
+        width: 0,
+        left: true,
+        text: |s| s.meta.cmdline.clone().unwrap_or_else(|| "-".to_string()),
+        value: None,
+    },
+];
+
+fn event_label(snapshot: &TaskSnapshot, event: SchedEvent) -> String {
+    snapshot
+        .events
+        .get(event)
+        .map_or("-".to_string(), |count| count.to_string())
+}
+
+fn event_value(snapshot: &TaskSnapshot, event: SchedEvent) -> f64 {
+    snapshot
+        .events
+        .get(event)
+        .map_or(f64::NAN, |count| count as f64)
+}
+
+/// A cell of `cgroup`'s row: the cgroup's id in the `PID` column, its mean
+/// tickets, and `-` for columns that only apply to a task.
+fn cgroup_text(key: ColumnKey, cgroup: &CgroupSnapshot) -> String {
+    match key {
+        ColumnKey::Entity => "cgroup".to_string(),
+        ColumnKey::Pid => cgroup.id.to_string(),
+        ColumnKey::Runtime => format!("{:.3}", cgroup.info.runtime_ns as f64 / 1_000_000.0),
+        ColumnKey::Delta => format!("{:.3}", cgroup.runtime_delta_ms()),
+        ColumnKey::Util => format!("{:.2}", cgroup.utilization * 100.0),
+        ColumnKey::Switches => cgroup.switch_delta.to_string(),
+        ColumnKey::Voluntary => cgroup.voluntary_delta().to_string(),
+        ColumnKey::Preempt => cgroup
+            .preemption_rate()
+            .map_or("-".to_string(), |rate| format!("{:.1}", rate * 100.0)),
+        ColumnKey::Tickets => cgroup
+            .tickets
+            .map_or("-".to_string(), |tickets| format!("{tickets:.0}")),
+        ColumnKey::Reset => if cgroup.counter_reset { "yes" } else { "no" }.to_string(),
+        ColumnKey::Cgroup => cgroup.label(),
+        _ => "-".to_string(),
+    }
+}
+
+/// Print the per-task table, followed by a row for each of `cgroups` that
+/// ran in the window. With `previous` (the last sample, by pid), task
+/// values that went up are shown in green and values that went down in red.
+pub fn print_task_table<'a>(
+    heading: &str,
+    columns: &[&Column],
+    total_tickets: u64,
+    entries: impl IntoIterator<Item = &'a TaskSnapshot>,
+    cgroups: &[CgroupSnapshot],
+    previous: Option<&HashMap<u32, TaskSnapshot>>,
+) {
+    println!("\n{heading}:");
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| column.pad(column.header))
+        .collect();
+    println!("{}", header.join(" ").trim_end());
+    for entry in entries {
+        let before = previous.and_then(|previous| previous.get(&entry.pid));
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let cell = column.pad(&(column.text)(entry));
+                let (Some(value), Some(before)) = (column.value, before) else {
+                    return cell;
+                };
+                let (now, then) = (value(entry), value(before));
+                if now > then {
+                    cell.green().to_string()
+                } else if now < then {
+                    cell.red().to_string()
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        println!("{}", cells.join(" ").trim_end());
+    }
+    for cgroup in cgroups.iter().filter(|cgroup| cgroup.runtime_delta_ns > 0) {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| column.pad(&cgroup_text(column.key, cgroup)))
+            .collect();
+        println!("{}", cells.join(" ").trim_end());
+    }
+    if total_tickets == 0 {
+        println!("Total tickets: 0 (all tasks currently inactive).");
+    } else {
+        println!("Total tickets: {total_tickets}");
+    }
+}
@@ -1,29 +1,118 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use ratatui::{
     Frame,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Paragraph, Row, Sparkline, Table},
+    symbols::Marker,
+    widgets::{
+        BarChart, Block, Borders, Cell, Clear, Gauge, Paragraph, Row, Sparkline, Table, Widget,
+        Wrap,
+        canvas::{Canvas, Line},
+    },
 };
 
-use crate::stats::TaskSnapshot;
+use neo_ebpf_core::cgroup::CgroupSnapshot;
+use neo_ebpf_core::control::Renice;
+use neo_ebpf_core::events::{SchedEvent, SchedEvents};
+use neo_ebpf_core::filter::glob_match;
+use neo_ebpf_core::group::{GroupBy, GroupSnapshot, aggregate, members};
+use neo_ebpf_core::histogram::LatenessHistogram;
+use neo_ebpf_core::inflation::{InflationTracker, TicketHolder};
+use neo_ebpf_core::latency::WakeupLatency;
+use neo_ebpf_core::procfs::format_cpu_list;
+use neo_ebpf_core::sketch::QuantileSketch;
+use neo_ebpf_core::stats::{MAX_NICE, MIN_NICE, Ranking, SchedClass, TaskSnapshot, pearson};
+use neo_ebpf_core::stream::SnapshotWindow;
+use neo_ebpf_core::tree::ProcessTree;
+
+use crate::sort::{SortKey, sort_rows};
 
 pub struct HistoryWindow {
     capacity: usize,
     samples: VecDeque<HistorySample>,
+    /// Lateness of every sample since the start, not just those kept.
+    run_lateness: LatenessHistogram,
+    /// Quantile sketch of the same, for the session-wide p95/p99.
+    run_quantiles: QuantileSketch,
 }
 
 #[derive(Clone, Default)]
 pub struct HistorySample {
-    pub avg_lateness: f64,
-    pub max_lateness: f64,
+    /// Lateness of every task in the window; the mean, worst and tail
+    /// quantiles all come from it.
+    pub lateness_quantiles: QuantileSketch,
     pub total_tasks: usize,
+    /// Ticket supply of the whole map.
+    pub total_tickets: u64,
     pub overdue_tasks: usize,
+    /// Tasks flagged as starved; see `TaskSnapshot::starved`.
+    pub starved_tasks: usize,
     pub total_runtime_ms: f64,
     pub avg_utilization: f64,
+    /// Share of the host's CPUs used; see `SnapshotWindow::system_utilization`.
+    pub system_utilization: f64,
+    pub cpus: usize,
+    /// Task utilisation of each CPU; see
+    /// `SnapshotWindow::per_cpu_task_utilization`.
+    pub per_cpu: Vec<f64>,
+    /// Share of each CPU's time spent in hard and soft interrupts; empty
+    /// when the source does not report it.
+    pub per_cpu_irq: Vec<f64>,
+    /// Hard and soft interrupt share of all CPUs together; `None` when the
+    /// source does not report it.
+    pub irq: Option<(f64, f64)>,
+    /// See `SnapshotWindow::unchanged_windows`.
+    pub unchanged_windows: u32,
+    pub stale: bool,
     pub top_pid: Option<u32>,
     pub top_share: f64,
+    /// Lateness of the tasks that ran in the window.
+    pub lateness: LatenessHistogram,
+    /// See `stats::ticket_concentration`.
+    pub ticket_hhi: f64,
+    /// `(pid, utilisation)` of the pinned tasks present in the window.
+    pub pinned: Vec<(u32, f64)>,
+    /// Wakeup latency of all tasks together; `None` when none waited or
+    /// the source records no latency.
+    pub latency: Option<WakeupLatency>,
+    /// Share of all switches that were preemptions; `None` without
+    /// switches or a preemption counter.
+    pub preemption_rate: Option<f64>,
+    /// Scheduler events of all tasks together.
+    pub events: SchedEvents,
+    /// Mean and busiest-CPU run-queue depth; `None` when the source does
+    /// not report it.
+    pub rq_depth: Option<(f64, f64)>,
+    /// Gini coefficient of the tasks' runtime; `None` when nothing ran.
+    pub runtime_gini: Option<f64>,
+    /// Spearman correlation of tickets and achieved share, and the tasks
+    /// far off their share; see `PriorityReport`.
+    pub priority_spearman: Option<f64>,
+    pub priority_outliers: usize,
+}
+
+impl HistorySample {
+    /// Mean lateness of the tasks; 0 for an empty window.
+    pub fn avg_lateness(&self) -> f64 {
+        self.lateness_quantiles.mean().unwrap_or(0.0)
+    }
+
+    /// Worst lateness of the tasks; 0 for an empty window.
+    pub fn max_lateness(&self) -> f64 {
+        self.lateness_quantiles.max().unwrap_or(0.0)
+    }
+
+    /// The `q` quantile of the tasks' lateness; 0 for an empty window.
+    pub fn lateness_quantile(&self, q: f64) -> f64 {
+        self.lateness_quantiles.quantile(q).unwrap_or(0.0)
+    }
 }
 
 impl HistoryWindow {
@@ -31,10 +120,14 @@ impl HistoryWindow {
         Self {
             capacity: capacity.max(1),
             samples: VecDeque::with_capacity(capacity.max(1)),
+            run_lateness: LatenessHistogram::default(),
+            run_quantiles: QuantileSketch::new(),
         }
     }
 
     pub fn push(&mut self, sample: HistorySample) {
+        self.run_lateness.merge(&sample.lateness);
+        self.run_quantiles.merge(&sample.lateness_quantiles);
         if self.samples.len() == self.capacity {
             self.samples.pop_front();
         }
@@ -48,89 +141,1636 @@ impl HistoryWindow {
     pub fn latest(&self) -> Option<&HistorySample> {
         self.samples.back()
     }
+
+    /// Lateness over the kept samples, and how many there are.
+    pub fn lateness_horizon(&self) -> (usize, QuantileSketch) {
+        let mut merged = QuantileSketch::new();
+        for sample in &self.samples {
+            merged.merge(&sample.lateness_quantiles);
+        }
+        (self.samples.len(), merged)
+    }
+
+    /// Relative change of the ticket supply from the oldest kept sample to
+    /// the latest, in percent; `None` with fewer than two samples.
+    pub fn supply_growth(&self) -> Option<f64> {
+        let first = self.samples.front()?.total_tickets;
+        let last = self.samples.back()?.total_tickets;
+        (self.samples.len() > 1 && first > 0).then(|| (last as f64 / first as f64 - 1.0) * 100.0)
+    }
+
+    /// How the worst lateness follows the mean run-queue depth over the
+    /// kept samples; see [`pearson`].
+    pub fn depth_lateness_correlation(&self) -> Option<f64> {
+        pearson(self.samples.iter().filter_map(|sample| {
+            sample
+                .rq_depth
+                .map(|(mean, _)| (mean, sample.max_lateness()))
+        }))
+    }
+
+    /// How the worst lateness follows the interrupt share of the CPUs over
+    /// the kept samples; see [`pearson`].
+    pub fn interrupt_lateness_correlation(&self) -> Option<f64> {
+        pearson(self.samples.iter().filter_map(|sample| {
+            sample
+                .irq
+                .map(|(irq, softirq)| (irq + softirq, sample.max_lateness()))
+        }))
+    }
 }
 
-fn render_table(frame: &mut Frame<'_>, snapshots: &[TaskSnapshot], top_n: usize, area: Rect) {
-    let mut ranking = snapshots.to_vec();
-    ranking.sort_by(|a, b| {
-        b.ticket_share
-            .partial_cmp(&a.ticket_share)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    let limit = ranking.len().min(top_n.max(1));
+/// Alerts kept for the session; the oldest are dropped beyond this.
+const ALERT_CAPACITY: usize = 10_000;
+
+/// Change of the EWMA alpha per `[`/`]` press.
+const ALPHA_STEP: f64 = 0.05;
+
+/// Refresh intervals `<`/`>` step through.
+const REFRESH_STEPS: [Duration; 8] = [
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+];
+
+/// Rows the alert pane scrolls per PageUp/PageDown.
+const ALERT_PAGE: usize = 5;
+
+/// Selection step of `g`/`G`: past either end of any table.
+const TO_END: isize = i32::MAX as isize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    /// A task's lateness went over the threshold.
+    Deadline,
+    /// A task's p99 wakeup latency went over the threshold.
+    Latency,
+    /// A task held tickets without running for too many windows.
+    Starved,
+    /// A task's or cgroup's tickets grew past the inflation threshold.
+    Inflation,
+    CounterReset,
+    /// The map stopped (or started) changing.
+    Stale,
+}
+
+impl AlertKind {
+    fn label(self) -> &'static str {
+        match self {
+            AlertKind::Deadline => "DEADLINE",
+            AlertKind::Latency => "LATENCY",
+            AlertKind::Starved => "STARVED",
+            AlertKind::Inflation => "TICKETS",
+            AlertKind::CounterReset => "RESET",
+            AlertKind::Stale => "STALE",
+        }
+    }
+}
+
+pub struct Alert {
+    /// Window timestamp, seconds since the epoch.
+    pub timestamp: f64,
+    pub kind: AlertKind,
+    pub pid: Option<u32>,
+    pub comm: Option<String>,
+    pub detail: String,
+}
+
+/// Deadline and map anomalies seen during the session, for the alert pane.
+/// Deadline alerts fire when a task goes over the threshold, not for every
+/// window it stays there, so a persistently late task does not bury the
+/// rest.
+pub struct AlertHistory {
+    threshold_ms: f64,
+    /// p99 wakeup latency that raises an alert; 0 disables them.
+    latency_ms: f64,
+    alerts: VecDeque<Alert>,
+    /// Alerts dropped to stay within [`ALERT_CAPACITY`].
+    dropped: usize,
+    /// Tasks over the threshold in the last window.
+    late: HashSet<u32>,
+    /// Tasks over the latency threshold in the last window.
+    slow: HashSet<u32>,
+    /// Tasks flagged as starved in the last window.
+    starved: HashSet<u32>,
+    /// Ticket growth per task and cgroup; `None` when off.
+    inflation: Option<InflationTracker>,
+    stale: bool,
+}
+
+impl AlertHistory {
+    pub fn new(threshold_ms: f64, latency_ms: f64) -> Self {
+        Self {
+            threshold_ms,
+            latency_ms,
+            alerts: VecDeque::new(),
+            dropped: 0,
+            late: HashSet::new(),
+            slow: HashSet::new(),
+            starved: HashSet::new(),
+            inflation: None,
+            stale: false,
+        }
+    }
+
+    /// Raise an alert when a task's or cgroup's tickets grow by more than
+    /// `growth` within `windows` windows.
+    pub fn with_inflation(mut self, growth: f64, windows: u32) -> Self {
+        self.inflation = Some(InflationTracker::new(growth, windows));
+        self
+    }
+
+    /// Record the alerts raised by `window`; returns how many there were.
+    pub fn observe(&mut self, window: &SnapshotWindow) -> usize {
+        let before = self.alerts.len() + self.dropped;
+        let timestamp = window.timestamp;
+        let mut late = HashSet::new();
+        let mut flagged: Vec<&TaskSnapshot> = window
+            .snapshots
+            .iter()
+            .filter(|entry| entry.lateness_ms > self.threshold_ms)
+            .collect();
+        // Least late first, so the pane (newest on top) lists the worst first.
+        flagged.sort_by(|a, b| a.lateness_ms.total_cmp(&b.lateness_ms));
+        for entry in flagged {
+            late.insert(entry.pid);
+            if self.late.contains(&entry.pid) {
+                continue;
+            }
+            self.push(Alert {
+                timestamp,
+                kind: AlertKind::Deadline,
+                pid: Some(entry.pid),
+                comm: entry.meta.comm.clone(),
+                detail: format!(
+                    "lateness {:.3} ms, util {:.1}%, period {:.3} ms, {} tickets (nice {})",
+                    entry.lateness_ms,
+                    entry.utilization * 100.0,
+                    entry.estimated_period_ms,
+                    entry.info.tickets,
+                    entry.info.nice
+                ),
+            });
+        }
+        self.late = late;
+        if self.latency_ms > 0.0 {
+            self.observe_latency(window);
+        }
+        let mut starved = HashSet::new();
+        for entry in window.snapshots.iter().filter(|entry| entry.starved) {
+            starved.insert(entry.pid);
+            if self.starved.contains(&entry.pid) {
+                continue;
+            }
+            self.push(Alert {
+                timestamp,
+                kind: AlertKind::Starved,
+                pid: Some(entry.pid),
+                comm: entry.meta.comm.clone(),
+                detail: format!(
+                    "no runtime for {} windows despite {:.1}% of the tickets ({} tickets, nice {})",
+                    entry.starved_windows,
+                    entry.ticket_share * 100.0,
+                    entry.info.tickets,
+                    entry.info.nice
+                ),
+            });
+        }
+        self.starved = starved;
+        if let Some(tracker) = &mut self.inflation {
+            let inflations = tracker.observe(&window.snapshots, window.total_tickets);
+            // Smallest growth first, so the pane lists the largest on top.
+            for inflation in inflations.into_iter().rev() {
+                let (pid, comm) = match &inflation.holder {
+                    TicketHolder::Task(pid) => (
+                        Some(*pid),
+                        window
+                            .snapshots
+                            .iter()
+                            .find(|entry| entry.pid == *pid)
+                            .and_then(|entry| entry.meta.comm.clone()),
+                    ),
+                    TicketHolder::Cgroup(_) => (None, None),
+                };
+                self.push(Alert {
+                    timestamp,
+                    kind: AlertKind::Inflation,
+                    pid,
+                    comm,
+                    detail: format!(
+                        "{} {} {} -> {} (+{:.0}%) in {} windows, now {:.1}% of {}",
+                        inflation.holder,
+                        inflation.unit(),
+                        inflation.from,
+                        inflation.to,
+                        inflation.growth() * 100.0,
+                        inflation.windows,
+                        inflation.share * 100.0,
+                        window.total_tickets
+                    ),
+                });
+            }
+        }
+
+        let resets: Vec<&TaskSnapshot> = window
+            .snapshots
+            .iter()
+            .filter(|entry| entry.counter_reset)
+            .collect();
+        match resets.as_slice() {
+            [] => {}
+            [entry] => self.push(Alert {
+                timestamp,
+                kind: AlertKind::CounterReset,
+                pid: Some(entry.pid),
+                comm: entry.meta.comm.clone(),
+                detail: "counters went backwards; deltas restart".to_string(),
+            }),
+            entries => self.push(Alert {
+                timestamp,
+                kind: AlertKind::CounterReset,
+                pid: None,
+                comm: None,
+                detail: format!(
+                    "counters reset for {} tasks (BPF program reloaded?)",
+                    entries.len()
+                ),
+            }),
+        }
+
+        if window.stale() != self.stale {
+            self.stale = window.stale();
+            let detail = if self.stale {
+                format!(
+                    "no counter moved in {} windows (BPF program detached?)",
+                    window.unchanged_windows
+                )
+            } else {
+                "map counters are moving again".to_string()
+            };
+            self.push(Alert {
+                timestamp,
+                kind: AlertKind::Stale,
+                pid: None,
+                comm: None,
+                detail,
+            });
+        }
+        self.alerts.len() + self.dropped - before
+    }
+
+    /// Latency alerts, raised like deadline ones when a task crosses the
+    /// threshold.
+    fn observe_latency(&mut self, window: &SnapshotWindow) {
+        let mut slow = HashSet::new();
+        let mut flagged: Vec<(&TaskSnapshot, WakeupLatency)> = window
+            .snapshots
+            .iter()
+            .filter_map(|entry| entry.latency.map(|latency| (entry, latency)))
+            .filter(|(_, latency)| latency.quantile_ms(0.99) > self.latency_ms)
+            .collect();
+        flagged.sort_by(|(_, a), (_, b)| a.quantile_ms(0.99).total_cmp(&b.quantile_ms(0.99)));
+        for (entry, latency) in flagged {
+            slow.insert(entry.pid);
+            if self.slow.contains(&entry.pid) {
+                continue;
+            }
+            self.push(Alert {
+                timestamp: window.timestamp,
+                kind: AlertKind::Latency,
+                pid: Some(entry.pid),
+                comm: entry.meta.comm.clone(),
+                detail: format!(
+                    "wakeup latency p99 {:.3} ms, max {:.3} ms over {} wakeups, {} tickets (nice {})",
+                    latency.quantile_ms(0.99),
+                    latency.max_ms(),
+                    latency.wakeups,
+                    entry.info.tickets,
+                    entry.info.nice
+                ),
+            });
+        }
+        self.slow = slow;
+    }
+
+    fn push(&mut self, alert: Alert) {
+        if self.alerts.len() == ALERT_CAPACITY {
+            self.alerts.pop_front();
+            self.dropped += 1;
+        }
+        self.alerts.push_back(alert);
+    }
+
+    pub fn len(&self) -> usize {
+        self.alerts.len()
+    }
+}
+
+/// What a key press asks of the event loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Quit,
+    /// The view may have changed.
+    Redraw,
+    /// Not a dashboard key.
+    Ignore,
+}
+
+/// The dashboard's panes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    /// Task, group, or process-tree table.
+    Table,
+    Detail,
+    Summary,
+    History,
+    Alerts,
+    Pinned,
+}
+
+/// Whether `new` shows the same tasks doing the same as `old`: equal
+/// counters, deltas, shares, and metadata. Periods and lateness may still
+/// differ by timer jitter in the window length, which is not worth
+/// rebuilding the table for.
+pub fn same_activity(old: &[TaskSnapshot], new: &[TaskSnapshot]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|(old, new)| {
+            old.pid == new.pid
+                && old.info == new.info
+                && old.runtime_delta_ns == new.runtime_delta_ns
+                && old.switch_delta == new.switch_delta
+                && old.ticket_share == new.ticket_share
+                && old.meta == new.meta
+        })
+}
+
+/// Panes as last rendered. A pane is only rebuilt when invalidated or
+/// moved; otherwise its cells are copied from here, so an unchanged table
+/// costs neither row formatting nor, after ratatui's diff, terminal output.
+#[derive(Default)]
+pub struct PaneCache {
+    panes: [Option<Buffer>; 6],
+    /// Some pane was invalidated since the last draw.
+    stale: bool,
+}
+
+impl PaneCache {
+    pub fn invalidate(&mut self, pane: Pane) {
+        self.panes[pane as usize] = None;
+        self.stale = true;
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.panes = Default::default();
+        self.stale = true;
+    }
+
+    /// Whether a draw would show anything new.
+    pub fn needs_redraw(&self) -> bool {
+        self.stale
+    }
+
+    /// Copy `pane` into the frame, rendering it first with `render` when
+    /// it is invalid or its area changed.
+    fn draw(
+        &mut self,
+        frame: &mut Frame<'_>,
+        pane: Pane,
+        area: Rect,
+        render: impl FnOnce(&mut Buffer, Rect),
+    ) {
+        let cached = &mut self.panes[pane as usize];
+        if cached.as_ref().is_none_or(|buffer| buffer.area != area) {
+            let mut buffer = Buffer::empty(area);
+            render(&mut buffer, area);
+            *cached = Some(buffer);
+        }
+        if let Some(buffer) = cached {
+            frame.buffer_mut().merge(buffer);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TuiState {
+    pub selected_pid: Option<u32>,
+    pub show_detail: bool,
+    pub auto_focus: bool,
+    pub group_by: GroupBy,
+    pub selected_group: Option<String>,
+    pub expanded_groups: HashSet<String>,
+    pub tree_view: bool,
+    pub collapsed_tgids: HashSet<u32>,
+    /// `(pid, nice)` asked for with `+`/`-`; applied by the event loop.
+    pub renice_request: Option<(u32, i32)>,
+    /// Last renice, until its new ticket count shows up in a sample.
+    pub pending_renice: Option<Renice>,
+    /// One-line outcome of the last action, shown in the summary panel.
+    pub status: Option<String>,
+    /// Top tasks by share in the current sample, see [`rank`](Self::rank).
+    pub ranking: Ranking,
+    pub show_alerts: bool,
+    /// Show lateness histograms instead of the trend sparklines.
+    pub show_histogram: bool,
+    /// Draw the trends as braille line charts instead of block sparklines.
+    pub braille: bool,
+    /// EWMA smoothing factor of the stream, as last set.
+    pub alpha: f64,
+    /// Alpha asked for with `[`/`]`; applied by the event loop.
+    pub alpha_request: Option<f64>,
+    /// Sampling interval, as last set.
+    pub refresh: Duration,
+    /// Interval asked for with `<`/`>`; applied by the event loop.
+    pub refresh_request: Option<Duration>,
+    /// Alerts scrolled past, counted from the newest.
+    pub alert_scroll: usize,
+    /// Show the key cheat sheet over the dashboard.
+    pub show_help: bool,
+    /// Active display filters, see `FilterArgs::describe`.
+    pub filters: String,
+    /// Table order set with `:sort`, largest first unless `sort_ascending`.
+    pub sort: SortKey,
+    pub sort_ascending: bool,
+    /// Command line being typed after `:`.
+    pub command: Option<String>,
+    /// Task name glob (or pid) set with `:filter`.
+    pub task_filter: Option<String>,
+    /// `:filter` changed the filter; the event loop re-applies it.
+    pub filter_changed: bool,
+    /// Path asked for with `:write`; written by the event loop.
+    pub write_request: Option<PathBuf>,
+    /// Tasks kept at the top of the table, in the order they were pinned.
+    pub pinned: Vec<u32>,
+}
+
+impl TuiState {
+    /// Apply a key press.
+    pub fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        snapshots: &[TaskSnapshot],
+        window_ms: f64,
+        top_n: usize,
+    ) -> KeyAction {
+        if self.command.is_some() {
+            return self.edit_command(key.code, snapshots, top_n);
+        }
+        let code = match key.code {
+            KeyCode::Char('j') => KeyCode::Down,
+            KeyCode::Char('k') => KeyCode::Up,
+            code => code,
+        };
+        let half_page = (top_n / 2).max(1) as isize;
+        match code {
+            KeyCode::Esc if self.show_help => self.show_help = false,
+            KeyCode::Char('q') | KeyCode::Esc => return KeyAction::Quit,
+            KeyCode::Char('?') => self.show_help = !self.show_help,
+            KeyCode::Char(':') => self.command = Some(String::new()),
+            KeyCode::Char('g') => self.cycle_group_by(),
+            KeyCode::Char(ch @ ('d' | 'u')) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let step = if ch == 'd' { half_page } else { -half_page };
+                self.move_rows(snapshots, window_ms, top_n, step);
+            }
+            KeyCode::Char('H') => self.move_rows(snapshots, window_ms, top_n, -TO_END),
+            KeyCode::Char('G') => self.move_rows(snapshots, window_ms, top_n, TO_END),
+            KeyCode::Char('p') if self.group_by == GroupBy::Pid && !self.tree_view => {
+                if let Some(pid) = self.selected_pid {
+                    self.toggle_pin(pid, snapshots, top_n);
+                }
+            }
+            KeyCode::Char('t') => {
+                self.tree_view = !self.tree_view;
+                self.selected_group = None;
+            }
+            KeyCode::Char('f') => {
+                self.toggle_auto_focus();
+                self.refresh_focus(snapshots);
+            }
+            KeyCode::Char('h') => self.show_histogram = !self.show_histogram,
+            KeyCode::Char('b') => self.braille = !self.braille,
+            KeyCode::Char(key @ ('[' | ']')) => {
+                let step = if key == ']' { ALPHA_STEP } else { -ALPHA_STEP };
+                // Snap to the step so repeated presses do not drift.
+                let alpha = ((self.alpha + step) / ALPHA_STEP).round() * ALPHA_STEP;
+                // Taken as applied right away, so quick presses add up.
+                self.alpha = alpha.clamp(ALPHA_STEP, 1.0);
+                self.alpha_request = Some(self.alpha);
+            }
+            KeyCode::Char(key @ ('<' | '>')) => {
+                let refresh = if key == '<' {
+                    REFRESH_STEPS
+                        .iter()
+                        .rev()
+                        .find(|&&step| step < self.refresh)
+                } else {
+                    REFRESH_STEPS.iter().find(|&&step| step > self.refresh)
+                };
+                if let Some(&refresh) = refresh {
+                    self.refresh = refresh;
+                    self.refresh_request = Some(refresh);
+                }
+            }
+            KeyCode::Char('a') => {
+                self.show_alerts = !self.show_alerts;
+                self.alert_scroll = 0;
+            }
+            KeyCode::PageUp if self.show_alerts => {
+                self.alert_scroll = self.alert_scroll.saturating_sub(ALERT_PAGE);
+            }
+            KeyCode::PageDown if self.show_alerts => self.alert_scroll += ALERT_PAGE,
+            KeyCode::Home if self.show_alerts => self.alert_scroll = 0,
+            KeyCode::Up | KeyCode::Down => {
+                let step = if code == KeyCode::Up { -1 } else { 1 };
+                self.move_rows(snapshots, window_ms, top_n, step);
+            }
+            KeyCode::Char(key @ ('+' | '-'))
+                if self.group_by == GroupBy::Pid && !self.tree_view =>
+            {
+                let step = if key == '+' { 1 } else { -1 };
+                if let Some(pid) = self.selected_pid
+                    && let Some(entry) = snapshots.iter().find(|e| e.pid == pid)
+                {
+                    // Real-time tasks report `prio - 120` as their nice, and
+                    // nice has no effect on them anyway.
+                    let nice = entry.info.nice;
+                    let target = (nice + step).clamp(MIN_NICE, MAX_NICE);
+                    if entry.sched_class() != SchedClass::Fair {
+                        self.status = Some(format!("pid {pid} is not a fair-class task"));
+                    } else if target == nice {
+                        self.status = Some(format!("pid {pid} is already at nice {nice}"));
+                    } else {
+                        self.renice_request = Some((pid, target));
+                    }
+                }
+            }
+            KeyCode::Enter => {
+                if self.tree_view {
+                    if let Some(tgid) = self.selected_group.as_ref().and_then(|k| k.parse().ok())
+                        && !self.collapsed_tgids.remove(&tgid)
+                    {
+                        self.collapsed_tgids.insert(tgid);
+                    }
+                } else if self.group_by == GroupBy::Pid {
+                    self.show_detail = !self.show_detail;
+                } else if let Some(key) = self.selected_group.clone()
+                    && !self.expanded_groups.remove(&key)
+                {
+                    self.expanded_groups.insert(key);
+                }
+            }
+            _ => return KeyAction::Ignore,
+        }
+        KeyAction::Redraw
+    }
+
+    /// Apply a key press on the `:` command line.
+    fn edit_command(
+        &mut self,
+        code: KeyCode,
+        snapshots: &[TaskSnapshot],
+        top_n: usize,
+    ) -> KeyAction {
+        let Some(command) = self.command.as_mut() else {
+            return KeyAction::Ignore;
+        };
+        match code {
+            KeyCode::Esc => self.command = None,
+            KeyCode::Backspace => {
+                if command.pop().is_none() {
+                    self.command = None;
+                }
+            }
+            KeyCode::Char(ch) => command.push(ch),
+            KeyCode::Enter => {
+                let line = self.command.take().unwrap_or_default();
+                return self.run_command(&line, snapshots, top_n);
+            }
+            _ => return KeyAction::Ignore,
+        }
+        KeyAction::Redraw
+    }
+
+    /// Run a `:` command: `sort`, `filter`, `write`, `group`, `help`, `quit`.
+    fn run_command(&mut self, line: &str, snapshots: &[TaskSnapshot], top_n: usize) -> KeyAction {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => {}
+            Some("q" | "quit") => return KeyAction::Quit,
+            Some("help") => self.show_help = true,
+            Some("sort") => match words.next().map(|word| SortKey::from_str(word, true)) {
+                Some(Ok(key)) => {
+                    self.sort = key;
+                    self.sort_ascending = words.next() == Some("asc");
+                    self.rank(snapshots, top_n);
+                    self.status = Some(format!(
+                        "sorted by {} ({})",
+                        format!("{key:?}").to_lowercase(),
+                        if self.sort_ascending {
+                            "smallest first"
+                        } else {
+                            "largest first"
+                        }
+                    ));
+                }
+                _ => {
+                    self.status = Some(
+                        "usage: :sort share|lateness|miss-rate|latency|delta|util|switches|wakeups|migrations|pid [asc]"
+                            .into(),
+                    )
+                }
+            },
+            Some("filter") => {
+                // A bare word matches anywhere in the name, like `grep`.
+                self.task_filter = words.next().map(|pattern| {
+                    if pattern.contains(['*', '?']) {
+                        pattern.to_string()
+                    } else {
+                        format!("*{pattern}*")
+                    }
+                });
+                self.filter_changed = true;
+                self.status = Some(match &self.task_filter {
+                    Some(pattern) => format!("showing tasks matching {pattern}"),
+                    None => "task filter cleared".to_string(),
+                });
+            }
+            Some("pin") => match words.next().map(str::parse) {
+                Some(Ok(pid)) => self.toggle_pin(pid, snapshots, top_n),
+                _ => self.status = Some("usage: :pin PID".into()),
+            },
+            Some("w" | "write") => match words.next() {
+                Some(path) => self.write_request = Some(PathBuf::from(path)),
+                None => self.status = Some("usage: :write PATH".into()),
+            },
+            Some("group") => match words.next().map(|word| GroupBy::from_str(word, true)) {
+                None => self.cycle_group_by(),
+                Some(Ok(group_by)) => {
+                    self.group_by = group_by;
+                    self.selected_group = None;
+                    self.expanded_groups.clear();
+                }
+                Some(Err(_)) => self.status = Some("usage: :group [pid|tgid|cgroup|user]".into()),
+            },
+            Some(other) => self.status = Some(format!("unknown command :{other} (? for help)")),
+        }
+        KeyAction::Redraw
+    }
+
+    /// Pin `pid`, or unpin it if it already is.
+    pub fn toggle_pin(&mut self, pid: u32, snapshots: &[TaskSnapshot], top_n: usize) {
+        if let Some(index) = self.pinned.iter().position(|&pinned| pinned == pid) {
+            self.pinned.remove(index);
+            self.status = Some(format!("pid {pid} unpinned"));
+        } else {
+            self.pinned.push(pid);
+            self.status = Some(format!("pid {pid} pinned"));
+        }
+        self.rank(snapshots, top_n);
+    }
 
+    /// Rows of the task table: the pinned tasks, then the top-N of the
+    /// ranking. A selected task outside both takes the last ranked place.
+    fn table_rows<'a>(
+        &'a self,
+        snapshots: &'a [TaskSnapshot],
+        top_n: usize,
+    ) -> Vec<&'a TaskSnapshot> {
+        let mut rows: Vec<&TaskSnapshot> = self
+            .pinned
+            .iter()
+            .filter_map(|&pid| snapshots.iter().find(|entry| entry.pid == pid))
+            .collect();
+        let pinned = rows.len();
+        rows.extend(
+            self.ranking
+                .top(snapshots)
+                .filter(|entry| !self.pinned.contains(&entry.pid))
+                .take(top_n.max(1)),
+        );
+        if let Some(pid) = self.selected_pid
+            && !rows.iter().any(|entry| entry.pid == pid)
+            && let Some(entry) = snapshots.iter().find(|entry| entry.pid == pid)
+        {
+            if rows.len() > pinned {
+                rows.pop();
+            }
+            rows.push(entry);
+        }
+        rows
+    }
+
+    /// Whether `snapshot` passes the `:filter` pattern, by name or pid.
+    pub fn matches_filter(&self, snapshot: &TaskSnapshot) -> bool {
+        self.task_filter.as_ref().is_none_or(|pattern| {
+            *pattern == format!("*{}*", snapshot.pid)
+                || snapshot
+                    .meta
+                    .comm
+                    .as_deref()
+                    .is_some_and(|comm| glob_match(pattern, comm))
+        })
+    }
+
+    /// The group table's rows, in `:sort` order.
+    fn groups(&self, snapshots: &[TaskSnapshot], window_ms: f64) -> Vec<GroupSnapshot> {
+        let mut groups = aggregate(snapshots, self.group_by, window_ms);
+        sort_rows(
+            &mut groups,
+            Some(self.sort),
+            !self.sort_ascending,
+            SortKey::groups,
+        );
+        groups
+    }
+
+    /// Move the selection of whichever table is shown by `step` rows.
+    fn move_rows(&mut self, snapshots: &[TaskSnapshot], window_ms: f64, top_n: usize, step: isize) {
+        if self.tree_view {
+            let tree = ProcessTree::build(snapshots, window_ms);
+            let keys: Vec<String> = tree
+                .visible_rows(&self.collapsed_tgids)
+                .into_iter()
+                .map(|(_, node)| node.tgid.to_string())
+                .collect();
+            self.move_group_selection(&keys, step);
+        } else if self.group_by == GroupBy::Pid {
+            self.move_selection(snapshots, top_n, step);
+        } else {
+            let keys: Vec<String> = self
+                .groups(snapshots, window_ms)
+                .into_iter()
+                .take(top_n.max(1))
+                .map(|g| g.key)
+                .collect();
+            self.move_group_selection(&keys, step);
+        }
+    }
+
+    /// Record the stream's new EWMA factor.
+    pub fn alpha_applied(&mut self, alpha: f64) {
+        self.alpha = alpha;
+        self.status = Some(format!(
+            "EWMA alpha now {alpha:.2}; rolling runtime and period keep their history"
+        ));
+    }
+
+    /// Record the stream's new sampling interval.
+    pub fn refresh_applied(&mut self, refresh: Duration) {
+        self.refresh = refresh;
+        self.status = Some(format!(
+            "refreshing every {refresh:?}; the next window spans the time since the last sample"
+        ));
+    }
+
+    /// Keep the alerts in view where they are when `added` new ones arrive
+    /// while the pane is scrolled back.
+    pub fn alerts_added(&mut self, added: usize) {
+        if self.alert_scroll > 0 {
+            self.alert_scroll += added;
+        }
+    }
+
+    /// Rank a new sample for the task table; done once per sample rather
+    /// than on every redraw and key press.
+    pub fn rank(&mut self, snapshots: &[TaskSnapshot], top_n: usize) {
+        let (key, ascending) = (self.sort, self.sort_ascending);
+        // Pinned tasks have their own rows; rank enough to fill the rest.
+        let limit = top_n.max(1) + self.pinned.len();
+        self.ranking.rank(snapshots, limit, |a, b| {
+            let order = key.tasks(a, b);
+            if ascending { order } else { order.reverse() }
+        });
+    }
+
+    /// Record the outcome of a renice and watch for its new ticket count.
+    pub fn renice_applied(&mut self, change: Renice) {
+        self.status = Some(format!(
+            "pid {}: nice {} -> {}, tickets {} -> {} on next switch-in",
+            change.pid,
+            change.old_nice,
+            change.new_nice,
+            change.old_tickets(),
+            change.new_tickets()
+        ));
+        self.pending_renice = Some(change);
+    }
+
+    /// Report once a sample shows the tickets of the last renice.
+    pub fn observe_renice(&mut self, snapshots: &[TaskSnapshot]) {
+        let Some(change) = self.pending_renice else {
+            return;
+        };
+        if let Some(entry) = snapshots.iter().find(|e| e.pid == change.pid)
+            && entry.info.tickets == change.new_tickets()
+        {
+            self.status = Some(format!(
+                "pid {}: now {} tickets ({:.2}% share, was {})",
+                change.pid,
+                entry.info.tickets,
+                entry.ticket_share * 100.0,
+                change.old_tickets()
+            ));
+            self.pending_renice = None;
+        }
+    }
+
+    pub fn move_group_selection(&mut self, keys: &[String], step: isize) {
+        if keys.is_empty() {
+            self.selected_group = None;
+            return;
+        }
+        let current = self
+            .selected_group
+            .as_ref()
+            .and_then(|sel| keys.iter().position(|k| k == sel));
+        let next = step_index(current, step, keys.len());
+        self.selected_group = Some(keys[next].clone());
+    }
+
+    pub fn cycle_group_by(&mut self) {
+        self.selected_group = None;
+        self.expanded_groups.clear();
+        self.group_by = match self.group_by {
+            GroupBy::Pid => GroupBy::Tgid,
+            GroupBy::Tgid => GroupBy::Cgroup,
+            GroupBy::Cgroup => GroupBy::User,
+            GroupBy::User => GroupBy::Pid,
+        };
+    }
+
+    pub fn toggle_auto_focus(&mut self) {
+        self.auto_focus = !self.auto_focus;
+        if !self.auto_focus {
+            self.show_detail = false;
+        }
+    }
+
+    /// Keep the worst offender selected while auto-focus is active.
+    pub fn refresh_focus(&mut self, snapshots: &[TaskSnapshot]) {
+        if !self.auto_focus {
+            return;
+        }
+        let worst = snapshots.iter().max_by(|a, b| {
+            a.lateness_ms
+                .partial_cmp(&b.lateness_ms)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        if let Some(entry) = worst {
+            self.selected_pid = Some(entry.pid);
+            self.show_detail = true;
+        }
+    }
+
+    /// Step the selection through the rows of the task table.
+    pub fn move_selection(&mut self, snapshots: &[TaskSnapshot], top_n: usize, step: isize) {
+        let rows: Vec<u32> = self
+            .table_rows(snapshots, top_n)
+            .iter()
+            .map(|entry| entry.pid)
+            .collect();
+        if rows.is_empty() {
+            self.selected_pid = None;
+            return;
+        }
+        self.auto_focus = false;
+        let current = self
+            .selected_pid
+            .and_then(|pid| rows.iter().position(|&row| row == pid));
+        self.selected_pid = Some(rows[step_index(current, step, rows.len())]);
+    }
+}
+
+/// Row `step` places from `current` in a table of `len > 0` rows. With
+/// nothing selected a single step lands on the first row.
+fn step_index(current: Option<usize>, step: isize, len: usize) -> usize {
+    let next = match current {
+        Some(idx) => idx as isize + step,
+        None if step.abs() == 1 => 0,
+        None => step,
+    };
+    next.clamp(0, len as isize - 1) as usize
+}
+
+fn render_table(
+    buf: &mut Buffer,
+    snapshots: &[TaskSnapshot],
+    top_n: usize,
+    state: &TuiState,
+    area: Rect,
+) {
     let header = Row::new(vec![
         "PID",
+        "USER",
+        "CPU",
         "SHARE%",
         "LAT(ms)",
+        "MISS%",
+        "LAT p99",
         "UTIL%",
+        "PRE%",
         "DELTA (ms)",
         "PERIOD (ms)",
         "TICKETS",
         "NICE",
+        "POLICY",
     ])
     .style(Style::default().add_modifier(Modifier::BOLD));
 
-    let rows: Vec<Row> = ranking
-        .iter()
-        .take(limit)
+    let rows: Vec<Row> = state
+        .table_rows(snapshots, top_n)
+        .into_iter()
         .map(|entry| {
             let cells = vec![
                 entry.pid.to_string(),
+                entry.meta.user_label(),
+                entry.meta.last_cpu_label(),
                 format!("{:.2}", entry.ticket_share * 100.0),
                 format!("{:.3}", entry.lateness_ms),
+                percent_label(entry.miss_rate()),
+                latency_p99_label(entry.latency),
                 format!("{:.1}", entry.utilization * 100.0),
+                percent_label(entry.preemption_rate()),
                 format!("{:.3}", entry.runtime_delta_ms()),
                 format!("{:.3}", entry.estimated_period_ms),
                 entry.info.tickets.to_string(),
                 entry.info.nice.to_string(),
+                entry.meta.policy_label(),
             ];
             let mut row = Row::new(cells);
-            if entry.lateness_ms > 0.0 {
-                row = row.style(Style::default().fg(Color::Red));
+            let mut style = Style::default();
+            if entry.starved {
+                style = style.fg(Color::Magenta);
+            } else if entry.lateness_ms > 0.0 {
+                style = style.fg(Color::Red);
+            }
+            if state.pinned.contains(&entry.pid) {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if state.selected_pid == Some(entry.pid) {
+                style = style.add_modifier(Modifier::REVERSED);
             }
+            row = row.style(style);
             row
         })
         .collect();
 
     let widths = [
         Constraint::Length(7),
+        Constraint::Length(10),
+        Constraint::Length(4),
         Constraint::Length(8),
         Constraint::Length(10),
+        Constraint::Length(6),
+        Constraint::Length(9),
         Constraint::Length(8),
+        Constraint::Length(6),
         Constraint::Length(12),
         Constraint::Length(12),
         Constraint::Length(10),
         Constraint::Length(6),
+        Constraint::Length(14),
     ];
+    let mut title = "Top tasks".to_string();
+    if !state.pinned.is_empty() {
+        title.push_str(&format!(" ({} pinned, bold)", state.pinned.len()));
+    }
+    if state.auto_focus {
+        title.push_str(" [auto-focus]");
+    }
     let table = Table::new(rows, widths)
         .header(header)
-        .block(Block::default().title("Top tasks").borders(Borders::ALL));
-    frame.render_widget(table, area);
+        .block(Block::default().title(title).borders(Borders::ALL));
+    table.render(area, buf);
+}
+
+/// 99th percentile wakeup latency for a table cell; `-` without any.
+fn latency_p99_label(latency: Option<WakeupLatency>) -> String {
+    latency.map_or("-".to_string(), |latency| {
+        format!("{:.3}", latency.quantile_ms(0.99))
+    })
 }
 
-fn render_summary(frame: &mut Frame<'_>, history: &HistoryWindow, total_tickets: u64, area: Rect) {
+/// A 0-1 share as a percentage table cell; `-` when unknown.
+fn percent_label(share: Option<f64>) -> String {
+    share.map_or("-".to_string(), |share| format!("{:.1}", share * 100.0))
+}
+
+/// The group table; grouped by cgroup with `cgroups` read from the BPF
+/// map, a BPF column adds the runtime of every task in the cgroup, tracked
+/// or not.
+fn render_group_table(
+    buf: &mut Buffer,
+    snapshots: &[TaskSnapshot],
+    cgroups: &[CgroupSnapshot],
+    window_ms: f64,
+    top_n: usize,
+    state: &TuiState,
+    area: Rect,
+) {
+    let group_by = state.group_by;
+    let groups = state.groups(snapshots, window_ms);
+    let bpf_runtime: Option<HashMap<&str, f64>> =
+        (group_by == GroupBy::Cgroup && !cgroups.is_empty()).then(|| {
+            cgroups
+                .iter()
+                .filter_map(|cgroup| Some((cgroup.path.as_deref()?, cgroup.runtime_delta_ms())))
+                .collect()
+        });
+    let mut header = vec![
+        "GROUP",
+        "TASKS",
+        "SHARE%",
+        "LAT(ms)",
+        "WORST LAT",
+        "AVG LAT",
+        "MISS%",
+        "LAT p99",
+        "PRE%",
+        "OVERDUE",
+        "DELTA (ms)",
+        "TICKETS",
+    ];
+    if bpf_runtime.is_some() {
+        header.push("BPF (ms)");
+    }
+    let header = Row::new(header).style(Style::default().add_modifier(Modifier::BOLD));
+
+    let mut rows: Vec<Row> = Vec::new();
+    for group in groups.iter().take(top_n.max(1)) {
+        let expanded = state.expanded_groups.contains(&group.key);
+        let marker = if expanded { "-" } else { "+" };
+        let mut style = Style::default();
+        if group.overdue > 0 {
+            style = style.fg(Color::Red);
+        }
+        if state.selected_group.as_deref() == Some(group.key.as_str()) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        let mut cells = vec![
+            format!("{marker} {}", group.key),
+            group.tasks.to_string(),
+            format!("{:.2}", group.ticket_share * 100.0),
+            format!("{:.3}", group.lateness_ms),
+            format!("{:.3}", group.worst_lateness_ms),
+            format!("{:.3}", group.avg_lateness_ms()),
+            percent_label(group.miss_rate()),
+            latency_p99_label(group.latency),
+            percent_label(group.preemption_rate()),
+            group.overdue.to_string(),
+            format!("{:.3}", group.runtime_delta_ms()),
+            group.tickets.to_string(),
+        ];
+        if let Some(bpf_runtime) = &bpf_runtime {
+            cells.push(
+                bpf_runtime
+                    .get(group.key.as_str())
+                    .map_or_else(|| "-".to_string(), |ms| format!("{ms:.3}")),
+            );
+        }
+        rows.push(Row::new(cells).style(style));
+        if !expanded {
+            continue;
+        }
+        for member in members(snapshots, group_by, &group.key) {
+            let style = if member.starved {
+                Style::default().fg(Color::Magenta)
+            } else if member.deadline_missed() {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            rows.push(
+                Row::new(vec![
+                    format!("    pid {}", member.pid),
+                    String::new(),
+                    format!("{:.2}", member.ticket_share * 100.0),
+                    format!("{:.3}", member.lateness_ms),
+                    String::new(),
+                    String::new(),
+                    percent_label(member.miss_rate()),
+                    latency_p99_label(member.latency),
+                    percent_label(member.preemption_rate()),
+                    match member.status() {
+                        "OK" => String::new(),
+                        status => status.to_string(),
+                    },
+                    format!("{:.3}", member.runtime_delta_ms()),
+                    member.info.tickets.to_string(),
+                ])
+                .style(style),
+            );
+        }
+    }
+
+    let mut widths = vec![
+        Constraint::Min(20),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(12),
+        Constraint::Length(10),
+    ];
+    if bpf_runtime.is_some() {
+        widths.push(Constraint::Length(12));
+    }
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .title(format!("Top groups (by {group_by:?}, Enter expands)"))
+            .borders(Borders::ALL),
+    );
+    table.render(area, buf);
+}
+
+fn render_tree_table(
+    buf: &mut Buffer,
+    snapshots: &[TaskSnapshot],
+    window_ms: f64,
+    state: &TuiState,
+    area: Rect,
+) {
+    let tree = ProcessTree::build(snapshots, window_ms);
+    let header = Row::new(vec![
+        "PROCESS",
+        "TASKS",
+        "SUBTREE%",
+        "SUBTREE (ms)",
+        "OWN%",
+        "OWN (ms)",
+        "LAT(ms)",
+        "OVERDUE",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows: Vec<Row> = tree
+        .visible_rows(&state.collapsed_tgids)
+        .into_iter()
+        .map(|(depth, node)| {
+            let marker = if node.children.is_empty() {
+                " "
+            } else if state.collapsed_tgids.contains(&node.tgid) {
+                "+"
+            } else {
+                "-"
+            };
+            let mut style = Style::default();
+            if node.subtree_overdue > 0 {
+                style = style.fg(Color::Red);
+            }
+            if state.selected_group.as_deref() == Some(node.tgid.to_string().as_str()) {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            Row::new(vec![
+                format!(
+                    "{}{marker} {} ({})",
+                    "  ".repeat(depth),
+                    node.name,
+                    node.tgid
+                ),
+                node.subtree_tasks.to_string(),
+                format!("{:.2}", node.subtree_share * 100.0),
+                format!("{:.3}", node.subtree_runtime_delta_ms()),
+                format!("{:.2}", node.own.ticket_share * 100.0),
+                format!("{:.3}", node.own.runtime_delta_ms()),
+                format!("{:.3}", node.own.lateness_ms),
+                node.subtree_overdue.to_string(),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Min(24),
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(13),
+        Constraint::Length(7),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(8),
+    ];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .title("Process tree (Enter collapses/expands, t exits)")
+            .borders(Borders::ALL),
+    );
+    table.render(area, buf);
+}
+
+fn render_summary(
+    buf: &mut Buffer,
+    history: &HistoryWindow,
+    total_tickets: u64,
+    state: &TuiState,
+    area: Rect,
+) {
     let latest = history.latest().cloned().unwrap_or_default();
     let top_line = match latest.top_pid {
         Some(pid) => format!("Top pid {pid} ({:.1}% share)", latest.top_share * 100.0),
         None => "Top pid n/a".to_string(),
     };
-    let status = format!(
-        "Tasks: {tasks}  Tickets: {tickets}  Avg lateness: {avg:.3} ms  Worst: {max:.3} ms  Avg util: {util:.1}%\nOverdue: {overdue}  Runtime window: {runtime:.3} ms  {top_line}  Press q/Esc to exit",
+    let mut status = format!(
+        "Tasks: {tasks}  Tickets: {tickets}  Avg lateness: {avg:.3} ms  Worst: {max:.3} ms  CPU: {cpu:.1}% of {cpus}  Avg util: {util:.1}%  Alpha: {alpha:.2}  Refresh: {refresh:?}\nOverdue: {overdue}  Starved: {starved}  Runtime window: {runtime:.3} ms  {top_line}  Keys: ? help, q quit",
         tasks = latest.total_tasks,
         tickets = total_tickets,
-        avg = latest.avg_lateness,
-        max = latest.max_lateness.max(0.0_f64),
+        avg = latest.avg_lateness(),
+        max = latest.max_lateness().max(0.0_f64),
+        cpu = latest.system_utilization * 100.0,
+        cpus = latest.cpus,
         util = latest.avg_utilization * 100.0,
+        alpha = state.alpha,
+        refresh = state.refresh,
         overdue = latest.overdue_tasks,
+        starved = latest.starved_tasks,
         runtime = latest.total_runtime_ms,
     );
-    let block =
-        Paragraph::new(status).block(Block::default().title("Summary").borders(Borders::ALL));
-    frame.render_widget(block, area);
+    if let Some(latency) = latest.latency {
+        status.push_str(&format!(
+            "\nWakeup latency: p50 {:.3} ms  p95 {:.3} ms  p99 {:.3} ms  max {:.3} ms  ({} wakeups)",
+            latency.quantile_ms(0.5),
+            latency.quantile_ms(0.95),
+            latency.quantile_ms(0.99),
+            latency.max_ms(),
+            latency.wakeups
+        ));
+    }
+    if let Some(rate) = latest.preemption_rate {
+        let sep = if latest.latency.is_some() { "  " } else { "\n" };
+        status.push_str(&format!("{sep}Preempted: {:.1}% of switches", rate * 100.0));
+    }
+    if let Some(spearman) = latest.priority_spearman {
+        let sep = if latest.latency.is_some() || latest.preemption_rate.is_some() {
+            "  "
+        } else {
+            "\n"
+        };
+        status.push_str(&format!(
+            "{sep}Priority fit: {spearman:.3} ({} outliers)",
+            latest.priority_outliers
+        ));
+    }
+    if !latest.events.is_empty() {
+        status.push_str(&format!("\nEvents: {}", events_label(&latest.events)));
+    }
+    let mut notes = Vec::new();
+    if latest.stale {
+        notes.push(format!(
+            "MAP STALE: no counter moved in {} windows (BPF program detached?)",
+            latest.unchanged_windows
+        ));
+    }
+    notes.extend(state.status.clone());
+    if let Some(command) = &state.command {
+        notes = vec![format!(":{command}_")];
+    }
+    if !notes.is_empty() {
+        status.push('\n');
+        status.push_str(&notes.join("  |  "));
+    }
+    let style = if latest.stale {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)].as_ref())
+        .split(area);
+    render_gauges(buf, &latest, sections[0]);
+    let block = Paragraph::new(status)
+        .style(style)
+        .block(Block::default().title("Summary").borders(Borders::ALL));
+    block.render(sections[1], buf);
+}
+
+/// Headline gauges, green/yellow/red by threshold, so trouble stands out
+/// before the summary text is read.
+fn render_gauges(buf: &mut Buffer, latest: &HistorySample, area: Rect) {
+    let sections = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let overdue_ratio = if latest.total_tasks == 0 {
+        0.0
+    } else {
+        latest.overdue_tasks as f64 / latest.total_tasks as f64
+    };
+    let gauges = [
+        (
+            format!("CPU of {} (headroom)", latest.cpus),
+            latest.system_utilization,
+            format!(
+                "{:.1}% ({:.1}% free)",
+                latest.system_utilization * 100.0,
+                (1.0 - latest.system_utilization).max(0.0) * 100.0
+            ),
+            (0.7, 0.9),
+        ),
+        (
+            "Ticket concentration (HHI)".to_string(),
+            latest.ticket_hhi,
+            format!("{:.3}", latest.ticket_hhi),
+            (0.15, 0.25),
+        ),
+        (
+            "Overdue tasks".to_string(),
+            overdue_ratio,
+            format!(
+                "{:.1}% ({}/{})",
+                overdue_ratio * 100.0,
+                latest.overdue_tasks,
+                latest.total_tasks
+            ),
+            (0.0, 0.1),
+        ),
+    ];
+    for ((title, ratio, label, (warn, critical)), area) in gauges.into_iter().zip(sections.iter()) {
+        let color = if ratio >= critical {
+            Color::Red
+        } else if ratio > warn {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        Gauge::default()
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .gauge_style(Style::default().fg(color))
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label)
+            .render(*area, buf);
+    }
+}
+
+/// The session's alerts, newest first, starting `scroll` alerts back.
+fn render_alerts(buf: &mut Buffer, alerts: &AlertHistory, scroll: usize, area: Rect) {
+    let scroll = scroll.min(alerts.len().saturating_sub(1));
+    let mut title = format!("Alerts ({}", alerts.len());
+    if alerts.dropped > 0 {
+        title.push_str(&format!(", {} oldest dropped", alerts.dropped));
+    }
+    if scroll > 0 {
+        title.push_str(&format!(", {scroll} newer above"));
+    }
+    title.push_str("; a closes, PgUp/PgDn/Home scroll)");
+    let text = if alerts.len() == 0 {
+        format!(
+            "No alerts yet (deadline threshold {:.3} ms)",
+            alerts.threshold_ms
+        )
+    } else {
+        alerts
+            .alerts
+            .iter()
+            .rev()
+            .skip(scroll)
+            .take(area.height as usize)
+            .map(|alert| {
+                format!(
+                    "{}  {:<8} {:>7} {:<16} {}",
+                    time_of_day(alert.timestamp),
+                    alert.kind.label(),
+                    alert.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+                    alert.comm.as_deref().unwrap_or("-"),
+                    alert.detail
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    Paragraph::new(text)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .render(area, buf);
+}
+
+/// Every key binding, as listed by the help overlay.
+const KEY_HELP: &[(&str, &str)] = &[
+    ("?", "show/hide this help"),
+    (
+        "q, Esc, Ctrl-C",
+        "quit (Esc closes this help or the command line first)",
+    ),
+    ("Up/Down, k/j", "select a task, group or process"),
+    ("H / G", "select the first/last row"),
+    ("Ctrl-U / Ctrl-D", "move the selection half a page up/down"),
+    (
+        "Enter",
+        "toggle task details; expand a group; collapse a subtree",
+    ),
+    ("f", "auto-focus the task with the worst lateness"),
+    ("g", "cycle grouping: pid, tgid, cgroup, user"),
+    ("t", "toggle the process tree"),
+    ("a", "show/hide the alert pane"),
+    ("PageUp/PageDown/Home", "scroll the alert pane"),
+    ("h", "lateness histograms instead of trends"),
+    ("b", "braille line charts instead of sparklines"),
+    ("[ / ]", "lower/raise the EWMA alpha by 0.05"),
+    ("< / >", "slower/faster refresh"),
+    ("+ / -", "renice the selected task"),
+    ("p", "pin/unpin the selected task"),
+    (
+        ":sort KEY [asc]",
+        "order by share, lateness, latency, delta, util, switches, preemption, pid",
+    ),
+    (
+        ":filter [GLOB]",
+        "show matching task names or a pid; none clears",
+    ),
+    (":pin PID", "pin/unpin a task by pid"),
+    (":write PATH", "save the current sample as JSON"),
+    (
+        ":group [MODE]",
+        "group by pid, tgid, cgroup or user; none cycles",
+    ),
+    (":q", "quit"),
+];
+
+/// Key cheat sheet and current modes, drawn over the middle of `area`.
+fn render_help(buf: &mut Buffer, state: &TuiState, area: Rect) {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let view = if state.tree_view {
+        "process tree".to_string()
+    } else {
+        format!("by {}", format!("{:?}", state.group_by).to_lowercase())
+    };
+    let mut lines: Vec<String> = KEY_HELP
+        .iter()
+        .map(|(keys, action)| format!("{keys:>20}  {action}"))
+        .collect();
+    lines.push(String::new());
+    lines.push(format!(
+        "Table: {view}  Auto-focus: {}  Details: {}",
+        on_off(state.auto_focus),
+        on_off(state.show_detail)
+    ));
+    lines.push(format!(
+        "Alerts: {}  Right pane: {}",
+        on_off(state.show_alerts),
+        match (state.show_histogram, state.braille) {
+            (true, _) => "histograms",
+            (false, true) => "braille trends",
+            (false, false) => "trends",
+        }
+    ));
+    lines.push(format!(
+        "Sort: {} {}  Task filter: {}",
+        format!("{:?}", state.sort).to_lowercase(),
+        if state.sort_ascending { "asc" } else { "desc" },
+        state.task_filter.as_deref().unwrap_or("none")
+    ));
+    lines.push(format!(
+        "Alpha: {:.2}  Refresh: {:?}  Filters: {}",
+        state.alpha, state.refresh, state.filters
+    ));
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count() as u16 + 4)
+        .max()
+        .unwrap_or_default()
+        .min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    Clear.render(overlay, buf);
+    Paragraph::new(lines.join("\n"))
+        .block(
+            Block::default()
+                .title("Help (? or Esc to close)")
+                .borders(Borders::ALL),
+        )
+        .render(overlay, buf);
+}
+
+/// `HH:MM:SS.mmmZ` of a Unix timestamp.
+fn time_of_day(timestamp: f64) -> String {
+    let millis = (timestamp.max(0.0) * 1000.0) as u64 % 86_400_000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}Z",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn render_detail(buf: &mut Buffer, snapshots: &[TaskSnapshot], pid: u32, alpha: f64, area: Rect) {
+    let text = match snapshots.iter().find(|e| e.pid == pid) {
+        Some(entry) => detail_lines(entry, alpha).join("\n"),
+        None => format!("PID {pid} is no longer present in the map"),
+    };
+    let block = Paragraph::new(text).block(
+        Block::default()
+            .title(format!("Task {pid}"))
+            .borders(Borders::ALL),
+    );
+    block.render(area, buf);
+}
+
+/// `alpha` labels the rolling figure, whose smoothing can change at runtime.
+fn detail_lines(entry: &TaskSnapshot, alpha: f64) -> Vec<String> {
+    let status = match entry.starved_windows {
+        0 => entry.status().to_string(),
+        windows => format!("{} ({windows} windows without runtime)", entry.status()),
+    };
+    vec![
+        format!(
+            "PID {}  user {}  nice {}  policy {}  tickets {} ({:.2}% share)",
+            entry.pid,
+            entry.meta.user_label(),
+            entry.info.nice,
+            entry.meta.policy_label(),
+            entry.info.tickets,
+            entry.ticket_share * 100.0
+        ),
+        format!(
+            "Runtime {:.3} ms  Delta {:.3} ms  Rolling (alpha {alpha:.2}) {:.3} ms  Switches +{}{}",
+            entry.info.runtime_ms(),
+            entry.runtime_delta_ms(),
+            entry.rolling_runtime_ms,
+            entry.switch_delta,
+            match (entry.voluntary_delta(), entry.preempt_delta) {
+                (Some(voluntary), Some(preempted)) => format!(
+                    " ({voluntary} voluntary, {preempted} preempted{})",
+                    entry
+                        .preemption_rate()
+                        .map(|rate| format!(", {:.1}%", rate * 100.0))
+                        .unwrap_or_default()
+                ),
+                _ => String::new(),
+            }
+        ),
+        format!(
+            "Period {:.3} ms  Lateness {:.3} ms  Missed {}/{} windows  Util {:.1}%  Status {status}",
+            entry.estimated_period_ms,
+            entry.lateness_ms,
+            entry.windows_missed,
+            entry.windows_observed,
+            entry.utilization * 100.0
+        ),
+        match entry.latency {
+            Some(latency) => format!(
+                "Wakeup latency: {} wakeups  mean {:.3} ms  p50 {:.3} ms  p99 {:.3} ms  max {:.3} ms",
+                latency.wakeups,
+                latency.mean_ms(),
+                latency.quantile_ms(0.5),
+                latency.quantile_ms(0.99),
+                latency.max_ms()
+            ),
+            None => "Wakeup latency: no wakeups recorded this window".to_string(),
+        },
+        format!(
+            "Container {}  cgroup {}",
+            entry.meta.short_container_id().unwrap_or("-"),
+            entry.meta.cgroup.as_deref().unwrap_or("-")
+        ),
+        format!(
+            "Last CPU {}  Affinity {}{}",
+            entry.meta.last_cpu_label(),
+            format_cpu_list(&entry.meta.cpus_allowed),
+            if entry.events.is_empty() {
+                String::new()
+            } else {
+                format!("  Events: {}", events_label(&entry.events))
+            }
+        ),
+        format!("cmd {}", entry.meta.cmdline.as_deref().unwrap_or("-")),
+    ]
+}
+
+/// `12 wakeups  14 wakings  2 migrations`, leaving out unmonitored events.
+fn events_label(events: &SchedEvents) -> String {
+    [
+        (SchedEvent::Wakeup, "wakeups"),
+        (SchedEvent::Waking, "wakings"),
+        (SchedEvent::Migrate, "migrations"),
+    ]
+    .into_iter()
+    .filter_map(|(event, label)| events.get(event).map(|count| format!("{count} {label}")))
+    .collect::<Vec<_>>()
+    .join("  ")
 }
 
-fn render_history(frame: &mut Frame<'_>, history: &HistoryWindow, area: Rect) {
+fn render_history(buf: &mut Buffer, history: &HistoryWindow, braille: bool, area: Rect) {
     let sections = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -140,74 +1780,146 @@ fn render_history(frame: &mut Frame<'_>, history: &HistoryWindow, area: Rect) {
                 Constraint::Length(4),
                 Constraint::Length(4),
                 Constraint::Length(4),
-                Constraint::Length(3),
+                Constraint::Length(4),
+                Constraint::Min(4),
+                Constraint::Length(4),
+                Constraint::Length(4),
                 Constraint::Length(5),
+                Constraint::Length(8),
             ]
             .as_ref(),
         )
         .split(area);
 
-    render_metric_sparkline(
-        frame,
-        sections[0],
-        history,
-        |s| s.avg_lateness.max(0.0),
-        1000.0,
-        "Avg lateness trend (ms)",
-        Color::Cyan,
-    );
-
-    render_metric_sparkline(
-        frame,
-        sections[1],
-        history,
-        |s| s.max_lateness.max(0.0),
-        1000.0,
-        "Worst lateness trend (ms)",
-        Color::LightMagenta,
-    );
-
-    render_metric_sparkline(
-        frame,
-        sections[2],
-        history,
-        |s| (s.avg_utilization * 100.0).clamp(0.0, 200.0),
-        1.0,
-        "Avg utilisation trend (%)",
-        Color::Yellow,
-    );
-
-    render_metric_sparkline(
-        frame,
-        sections[3],
-        history,
-        |s| s.overdue_tasks as f64,
-        1.0,
-        "Overdue tasks",
-        Color::Red,
-    );
+    type Trend = (&'static str, Color, fn(&HistorySample) -> f64, f64);
+    let trends: [(Rect, Trend); 8] = [
+        (
+            sections[0],
+            (
+                "Lateness p95 trend (ms)",
+                Color::Cyan,
+                |s| s.lateness_quantile(0.95),
+                1000.0,
+            ),
+        ),
+        (
+            sections[1],
+            (
+                "Lateness p99 trend (ms)",
+                Color::LightMagenta,
+                |s| s.lateness_quantile(0.99),
+                1000.0,
+            ),
+        ),
+        (
+            sections[2],
+            (
+                "CPU utilisation trend (% of all CPUs)",
+                Color::Yellow,
+                |s| (s.system_utilization * 100.0).clamp(0.0, 100.0),
+                1.0,
+            ),
+        ),
+        (
+            sections[3],
+            (
+                "Run-queue depth (runnable per CPU)",
+                Color::LightBlue,
+                |s| s.rq_depth.map_or(0.0, |(mean, _)| mean),
+                100.0,
+            ),
+        ),
+        (
+            sections[4],
+            (
+                "Runtime inequality (Gini: 0 even, 1 one task)",
+                Color::LightRed,
+                |s| s.runtime_gini.unwrap_or(0.0),
+                1000.0,
+            ),
+        ),
+        (
+            sections[5],
+            (
+                "Ticket supply (total tickets)",
+                Color::LightGreen,
+                |s| s.total_tickets as f64,
+                1.0,
+            ),
+        ),
+        (
+            sections[7],
+            ("Overdue tasks", Color::Red, |s| s.overdue_tasks as f64, 1.0),
+        ),
+        (
+            sections[8],
+            (
+                "Runtime window (ms)",
+                Color::Green,
+                |s| s.total_runtime_ms,
+                1.0,
+            ),
+        ),
+    ];
+    for (area, (title, color, projection, scale)) in trends {
+        if braille {
+            render_metric_braille(buf, area, history, projection, title, color);
+        } else {
+            render_metric_sparkline(buf, area, history, projection, scale, title, color);
+        }
+    }
 
-    render_metric_sparkline(
-        frame,
-        sections[4],
-        history,
-        |s| s.total_runtime_ms.max(0.0),
-        1.0,
-        "Runtime window (ms)",
-        Color::Green,
-    );
+    render_cpu_heatmap(buf, history, sections[6]);
 
     let latest = history.latest().cloned().unwrap_or_default();
-    let text = format!(
+    let mut text = format!(
         "Latest avg: {avg:.3} ms  Worst: {max:.3} ms  Tasks: {tasks}  Overdue: {overdue}",
-        avg = latest.avg_lateness,
-        max = latest.max_lateness.max(0.0_f64),
+        avg = latest.avg_lateness(),
+        max = latest.max_lateness().max(0.0_f64),
         tasks = latest.total_tasks,
         overdue = latest.overdue_tasks,
     );
-    let footer =
-        Paragraph::new(text).block(Block::default().title("Trend stats").borders(Borders::ALL));
-    frame.render_widget(footer, sections[5]);
+    let (kept, horizon) = history.lateness_horizon();
+    if let (Some(p95), Some(p99)) = (horizon.quantile(0.95), horizon.quantile(0.99)) {
+        text.push_str(&format!(
+            "  p95/p99: {p95:.3}/{p99:.3} ms over {kept} samples"
+        ));
+    }
+    if let (Some(p95), Some(p99)) = (
+        history.run_quantiles.quantile(0.95),
+        history.run_quantiles.quantile(0.99),
+    ) {
+        text.push_str(&format!(", {p95:.3}/{p99:.3} ms session"));
+    }
+    if let Some((mean, max)) = latest.rq_depth {
+        text.push_str(&format!("  RQ: {mean:.2} avg / {max:.2} max"));
+        if let Some(corr) = history.depth_lateness_correlation() {
+            text.push_str(&format!("  r(RQ, worst): {corr:+.2}"));
+        }
+    }
+    if let Some((irq, softirq)) = latest.irq {
+        text.push_str(&format!(
+            "  IRQ: {:.1}% + soft {:.1}%",
+            irq * 100.0,
+            softirq * 100.0
+        ));
+        if let Some(corr) = history.interrupt_lateness_correlation() {
+            text.push_str(&format!("  r(IRQ, worst): {corr:+.2}"));
+        }
+    }
+    if let Some(gini) = latest.runtime_gini {
+        text.push_str(&format!("  Gini: {gini:.3}"));
+    }
+    if let Some(growth) = history.supply_growth() {
+        text.push_str(&format!(
+            "  Tickets: {} ({growth:+.1}% over history)",
+            latest.total_tickets
+        ));
+    }
+    let footer = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().title("Trend stats").borders(Borders::ALL));
+    footer.render(sections[9], buf);
 
     let ascii_lines = [
         " _______________________ ",
@@ -219,29 +1931,247 @@ fn render_history(frame: &mut Frame<'_>, history: &HistoryWindow, area: Rect) {
     ];
     let ascii_width = ascii_lines.iter().map(|l| l.len()).max().unwrap_or(0) as u16;
     let ascii_height = ascii_lines.len() as u16;
-    let offset_x = sections[6]
+    let offset_x = sections[10]
         .width
         .saturating_sub(ascii_width)
         .checked_div(2)
         .unwrap_or(0);
-    let offset_y = sections[6]
+    let offset_y = sections[10]
         .height
-        .saturating_sub(ascii_height)
+        .saturating_sub(ascii_height + 2)
         .checked_div(2)
         .unwrap_or(0);
     let art_area = Rect {
-        x: sections[6].x + offset_x,
-        y: sections[6].y + offset_y,
-        width: ascii_width.min(sections[6].width),
-        height: 15,
-    };
+        x: sections[10].x + offset_x,
+        y: sections[10].y + offset_y,
+        width: ascii_width.min(sections[10].width),
+        height: ascii_height + 2,
+    }
+    .intersection(sections[10]);
     let art = Paragraph::new(ascii_lines.join("\n")).style(Style::default().fg(Color::Blue));
     let block = Block::default().title("LOGO").borders(Borders::ALL);
-    frame.render_widget(art.block(block), art_area);
+    art.block(block).render(art_area, buf);
+}
+
+/// Lateness distribution of the latest window above that of the whole run,
+/// for the bimodal behaviour averages and maxima hide.
+fn render_lateness_histograms(buf: &mut Buffer, history: &HistoryWindow, area: Rect) {
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    let latest = history
+        .latest()
+        .map(|sample| sample.lateness)
+        .unwrap_or_default();
+    render_lateness_histogram(
+        buf,
+        sections[0],
+        &latest,
+        "Lateness now (ms, tasks that ran; h for trends)",
+        Color::Cyan,
+    );
+    render_lateness_histogram(
+        buf,
+        sections[1],
+        &history.run_lateness,
+        "Lateness over the run (ms, task-windows)",
+        Color::LightMagenta,
+    );
+}
+
+fn render_lateness_histogram(
+    buf: &mut Buffer,
+    area: Rect,
+    histogram: &LatenessHistogram,
+    title: &str,
+    color: Color,
+) {
+    let data: Vec<(&str, u64)> = histogram.buckets().collect();
+    BarChart::default()
+        .block(
+            Block::default()
+                .title(format!("{title} [{}]", histogram.total()))
+                .borders(Borders::ALL),
+        )
+        .bar_width(5)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(color))
+        .value_style(Style::default().fg(Color::Black).bg(color))
+        .data(&data)
+        .render(area, buf);
+}
+
+/// Width of the CPU labels in front of the heatmap rows.
+const HEATMAP_LABEL: u16 = 8;
+
+/// Per-CPU utilisation over the history: time runs left to right, one row
+/// per CPU. With more CPUs than rows, a row covers a range of CPUs and shows
+/// the busiest, so a single overloaded core still stands out. Task and
+/// interrupt time together set the shade, and cells where interrupts took
+/// more of the CPU than tasks are magenta.
+fn render_cpu_heatmap(buf: &mut Buffer, history: &HistoryWindow, area: Rect) {
+    let block = Block::default()
+        .title(
+            "Per-CPU utilisation (\u{2591}<25% \u{2592}<50% \u{2593}<75% \u{2588}, magenta: mostly IRQ)",
+        )
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    block.render(area, buf);
+    let cpus = history.latest().map_or(0, |sample| sample.per_cpu.len());
+    if cpus == 0 || inner.height == 0 || inner.width <= HEATMAP_LABEL {
+        Paragraph::new("Collecting history...").render(inner, buf);
+        return;
+    }
+    let per_row = cpus.div_ceil(inner.height as usize);
+    let columns = (inner.width - HEATMAP_LABEL) as usize;
+    let samples: Vec<&HistorySample> = history.iter().collect();
+    let samples = &samples[samples.len().saturating_sub(columns)..];
+    // Newest sample at the right edge.
+    let left = inner.right() - samples.len() as u16;
+    for (row, first) in (0..cpus).step_by(per_row).enumerate() {
+        let last = (first + per_row).min(cpus) - 1;
+        let y = inner.y + row as u16;
+        let label = if first == last {
+            format!("cpu{first}")
+        } else {
+            format!("{first}-{last}")
+        };
+        buf.set_stringn(
+            inner.x,
+            y,
+            label,
+            HEATMAP_LABEL as usize - 1,
+            Style::default(),
+        );
+        for (column, sample) in samples.iter().enumerate() {
+            let (tasks, irq) = (first..=last)
+                .map(|cpu| {
+                    (
+                        sample.per_cpu.get(cpu).copied().unwrap_or(0.0),
+                        sample.per_cpu_irq.get(cpu).copied().unwrap_or(0.0),
+                    )
+                })
+                .max_by(|a, b| (a.0 + a.1).total_cmp(&(b.0 + b.1)))
+                .unwrap_or_default();
+            let (symbol, mut color) = heat(tasks + irq);
+            if irq > tasks && tasks + irq >= 0.05 {
+                color = Color::Magenta;
+            }
+            buf.get_mut(left + column as u16, y)
+                .set_char(symbol)
+                .set_fg(color);
+        }
+    }
+}
+
+/// Shade and colour of a heatmap cell at utilisation `load` (1.0 = busy).
+fn heat(load: f64) -> (char, Color) {
+    match load {
+        load if load < 0.05 => ('\u{00b7}', Color::DarkGray),
+        load if load < 0.25 => ('\u{2591}', Color::Blue),
+        load if load < 0.5 => ('\u{2592}', Color::Green),
+        load if load < 0.75 => ('\u{2593}', Color::Yellow),
+        _ => ('\u{2588}', Color::Red),
+    }
+}
+
+/// Width of a pinned task's label in front of its sparkline.
+const PINNED_LABEL: u16 = 24;
+
+/// One CPU-utilisation sparkline per pinned task, whether or not it
+/// makes the table's top-N.
+fn render_pinned(buf: &mut Buffer, history: &HistoryWindow, pinned: &[u32], area: Rect) {
+    let block = Block::default()
+        .title("Pinned (CPU %; p or :pin PID unpins)")
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    block.render(area, buf);
+    for (row, &pid) in pinned.iter().enumerate().take(inner.height as usize) {
+        let y = inner.y + row as u16;
+        let series: Vec<u64> = history
+            .iter()
+            .map(|sample| {
+                let util = sample
+                    .pinned
+                    .iter()
+                    .find_map(|&(pinned, util)| (pinned == pid).then_some(util));
+                (util.unwrap_or(0.0) * 1000.0) as u64
+            })
+            .collect();
+        let latest = history.latest().and_then(|sample| {
+            sample
+                .pinned
+                .iter()
+                .find_map(|&(pinned, util)| (pinned == pid).then_some(util))
+        });
+        let label = match latest {
+            Some(util) => format!("{pid:<8}{:>6.1}%", util * 100.0),
+            None => format!("{pid:<8}absent"),
+        };
+        let label_width = PINNED_LABEL.min(inner.width);
+        Paragraph::new(label).render(Rect::new(inner.x, y, label_width, 1), buf);
+        let spark_area = Rect::new(inner.x + label_width, y, inner.width - label_width, 1);
+        // Keep the newest samples when the row is narrower than the history.
+        let skip = series.len().saturating_sub(spark_area.width as usize);
+        Sparkline::default()
+            .style(Style::default().fg(Color::Cyan))
+            .max(series.iter().copied().max().unwrap_or(0).max(1))
+            .data(&series[skip..])
+            .render(spark_area, buf);
+    }
+}
+
+/// A trend as a braille line chart: each cell holds 2x4 dots, so small
+/// changes that a sparkline's eighth-blocks round away stay visible, and
+/// negative values (early tasks) are drawn too. The title gives the range.
+fn render_metric_braille(
+    buf: &mut Buffer,
+    area: Rect,
+    history: &HistoryWindow,
+    projection: fn(&HistorySample) -> f64,
+    title: &str,
+    color: Color,
+) {
+    if history.samples.len() < 2 {
+        let block = Paragraph::new("Collecting history...")
+            .block(Block::default().title(title).borders(Borders::ALL));
+        block.render(area, buf);
+        return;
+    }
+
+    let data: Vec<f64> = history.iter().map(projection).collect();
+    let (min, max) = data
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    // A flat series still needs a range to be drawn in.
+    let pad = ((max - min) * 0.05).max(1e-9);
+    let last = (data.len() - 1) as f64;
+    Canvas::default()
+        .block(
+            Block::default()
+                .title(format!("{title} [{min:.3} .. {max:.3}]"))
+                .borders(Borders::ALL),
+        )
+        .marker(Marker::Braille)
+        .x_bounds([0.0, last])
+        .y_bounds([min - pad, max + pad])
+        .paint(|ctx| {
+            if min < 0.0 && max > 0.0 {
+                ctx.draw(&Line::new(0.0, 0.0, last, 0.0, Color::DarkGray));
+            }
+            for (x, pair) in data.windows(2).enumerate() {
+                let x = x as f64;
+                ctx.draw(&Line::new(x, pair[0], x + 1.0, pair[1], color));
+            }
+        })
+        .render(area, buf);
 }
 
 fn render_metric_sparkline<F>(
-    frame: &mut Frame<'_>,
+    buf: &mut Buffer,
     area: Rect,
     history: &HistoryWindow,
     projection: F,
@@ -254,7 +2184,7 @@ fn render_metric_sparkline<F>(
     if history.samples.len() < 2 {
         let block = Paragraph::new("Collecting history...")
             .block(Block::default().title(title).borders(Borders::ALL));
-        frame.render_widget(block, area);
+        block.render(area, buf);
         return;
     }
 
@@ -268,27 +2198,341 @@ fn render_metric_sparkline<F>(
         .style(Style::default().fg(color))
         .max(max_val)
         .data(&data);
-    frame.render_widget(spark, area);
+    spark.render(area, buf);
 }
+
+/// Draw the dashboard, rebuilding only the panes `panes` has no valid
+/// rendering of.
 pub fn draw_dashboard(
     frame: &mut Frame<'_>,
-    snapshots: &[TaskSnapshot],
-    total_tickets: u64,
+    window: &SnapshotWindow,
     history: &HistoryWindow,
+    alerts: &AlertHistory,
     top_n: usize,
+    state: &TuiState,
+    panes: &mut PaneCache,
 ) {
+    let SnapshotWindow {
+        snapshots,
+        total_tickets,
+        window_ms,
+        cgroups,
+        ..
+    } = window;
+    let (total_tickets, window_ms) = (*total_tickets, *window_ms);
     let main_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
         .split(frame.size());
 
+    let detail_pid = state.selected_pid.filter(|_| state.show_detail);
+    let mut left_constraints = vec![Constraint::Min(5)];
+    if !state.pinned.is_empty() {
+        left_constraints.push(Constraint::Length(state.pinned.len() as u16 + 2));
+    }
+    if detail_pid.is_some() {
+        left_constraints.push(Constraint::Length(9));
+    }
+    if state.show_alerts {
+        left_constraints.push(Constraint::Length(10));
+    }
+    left_constraints.push(Constraint::Length(9));
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(5), Constraint::Length(5)].as_ref())
+        .constraints(left_constraints)
         .split(main_layout[0]);
+    let mut next_chunk = left_chunks[1..].iter().copied();
+
+    panes.draw(
+        frame,
+        Pane::Table,
+        left_chunks[0],
+        |buf, area| match state.group_by {
+            _ if state.tree_view => render_tree_table(buf, snapshots, window_ms, state, area),
+            GroupBy::Pid => render_table(buf, snapshots, top_n, state, area),
+            _ => render_group_table(buf, snapshots, cgroups, window_ms, top_n, state, area),
+        },
+    );
+    if !state.pinned.is_empty()
+        && let Some(area) = next_chunk.next()
+    {
+        panes.draw(frame, Pane::Pinned, area, |buf, area| {
+            render_pinned(buf, history, &state.pinned, area)
+        });
+    }
+    if let Some(pid) = detail_pid
+        && let Some(area) = next_chunk.next()
+    {
+        panes.draw(frame, Pane::Detail, area, |buf, area| {
+            render_detail(buf, snapshots, pid, window.alpha, area)
+        });
+    }
+    if state.show_alerts
+        && let Some(area) = next_chunk.next()
+    {
+        panes.draw(frame, Pane::Alerts, area, |buf, area| {
+            render_alerts(buf, alerts, state.alert_scroll, area)
+        });
+    }
+    let summary_area = next_chunk.next().unwrap_or_default();
+    panes.draw(frame, Pane::Summary, summary_area, |buf, area| {
+        render_summary(buf, history, total_tickets, state, area)
+    });
+    panes.draw(frame, Pane::History, main_layout[1], |buf, area| {
+        if state.show_histogram {
+            render_lateness_histograms(buf, history, area)
+        } else {
+            render_history(buf, history, state.braille, area)
+        }
+    });
+    // Cheap enough to redraw every frame, so it bypasses the pane cache.
+    if state.show_help {
+        let area = frame.size();
+        render_help(frame.buffer_mut(), state, area);
+    }
+    panes.stale = false;
+}
+
+/// One map of the `--compare` view.
+pub struct ComparisonSide {
+    pub label: String,
+    pub window: Option<SnapshotWindow>,
+    pub summary: HistorySample,
+    ranking: Ranking,
+}
+
+impl ComparisonSide {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            window: None,
+            summary: HistorySample::default(),
+            ranking: Ranking::default(),
+        }
+    }
+
+    /// Show `window`, summarised as `summary`; returns the window it
+    /// replaces so its snapshots can be recycled.
+    pub fn update(
+        &mut self,
+        window: SnapshotWindow,
+        summary: HistorySample,
+        top_n: usize,
+    ) -> Option<SnapshotWindow> {
+        self.ranking.rank(&window.snapshots, top_n.max(1), |a, b| {
+            b.ticket_share.total_cmp(&a.ticket_share)
+        });
+        self.summary = summary;
+        self.window.replace(window)
+    }
+}
+
+/// Draw two maps side by side: their summary metrics with the difference,
+/// then each map's top tasks with every task's lateness against the same
+/// pid in the other map. Redrawn whole; the view is small.
+pub fn draw_comparison(frame: &mut Frame<'_>, sides: &[ComparisonSide; 2]) {
+    let metrics = comparison_metrics(sides);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(metrics.len() as u16 + 3),
+            Constraint::Min(5),
+        ])
+        .split(frame.size());
+    let [left, right] = sides;
+    let header = Row::new(vec![
+        "METRIC".to_string(),
+        left.label.clone(),
+        right.label.clone(),
+        "DIFF".to_string(),
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = metrics
+        .iter()
+        .map(|metric| {
+            let diff = metric.left - metric.right;
+            Row::new(vec![
+                Cell::from(metric.name),
+                Cell::from(format!("{:.*}", metric.decimals, metric.left)),
+                Cell::from(format!("{:.*}", metric.decimals, metric.right)),
+                Cell::from(format!("{:+.*}", metric.decimals, diff))
+                    .style(diff_style(diff, metric.lower_is_better)),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(22),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(16),
+    ];
+    Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .title(format!(
+                    "{} vs {} (DIFF = left - right, green where the left map does better; q quits)",
+                    left.label, right.label
+                ))
+                .borders(Borders::ALL),
+        )
+        .render(layout[0], frame.buffer_mut());
 
-    render_table(frame, snapshots, top_n, left_chunks[0]);
-    render_summary(frame, history, total_tickets, left_chunks[1]);
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(layout[1]);
+    render_comparison_table(frame.buffer_mut(), left, right, halves[0]);
+    render_comparison_table(frame.buffer_mut(), right, left, halves[1]);
+}
 
-    render_history(frame, history, main_layout[1]);
+struct ComparisonMetric {
+    name: &'static str,
+    left: f64,
+    right: f64,
+    decimals: usize,
+    /// Whether a lower value is better; `None` for neutral figures.
+    lower_is_better: Option<bool>,
+}
+
+fn comparison_metrics(sides: &[ComparisonSide; 2]) -> Vec<ComparisonMetric> {
+    let metric =
+        |name, value: fn(&ComparisonSide) -> f64, decimals, lower_is_better| ComparisonMetric {
+            name,
+            left: value(&sides[0]),
+            right: value(&sides[1]),
+            decimals,
+            lower_is_better,
+        };
+    vec![
+        metric("Tasks", |side| side.summary.total_tasks as f64, 0, None),
+        metric(
+            "Tickets",
+            |side| side.window.as_ref().map_or(0.0, |w| w.total_tickets as f64),
+            0,
+            None,
+        ),
+        metric(
+            "CPU %",
+            |side| side.summary.system_utilization * 100.0,
+            1,
+            None,
+        ),
+        metric(
+            "Avg lateness (ms)",
+            |side| side.summary.avg_lateness(),
+            3,
+            Some(true),
+        ),
+        metric(
+            "p99 lateness (ms)",
+            |side| side.summary.lateness_quantile(0.99),
+            3,
+            Some(true),
+        ),
+        metric(
+            "Worst lateness (ms)",
+            |side| side.summary.max_lateness(),
+            3,
+            Some(true),
+        ),
+        metric(
+            "Overdue tasks",
+            |side| side.summary.overdue_tasks as f64,
+            0,
+            Some(true),
+        ),
+        metric(
+            "Avg util %",
+            |side| side.summary.avg_utilization * 100.0,
+            1,
+            None,
+        ),
+        metric("Ticket HHI", |side| side.summary.ticket_hhi, 3, None),
+        metric(
+            "Priority fit",
+            |side| side.summary.priority_spearman.unwrap_or(0.0),
+            3,
+            Some(false),
+        ),
+        metric(
+            "Runtime Gini",
+            |side| side.summary.runtime_gini.unwrap_or(0.0),
+            3,
+            None,
+        ),
+    ]
+}
+
+/// Green when `diff` favours the left side, red when it does not.
+fn diff_style(diff: f64, lower_is_better: Option<bool>) -> Style {
+    match lower_is_better {
+        Some(lower) if diff != 0.0 && (diff < 0.0) == lower => Style::default().fg(Color::Green),
+        Some(_) if diff != 0.0 => Style::default().fg(Color::Red),
+        _ => Style::default(),
+    }
+}
+
+/// `side`'s top tasks; DLAT is each task's lateness minus that of the same
+/// pid in `other`, red where this map runs it later.
+fn render_comparison_table(
+    buf: &mut Buffer,
+    side: &ComparisonSide,
+    other: &ComparisonSide,
+    area: Rect,
+) {
+    let block = Block::default()
+        .title(side.label.as_str())
+        .borders(Borders::ALL);
+    let Some(window) = &side.window else {
+        Paragraph::new("Waiting for the first sample...")
+            .block(block)
+            .render(area, buf);
+        return;
+    };
+    let others: HashMap<u32, &TaskSnapshot> = other
+        .window
+        .iter()
+        .flat_map(|window| &window.snapshots)
+        .map(|entry| (entry.pid, entry))
+        .collect();
+    let header = Row::new(vec![
+        "PID", "USER", "SHARE%", "LAT(ms)", "DLAT(ms)", "UTIL%",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = side
+        .ranking
+        .top(&window.snapshots)
+        .map(|entry| {
+            let delta = others
+                .get(&entry.pid)
+                .map(|o| entry.lateness_ms - o.lateness_ms);
+            let delta_cell = match delta {
+                Some(delta) => {
+                    Cell::from(format!("{delta:+.3}")).style(diff_style(delta, Some(true)))
+                }
+                None => Cell::from("only here"),
+            };
+            Row::new(vec![
+                Cell::from(entry.pid.to_string()),
+                Cell::from(entry.meta.user_label()),
+                Cell::from(format!("{:.2}", entry.ticket_share * 100.0)),
+                Cell::from(format!("{:.3}", entry.lateness_ms)),
+                delta_cell,
+                Cell::from(format!("{:.1}", entry.utilization * 100.0)),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(7),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+    ];
+    Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .render(area, buf);
 }
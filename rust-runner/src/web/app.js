@@ -0,0 +1,220 @@
+"use strict";
+
+// Windows kept for the charts, and alerts kept in the list.
+const HISTORY = 120;
+const MAX_ALERTS = 200;
+
+const state = {
+  maps: new Map(), // map label -> { last, history: [{lateness, runtime, misses}] }
+  current: null,
+  sortKey: "ticket_share",
+  sortDesc: true,
+  alerts: [],
+  seen: new Set(), // keys of the alerts in the list
+};
+
+const $ = (id) => document.getElementById(id);
+
+function command(task) {
+  const cmd = task.cmdline || "";
+  return cmd.split(" ")[0].split("/").pop() || (task.kthread ? "[kthread]" : "?");
+}
+
+function fmt(value, digits = 3) {
+  return typeof value === "number" ? value.toFixed(digits) : "-";
+}
+
+// Only wall-clock timestamps are dates; the others count from boot.
+function timeOf(batch) {
+  if (!batch.clock || batch.clock === "realtime") {
+    return new Date(batch.timestamp_s * 1000).toLocaleTimeString();
+  }
+  return `${batch.timestamp_s.toFixed(3)} s ${batch.clock}`;
+}
+
+function onBatch(batch) {
+  let entry = state.maps.get(batch.map);
+  if (!entry) {
+    entry = { last: null, history: [] };
+    state.maps.set(batch.map, entry);
+    const option = document.createElement("option");
+    option.value = option.textContent = batch.map;
+    $("map").appendChild(option);
+    if (state.current === null) state.current = batch.map;
+  }
+  entry.last = batch;
+
+  const active = batch.tasks.filter((task) => task.delta_ms > 0);
+  const misses = active.filter((task) => task.lateness_ms > 0);
+  entry.history.push({
+    lateness: active.reduce((worst, task) => Math.max(worst, task.lateness_ms), 0),
+    runtime: batch.tasks.reduce((sum, task) => sum + task.delta_ms, 0),
+    misses: misses.length,
+  });
+  if (entry.history.length > HISTORY) entry.history.shift();
+
+  const time = timeOf(batch);
+  for (const task of misses) {
+    pushAlert(`miss ${batch.map} ${batch.timestamp_s} ${task.pid}`, "miss", time, batch.map,
+      `pid ${task.pid} (${command(task)}) late by ${fmt(task.lateness_ms)} ms`);
+  }
+  for (const event of batch.events || []) {
+    pushAlert(`change ${batch.map} ${batch.timestamp_s} ${event.pid} ${event.field}`, "change", time, batch.map,
+      `pid ${event.pid} ${event.field} ${event.from} → ${event.to} (${event.source}${event.reason ? ": " + event.reason : ""})`);
+  }
+  if (batch.map === state.current) render();
+}
+
+// A window delivered twice (e.g. again after a reconnect) adds nothing.
+function pushAlert(key, kind, time, map, text) {
+  if (state.seen.has(key)) return;
+  state.seen.add(key);
+  state.alerts.unshift({ key, kind, time, map, text });
+  if (state.alerts.length > MAX_ALERTS) state.seen.delete(state.alerts.pop().key);
+}
+
+function visibleTasks(batch) {
+  const needle = $("filter").value.trim().toLowerCase();
+  const tasks = batch.tasks.filter((task) => !needle
+    || String(task.pid).includes(needle)
+    || (task.user || "").toLowerCase().includes(needle)
+    || (task.cmdline || "").toLowerCase().includes(needle));
+  const key = state.sortKey;
+  const value = (task) => key === "command" ? command(task) : task[key];
+  tasks.sort((a, b) => {
+    const [x, y] = [value(a), value(b)];
+    const order = typeof x === "string" ? String(x).localeCompare(y) : (x ?? 0) - (y ?? 0);
+    return state.sortDesc ? -order : order;
+  });
+  return tasks.slice(0, Math.max(1, Number($("top").value) || 20));
+}
+
+function render() {
+  const entry = state.maps.get(state.current);
+  if (!entry || !entry.last) return;
+  const batch = entry.last;
+  renderSummary(batch, entry.history[entry.history.length - 1]);
+  renderTable(batch);
+  chart($("lateness"), entry.history.map((h) => h.lateness), "#f2685c");
+  chart($("runtime"), entry.history.map((h) => h.runtime), "#5fb3f5");
+  chart($("misses"), entry.history.map((h) => h.misses), "#e8c15a");
+  renderAlerts();
+}
+
+function renderSummary(batch, latest) {
+  const active = batch.tasks.filter((task) => task.delta_ms > 0);
+  const avg = active.length
+    ? active.reduce((sum, task) => sum + task.lateness_ms, 0) / active.length : 0;
+  const util = batch.tasks.reduce((sum, task) => sum + task.utilization, 0);
+  const top = batch.tasks.reduce((best, task) =>
+    !best || task.ticket_share > best.ticket_share ? task : best, null);
+  const items = [
+    ["iteration", batch.iteration],
+    ["time", timeOf(batch)],
+    ["map", batch.stale ? `STALE: no counter moved in ${batch.unchanged_windows} windows (BPF program detached?)` : "live"],
+    ["window", `${fmt(batch.window_ms, 1)} ms`],
+    ["tasks", `${batch.tasks.length} (${active.length} active)`],
+    ["total tickets", batch.total_tickets],
+    ["avg lateness", `${fmt(avg)} ms`],
+    ["worst lateness", `${fmt(latest.lateness)} ms`],
+    ["misses", latest.misses],
+    ["cpu", `${(batch.system_utilization * 100).toFixed(1)}% of ${batch.cpus}`],
+    ["utilization", `${(util * 100).toFixed(1)}%`],
+    ["top candidate", top ? `${top.pid} ${command(top)} ${(top.ticket_share * 100).toFixed(2)}%` : "-"],
+  ];
+  $("summary").replaceChildren(...items.map(([label, value]) => {
+    const div = document.createElement("div");
+    const b = document.createElement("b");
+    b.textContent = label;
+    div.append(b, String(value));
+    return div;
+  }));
+}
+
+function renderTable(batch) {
+  const rows = visibleTasks(batch).map((task) => {
+    const tr = document.createElement("tr");
+    if (task.delta_ms > 0 && task.lateness_ms > 0) tr.className = "miss";
+    const cells = [
+      [task.pid], [task.user ?? task.uid ?? "-"], [command(task), "command"],
+      [fmt(task.delta_ms), "num"], [fmt(task.rolling_runtime_ms), "num"],
+      [fmt(task.estimated_period_ms), "num"], [fmt(task.lateness_ms), "num"],
+      [(task.utilization * 100).toFixed(2), "num"], [task.nice, "num"],
+      [task.tickets, "num"], [(task.ticket_share * 100).toFixed(2), "num"],
+    ];
+    for (const [text, cls] of cells) {
+      const td = document.createElement("td");
+      td.textContent = text;
+      if (cls) td.className = cls;
+      if (cls === "command") td.title = task.cmdline || "";
+      tr.appendChild(td);
+    }
+    return tr;
+  });
+  $("rows").replaceChildren(...rows);
+  for (const th of document.querySelectorAll("th")) {
+    th.classList.toggle("sorted", th.dataset.key === state.sortKey);
+  }
+}
+
+function renderAlerts() {
+  $("alerts").replaceChildren(...state.alerts.map((alert) => {
+    const li = document.createElement("li");
+    li.className = alert.kind;
+    const time = document.createElement("time");
+    time.textContent = state.maps.size > 1 ? `${alert.time} ${alert.map}` : alert.time;
+    li.append(time, alert.text);
+    return li;
+  }));
+}
+
+function chart(canvas, values, color) {
+  const ratio = window.devicePixelRatio || 1;
+  const width = canvas.clientWidth * ratio;
+  const height = canvas.clientHeight * ratio;
+  canvas.width = width;
+  canvas.height = height;
+  const ctx = canvas.getContext("2d");
+  ctx.clearRect(0, 0, width, height);
+  if (values.length < 2) return;
+  const max = Math.max(...values, 1e-9);
+  const min = Math.min(...values, 0);
+  const x = (i) => (i / (HISTORY - 1)) * width;
+  const y = (v) => height - ((v - min) / (max - min || 1)) * (height - 14 * ratio) - 2;
+  ctx.strokeStyle = color;
+  ctx.lineWidth = 1.5 * ratio;
+  ctx.beginPath();
+  values.forEach((v, i) => (i ? ctx.lineTo(x(i), y(v)) : ctx.moveTo(x(i), y(v))));
+  ctx.stroke();
+  ctx.fillStyle = "#8a93a3";
+  ctx.font = `${11 * ratio}px monospace`;
+  ctx.fillText(`max ${max.toFixed(2)}  last ${values[values.length - 1].toFixed(2)}`, 4, 11 * ratio);
+}
+
+function connect() {
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  // Same origin only; the server refuses other origins' handshakes.
+  const ws = new WebSocket(`${scheme}://${location.host}/ws`);
+  ws.onopen = () => {
+    $("status").textContent = "live";
+    $("status").className = "status live";
+  };
+  ws.onmessage = (msg) => onBatch(JSON.parse(msg.data));
+  ws.onclose = () => {
+    $("status").textContent = "disconnected, retrying…";
+    $("status").className = "status down";
+    setTimeout(connect, 2000);
+  };
+}
+
+for (const th of document.querySelectorAll("th")) {
+  th.addEventListener("click", () => {
+    state.sortDesc = th.dataset.key === state.sortKey ? !state.sortDesc : true;
+    state.sortKey = th.dataset.key;
+    render();
+  });
+}
+$("map").addEventListener("change", (e) => { state.current = e.target.value; render(); });
+$("top").addEventListener("input", render);
+$("filter").addEventListener("input", render);
+connect();
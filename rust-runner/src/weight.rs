@@ -0,0 +1,125 @@
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+use neo_ebpf_core::RunnerError;
+use neo_ebpf_core::control::{self, normalize_cgroup};
+use neo_ebpf_core::stats::TaskSnapshot;
+use neo_ebpf_core::stream::SnapshotStream;
+
+/// What a cgroup subtree received in one sample.
+#[derive(Default)]
+struct CgroupSample {
+    tasks: usize,
+    share: f64,
+    delta_ms: f64,
+    utilization: f64,
+}
+
+impl CgroupSample {
+    fn collect(cgroup: &str, snapshots: &[TaskSnapshot]) -> Self {
+        let nested = format!("{}/", cgroup.trim_end_matches('/'));
+        snapshots
+            .iter()
+            .filter(|snap| {
+                snap.meta
+                    .cgroup
+                    .as_deref()
+                    .is_some_and(|path| path == cgroup || path.starts_with(&nested))
+            })
+            .fold(Self::default(), |mut acc, snap| {
+                acc.tasks += 1;
+                acc.share += snap.ticket_share;
+                acc.delta_ms += snap.runtime_delta_ms();
+                acc.utilization += snap.utilization;
+                acc
+            })
+    }
+
+    fn print(&self, phase: &str, sample: u32) {
+        println!(
+            "{phase:<7} {sample:>6} {:>6} {:>8.2} {:>11.3} {:>8.2}",
+            self.tasks,
+            self.share * 100.0,
+            self.delta_ms,
+            self.utilization * 100.0
+        );
+    }
+}
+
+/// Write `cpu.weight` of `cgroup`, sampling `map` `samples` times before and
+/// after so the effect on the subtree's tasks is visible side by side.
+pub fn run_set_weight(
+    map: &str,
+    cgroup: &str,
+    weight: u32,
+    samples: u32,
+    interval: Duration,
+) -> Result<(), RunnerError> {
+    let cgroup = normalize_cgroup(cgroup).map_err(|err| RunnerError::Config(err.to_string()))?;
+    let mut stream = if samples == 0 {
+        None
+    } else {
+        match SnapshotStream::open(map, interval, 0.5) {
+            Ok(stream) => Some(stream),
+            Err(err) => {
+                warn!(target: "map", "not sampling before/after: {err}");
+                None
+            }
+        }
+    };
+
+    let mut before = Vec::new();
+    if let Some(stream) = stream.as_mut() {
+        // The first window has no deltas yet.
+        stream.sample()?;
+        for _ in 0..samples {
+            thread::sleep(interval);
+            before.push(CgroupSample::collect(&cgroup, &stream.sample()?.snapshots));
+        }
+    }
+
+    let change =
+        control::set_cgroup_weight(&cgroup, weight).map_err(|source| RunnerError::Control {
+            target: format!("cgroup {cgroup}"),
+            source,
+        })?;
+    println!(
+        "cgroup {}: cpu.weight {} -> {}",
+        change.cgroup, change.old_weight, change.new_weight
+    );
+    let Some(mut stream) = stream else {
+        return Ok(());
+    };
+
+    let mut after = Vec::new();
+    for _ in 0..samples {
+        thread::sleep(interval);
+        after.push(CgroupSample::collect(&cgroup, &stream.sample()?.snapshots));
+    }
+
+    println!(
+        "\n{:<7} {:>6} {:>6} {:>8} {:>11} {:>8}",
+        "PHASE", "SAMPLE", "TASKS", "SHARE%", "DELTA_MS", "UTIL%"
+    );
+    for (phase, rows) in [("before", &before), ("after", &after)] {
+        for (index, row) in rows.iter().enumerate() {
+            row.print(phase, index as u32 + 1);
+        }
+    }
+    let mean = |rows: &[CgroupSample], value: fn(&CgroupSample) -> f64| {
+        rows.iter().map(value).sum::<f64>() / rows.len().max(1) as f64
+    };
+    println!(
+        "Mean utilisation {:.2}% -> {:.2}%, runtime {:.3} -> {:.3} ms per sample",
+        mean(&before, |row| row.utilization) * 100.0,
+        mean(&after, |row| row.utilization) * 100.0,
+        mean(&before, |row| row.delta_ms),
+        mean(&after, |row| row.delta_ms)
+    );
+    if before.iter().chain(&after).all(|row| row.tasks == 0) {
+        warn!(target: "sampler", cgroup = %cgroup, "no sampled task belongs to this cgroup");
+    }
+    Ok(())
+}
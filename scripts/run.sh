@@ -9,6 +9,16 @@ RUST_DIR="$ROOT_DIR/rust-runner"
 TEST_SRC="$ROOT_DIR/tests/cpu_bound.c"
 TEST_BIN="$ROOT_DIR/tests/cpu_bound"
 MAP_PIN=${MAP_PIN:-/sys/fs/bpf/task_map}
+OVERRIDE_PIN=${OVERRIDE_PIN:-/sys/fs/bpf/ticket_override}
+# rust-runner looks for the latency map next to the task map.
+LATENCY_PIN=${LATENCY_PIN:-$(dirname "$MAP_PIN")/task_latency}
+# ...and for the per-cgroup map there too (dump/tui --cgroups).
+CGROUP_PIN=${CGROUP_PIN:-$(dirname "$MAP_PIN")/cgroup_map}
+# Scheduler events counted besides sched_switch, e.g. wakeup,waking,migrate;
+# none unless asked for. Their maps are pinned next to the task map as
+# <event>_events.
+EVENTS=${EVENTS-}
+EVENT_NAMES=(wakeup waking migrate)
 PROG_PIN=${PROG_PIN:-/sys/fs/bpf/sched_lottery}
 LINK_PIN_DEFAULT="${PROG_PIN}_link"
 LINK_PIN=${LINK_PIN:-$LINK_PIN_DEFAULT}
@@ -49,7 +59,7 @@ build_loader() {
 build_rust() {
     require_cmd cargo
     echo "[+] Building Rust runner"
-    (cd "$RUST_DIR" && cargo build --release)
+    (cd "$RUST_DIR" && cargo build --release "$@")
 }
 
 build_workload() {
@@ -68,6 +78,16 @@ run_as_root() {
     fi
 }
 
+# Event map and link pins, whichever events were attached last time.
+remove_event_pins() {
+    local event
+    for event in "${EVENT_NAMES[@]}"; do
+        remove_path "$(dirname "$MAP_PIN")/${event}_events"
+        remove_path "${LINK_PIN}_${event}_events"
+    done
+    remove_path "${LINK_PIN}_wakeup_new_events"
+}
+
 remove_path() {
     local path="$1"
     if [ -e "$path" ]; then
@@ -97,8 +117,14 @@ load_bpf() {
     build_loader
     echo "[+] Cleaning up any existing pins"
     remove_path "$LINK_PIN"
+    remove_path "${LINK_PIN}_wakeup"
+    remove_path "${LINK_PIN}_wakeup_new"
     remove_path "$PROG_PIN"
     remove_path "$MAP_PIN"
+    remove_path "$OVERRIDE_PIN"
+    remove_path "$LATENCY_PIN"
+    remove_path "$CGROUP_PIN"
+    remove_event_pins
     ensure_dir "$(dirname "$MAP_PIN")"
     ensure_dir "$(dirname "$PROG_PIN")"
     ensure_dir "$(dirname "$LINK_PIN")"
@@ -108,7 +134,13 @@ load_bpf() {
         --obj "$BPF_DIR/sched_lottery.bpf.o" \
         --prog-pin "$PROG_PIN" \
         --map-pin "$MAP_PIN" \
+        --override-pin "$OVERRIDE_PIN" \
+        --latency-pin "$LATENCY_PIN" \
+        --cgroup-pin "$CGROUP_PIN" \
         --link-pin "$LINK_PIN")
+    if [ -n "$EVENTS" ]; then
+        loader_cmd+=("--events" "$EVENTS")
+    fi
     if [ -r "$BTF_PATH" ]; then
         loader_cmd+=("--btf" "$BTF_PATH")
     else
@@ -116,14 +148,26 @@ load_bpf() {
     fi
     run_as_root "${loader_cmd[@]}"
     echo "[+] Relaxing map permissions for user access"
-    run_as_root chmod 644 "$MAP_PIN"
+    run_as_root chmod 644 "$MAP_PIN" "$LATENCY_PIN" "$CGROUP_PIN"
+    local event
+    for event in "${EVENT_NAMES[@]}"; do
+        if [ -e "$(dirname "$MAP_PIN")/${event}_events" ]; then
+            run_as_root chmod 644 "$(dirname "$MAP_PIN")/${event}_events"
+        fi
+    done
 }
 
 unload_bpf() {
     echo "[+] Removing pinned objects"
     remove_path "$LINK_PIN"
+    remove_path "${LINK_PIN}_wakeup"
+    remove_path "${LINK_PIN}_wakeup_new"
     remove_path "$PROG_PIN"
     remove_path "$MAP_PIN"
+    remove_path "$OVERRIDE_PIN"
+    remove_path "$LATENCY_PIN"
+    remove_path "$CGROUP_PIN"
+    remove_event_pins
 }
 
 dump_stats() {
@@ -156,6 +200,17 @@ run_tui() {
     fi
 }
 
+run_bench() {
+    build_rust --features count-allocs
+    local bin="$RUST_DIR/target/release/rust-runner"
+    if [ ! -x "$bin" ]; then
+        echo "Runner binary missing: $bin" >&2
+        exit 1
+    fi
+    echo "[+] Benchmarking observer overhead"
+    run_as_root "$bin" bench "$@"
+}
+
 run_workload() {
     build_workload
     echo "[+] Launching workload: $TEST_BIN $*"
@@ -172,11 +227,16 @@ Commands:
   unload            Detach and remove pinned program/map
   dump [args]       Run the Rust CLI (extra args passed through to 'dump')
   tui  [args]       Launch the interactive terminal dashboard (press 'q' to exit)
+  bench [args]      Measure the CLI's per-tick overhead on synthetic maps (requires root/sudo)
   workload [args]   Run the CPU workload helper (defaults see tests/cpu_bound.c)
   help              Show this help
 
 Environment overrides:
   MAP_PIN    (default: /sys/fs/bpf/task_map)
+  OVERRIDE_PIN (default: /sys/fs/bpf/ticket_override)
+  LATENCY_PIN (default: task_latency next to MAP_PIN)
+  CGROUP_PIN (default: cgroup_map next to MAP_PIN)
+  EVENTS     (default: none; e.g. wakeup,waking,migrate)
   PROG_PIN   (default: /sys/fs/bpf/sched_lottery)
 USAGE
 }
@@ -203,6 +263,9 @@ case "$CMD" in
     tui)
         run_tui "$@"
         ;;
+    bench)
+        run_bench "$@"
+        ;;
     workload)
         run_workload "$@"
         ;;